@@ -1,3 +1,7 @@
+// A test target which spins until it is killed
+#![allow(unused_variables, unused_assignments, unreachable_code)]
+#![allow(clippy::diverging_sub_expression)]
+
 use std::hint::black_box;
 
 fn forever(mut i: i32) -> ! {
@@ -0,0 +1,42 @@
+// Serves a process to GDB, LLDB or kitt over the GDB remote serial protocol
+
+use anyhow::{anyhow, Result};
+use kitt::process::{DebugProcess, Process};
+use kitt::rsp::{Connection, Server};
+use nix::unistd::Pid;
+use std::env;
+use std::net::TcpListener;
+use std::os::unix::net::UnixListener;
+
+// A single connection is accepted on the TCP address or, with a unix: prefix, the socket path,
+// before the program is launched or attached to
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let usage =
+        || anyhow!("usage: kitt-server <host:port|unix:<path>> (-p <pid> | -- <program> [args])");
+    let (address, rest) = args.split_first().ok_or_else(usage)?;
+    let (connection, description): (Box<dyn Connection>, _) = match address.strip_prefix("unix:") {
+        Some(path) => {
+            let listener = UnixListener::bind(path)?;
+            eprintln!("Listening on {path}");
+            let (connection, _) = listener.accept()?;
+            (Box::new(connection), path.to_string())
+        }
+        None => {
+            let listener = TcpListener::bind(address)?;
+            eprintln!("Listening on port {}", listener.local_addr()?.port());
+            let (connection, peer) = listener.accept()?;
+            (Box::new(connection), peer.to_string())
+        }
+    };
+    eprintln!("Remote debugging from {description}");
+    let (process, attached) = match rest {
+        [flag, pid] if flag == "-p" => (Process::attach(Pid::from_raw(pid.parse()?))?, true),
+        [separator, program, program_args @ ..] if separator == "--" => (
+            Process::launch_with_args(program, program_args, DebugProcess::YES)?,
+            false,
+        ),
+        _ => return Err(usage()),
+    };
+    Server::new(process, attached, connection)?.serve()
+}
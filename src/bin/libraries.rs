@@ -0,0 +1,27 @@
+// Test program for shared library tracking: loads libm at runtime, calls into it, unloads it
+// and then traps
+use std::arch::asm;
+use std::ffi::{c_char, c_int, c_void};
+
+const RTLD_NOW: c_int = 2;
+
+unsafe extern "C" {
+    fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    fn dlclose(handle: *mut c_void) -> c_int;
+}
+
+fn main() {
+    unsafe {
+        let handle = dlopen(c"libm.so.6".as_ptr(), RTLD_NOW);
+        assert!(!handle.is_null());
+
+        let cbrt = dlsym(handle, c"cbrt".as_ptr());
+        assert!(!cbrt.is_null());
+        let cbrt: extern "C" fn(f64) -> f64 = std::mem::transmute(cbrt);
+        println!("{}", cbrt(27.0));
+
+        dlclose(handle);
+        asm!("int3");
+    }
+}
@@ -0,0 +1,30 @@
+// Test program for catchpoints: starts a thread, forks a child, raises SIGUSR1 and then execs
+// itself, exiting with code 3 after the exec
+use nix::sys::signal::{self, SigHandler, Signal};
+use nix::sys::wait::waitpid;
+use nix::unistd::{fork, ForkResult};
+use std::env;
+use std::os::unix::process::CommandExt;
+use std::process::{self, Command};
+use std::thread;
+
+fn main() {
+    if env::args().nth(1).as_deref() == Some("exec") {
+        process::exit(3);
+    }
+
+    thread::spawn(|| {}).join().unwrap();
+
+    match unsafe { fork() }.unwrap() {
+        ForkResult::Child => process::exit(0),
+        ForkResult::Parent { child } => {
+            waitpid(child, None).unwrap();
+        }
+    }
+
+    unsafe { signal::signal(Signal::SIGUSR1, SigHandler::SigIgn) }.unwrap();
+    signal::raise(Signal::SIGUSR1).unwrap();
+
+    let err = Command::new(env::current_exe().unwrap()).arg("exec").exec();
+    panic!("exec failed: {err}");
+}
@@ -0,0 +1,55 @@
+use std::arch::asm;
+use std::hint::black_box;
+
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+enum Colour {
+    Red,
+    Green,
+    Blue,
+}
+
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+struct Point {
+    x: i32,
+    y: i64,
+}
+
+#[allow(dead_code)]
+struct Shape {
+    origin: Point,
+    colour: Colour,
+    corners: [u16; 3],
+    scale: f64,
+    label: Option<u8>,
+}
+
+static COUNTER: u32 = 17;
+
+#[inline(never)]
+fn inspect(shape: &Shape, factor: u32) -> i64 {
+    let total = shape.origin.x as i64 + shape.origin.y * factor as i64;
+    let local_shape = Shape {
+        origin: Point { x: -1, y: 2 },
+        colour: Colour::Blue,
+        corners: [1, 2, 3],
+        scale: 1.5,
+        label: Some(9),
+    };
+    black_box(&local_shape);
+    // Stop here so that the debugger can inspect the frame
+    unsafe { asm!("int3") };
+    black_box(total) + black_box(COUNTER) as i64
+}
+
+fn main() {
+    let shape = Shape {
+        origin: Point { x: 3, y: 4 },
+        colour: Colour::Green,
+        corners: [10, 20, 30],
+        scale: 0.25,
+        label: None,
+    };
+    black_box(inspect(&shape, 7));
+}
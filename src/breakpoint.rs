@@ -0,0 +1,50 @@
+//! Breakpoints set by the user.
+
+/// A breakpoint set by the user. A location can resolve to several addresses, for example when
+/// a name matches more than one function, and each of them gets a breakpoint site in the process.
+pub struct Breakpoint {
+    /// The number the user refers to the breakpoint by
+    pub id: usize,
+    /// The location as the user gave it
+    pub location: String,
+    /// Runtime addresses
+    pub addresses: Vec<u64>,
+    /// Disabled breakpoints keep their addresses but have no sites
+    pub enabled: bool,
+    /// Temporary breakpoints are deleted when they first stop the process
+    pub temporary: bool,
+    /// An expression which must hold for a hit to stop the process
+    pub condition: Option<String>,
+    /// Hits which stopped the process or were ignored, not counting those whose condition failed
+    pub hit_count: usize,
+    /// The number of upcoming hits to carry on from
+    pub ignore_count: usize,
+    /// Commands run when the breakpoint stops the process
+    pub commands: Vec<String>,
+}
+
+impl Breakpoint {
+    /// An enabled breakpoint with no condition, commands or hits
+    pub fn new(id: usize, location: &str, addresses: Vec<u64>) -> Self {
+        Self {
+            id,
+            location: location.to_string(),
+            addresses,
+            enabled: true,
+            temporary: false,
+            condition: None,
+            hit_count: 0,
+            ignore_count: 0,
+            commands: Vec::new(),
+        }
+    }
+
+    /// How the breakpoint is referred to in messages
+    pub fn kind(&self) -> &'static str {
+        if self.temporary {
+            "Temporary breakpoint"
+        } else {
+            "Breakpoint"
+        }
+    }
+}
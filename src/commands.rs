@@ -0,0 +1,706 @@
+use crate::console::Console;
+use anyhow::{anyhow, bail, Error, Result};
+use kitt::debugger::Debugger;
+use kitt::{reginfo, syscalls};
+use nix::sys::signal::Signal;
+use std::fmt::Display;
+use std::str::FromStr;
+
+// The commands of the REPL form a tree: groups such as `break` hold subcommands, and the leaves
+// run. A word selects the command it is a unique prefix of, or one whose short names it
+// matches exactly, such as `c` for `continue` where the prefix alone would be ambiguous.
+
+pub type Handler = fn(&mut Console, &mut Debugger, &Arguments) -> Result<()>;
+
+pub enum Action {
+    Run(Handler),
+    Group(&'static [Command]),
+}
+
+pub struct Command {
+    pub name: &'static str,
+    pub short: &'static [&'static str],
+    // The first line summarises the command for listings, and the rest of the text explains it
+    pub help: &'static str,
+    pub params: &'static [Param],
+    // Whether the command word takes a /format suffix, as `x/4xg` does
+    pub format: bool,
+    pub action: Action,
+}
+
+impl Command {
+    pub const fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            short: &[],
+            help,
+            params: &[],
+            format: false,
+            action: Action::Group(&[]),
+        }
+    }
+
+    pub const fn short(mut self, short: &'static [&'static str]) -> Self {
+        self.short = short;
+        self
+    }
+
+    pub const fn params(mut self, params: &'static [Param]) -> Self {
+        self.params = params;
+        self
+    }
+
+    pub const fn with_format(mut self) -> Self {
+        self.format = true;
+        self
+    }
+
+    pub const fn run(mut self, handler: Handler) -> Self {
+        self.action = Action::Run(handler);
+        self
+    }
+
+    pub const fn group(mut self, subcommands: &'static [Command]) -> Self {
+        self.action = Action::Group(subcommands);
+        self
+    }
+
+    pub fn summary(&self) -> &'static str {
+        self.help.lines().next().unwrap_or_default()
+    }
+
+    pub fn subcommands(&self) -> &'static [Command] {
+        match self.action {
+            Action::Group(subcommands) => subcommands,
+            Action::Run(_) => &[],
+        }
+    }
+
+    // The syntax of the arguments, as in `<location> [if <condition>]`
+    fn syntax(&self) -> String {
+        let subcommands = self.subcommands();
+        if !subcommands.is_empty() {
+            let names: Vec<&str> = subcommands.iter().map(|command| command.name).collect();
+            return names.join("|");
+        }
+        let params: Vec<String> = self.params.iter().map(Param::syntax).collect();
+        params.join(" ")
+    }
+}
+
+// What an argument is, for checking and completing it
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Kind {
+    // A count, as a decimal number
+    Number,
+    // The id of a breakpoint
+    Breakpoint,
+    // Where to put a breakpoint or run to: a function, a symbol or an address
+    Location,
+    // An address as a number or a symbol
+    Address,
+    // A signal by name, with or without SIG, or by number
+    Signal,
+    // A system call by name or number, or a group of them as group:<name>
+    Syscall,
+    // A register by name, as rax or pc
+    Register,
+    Path,
+    // One of a fixed set of words
+    Choice(&'static [&'static str]),
+    // `if` and an expression
+    Condition,
+    Expression,
+    // The words naming a command, as `help` takes
+    Command,
+    Word,
+}
+
+pub struct Param {
+    pub name: &'static str,
+    pub kind: Kind,
+    pub optional: bool,
+    // Taking any number of words, or the rest of the line as a single argument
+    pub many: bool,
+    pub line: bool,
+}
+
+impl Param {
+    pub const fn word(name: &'static str, kind: Kind) -> Self {
+        Self {
+            name,
+            kind,
+            optional: false,
+            many: false,
+            line: false,
+        }
+    }
+
+    pub const fn optional(name: &'static str, kind: Kind) -> Self {
+        Self {
+            optional: true,
+            ..Self::word(name, kind)
+        }
+    }
+
+    pub const fn words(name: &'static str, kind: Kind) -> Self {
+        Self {
+            optional: true,
+            many: true,
+            ..Self::word(name, kind)
+        }
+    }
+
+    pub const fn line(name: &'static str, kind: Kind) -> Self {
+        Self {
+            line: true,
+            ..Self::word(name, kind)
+        }
+    }
+
+    pub const fn optional_line(name: &'static str, kind: Kind) -> Self {
+        Self {
+            optional: true,
+            line: true,
+            ..Self::word(name, kind)
+        }
+    }
+
+    fn syntax(&self) -> String {
+        let syntax = match self.kind {
+            Kind::Choice(words) => words.join("|"),
+            Kind::Condition => format!("if <{}>", self.name),
+            _ => format!("<{}>", self.name),
+        };
+        match (self.optional, self.many) {
+            (_, true) => format!("[{syntax}]..."),
+            (true, false) => format!("[{syntax}]"),
+            (false, false) => syntax,
+        }
+    }
+
+    // Checks a word given for the parameter, where that needs nothing but the word
+    fn check(&self, word: &str) -> Result<()> {
+        match self.kind {
+            Kind::Number | Kind::Breakpoint => _ = word.parse::<usize>()?,
+            Kind::Signal => _ = parse_signal(word)?,
+            Kind::Syscall => _ = syscalls::parse_syscalls(word)?,
+            Kind::Register => _ = reginfo::lookup_register_info_by_name(&word.to_uppercase())?,
+            Kind::Choice(words) if !words.contains(&word) => {
+                bail!("expected {}", words.join(", "))
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+pub fn parse_signal(name: &str) -> Result<Signal> {
+    if let Ok(number) = name.parse::<i32>() {
+        return Ok(Signal::try_from(number)?);
+    }
+    let name = name.to_ascii_uppercase();
+    let name = if name.starts_with("SIG") {
+        name
+    } else {
+        format!("SIG{name}")
+    };
+    name.parse().map_err(|_| anyhow!("unknown signal {name}"))
+}
+
+// The arguments of a command, checked against its parameters
+pub struct Arguments<'a> {
+    // The full name of the command, as in `break set`
+    name: String,
+    usage: String,
+    format: Option<&'a str>,
+    values: Vec<(&'static str, &'a str)>,
+}
+
+impl<'a> Arguments<'a> {
+    fn parse(
+        name: String,
+        command: &Command,
+        format: Option<&'a str>,
+        mut rest: &'a str,
+    ) -> Result<Self> {
+        let usage = format!("usage: {}", usage(&name, command));
+        if format.is_some() && !command.format {
+            bail!("{name} takes no /format\n{usage}");
+        }
+        let mut values = Vec::new();
+        for param in command.params {
+            if param.line {
+                let text = match param.kind {
+                    Kind::Condition => match split_word(rest) {
+                        ("if", condition) => condition,
+                        ("", _) => "",
+                        _ => bail!(usage),
+                    },
+                    _ => rest,
+                };
+                if !text.is_empty() {
+                    values.push((param.name, text));
+                } else if !param.optional || !rest.is_empty() {
+                    bail!(usage);
+                }
+                rest = "";
+                continue;
+            }
+            loop {
+                let (word, after) = split_word(rest);
+                if word.is_empty() {
+                    if !param.optional {
+                        bail!(usage);
+                    }
+                    break;
+                }
+                param
+                    .check(word)
+                    .map_err(|err| anyhow!("invalid {} {word}: {err}\n{usage}", param.name))?;
+                values.push((param.name, word));
+                rest = after;
+                if !param.many {
+                    break;
+                }
+            }
+        }
+        if !rest.is_empty() {
+            bail!(usage);
+        }
+        Ok(Self {
+            name,
+            usage,
+            format,
+            values,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn format(&self) -> Option<&'a str> {
+        self.format
+    }
+
+    // The argument of a required parameter
+    pub fn text(&self, name: &str) -> Result<&'a str> {
+        self.get(name).ok_or_else(|| self.usage())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&'a str> {
+        self.all(name).next()
+    }
+
+    // The words of a parameter taking many
+    pub fn all<'b>(&'b self, name: &'b str) -> impl Iterator<Item = &'a str> + 'b {
+        let values = self.values.iter();
+        values
+            .filter(move |(param, _)| *param == name)
+            .map(|(_, value)| *value)
+    }
+
+    // The argument of a required parameter, converted to its type
+    pub fn value<T: FromStr>(&self, name: &str) -> Result<T>
+    where
+        T::Err: Display,
+    {
+        self.optional(name)?.ok_or_else(|| self.usage())
+    }
+
+    pub fn optional<T: FromStr>(&self, name: &str) -> Result<Option<T>>
+    where
+        T::Err: Display,
+    {
+        let Some(text) = self.get(name) else {
+            return Ok(None);
+        };
+        let value = text
+            .parse()
+            .map_err(|err| anyhow!("invalid {name} {text}: {err}"))?;
+        Ok(Some(value))
+    }
+
+    pub fn usage(&self) -> Error {
+        anyhow!("{}", self.usage)
+    }
+}
+
+// A command line resolved to the command it runs
+pub struct Invocation<'a> {
+    handler: Handler,
+    pub arguments: Arguments<'a>,
+}
+
+impl Invocation<'_> {
+    pub fn name(&self) -> &str {
+        self.arguments.name()
+    }
+
+    pub fn run(&self, console: &mut Console, debugger: &mut Debugger) -> Result<()> {
+        (self.handler)(console, debugger, &self.arguments)
+    }
+}
+
+fn split_word(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    let end = text.find(char::is_whitespace).unwrap_or(text.len());
+    (&text[..end], text[end..].trim())
+}
+
+// The command among `commands` which a word selects
+pub fn find<'c>(commands: &'c [Command], word: &str) -> Result<&'c Command> {
+    let exact = commands
+        .iter()
+        .find(|command| command.name == word || command.short.contains(&word));
+    if let Some(command) = exact {
+        return Ok(command);
+    }
+    let matches: Vec<&Command> = commands
+        .iter()
+        .filter(|command| command.name.starts_with(word))
+        .collect();
+    match matches[..] {
+        [command] => Ok(command),
+        [] => bail!("unknown command \"{word}\", try \"help\""),
+        _ => {
+            let names: Vec<&str> = matches.iter().map(|command| command.name).collect();
+            bail!("ambiguous command \"{word}\": {}", names.join(", "))
+        }
+    }
+}
+
+// Resolves a command line, which is nothing to run when blank
+pub fn parse<'a>(commands: &'static [Command], line: &'a str) -> Result<Option<Invocation<'a>>> {
+    let (word, mut rest) = split_word(line);
+    if word.is_empty() {
+        return Ok(None);
+    }
+    let (mut word, format) = match word.split_once('/') {
+        Some((word, format)) => (word, Some(format)),
+        None => (word, None),
+    };
+    let mut name = String::new();
+    let mut group: Option<&Command> = None;
+    let mut commands = commands;
+    loop {
+        let command = find(commands, word).map_err(|err| match group {
+            Some(group) => anyhow!("{err}\nusage: {}", usage(&name, group)),
+            None => err,
+        })?;
+        if !name.is_empty() {
+            name.push(' ');
+        }
+        name.push_str(command.name);
+        match command.action {
+            Action::Run(handler) => {
+                let arguments = Arguments::parse(name, command, format, rest)?;
+                return Ok(Some(Invocation { handler, arguments }));
+            }
+            Action::Group(subcommands) => {
+                (word, rest) = split_word(rest);
+                if word.is_empty() {
+                    bail!("usage: {}", usage(&name, command));
+                }
+                group = Some(command);
+                commands = subcommands;
+            }
+        }
+    }
+}
+
+fn usage(name: &str, command: &Command) -> String {
+    let format = if command.format { "[/<format>]" } else { "" };
+    let syntax = command.syntax();
+    match syntax.is_empty() {
+        true => format!("{name}{format}"),
+        false => format!("{name}{format} {syntax}"),
+    }
+}
+
+// The help for the command which `words` name, or the list of commands without any
+pub fn help(commands: &'static [Command], words: &[&str]) -> Result<String> {
+    let mut text = String::new();
+    if words.is_empty() {
+        text.push_str("List of commands:\n\n");
+        list(&mut text, "", commands);
+        text.push_str(
+            "\nType \"help\" followed by a command for its full documentation. Commands may be \
+             abbreviated to any unique prefix.\n",
+        );
+        return Ok(text);
+    }
+    let mut names: Vec<&str> = Vec::new();
+    let mut commands = commands;
+    let mut command = None;
+    for word in words {
+        let found = find(commands, word)?;
+        names.push(found.name);
+        commands = found.subcommands();
+        command = Some(found);
+    }
+    let command = command.unwrap();
+    let name = names.join(" ");
+    text.push_str(&format!("usage: {}\n", usage(&name, command)));
+    text.push_str(command.help);
+    text.push('\n');
+    if !command.short.is_empty() {
+        text.push_str(&format!("Also {}.\n", command.short.join(", ")));
+    }
+    if !commands.is_empty() {
+        text.push_str(&format!("\nList of {name} subcommands:\n\n"));
+        list(&mut text, &format!("{name} "), commands);
+    }
+    Ok(text)
+}
+
+// What the word at the end of a partial command line is, for completing it
+#[derive(Debug, PartialEq)]
+pub enum Completing {
+    // One of a fixed set, such as the subcommands of a group
+    Words(Vec<&'static str>),
+    Argument(Kind),
+}
+
+// The words of a line, with where each starts
+pub fn words(line: &str) -> impl Iterator<Item = (usize, &str)> {
+    line.split_whitespace()
+        .map(move |word| (word.as_ptr() as usize - line.as_ptr() as usize, word))
+}
+
+// Where the word being typed at the end of `line` starts, and what it is. Nothing can be
+// completed after words which name no command.
+pub fn completing(commands: &'static [Command], line: &str) -> Option<(usize, Completing)> {
+    let mut words: Vec<(usize, &str)> = words(line).collect();
+    let start = match line.ends_with(char::is_whitespace) {
+        true => line.len(),
+        false => words.pop().map_or(0, |(start, _)| start),
+    };
+    let words: Vec<&str> = words.into_iter().map(|(_, word)| word).collect();
+    let (command, rest) = match resolve(commands, &words) {
+        Ok(resolved) => resolved,
+        Err(commands) => return Some((start, command_names(commands))),
+    };
+    let mut rest = rest.iter();
+    for param in command.params {
+        if param.kind == Kind::Command {
+            let words: Vec<&str> = rest.copied().collect();
+            return match resolve(commands, &words) {
+                Err(commands) => Some((start, command_names(commands))),
+                Ok(_) => None,
+            };
+        }
+        if param.line {
+            let completing = match (param.kind, rest.next()) {
+                (Kind::Condition, None) => Completing::Words(vec!["if"]),
+                (Kind::Condition, Some(_)) => Completing::Argument(Kind::Expression),
+                (kind, _) => Completing::Argument(kind),
+            };
+            return Some((start, completing));
+        }
+        if param.many || rest.next().is_none() {
+            let completing = match param.kind {
+                Kind::Choice(words) => Completing::Words(words.to_vec()),
+                kind => Completing::Argument(kind),
+            };
+            return Some((start, completing));
+        }
+    }
+    None
+}
+
+// The command which the leading words name and the words after it, or the commands from which
+// the next word is to be chosen when the words name a group
+fn resolve<'w>(
+    commands: &'static [Command],
+    words: &'w [&'w str],
+) -> Result<(&'static Command, &'w [&'w str]), &'static [Command]> {
+    let mut commands = commands;
+    for (index, word) in words.iter().enumerate() {
+        let word = word.split('/').next().unwrap_or_default();
+        let Ok(command) = find(commands, word) else {
+            return Err(&[]);
+        };
+        if let Action::Run(_) = command.action {
+            return Ok((command, &words[index + 1..]));
+        }
+        commands = command.subcommands();
+    }
+    Err(commands)
+}
+
+fn command_names(commands: &[Command]) -> Completing {
+    Completing::Words(commands.iter().map(|command| command.name).collect())
+}
+
+// The syntax still to be typed after a line of command words, as a hint
+pub fn syntax_hint(commands: &'static [Command], line: &str) -> Option<String> {
+    if !line.ends_with(' ') {
+        return None;
+    }
+    let mut commands = commands;
+    let mut command: Option<&Command> = None;
+    for word in line.split_whitespace() {
+        if command.is_some() && commands.is_empty() {
+            return None;
+        }
+        let found = find(commands, word.split('/').next().unwrap_or_default()).ok()?;
+        commands = found.subcommands();
+        command = Some(found);
+    }
+    let syntax = command?.syntax();
+    (!syntax.is_empty()).then_some(syntax)
+}
+
+fn list(text: &mut String, prefix: &str, commands: &[Command]) {
+    for command in commands {
+        let mut names = vec![command.name];
+        names.extend(command.short);
+        text.push_str(&format!(
+            "{prefix}{} -- {}\n",
+            names.join(", "),
+            command.summary()
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{self, Command, Completing, Kind, Param};
+    use crate::console::Console;
+    use anyhow::Result;
+    use kitt::debugger::Debugger;
+
+    fn nothing(_: &mut Console, _: &mut Debugger, _: &commands::Arguments) -> Result<()> {
+        Ok(())
+    }
+
+    static COMMANDS: &[Command] = &[
+        Command::new("break", "Breakpoints").short(&["b"]).group(&[
+            Command::new("set", "Sets one")
+                .params(&[
+                    Param::word("location", Kind::Location),
+                    Param::optional_line("condition", Kind::Condition),
+                ])
+                .run(nothing),
+            Command::new("delete", "Deletes one")
+                .params(&[Param::word("id", Kind::Breakpoint)])
+                .run(nothing),
+            Command::new("disable", "Disables one")
+                .params(&[Param::word("id", Kind::Breakpoint)])
+                .run(nothing),
+        ]),
+        Command::new("backtrace", "The stack")
+            .short(&["bt"])
+            .run(nothing),
+        Command::new("catch", "Catchpoints")
+            .params(&[Param::words("signal", Kind::Signal)])
+            .run(nothing),
+        Command::new("x", "Memory")
+            .params(&[Param::line("expression", Kind::Expression)])
+            .with_format()
+            .run(nothing),
+    ];
+
+    fn error(line: &str) -> String {
+        match commands::parse(COMMANDS, line) {
+            Ok(_) => panic!("{line} parsed"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn commands_resolve_by_prefix_and_check_their_arguments() {
+        assert!(commands::parse(COMMANDS, " \t ").unwrap().is_none());
+
+        let invocation = commands::parse(COMMANDS, "b s main if  x == 1")
+            .unwrap()
+            .unwrap();
+        assert_eq!(invocation.name(), "break set");
+        assert_eq!(invocation.arguments.get("location"), Some("main"));
+        assert_eq!(invocation.arguments.get("condition"), Some("x == 1"));
+        let invocation = commands::parse(COMMANDS, "bt").unwrap().unwrap();
+        assert_eq!(invocation.name(), "backtrace");
+        let invocation = commands::parse(COMMANDS, "br del 2").unwrap().unwrap();
+        assert_eq!(invocation.arguments.value::<usize>("id").unwrap(), 2);
+        let invocation = commands::parse(COMMANDS, "x/4xg $rsp + 8")
+            .unwrap()
+            .unwrap();
+        assert_eq!(invocation.arguments.format(), Some("4xg"));
+        assert_eq!(invocation.arguments.get("expression"), Some("$rsp + 8"));
+        let invocation = commands::parse(COMMANDS, "catch int SIGTERM 9")
+            .unwrap()
+            .unwrap();
+        let signals: Vec<&str> = invocation.arguments.all("signal").collect();
+        assert_eq!(signals, ["int", "SIGTERM", "9"]);
+
+        assert_eq!(
+            error("break d 1"),
+            "ambiguous command \"d\": delete, disable\nusage: break set|delete|disable"
+        );
+        assert_eq!(
+            error("frobnicate"),
+            "unknown command \"frobnicate\", try \"help\""
+        );
+        assert_eq!(error("break"), "usage: break set|delete|disable");
+        assert_eq!(
+            error("break set"),
+            "usage: break set <location> [if <condition>]"
+        );
+        assert_eq!(
+            error("break set main when"),
+            "usage: break set <location> [if <condition>]"
+        );
+        assert!(error("break delete one").starts_with("invalid id one: "));
+        assert!(error("catch SIGNOPE").starts_with("invalid signal SIGNOPE: unknown signal"));
+        assert_eq!(
+            error("bt/x"),
+            "backtrace takes no /format\nusage: backtrace"
+        );
+    }
+
+    #[test]
+    fn help_lists_commands_and_describes_them() {
+        let help = commands::help(COMMANDS, &[]).unwrap();
+        assert!(help.contains("\nbreak, b -- Breakpoints\nbacktrace, bt -- The stack\n"));
+        let help = commands::help(COMMANDS, &["b"]).unwrap();
+        assert!(help.starts_with("usage: break set|delete|disable\nBreakpoints\nAlso b.\n"));
+        assert!(help.contains("break delete -- Deletes one\n"));
+        let help = commands::help(COMMANDS, &["x"]).unwrap();
+        assert_eq!(help, "usage: x[/<format>] <expression>\nMemory\n");
+    }
+
+    #[test]
+    fn partial_lines_complete_and_hint_from_the_tree() {
+        let words = |words: &[&'static str]| Completing::Words(words.to_vec());
+        assert_eq!(
+            commands::completing(COMMANDS, "b"),
+            Some((0, words(&["break", "backtrace", "catch", "x"])))
+        );
+        assert_eq!(
+            commands::completing(COMMANDS, "br d"),
+            Some((3, words(&["set", "delete", "disable"])))
+        );
+        assert_eq!(
+            commands::completing(COMMANDS, "b s main "),
+            Some((9, words(&["if"])))
+        );
+        assert_eq!(
+            commands::completing(COMMANDS, "b s main if $r"),
+            Some((12, Completing::Argument(Kind::Expression)))
+        );
+        assert_eq!(
+            commands::completing(COMMANDS, "catch int T"),
+            Some((10, Completing::Argument(Kind::Signal)))
+        );
+        assert_eq!(commands::completing(COMMANDS, "bt "), None);
+
+        assert_eq!(
+            commands::syntax_hint(COMMANDS, "b set ").as_deref(),
+            Some("<location> [if <condition>]")
+        );
+        assert_eq!(commands::syntax_hint(COMMANDS, "b set main "), None);
+        assert_eq!(commands::syntax_hint(COMMANDS, "bt "), None);
+    }
+}
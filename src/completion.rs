@@ -0,0 +1,300 @@
+use crate::commands::{self, Command, Completing, Kind};
+use crate::console::Console;
+use kitt::debugger::Debugger;
+use kitt::{reginfo, syscalls};
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::highlight::{CmdKind, Highlighter};
+use rustyline::hint::{Hinter, HistoryHinter};
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+use std::borrow::Cow;
+
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[31m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+// Completion, hints and highlighting for the prompt. The editor owns its helper, so the helper
+// keeps a copy of what it completes from the debugger, refreshed before each prompt.
+pub struct KittHelper {
+    commands: &'static [Command],
+    symbols: Vec<String>,
+    // The process and the number of shared libraries when the symbols were collected
+    loaded: Option<(Pid, usize)>,
+    breakpoints: Vec<String>,
+    // Aliases and user commands
+    user: Vec<String>,
+    // Convenience variables, with their `$`
+    variables: Vec<String>,
+    filenames: FilenameCompleter,
+    history: HistoryHinter,
+}
+
+impl KittHelper {
+    pub fn new(commands: &'static [Command]) -> Self {
+        Self {
+            commands,
+            symbols: Vec::new(),
+            loaded: None,
+            breakpoints: Vec::new(),
+            user: Vec::new(),
+            variables: Vec::new(),
+            filenames: FilenameCompleter::new(),
+            history: HistoryHinter::new(),
+        }
+    }
+
+    pub fn refresh(&mut self, console: &Console, debugger: &Debugger) {
+        let loaded = (debugger.target().pid(), debugger.shared_libraries().len());
+        if self.loaded != Some(loaded) {
+            let mut symbols: Vec<String> = debugger.symbol_names().map(str::to_string).collect();
+            symbols.sort();
+            symbols.dedup();
+            self.symbols = symbols;
+            self.loaded = Some(loaded);
+        }
+        let breakpoints = debugger.breakpoints().iter();
+        self.breakpoints = breakpoints
+            .map(|breakpoint| breakpoint.id.to_string())
+            .collect();
+        self.user = console.user.names().map(str::to_string).collect();
+        let variables = debugger.variables().variables();
+        self.variables = variables.map(|(name, _)| format!("${name}")).collect();
+    }
+
+    // The candidates for the word at the end of `line`, and where the word starts
+    fn candidates(&self, line: &str) -> (usize, Vec<String>) {
+        let Some((start, completing)) = commands::completing(self.commands, line) else {
+            return (line.len(), Vec::new());
+        };
+        let word = &line[start..];
+        let kind = match completing {
+            Completing::Words(words) if line[..start].trim().is_empty() => {
+                let words = words.into_iter().map(str::to_string);
+                return (start, matching(words.chain(self.user.clone()), word));
+            }
+            Completing::Words(words) => return (start, matching(words, word)),
+            Completing::Argument(kind) => kind,
+        };
+        match kind {
+            Kind::Breakpoint => (start, matching(&self.breakpoints, word)),
+            Kind::Location | Kind::Address => (start, matching(&self.symbols, word)),
+            Kind::Signal => (start, signals(word)),
+            Kind::Syscall => (start, matching(syscalls::syscall_names(), word)),
+            Kind::Register => {
+                let names = registers().map(|name| name[1..].to_string());
+                (start, matching(names, word))
+            }
+            Kind::Expression | Kind::Condition => {
+                // Only the identifier or register being typed is completed
+                let name_start = word
+                    .char_indices()
+                    .rfind(|&(_, c)| !(c.is_alphanumeric() || "_:$".contains(c)))
+                    .map_or(0, |(index, c)| index + c.len_utf8());
+                let name = &word[name_start..];
+                let candidates = match name.starts_with('$') {
+                    true => matching(registers().chain(self.variables.clone()), name),
+                    false => matching(&self.symbols, name),
+                };
+                (start + name_start, candidates)
+            }
+            _ => (start, Vec::new()),
+        }
+    }
+}
+
+fn matching<S: AsRef<str>>(candidates: impl IntoIterator<Item = S>, word: &str) -> Vec<String> {
+    let candidates = candidates.into_iter();
+    let mut matches: Vec<String> = candidates
+        .filter(|candidate| candidate.as_ref().starts_with(word))
+        .map(|candidate| candidate.as_ref().to_string())
+        .collect();
+    matches.sort();
+    matches.dedup();
+    matches
+}
+
+// Registers as expressions name them, as in `$rip` or `$pc`
+fn registers() -> impl Iterator<Item = String> {
+    let aliases = reginfo::REGISTER_ALIASES.iter().map(|(alias, _)| *alias);
+    let names = reginfo::registers()
+        .map(|register| register.name.as_str())
+        .chain(aliases);
+    names.map(|name| format!("${}", name.to_lowercase()))
+}
+
+// Signal names in the case of the word, with SIG when the word has it
+fn signals(word: &str) -> Vec<String> {
+    let upper = word.to_ascii_uppercase();
+    let lower = word.chars().any(|c| c.is_ascii_lowercase());
+    let names = Signal::iterator().map(|signal| match upper.starts_with("SIG") {
+        true => signal.as_str(),
+        false => &signal.as_str()[3..],
+    });
+    let candidates = matching(names, &upper);
+    match lower {
+        true => candidates.iter().map(|name| name.to_lowercase()).collect(),
+        false => candidates,
+    }
+}
+
+impl Completer for KittHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        if let Some((_, Completing::Argument(Kind::Path))) =
+            commands::completing(self.commands, &line[..pos])
+        {
+            return self.filenames.complete_path(line, pos);
+        }
+        // A unique candidate is finished with a space, ready for the next word
+        let (start, candidates) = self.candidates(&line[..pos]);
+        let unique = candidates.len() == 1;
+        let pairs = candidates.into_iter().map(|candidate| Pair {
+            replacement: if unique {
+                format!("{candidate} ")
+            } else {
+                candidate.clone()
+            },
+            display: candidate,
+        });
+        Ok((start, pairs.collect()))
+    }
+}
+
+impl Hinter for KittHelper {
+    type Hint = String;
+
+    // What history suggests, or else the syntax of the command typed so far
+    fn hint(&self, line: &str, pos: usize, context: &Context<'_>) -> Option<String> {
+        if pos < line.len() {
+            return None;
+        }
+        let hint = self.history.hint(line, pos, context);
+        hint.or_else(|| commands::syntax_hint(self.commands, line))
+    }
+}
+
+impl Highlighter for KittHelper {
+    // Command words are bold, and a word which cannot start any command is red
+    fn highlight<'l>(&self, line: &'l str, _: usize) -> Cow<'l, str> {
+        let mut commands = self.commands;
+        let mut end = 0;
+        let mut unknown = None;
+        for (start, word) in commands::words(line) {
+            if commands.is_empty() {
+                break;
+            }
+            let name = word.split('/').next().unwrap_or_default();
+            let first = end == 0;
+            if first && self.user.iter().any(|user| user == name) {
+                end = start + word.len();
+                break;
+            }
+            match commands::find(commands, name) {
+                Ok(command) => {
+                    end = start + word.len();
+                    commands = command.subcommands();
+                }
+                Err(_) => {
+                    let typing = commands
+                        .iter()
+                        .any(|command| command.name.starts_with(name))
+                        || first && self.user.iter().any(|user| user.starts_with(name));
+                    // `set $name = ...` assigns without a subcommand
+                    if !typing && !name.starts_with('$') {
+                        unknown = Some(start..start + word.len());
+                    }
+                    break;
+                }
+            }
+        }
+        if end == 0 && unknown.is_none() {
+            return Cow::Borrowed(line);
+        }
+        let mut text = String::new();
+        if end > 0 {
+            text.push_str(&format!("{BOLD}{}{RESET}", &line[..end]));
+        }
+        match unknown {
+            Some(word) => text.push_str(&format!(
+                "{}{RED}{}{RESET}{}",
+                &line[end..word.start],
+                &line[word.clone()],
+                &line[word.end..]
+            )),
+            None => text.push_str(&line[end..]),
+        }
+        Cow::Owned(text)
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(format!("{DIM}{hint}{RESET}"))
+    }
+
+    fn highlight_char(&self, _: &str, _: usize, _: CmdKind) -> bool {
+        true
+    }
+}
+
+impl Validator for KittHelper {}
+
+impl Helper for KittHelper {}
+
+#[cfg(test)]
+mod tests {
+    use crate::completion::KittHelper;
+    use crate::console::Console;
+    use crate::COMMANDS;
+    use kitt::debugger::Debugger;
+    use kitt::expression::{Value, ValueType};
+    use kitt::process::{DebugProcess, Process};
+
+    #[test]
+    fn arguments_complete_from_the_debugger() {
+        let process = Process::launch("target/debug/variables", DebugProcess::YES).unwrap();
+        let mut debugger = Debugger::new(process).unwrap();
+        let id = debugger.set_breakpoint("variables::inspect").unwrap().id;
+        let mut console = Console::terminal();
+        console.user.alias(COMMANDS, "bkpt", "break set").unwrap();
+        let mut helper = KittHelper::new(COMMANDS);
+        helper.refresh(&console, &debugger);
+        let candidates = |line: &str| helper.candidates(line);
+
+        assert_eq!(candidates("bre"), (0, vec!["break".to_string()]));
+        assert_eq!(candidates("bk"), (0, vec!["bkpt".to_string()]));
+        assert_eq!(candidates("break dele"), (6, vec!["delete".to_string()]));
+        assert_eq!(candidates("break delete "), (13, vec![id.to_string()]));
+        assert_eq!(
+            candidates("break set variables::ins"),
+            (10, vec!["variables::inspect".to_string()])
+        );
+        assert_eq!(candidates("p 1 + $ri"), (6, vec!["$rip".to_string()]));
+        assert_eq!(candidates("p $p"), (2, vec!["$pc".to_string()]));
+        assert_eq!(
+            candidates("catch signal sigte"),
+            (13, vec!["sigterm".to_string()])
+        );
+        assert_eq!(
+            candidates("catch signal TER"),
+            (13, vec!["TERM".to_string()])
+        );
+        assert!(candidates("catch syscall wri")
+            .1
+            .contains(&"write".to_string()));
+        assert_eq!(candidates("help info sh").1, ["sharedlibrary"]);
+        assert_eq!(candidates("frob "), (5, vec![]));
+
+        let value = Value::new(ValueType::INT, vec![0; 4]);
+        debugger.variables_mut().set("base", &value).unwrap();
+        helper.refresh(&console, &debugger);
+        assert_eq!(helper.candidates("p $ba"), (2, vec!["$base".to_string()]));
+    }
+}
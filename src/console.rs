@@ -0,0 +1,211 @@
+use crate::user::UserCommands;
+use anyhow::Result;
+use kitt::debugger::Debugger;
+use kitt::process::{StopReason, SyscallStop};
+use kitt::syscalls;
+use serde_json::{json, Value};
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::fd::AsFd;
+
+// Where commands send what they print. At the terminal that is stdout. For the JSON interpreter
+// the text is collected for the response to the request, and stops are reported as events
+// rather than described. The console also keeps the commands users add in the session.
+pub struct Console {
+    json: bool,
+    output: Vec<u8>,
+    events: Vec<Value>,
+    result: Option<Value>,
+    pub user: UserCommands,
+}
+
+impl Console {
+    pub fn terminal() -> Self {
+        Self {
+            json: false,
+            output: Vec::new(),
+            events: Vec::new(),
+            result: None,
+            user: UserCommands::default(),
+        }
+    }
+
+    pub fn json() -> Self {
+        Self {
+            json: true,
+            ..Self::terminal()
+        }
+    }
+
+    // The text printed since the last call
+    pub fn take_output(&mut self) -> String {
+        String::from_utf8_lossy(&std::mem::take(&mut self.output)).into_owned()
+    }
+
+    // The events reported since the last call
+    pub fn take_events(&mut self) -> Vec<Value> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn add_event(&mut self, event: Value) {
+        self.events.push(event);
+    }
+
+    // Records what a command printed as data, for the response of the JSON interpreter
+    pub fn set_result(&mut self, result: Value) {
+        if self.json {
+            self.result = Some(result);
+        }
+    }
+
+    // The result recorded since the last call
+    pub fn take_result(&mut self) -> Option<Value> {
+        self.result.take()
+    }
+
+    // Reports where the process stopped, or how it ended
+    pub fn report_stop(&mut self, debugger: &Debugger, reason: &StopReason) -> Result<()> {
+        if self.json {
+            let event = stop_event(debugger, reason)?;
+            self.add_event(event);
+            return Ok(());
+        }
+        writeln!(self, "process id {} {}", debugger.target().pid(), reason)?;
+        match reason.syscall() {
+            Some(SyscallStop::Entry { number, args }) => {
+                let call = syscalls::format_call(number, &args, debugger.target());
+                writeln!(self, "{call}")?
+            }
+            Some(SyscallStop::Exit { number, value }) => {
+                writeln!(self, "returned {}", syscalls::format_return(number, value))?
+            }
+            None => {}
+        }
+        if reason.is_stopped() {
+            let pc = debugger.pc()?;
+            match debugger.stopped_breakpoint() {
+                Some(breakpoint) => writeln!(
+                    self,
+                    "{} {}, {}",
+                    breakpoint.kind(),
+                    breakpoint.id,
+                    debugger.describe_address(pc)
+                )?,
+                None => writeln!(self, "stopped at {}", debugger.describe_address(pc))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for Console {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        match self.json {
+            true => self.output.write(data),
+            false => io::stdout().write(data),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.json {
+            true => Ok(()),
+            false => io::stdout().flush(),
+        }
+    }
+}
+
+// Takes stdout for the messages of a protocol, pointing it at stderr for kitt and the programs it
+// launches, whose output would otherwise corrupt the messages
+pub fn take_stdout() -> Result<File> {
+    let output = io::stdout().as_fd().try_clone_to_owned()?;
+    nix::unistd::dup2_stdout(io::stderr().as_fd())?;
+    Ok(File::from(output))
+}
+
+// The event for a stop. Every event has `type`, `event` and `pid`. Processes which are gone
+// are `exited` with a `code` or `killed` with a `signal`. Otherwise the event is `stopped`, with
+// the `pc`, its `location` and a `reason` of:
+//   breakpoint-hit, with the `breakpoint` id
+//   syscall-entry, with the `call`, or syscall-exit, with the `return` value
+//   process-event, with a description of the `process_event` such as a fork
+//   signal, with the `signal` name
+pub fn stop_event(debugger: &Debugger, reason: &StopReason) -> Result<Value> {
+    let pid = debugger.target().pid().as_raw();
+    if let Some(code) = reason.exit_code() {
+        return Ok(json!({"type": "event", "event": "exited", "pid": pid, "code": code}));
+    }
+    let signal = reason.signal().map(|signal| signal.as_str());
+    if !reason.is_stopped() {
+        return Ok(json!({"type": "event", "event": "killed", "pid": pid, "signal": signal}));
+    }
+    let pc = debugger.pc()?;
+    let mut event = json!({
+        "type": "event",
+        "event": "stopped",
+        "pid": pid,
+        "pc": format!("{pc:#x}"),
+        "location": debugger.describe_address(pc),
+    });
+    match (
+        reason.syscall(),
+        reason.event(),
+        debugger.stopped_breakpoint(),
+    ) {
+        (Some(SyscallStop::Entry { number, args }), _, _) => {
+            event["reason"] = json!("syscall-entry");
+            event["call"] = json!(syscalls::format_call(number, &args, debugger.target()));
+        }
+        (Some(SyscallStop::Exit { number, value }), _, _) => {
+            event["reason"] = json!("syscall-exit");
+            event["return"] = json!(syscalls::format_return(number, value));
+        }
+        (None, Some(process_event), _) => {
+            event["reason"] = json!("process-event");
+            event["process_event"] = json!(process_event.to_string());
+        }
+        (None, None, Some(breakpoint)) => {
+            event["reason"] = json!("breakpoint-hit");
+            event["breakpoint"] = json!(breakpoint.id);
+        }
+        (None, None, None) => {
+            event["reason"] = json!("signal");
+            event["signal"] = json!(signal);
+        }
+    }
+    Ok(event)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::console::Console;
+    use kitt::debugger::Debugger;
+    use kitt::process::{DebugProcess, Process};
+    use std::io::Write;
+
+    #[test]
+    fn json_consoles_collect_output_and_stops() {
+        let process = Process::launch("target/debug/variables", DebugProcess::YES).unwrap();
+        let mut debugger = Debugger::new(process).unwrap();
+        let id = debugger.set_breakpoint("variables::inspect").unwrap().id;
+        let mut console = Console::json();
+        writeln!(console, "Breakpoint {id}").unwrap();
+        assert_eq!(console.take_output(), format!("Breakpoint {id}\n"));
+
+        let reason = debugger.continue_execution().unwrap();
+        console.report_stop(&debugger, &reason).unwrap();
+        let events = console.take_events();
+        assert_eq!(events[0]["event"], "stopped");
+        assert_eq!(events[0]["reason"], "breakpoint-hit");
+        assert_eq!(events[0]["breakpoint"], id);
+        assert!(events[0]["location"]
+            .as_str()
+            .unwrap()
+            .ends_with("in variables::inspect"));
+        assert!(console.take_output().is_empty());
+
+        debugger.delete_breakpoint(id).unwrap();
+        let reason = debugger.continue_execution().unwrap();
+        console.report_stop(&debugger, &reason).unwrap();
+        assert_eq!(console.take_events()[0]["signal"], "SIGTRAP");
+    }
+}
@@ -0,0 +1,697 @@
+//! Writing core files of a process, and debugging the programs they hold.
+
+use crate::dwarf::expr::MemoryReader;
+use crate::elf::{read_struct, struct_bytes};
+use crate::maps::{self, MemoryRegion, Permissions};
+use crate::process::{Process, ThreadRegisters};
+use crate::reginfo::{lookup_register_info_by_id, RegisterId};
+use crate::registers::Registers;
+use crate::target::Target;
+use anyhow::{anyhow, bail, Result};
+use nix::libc::{
+    user_fpregs_struct, user_regs_struct, Elf64_Ehdr, Elf64_Phdr, AT_ENTRY, EM_X86_64, ET_CORE,
+    EV_CURRENT, PF_R, PF_W, PF_X, PT_LOAD, PT_NOTE,
+};
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::mem;
+use std::ops::Range;
+use std::os::unix::fs::{FileExt, MetadataExt};
+use std::path::{Path, PathBuf};
+
+// Core files in the layout the Linux kernel writes them: an ELF header, a PT_NOTE segment
+// describing the process and its threads, and a PT_LOAD segment for every memory mapping.
+
+pub(crate) const NT_PRSTATUS: u32 = 1;
+pub(crate) const NT_PRFPREG: u32 = 2;
+pub(crate) const NT_PRPSINFO: u32 = 3;
+pub(crate) const NT_AUXV: u32 = 6;
+pub(crate) const NT_FILE: u32 = 0x4649_4c45;
+
+const PAGE_SIZE: u64 = 4096;
+// Memory is copied into the file in pieces of this size, which keeps large mappings from being
+// held in memory all at once
+const CHUNK_SIZE: u64 = 1 << 20;
+
+// The offsets of the fields of struct elf_prstatus which kitt fills in
+const PRSTATUS_SIZE: usize = 336;
+const PRSTATUS_CURSIG: usize = 12;
+const PRSTATUS_PID: usize = 32;
+const PRSTATUS_REGS: usize = 112;
+const PRSTATUS_FPVALID: usize = 328;
+// And of struct elf_prpsinfo
+const PRPSINFO_SIZE: usize = 136;
+const PRPSINFO_PID: usize = 24;
+const PRPSINFO_FNAME: usize = 40;
+const PRPSINFO_PSARGS: usize = 56;
+
+pub(crate) struct Note {
+    pub name: String,
+    pub kind: u32,
+    pub desc: Vec<u8>,
+}
+
+fn align(value: u64, alignment: u64) -> u64 {
+    value.div_ceil(alignment) * alignment
+}
+
+fn encode_note(out: &mut Vec<u8>, note: &Note) {
+    let name_size = note.name.len() + 1;
+    out.extend((name_size as u32).to_le_bytes());
+    out.extend((note.desc.len() as u32).to_le_bytes());
+    out.extend(note.kind.to_le_bytes());
+    out.extend(note.name.as_bytes());
+    out.push(0);
+    out.resize(align(out.len() as u64, 4) as usize, 0);
+    out.extend(&note.desc);
+    out.resize(align(out.len() as u64, 4) as usize, 0);
+}
+
+pub(crate) fn parse_notes(data: &[u8]) -> Result<Vec<Note>> {
+    let mut notes = Vec::new();
+    let mut offset = 0;
+    while offset + 12 <= data.len() {
+        let word = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+        let (name_size, desc_size, kind) = (
+            word(offset) as usize,
+            word(offset + 4) as usize,
+            word(offset + 8),
+        );
+        let name_start = offset + 12;
+        let desc_start = name_start + align(name_size as u64, 4) as usize;
+        let end = desc_start + desc_size;
+        if end > data.len() {
+            bail!("note at {offset:#x} runs past the end of the notes");
+        }
+        let name = &data[name_start..name_start + name_size];
+        notes.push(Note {
+            name: String::from_utf8_lossy(name.strip_suffix(&[0]).unwrap_or(name)).into_owned(),
+            kind,
+            desc: data[desc_start..end].to_vec(),
+        });
+        offset = align(end as u64, 4) as usize;
+    }
+    Ok(notes)
+}
+
+fn prstatus(thread: &ThreadRegisters, ppid: i32) -> Vec<u8> {
+    let mut desc = vec![0u8; PRSTATUS_SIZE];
+    desc[PRSTATUS_CURSIG..PRSTATUS_CURSIG + 2]
+        .copy_from_slice(&(thread.signal as u16).to_le_bytes());
+    desc[PRSTATUS_PID..PRSTATUS_PID + 4].copy_from_slice(&thread.tid.as_raw().to_le_bytes());
+    desc[PRSTATUS_PID + 4..PRSTATUS_PID + 8].copy_from_slice(&ppid.to_le_bytes());
+    let regs = struct_bytes(&thread.regs);
+    desc[PRSTATUS_REGS..PRSTATUS_REGS + regs.len()].copy_from_slice(&regs);
+    desc[PRSTATUS_FPVALID..PRSTATUS_FPVALID + 4].copy_from_slice(&1u32.to_le_bytes());
+    desc
+}
+
+pub(crate) fn prstatus_pid(desc: &[u8]) -> Result<Pid> {
+    Ok(Pid::from_raw(read_struct(desc, PRSTATUS_PID)?))
+}
+
+pub(crate) fn prstatus_regs(desc: &[u8]) -> Result<user_regs_struct> {
+    read_struct(desc, PRSTATUS_REGS)
+}
+
+fn prpsinfo(pid: Pid, ppid: i32) -> Result<Vec<u8>> {
+    let mut desc = vec![0u8; PRPSINFO_SIZE];
+    // The state is stopped, shown as `t` for traced
+    desc[0] = 3;
+    desc[1] = b't';
+    desc[PRPSINFO_PID..PRPSINFO_PID + 4].copy_from_slice(&pid.as_raw().to_le_bytes());
+    desc[PRPSINFO_PID + 4..PRPSINFO_PID + 8].copy_from_slice(&ppid.to_le_bytes());
+
+    let comm = fs::read_to_string(format!("/proc/{pid}/comm"))?;
+    let comm = comm.trim_end().as_bytes();
+    let length = comm.len().min(15);
+    desc[PRPSINFO_FNAME..PRPSINFO_FNAME + length].copy_from_slice(&comm[..length]);
+    // The command line with its arguments separated by spaces
+    let mut args = fs::read(format!("/proc/{pid}/cmdline"))?;
+    while args.last() == Some(&0) {
+        args.pop();
+    }
+    let args: Vec<u8> = args
+        .iter()
+        .map(|&b| if b == 0 { b' ' } else { b })
+        .collect();
+    let length = args.len().min(79);
+    desc[PRPSINFO_PSARGS..PRPSINFO_PSARGS + length].copy_from_slice(&args[..length]);
+    Ok(desc)
+}
+
+// The file backed mappings: a count and the page size, then the start, end and page offset of
+// each mapping, then their paths
+fn file_note(regions: &[MemoryRegion]) -> Vec<u8> {
+    let files: Vec<&MemoryRegion> = regions.iter().filter(|r| r.is_file_backed()).collect();
+    let mut desc = Vec::new();
+    desc.extend((files.len() as u64).to_le_bytes());
+    desc.extend(PAGE_SIZE.to_le_bytes());
+    for region in &files {
+        desc.extend(region.range.start.to_le_bytes());
+        desc.extend(region.range.end.to_le_bytes());
+        desc.extend((region.offset / PAGE_SIZE).to_le_bytes());
+    }
+    for region in &files {
+        desc.extend(region.path.as_bytes());
+        desc.push(0);
+    }
+    desc
+}
+
+fn parent_pid(pid: Pid) -> Result<i32> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat"))?;
+    // The command name may contain spaces, so the fields are counted from its closing paren
+    let after_name = &stat[stat.rfind(')').map_or(0, |end| end + 1)..];
+    Ok(after_name
+        .split_ascii_whitespace()
+        .nth(1)
+        .and_then(|ppid| ppid.parse().ok())
+        .unwrap_or(0))
+}
+
+fn segment_flags(region: &MemoryRegion) -> u32 {
+    let permissions = region.permissions;
+    let mut flags = 0;
+    if permissions.read {
+        flags |= PF_R;
+    }
+    if permissions.write {
+        flags |= PF_W;
+    }
+    if permissions.execute {
+        flags |= PF_X;
+    }
+    flags
+}
+
+/// Writes a core file of a stopped process. Mappings which cannot be read, such as guard pages,
+/// are recorded with no contents, and pages which fail to read are written as zeros.
+pub fn write_core(process: &Process, path: &Path) -> Result<()> {
+    let pid = process.pid;
+    // The other threads stay stopped until the core is written, so that their registers and
+    // the memory agree
+    let stopped = process.stop_threads()?;
+    let regions = maps::read_maps(pid)?;
+    let ppid = parent_pid(pid)?;
+
+    let mut notes = vec![Note {
+        name: "CORE".to_string(),
+        kind: NT_PRPSINFO,
+        desc: prpsinfo(pid, ppid)?,
+    }];
+    // Each thread gets its registers, the first of them being the thread the process stopped in
+    for thread in process.thread_registers(&stopped)? {
+        notes.push(Note {
+            name: "CORE".to_string(),
+            kind: NT_PRSTATUS,
+            desc: prstatus(&thread, ppid),
+        });
+        notes.push(Note {
+            name: "CORE".to_string(),
+            kind: NT_PRFPREG,
+            desc: struct_bytes(&thread.fp_regs),
+        });
+    }
+    notes.push(Note {
+        name: "CORE".to_string(),
+        kind: NT_AUXV,
+        desc: fs::read(format!("/proc/{pid}/auxv"))?,
+    });
+    notes.push(Note {
+        name: "CORE".to_string(),
+        kind: NT_FILE,
+        desc: file_note(&regions),
+    });
+    let mut note_data = Vec::new();
+    for note in &notes {
+        encode_note(&mut note_data, note);
+    }
+
+    let header_size = mem::size_of::<Elf64_Ehdr>() as u64;
+    let phdr_size = mem::size_of::<Elf64_Phdr>() as u64;
+    let phnum = 1 + regions.len() as u64;
+    let notes_offset = header_size + phnum * phdr_size;
+
+    let mut header: Elf64_Ehdr = unsafe { mem::zeroed() };
+    header.e_ident[..4].copy_from_slice(b"\x7fELF");
+    // 64 bit, little endian, the current version and the System V ABI
+    header.e_ident[4] = 2;
+    header.e_ident[5] = 1;
+    header.e_ident[6] = EV_CURRENT as u8;
+    header.e_type = ET_CORE;
+    header.e_machine = EM_X86_64;
+    header.e_version = EV_CURRENT;
+    header.e_phoff = header_size;
+    header.e_ehsize = header_size as u16;
+    header.e_phentsize = phdr_size as u16;
+    header.e_phnum = phnum as u16;
+
+    let mut program_headers = vec![Elf64_Phdr {
+        p_type: PT_NOTE,
+        p_flags: 0,
+        p_offset: notes_offset,
+        p_vaddr: 0,
+        p_paddr: 0,
+        p_filesz: note_data.len() as u64,
+        p_memsz: 0,
+        p_align: 4,
+    }];
+    let mut offset = align(notes_offset + note_data.len() as u64, PAGE_SIZE);
+    for region in &regions {
+        let size = region.range.end - region.range.start;
+        let file_size = if region.permissions.read { size } else { 0 };
+        program_headers.push(Elf64_Phdr {
+            p_type: PT_LOAD,
+            p_flags: segment_flags(region),
+            p_offset: offset,
+            p_vaddr: region.range.start,
+            p_paddr: 0,
+            p_filesz: file_size,
+            p_memsz: size,
+            p_align: PAGE_SIZE,
+        });
+        offset += file_size;
+    }
+
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(&struct_bytes(&header))?;
+    for program_header in &program_headers {
+        out.write_all(&struct_bytes(program_header))?;
+    }
+    out.write_all(&note_data)?;
+    let padding = program_headers[1..].first().map_or(0, |first| {
+        first.p_offset - notes_offset - note_data.len() as u64
+    });
+    out.write_all(&vec![0; padding as usize])?;
+
+    for program_header in &program_headers[1..] {
+        let start = program_header.p_vaddr;
+        let end = start + program_header.p_filesz;
+        let mut address = start;
+        while address < end {
+            let amount = CHUNK_SIZE.min(end - address) as usize;
+            // The int3s of breakpoint sites are not part of the program
+            let data = process
+                .read_memory_without_sites(address, amount)
+                .unwrap_or_else(|_| vec![0; amount]);
+            out.write_all(&data)?;
+            address += amount as u64;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+// A PT_LOAD segment of a core file. Mappings of files may have been left out of the core, in
+// which case their contents are read from the file itself.
+struct Segment {
+    range: Range<u64>,
+    offset: u64,
+    file_size: u64,
+    flags: u32,
+}
+
+// An entry of the NT_FILE note
+struct MappedFile {
+    range: Range<u64>,
+    offset: u64,
+    path: String,
+}
+
+/// A process as saved in a core file, whether by the kernel or by gcore. It cannot run, but its
+/// registers, memory and loaded objects can be inspected like those of a live process.
+pub struct CoreFile {
+    data: Vec<u8>,
+    pid: Pid,
+    segments: Vec<Segment>,
+    files: Vec<MappedFile>,
+    // The registers of each thread, the thread which stopped first
+    threads: Vec<(Pid, Registers)>,
+    auxv: HashMap<u64, u64>,
+    executable: Option<PathBuf>,
+    signal: Option<Signal>,
+    command: String,
+}
+
+fn parse_file_note(desc: &[u8]) -> Result<Vec<MappedFile>> {
+    let word = |index: usize| read_struct::<u64>(desc, index * 8);
+    let (count, page_size) = (word(0)? as usize, word(1)?);
+    let mut names = desc
+        .get(16 + count * 24..)
+        .ok_or_else(|| anyhow!("truncated NT_FILE note"))?
+        .split(|&b| b == 0);
+    let mut files = Vec::with_capacity(count);
+    for index in 0..count {
+        let entry = 2 + index * 3;
+        let path = names
+            .next()
+            .ok_or_else(|| anyhow!("NT_FILE note is missing the path of mapping {index}"))?;
+        files.push(MappedFile {
+            range: word(entry)?..word(entry + 1)?,
+            offset: word(entry + 2)? * page_size,
+            path: String::from_utf8_lossy(path).into_owned(),
+        });
+    }
+    Ok(files)
+}
+
+impl CoreFile {
+    /// Opens a core file. The executable is found from the mapped files unless it is given.
+    pub fn open(path: &Path, executable: Option<PathBuf>) -> Result<Self> {
+        let data = fs::read(path)?;
+        let header: Elf64_Ehdr = read_struct(&data, 0)?;
+        if data[..4] != *b"\x7fELF" || header.e_type != ET_CORE {
+            bail!("{} is not a core file", path.display());
+        }
+
+        let mut core = CoreFile {
+            data: Vec::new(),
+            pid: Pid::from_raw(0),
+            segments: Vec::new(),
+            files: Vec::new(),
+            threads: Vec::new(),
+            auxv: HashMap::new(),
+            executable,
+            signal: None,
+            command: String::new(),
+        };
+        let mut notes = Vec::new();
+        for i in 0..header.e_phnum as usize {
+            let offset = header.e_phoff as usize + i * header.e_phentsize as usize;
+            let program_header: Elf64_Phdr = read_struct(&data, offset)?;
+            let start = program_header.p_offset as usize;
+            let end = start + program_header.p_filesz as usize;
+            if end > data.len() {
+                bail!("{} is truncated", path.display());
+            }
+            match program_header.p_type {
+                PT_NOTE => notes.extend(parse_notes(&data[start..end])?),
+                PT_LOAD => core.segments.push(Segment {
+                    range: program_header.p_vaddr..program_header.p_vaddr + program_header.p_memsz,
+                    offset: program_header.p_offset,
+                    file_size: program_header.p_filesz,
+                    flags: program_header.p_flags,
+                }),
+                _ => {}
+            }
+        }
+
+        for note in notes.iter().filter(|note| note.name == "CORE") {
+            match note.kind {
+                NT_PRSTATUS => {
+                    let mut user = Registers::default().user_data();
+                    user.regs = prstatus_regs(&note.desc)?;
+                    let mut registers = Registers::default();
+                    registers.set_user_data(user);
+                    core.threads.push((prstatus_pid(&note.desc)?, registers));
+                    if core.threads.len() == 1 {
+                        let signal: u16 = read_struct(&note.desc, PRSTATUS_CURSIG)?;
+                        core.signal = Signal::try_from(signal as i32).ok();
+                    }
+                }
+                // The floating point registers follow the NT_PRSTATUS of their thread
+                NT_PRFPREG => {
+                    if let Some((_, registers)) = core.threads.last_mut() {
+                        let mut user = registers.user_data();
+                        user.i387 = read_struct::<user_fpregs_struct>(&note.desc, 0)?;
+                        registers.set_user_data(user);
+                    }
+                }
+                NT_PRPSINFO => {
+                    core.pid = Pid::from_raw(read_struct(&note.desc, PRPSINFO_PID)?);
+                    let args = note
+                        .desc
+                        .get(PRPSINFO_PSARGS..)
+                        .ok_or_else(|| anyhow!("NT_PRPSINFO note is too short"))?;
+                    let end = args.iter().position(|&b| b == 0).unwrap_or(args.len());
+                    core.command = String::from_utf8_lossy(&args[..end]).into_owned();
+                }
+                NT_AUXV => {
+                    core.auxv = note
+                        .desc
+                        .chunks_exact(16)
+                        .map(|entry| Ok((read_struct(entry, 0)?, read_struct(entry, 8)?)))
+                        .collect::<Result<_>>()?;
+                }
+                NT_FILE => core.files = parse_file_note(&note.desc)?,
+                _ => {}
+            }
+        }
+        let Some(&(first_thread, _)) = core.threads.first() else {
+            bail!("{} has no threads", path.display());
+        };
+        if core.pid.as_raw() == 0 {
+            core.pid = first_thread;
+        }
+        core.data = data;
+        Ok(core)
+    }
+
+    /// The signal which stopped the process, usually the one which killed it
+    pub fn signal(&self) -> Option<Signal> {
+        self.signal
+    }
+
+    /// The command line the process was started with, as far as the core recorded it
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    // Reads as much of `amount` bytes at the address as lies in a single segment
+    fn read_piece(&self, address: u64, amount: usize) -> Result<Vec<u8>> {
+        let not_mapped = || anyhow!("cannot access memory at {address:#x}");
+        let segment = self
+            .segments
+            .iter()
+            .find(|segment| segment.range.contains(&address))
+            .ok_or_else(not_mapped)?;
+        let amount = amount.min((segment.range.end - address) as usize);
+        let in_segment = address - segment.range.start;
+        if in_segment < segment.file_size {
+            let amount = amount.min((segment.file_size - in_segment) as usize);
+            let start = (segment.offset + in_segment) as usize;
+            return Ok(self.data[start..start + amount].to_vec());
+        }
+        let file = self
+            .files
+            .iter()
+            .find(|file| file.range.contains(&address))
+            .ok_or_else(not_mapped)?;
+        let amount = amount.min((file.range.end - address) as usize);
+        let mut data = vec![0; amount];
+        File::open(&file.path)
+            .and_then(|f| f.read_exact_at(&mut data, file.offset + address - file.range.start))
+            .map_err(|err| {
+                anyhow!(
+                    "cannot access memory at {address:#x} in {}: {err}",
+                    file.path
+                )
+            })?;
+        Ok(data)
+    }
+}
+
+impl MemoryReader for CoreFile {
+    fn read_memory(&self, address: u64, amount: usize) -> Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(amount);
+        while data.len() < amount {
+            let piece = self.read_piece(address + data.len() as u64, amount - data.len())?;
+            data.extend(piece);
+        }
+        Ok(data)
+    }
+}
+
+impl Target for CoreFile {
+    fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    fn registers(&self) -> &Registers {
+        &self.threads[0].1
+    }
+
+    // The executable is the mapped file which holds the entry point
+    fn executable_path(&self) -> Result<PathBuf> {
+        if let Some(executable) = &self.executable {
+            return Ok(executable.clone());
+        }
+        let entry = self.auxv.get(&AT_ENTRY).copied().unwrap_or(0);
+        self.files
+            .iter()
+            .find(|file| file.range.contains(&entry))
+            .map(|file| PathBuf::from(&file.path))
+            .ok_or_else(|| anyhow!("the core file does not name the executable"))
+    }
+
+    fn read_auxv(&self) -> Result<HashMap<u64, u64>> {
+        Ok(self.auxv.clone())
+    }
+
+    fn threads(&self) -> Result<Vec<Pid>> {
+        Ok(self.threads.iter().map(|&(tid, _)| tid).collect())
+    }
+
+    fn thread_stack_pointer(&self, tid: Pid) -> Result<Option<u64>> {
+        let rsp = lookup_register_info_by_id(RegisterId::RSP)?;
+        self.threads
+            .iter()
+            .find(|&&(thread, _)| thread == tid)
+            .map(|(_, registers)| registers.read_as_u64(rsp))
+            .transpose()
+    }
+
+    fn memory_regions(&self) -> Result<Vec<MemoryRegion>> {
+        Ok(self
+            .segments
+            .iter()
+            .map(|segment| {
+                let file = self
+                    .files
+                    .iter()
+                    .find(|file| file.range.start == segment.range.start);
+                MemoryRegion {
+                    range: segment.range.clone(),
+                    permissions: Permissions {
+                        read: segment.flags & PF_R != 0,
+                        write: segment.flags & PF_W != 0,
+                        execute: segment.flags & PF_X != 0,
+                        shared: false,
+                    },
+                    offset: file.map_or(0, |file| file.offset),
+                    device: "00:00".to_string(),
+                    inode: file
+                        .and_then(|file| fs::metadata(&file.path).ok())
+                        .map_or(0, |metadata| metadata.ino()),
+                    path: file.map(|file| file.path.clone()).unwrap_or_default(),
+                    rss: None,
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::corefile::{
+        parse_notes, prstatus_pid, prstatus_regs, write_core, CoreFile, NT_AUXV, NT_FILE,
+        NT_PRFPREG, NT_PRPSINFO, NT_PRSTATUS,
+    };
+    use crate::debugger::Debugger;
+    use crate::dwarf::expr::MemoryReader;
+    use crate::elf::read_struct;
+    use crate::expression::{evaluate, format_value};
+    use crate::process::{DebugProcess, Process};
+    use nix::libc::{Elf64_Ehdr, Elf64_Phdr, ET_CORE, PT_LOAD, PT_NOTE};
+    use nix::sys::signal::Signal;
+    use std::path::PathBuf;
+    use std::{env, fs};
+
+    // A debugger stopped at the int3 in variables::inspect, and a core file written of it
+    fn stopped_with_core() -> (Debugger, PathBuf) {
+        let process = Process::launch("target/debug/variables", DebugProcess::YES).unwrap();
+        let mut debugger = Debugger::new(process).unwrap();
+        debugger.continue_execution().unwrap();
+
+        let path = env::temp_dir().join(format!("kitt-core-{}", debugger.target.pid()));
+        write_core(debugger.target.process().unwrap(), &path).unwrap();
+        (debugger, path)
+    }
+
+    #[test]
+    fn core_files_hold_registers_and_memory() {
+        let (debugger, path) = stopped_with_core();
+        let data = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let header: Elf64_Ehdr = read_struct(&data, 0).unwrap();
+        assert_eq!(header.e_type, ET_CORE);
+        let segments: Vec<Elf64_Phdr> = (0..header.e_phnum as usize)
+            .map(|i| read_struct(&data, header.e_phoff as usize + i * 56).unwrap())
+            .collect();
+        assert_eq!(segments[0].p_type, PT_NOTE);
+        assert!(segments[1..].iter().all(|s| s.p_type == PT_LOAD));
+
+        let notes_start = segments[0].p_offset as usize;
+        let notes =
+            parse_notes(&data[notes_start..notes_start + segments[0].p_filesz as usize]).unwrap();
+        let kinds: Vec<u32> = notes.iter().map(|note| note.kind).collect();
+        assert_eq!(
+            kinds,
+            [NT_PRPSINFO, NT_PRSTATUS, NT_PRFPREG, NT_AUXV, NT_FILE]
+        );
+        assert!(notes.iter().all(|note| note.name == "CORE"));
+        assert_eq!(prstatus_pid(&notes[1].desc).unwrap(), debugger.target.pid());
+        let regs = prstatus_regs(&notes[1].desc).unwrap();
+        assert_eq!(regs.rip, debugger.pc().unwrap());
+
+        // The bytes at the pc are in the segment which covers it
+        let text = segments[1..]
+            .iter()
+            .find(|s| (s.p_vaddr..s.p_vaddr + s.p_filesz).contains(&regs.rip))
+            .unwrap();
+        let offset = (text.p_offset + regs.rip - text.p_vaddr) as usize;
+        let expected = debugger.target.read_memory(regs.rip, 16).unwrap();
+        assert_eq!(&data[offset..offset + 16], &expected[..]);
+    }
+
+    #[test]
+    fn cores_are_debugged_like_the_live_process() {
+        let (mut live, path) = stopped_with_core();
+        let core = CoreFile::open(&path, None).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(core.command(), "target/debug/variables");
+        let mut dead = Debugger::new(core).unwrap();
+
+        assert_eq!(dead.target.pid(), live.target.pid());
+        assert_eq!(dead.shared_libraries().len(), live.shared_libraries().len());
+        let describe = |debugger: &Debugger| {
+            let frames = debugger.backtrace().unwrap();
+            frames
+                .iter()
+                .enumerate()
+                .map(|(index, frame)| debugger.describe_frame(index, frame))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(describe(&dead), describe(&live));
+
+        let print = |debugger: &mut Debugger, text| {
+            let value = evaluate(text, debugger).unwrap();
+            format_value(&value, debugger).unwrap()
+        };
+        assert_eq!(print(&mut dead, "factor"), print(&mut live, "factor"));
+        assert_eq!(
+            print(&mut dead, "*(long *)$rsp"),
+            print(&mut live, "*(long *)$rsp")
+        );
+
+        // A core cannot run or be changed
+        assert!(dead.continue_execution().is_err());
+        assert!(dead.set_breakpoint("variables::inspect").is_err());
+        assert!(evaluate("$rax = 1", &mut dead).is_err());
+    }
+
+    #[test]
+    fn cores_hold_the_bytes_under_breakpoints_and_the_stop_signal() {
+        let process = Process::launch("target/debug/variables", DebugProcess::YES).unwrap();
+        let mut debugger = Debugger::new(process).unwrap();
+        let breakpoint = debugger.set_breakpoint("variables::main").unwrap();
+        let address = breakpoint.addresses[0];
+        debugger.continue_execution().unwrap();
+        let process = debugger.target.process().unwrap();
+        assert_eq!(process.read_memory(address, 1).unwrap(), [0xcc]);
+
+        let path = env::temp_dir().join(format!("kitt-core-{}", debugger.target.pid()));
+        write_core(process, &path).unwrap();
+        let core = CoreFile::open(&path, None).unwrap();
+        fs::remove_file(&path).unwrap();
+        let original = debugger.target.read_memory(address, 1).unwrap();
+        assert_ne!(original, [0xcc]);
+        assert_eq!(core.read_memory(address, 1).unwrap(), original);
+        assert_eq!(core.signal(), Some(Signal::SIGTRAP));
+    }
+}
@@ -0,0 +1,600 @@
+use crate::console;
+use anyhow::{anyhow, bail, Result};
+use kitt::debugger::{Debugger, VariableKind};
+use kitt::process::{DebugProcess, Process, StopReason};
+use kitt::reginfo::{registers_of_kind, RegisterKind};
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
+use serde_json::{json, Value};
+use std::io::{self, BufRead, BufReader, Write};
+
+// The Debug Adapter Protocol, through which editors such as VS Code drive kitt. Messages are
+// JSON objects preceded by a Content-Length header. The adapter answers each request in turn,
+// and runs the program synchronously, so a request to continue is answered before the program
+// runs and is followed by the event for where it stopped.
+//
+// kitt does not read line tables, so breakpoints are set on functions and instructions rather
+// than source lines, and stepping is by instruction.
+
+// Register groups shown as scopes, each with its variables reference
+const REGISTER_SCOPES: [(&str, RegisterKind, i64); 4] = [
+    ("General Purpose Registers", RegisterKind::GeneralPurpose, 3),
+    ("Sub-registers", RegisterKind::SubGeneralPurpose, 4),
+    ("Floating Point Registers", RegisterKind::FloatingPoint, 5),
+    ("Debug Registers", RegisterKind::Debug, 6),
+];
+const ARGUMENTS_REFERENCE: i64 = 1;
+const LOCALS_REFERENCE: i64 = 2;
+
+pub struct DapServer<R, W> {
+    input: R,
+    output: W,
+    seq: i64,
+    debugger: Option<Debugger>,
+    stop_on_entry: bool,
+    // Breakpoints set through each request, which replaces those it set before
+    function_breakpoints: Vec<usize>,
+    instruction_breakpoints: Vec<usize>,
+    done: bool,
+}
+
+// Reads the next message, or None at the end of the input
+fn read_message(input: &mut impl BufRead) -> Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            length = Some(value.trim().parse::<usize>()?);
+        }
+    }
+    let length = length.ok_or_else(|| anyhow!("message without Content-Length"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+// Runs the adapter over stdin and stdout
+pub fn serve_stdio() -> Result<()> {
+    let output = console::take_stdout()?;
+    DapServer::new(BufReader::new(io::stdin()), output).serve()
+}
+
+// The location of an instruction breakpoint, an address given in hex and an offset from it
+fn instruction_location(reference: &str, breakpoint: &Value) -> Result<String> {
+    let offset = breakpoint["offset"].as_i64().unwrap_or(0);
+    let address = u64::from_str_radix(reference.trim_start_matches("0x"), 16)
+        .map_err(|_| anyhow!("invalid instruction reference \"{reference}\""))?;
+    Ok(format!("*{:#x}", address.wrapping_add_signed(offset)))
+}
+
+impl<R: BufRead, W: Write> DapServer<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self {
+            input,
+            output,
+            seq: 0,
+            debugger: None,
+            stop_on_entry: false,
+            function_breakpoints: Vec::new(),
+            instruction_breakpoints: Vec::new(),
+            done: false,
+        }
+    }
+
+    // Answers requests until the client disconnects or the input ends
+    pub fn serve(&mut self) -> Result<()> {
+        while !self.done {
+            let Some(message) = read_message(&mut self.input)? else {
+                break;
+            };
+            if message["type"] == "request" {
+                self.handle(&message)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, mut message: Value) -> Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        let body = serde_json::to_vec(&message)?;
+        write!(self.output, "Content-Length: {}\r\n\r\n", body.len())?;
+        self.output.write_all(&body)?;
+        self.output.flush()?;
+        Ok(())
+    }
+
+    fn respond(&mut self, request: &Value, result: Result<Value>) -> Result<()> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(body) => response["body"] = body,
+            Err(err) => response["message"] = json!(err.to_string()),
+        }
+        self.send(response)
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> Result<()> {
+        self.send(json!({"type": "event", "event": event, "body": body}))
+    }
+
+    fn debugger(&mut self) -> Result<&mut Debugger> {
+        self.debugger
+            .as_mut()
+            .ok_or_else(|| anyhow!("no program is being debugged"))
+    }
+
+    fn handle(&mut self, request: &Value) -> Result<()> {
+        let arguments = &request["arguments"];
+        let command = request["command"].as_str().unwrap_or_default();
+        match command {
+            "initialize" => {
+                // Source breakpoints are out of scope, as kitt reads no line tables. DAP has no
+                // capability for them since every adapter is expected to take them, so kitt
+                // states it with a field of its own, and setBreakpoints refuses each one.
+                let capabilities = json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsSourceBreakpoints": false,
+                    "supportsFunctionBreakpoints": true,
+                    "supportsInstructionBreakpoints": true,
+                    "supportsConditionalBreakpoints": true,
+                    "supportsHitConditionalBreakpoints": true,
+                });
+                self.respond(request, Ok(capabilities))
+            }
+            "launch" | "attach" => {
+                let result = self.start(command, arguments);
+                let started = result.is_ok();
+                self.respond(request, result.map(|_| Value::Null))?;
+                // Breakpoints are only taken once there is a program to set them in
+                if started {
+                    self.event("initialized", Value::Null)?;
+                }
+                Ok(())
+            }
+            "configurationDone" => {
+                self.respond(request, Ok(Value::Null))?;
+                if self.stop_on_entry {
+                    let stop = self.entry_stop()?;
+                    self.report_stop(&stop, "entry")
+                } else {
+                    self.run(Debugger::continue_execution, "breakpoint")
+                }
+            }
+            "setBreakpoints" => {
+                // Without line tables source breakpoints cannot be resolved, so none is set
+                let breakpoints: Vec<Value> = (arguments["breakpoints"].as_array())
+                    .into_iter()
+                    .flatten()
+                    .map(|breakpoint| {
+                        json!({
+                            "verified": false,
+                            "line": breakpoint["line"],
+                            "message": "kitt cannot set breakpoints on source lines, only on \
+                                        functions and instructions",
+                        })
+                    })
+                    .collect();
+                self.respond(request, Ok(json!({"breakpoints": breakpoints})))
+            }
+            "setFunctionBreakpoints" => {
+                let result = self.replace_breakpoints(arguments, false);
+                self.respond(request, result)
+            }
+            "setInstructionBreakpoints" => {
+                let result = self.replace_breakpoints(arguments, true);
+                self.respond(request, result)
+            }
+            "threads" => {
+                let result = self.threads();
+                self.respond(request, result)
+            }
+            "stackTrace" => {
+                let result = self.stack_trace(arguments);
+                self.respond(request, result)
+            }
+            "scopes" => {
+                let result = self.scopes(arguments["frameId"].as_i64().unwrap_or_default());
+                self.respond(request, result)
+            }
+            "variables" => {
+                let reference = arguments["variablesReference"].as_i64().unwrap_or_default();
+                let result = self.variables(reference);
+                self.respond(request, result)
+            }
+            "continue" => {
+                self.respond(request, Ok(json!({"allThreadsContinued": true})))?;
+                self.run(Debugger::continue_execution, "breakpoint")
+            }
+            "next" => {
+                self.respond(request, Ok(Value::Null))?;
+                self.run(Debugger::step_over_instruction, "step")
+            }
+            "stepIn" => {
+                self.respond(request, Ok(Value::Null))?;
+                self.run(Debugger::step_instruction, "step")
+            }
+            "disconnect" => {
+                // A launched program is killed and an attached one left running, as the
+                // process does when dropped
+                self.debugger = None;
+                self.done = true;
+                self.respond(request, Ok(Value::Null))
+            }
+            _ => self.respond(request, Err(anyhow!("unsupported request {command}"))),
+        }
+    }
+
+    fn start(&mut self, command: &str, arguments: &Value) -> Result<()> {
+        let process = if command == "launch" {
+            let program = arguments["program"]
+                .as_str()
+                .ok_or_else(|| anyhow!("launch needs a program"))?;
+            let args: Vec<String> = (arguments["args"].as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|arg| arg.as_str().map(str::to_string))
+                .collect();
+            self.stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or(false);
+            Process::launch_with_args(program, &args, DebugProcess::YES)?
+        } else {
+            let pid = arguments["pid"]
+                .as_i64()
+                .ok_or_else(|| anyhow!("attach needs a pid"))?;
+            // An attached program stays stopped where it was, for the user to look at
+            self.stop_on_entry = true;
+            Process::attach(Pid::from_raw(pid as i32))?
+        };
+        self.debugger = Some(Debugger::new(process)?);
+        Ok(())
+    }
+
+    // The reason reported for a stop the adapter did not cause
+    fn entry_stop(&mut self) -> Result<StopReason> {
+        let pid = self.debugger()?.target().pid();
+        Ok(StopReason::new(nix::sys::wait::WaitStatus::Stopped(
+            pid,
+            Signal::SIGSTOP,
+        )))
+    }
+
+    // Sets the function or instruction breakpoints of a request in place of the earlier ones
+    fn replace_breakpoints(&mut self, arguments: &Value, instructions: bool) -> Result<Value> {
+        let old = match instructions {
+            true => std::mem::take(&mut self.instruction_breakpoints),
+            false => std::mem::take(&mut self.function_breakpoints),
+        };
+        let debugger = self.debugger()?;
+        for id in old {
+            debugger.delete_breakpoint(id)?;
+        }
+        let mut ids = Vec::new();
+        let mut replies = Vec::new();
+        for breakpoint in arguments["breakpoints"].as_array().into_iter().flatten() {
+            let location = match instructions {
+                true => breakpoint["instructionReference"]
+                    .as_str()
+                    .map(|reference| instruction_location(reference, breakpoint)),
+                false => breakpoint["name"].as_str().map(|name| Ok(name.to_string())),
+            };
+            let Some(location) = location else {
+                replies.push(json!({"verified": false, "message": "no location given"}));
+                continue;
+            };
+            let result =
+                location.and_then(|location| Self::set_breakpoint(debugger, &location, breakpoint));
+            match result {
+                Ok((id, verified)) => {
+                    ids.push(id);
+                    replies.push(json!({"id": id, "verified": verified}));
+                }
+                Err(err) => replies.push(json!({"verified": false, "message": err.to_string()})),
+            }
+        }
+        match instructions {
+            true => self.instruction_breakpoints = ids,
+            false => self.function_breakpoints = ids,
+        }
+        Ok(json!({"breakpoints": replies}))
+    }
+
+    // Sets a breakpoint with the condition and hit count given, returning its id and whether
+    // it resolved
+    fn set_breakpoint(
+        debugger: &mut Debugger,
+        location: &str,
+        breakpoint: &Value,
+    ) -> Result<(usize, bool)> {
+        // Only plain counts are supported: a hit condition of n stops at the nth hit. It is
+        // checked first, so that a breakpoint which is refused is not left behind.
+        let hits = breakpoint["hitCondition"].as_str().map(|hits| {
+            hits.trim()
+                .parse::<usize>()
+                .map_err(|_| anyhow!("unsupported hit condition \"{hits}\", expected a count"))
+        });
+        let hits = hits.transpose()?;
+        let set = debugger.set_breakpoint(location)?;
+        let (id, verified) = (set.id, !set.addresses.is_empty());
+        if let Some(condition) = breakpoint["condition"].as_str() {
+            debugger.set_breakpoint_condition(id, Some(condition.to_string()))?;
+        }
+        if let Some(hits) = hits {
+            debugger.set_breakpoint_ignore_count(id, hits.saturating_sub(1))?;
+        }
+        Ok((id, verified))
+    }
+
+    fn threads(&mut self) -> Result<Value> {
+        let threads: Vec<Value> = (self.debugger()?.target().threads()?.iter())
+            .map(|tid| json!({"id": tid.as_raw(), "name": format!("thread {tid}")}))
+            .collect();
+        Ok(json!({"threads": threads}))
+    }
+
+    fn stack_trace(&mut self, arguments: &Value) -> Result<Value> {
+        let debugger = self.debugger()?;
+        let frames = debugger.backtrace()?;
+        let start = arguments["startFrame"].as_u64().unwrap_or(0) as usize;
+        let levels = match arguments["levels"].as_u64() {
+            Some(0) | None => frames.len(),
+            Some(levels) => levels as usize,
+        };
+        let stack: Vec<Value> = (frames.iter().enumerate())
+            .skip(start)
+            .take(levels)
+            .map(|(index, frame)| {
+                // Callers are named by the call, just before the return address
+                let lookup = if index == 0 {
+                    frame.pc
+                } else {
+                    frame.pc.saturating_sub(1)
+                };
+                json!({
+                    "id": index,
+                    "name": debugger.function_name(lookup).unwrap_or_else(|| "??".to_string()),
+                    "line": 0,
+                    "column": 0,
+                    "instructionPointerReference": format!("{:#x}", frame.pc),
+                })
+            })
+            .collect();
+        Ok(json!({"stackFrames": stack, "totalFrames": frames.len()}))
+    }
+
+    // Variables are only read in the innermost frame, so the frames of callers have no scopes
+    fn scopes(&mut self, frame: i64) -> Result<Value> {
+        if frame != 0 {
+            return Ok(json!({"scopes": []}));
+        }
+        let mut scopes = vec![
+            json!({"name": "Arguments", "variablesReference": ARGUMENTS_REFERENCE, "expensive": false}),
+            json!({"name": "Locals", "variablesReference": LOCALS_REFERENCE, "expensive": false}),
+        ];
+        for (name, _, reference) in REGISTER_SCOPES {
+            scopes.push(json!({"name": name, "variablesReference": reference, "expensive": false}));
+        }
+        Ok(json!({"scopes": scopes}))
+    }
+
+    fn variables(&mut self, reference: i64) -> Result<Value> {
+        let debugger = self.debugger()?;
+        let mut variables = Vec::new();
+        let kind = match reference {
+            ARGUMENTS_REFERENCE => Some(VariableKind::Arguments),
+            LOCALS_REFERENCE => Some(VariableKind::Locals),
+            _ => None,
+        };
+        if let Some(kind) = kind {
+            for variable in debugger.frame_variables(kind)? {
+                let (name, value) = debugger.variable_name_and_value(&variable)?;
+                variables.push(json!({"name": name, "value": value, "variablesReference": 0}));
+            }
+        } else {
+            let (_, kind, _) = REGISTER_SCOPES
+                .iter()
+                .find(|&&(_, _, scope)| scope == reference)
+                .ok_or_else(|| anyhow!("no variables with reference {reference}"))?;
+            let registers = debugger.target().registers();
+            for info in registers_of_kind(*kind) {
+                let value = registers.format(info)?;
+                variables.push(json!({"name": info.name, "value": value, "variablesReference": 0}));
+            }
+        }
+        Ok(json!({"variables": variables}))
+    }
+
+    // Runs the program with one of the debugger's ways of resuming it and reports the stop.
+    // Failures are reported as output, since the request has already been answered.
+    fn run(&mut self, resume: fn(&mut Debugger) -> Result<StopReason>, reason: &str) -> Result<()> {
+        match resume(self.debugger()?) {
+            Ok(stop) => self.report_stop(&stop, reason),
+            Err(err) => self.event(
+                "output",
+                json!({"category": "stderr", "output": format!("{err}\n")}),
+            ),
+        }
+    }
+
+    // Sends the stopped event for a stop, or the exited and terminated events when the program
+    // is gone. Stops with signals other than the breakpoint trap are shown as exceptions.
+    fn report_stop(&mut self, stop: &StopReason, reason: &str) -> Result<()> {
+        let debugger = self.debugger()?;
+        let pid = debugger.target().pid().as_raw();
+        if !stop.is_stopped() {
+            // As shells do, a death by a signal is reported as 128 plus its number
+            let code = match (stop.exit_code(), stop.signal()) {
+                (Some(code), _) => code,
+                (None, Some(signal)) => 128 + signal as i32,
+                (None, None) => bail!("the program stopped for no reason"),
+            };
+            self.event("exited", json!({"exitCode": code}))?;
+            return self.event("terminated", Value::Null);
+        }
+        let mut body = json!({"reason": reason, "threadId": pid, "allThreadsStopped": true});
+        if let Some(breakpoint) = debugger.stopped_breakpoint() {
+            body["reason"] = json!("breakpoint");
+            body["hitBreakpointIds"] = json!([breakpoint.id]);
+        } else if let Some(signal) = stop.signal()
+            && ![Signal::SIGTRAP, Signal::SIGSTOP].contains(&signal)
+        {
+            body["reason"] = json!("exception");
+            body["description"] = json!(format!("received {signal}"));
+        } else if reason == "breakpoint" {
+            // A trap not set by kitt, such as an int3 in the program
+            body["reason"] = json!("pause");
+        }
+        self.event("stopped", body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dap::DapServer;
+    use serde_json::{json, Value};
+    use std::io::{BufReader, Cursor};
+
+    fn encode(messages: &[Value]) -> Vec<u8> {
+        let mut input = Vec::new();
+        for (seq, message) in messages.iter().enumerate() {
+            let mut message = message.clone();
+            message["seq"] = json!(seq + 1);
+            message["type"] = json!("request");
+            let body = serde_json::to_vec(&message).unwrap();
+            input.extend(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes());
+            input.extend(body);
+        }
+        input
+    }
+
+    fn decode(output: &[u8]) -> Vec<Value> {
+        let mut reader = BufReader::new(output);
+        let mut messages = Vec::new();
+        while let Some(message) = super::read_message(&mut reader).unwrap() {
+            messages.push(message);
+        }
+        messages
+    }
+
+    #[test]
+    fn a_session_of_the_debug_adapter_protocol() {
+        let input = encode(&[
+            json!({"command": "initialize", "arguments": {"adapterID": "kitt"}}),
+            json!({"command": "launch", "arguments": {"program": "target/debug/variables"}}),
+            json!({"command": "setFunctionBreakpoints",
+                   "arguments": {"breakpoints": [{"name": "variables::inspect"}]}}),
+            json!({"command": "configurationDone"}),
+            json!({"command": "stackTrace", "arguments": {"threadId": 1}}),
+            json!({"command": "scopes", "arguments": {"frameId": 0}}),
+            json!({"command": "variables", "arguments": {"variablesReference": 1}}),
+            json!({"command": "variables", "arguments": {"variablesReference": 3}}),
+            json!({"command": "stepIn", "arguments": {"threadId": 1}}),
+            json!({"command": "setFunctionBreakpoints", "arguments": {"breakpoints": []}}),
+            json!({"command": "continue", "arguments": {"threadId": 1}}),
+            json!({"command": "continue", "arguments": {"threadId": 1}}),
+            json!({"command": "disconnect"}),
+        ]);
+        let mut output = Vec::new();
+        DapServer::new(Cursor::new(input), &mut output)
+            .serve()
+            .unwrap();
+        let messages = decode(&output);
+
+        let response = |command: &str| {
+            messages
+                .iter()
+                .find(|m| m["type"] == "response" && m["command"] == command)
+                .unwrap()
+        };
+        let events: Vec<&Value> = messages.iter().filter(|m| m["type"] == "event").collect();
+        assert!(
+            messages.iter().all(|m| m["success"] != false),
+            "{messages:?}"
+        );
+        assert_eq!(events[0]["event"], "initialized");
+        assert_eq!(events[1]["body"]["reason"], "breakpoint");
+        assert_eq!(events[2]["body"]["reason"], "step");
+
+        let frames = &response("stackTrace")["body"]["stackFrames"];
+        assert_eq!(frames[0]["name"], "variables::inspect");
+        let arguments = &messages
+            .iter()
+            .find(|m| m["command"] == "variables")
+            .unwrap()["body"]["variables"];
+        // At the entry of inspect the arguments are not yet stored, so only names are known
+        assert_eq!(arguments[0]["name"], "shape");
+        assert_eq!(arguments[1]["name"], "factor");
+        assert_eq!(
+            response("scopes")["body"]["scopes"][2]["variablesReference"],
+            3
+        );
+
+        // The int3 in the program, and then the exit
+        assert_eq!(events[3]["body"]["reason"], "pause");
+        assert_eq!(events[4]["event"], "exited");
+        assert_eq!(events[4]["body"]["exitCode"], 0);
+        assert_eq!(events[5]["event"], "terminated");
+    }
+
+    #[test]
+    fn refused_breakpoints_are_not_set() {
+        let input = encode(&[
+            json!({"command": "initialize", "arguments": {"adapterID": "kitt"}}),
+            json!({"command": "launch", "arguments": {"program": "target/debug/variables"}}),
+            json!({"command": "setFunctionBreakpoints",
+                   "arguments": {"breakpoints": [{"name": "variables::inspect", "hitCondition": ">5"}]}}),
+            json!({"command": "setInstructionBreakpoints",
+                   "arguments": {"breakpoints": [{"instructionReference": "main"}]}}),
+            json!({"command": "setBreakpoints",
+                   "arguments": {"source": {"path": "variables.rs"}, "breakpoints": [{"line": 3}]}}),
+            json!({"command": "configurationDone"}),
+            json!({"command": "disconnect"}),
+        ]);
+        let mut output = Vec::new();
+        DapServer::new(Cursor::new(input), &mut output)
+            .serve()
+            .unwrap();
+        let messages = decode(&output);
+
+        let breakpoint = |command: &str| {
+            let response = messages.iter().find(|m| m["command"] == command).unwrap();
+            response["body"]["breakpoints"][0].clone()
+        };
+        let function = breakpoint("setFunctionBreakpoints");
+        assert_eq!(function["verified"], false);
+        assert_eq!(
+            function["message"],
+            "unsupported hit condition \">5\", expected a count"
+        );
+        let instruction = breakpoint("setInstructionBreakpoints");
+        assert_eq!(instruction["verified"], false);
+        assert_eq!(
+            instruction["message"],
+            "invalid instruction reference \"main\""
+        );
+        let initialize = messages
+            .iter()
+            .find(|m| m["command"] == "initialize")
+            .unwrap();
+        assert_eq!(initialize["body"]["supportsSourceBreakpoints"], false);
+        let source = breakpoint("setBreakpoints");
+        assert_eq!(source["verified"], false);
+        assert_eq!(source["line"], 3);
+
+        // Nothing stops the program before the int3 in inspect
+        let stopped = messages.iter().find(|m| m["event"] == "stopped").unwrap();
+        assert_eq!(stopped["body"]["reason"], "pause");
+    }
+}
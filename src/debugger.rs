@@ -0,0 +1,1603 @@
+//! Debugging sessions over a target.
+
+use crate::breakpoint::Breakpoint;
+use crate::dwarf::consts::{DW_AT_FRAME_BASE, DW_AT_LOCATION};
+use crate::dwarf::expr::{evaluate, read_location, DebugInfo, EvalContext, Location};
+use crate::dwarf::types::{Type, TypeKind};
+use crate::dwarf::{Die, Dwarf};
+use crate::expression::{self, ExpressionContext, Place, ValueType, Variables};
+use crate::maps::MemoryRegion;
+use crate::process::{ProcessEvent, StopReason, SyscallCatchPolicy};
+use crate::reginfo::{
+    lookup_register_by_dwarf, lookup_register_info_by_id, RegisterId, RegisterInfo,
+};
+use crate::registers::Registers;
+use crate::solib::{self, RT_CONSISTENT};
+use crate::symbols::SymbolKind;
+use crate::target::Target;
+use anyhow::{anyhow, bail, Result};
+use nix::libc::{AT_BASE, AT_ENTRY};
+use nix::sys::signal::Signal;
+
+pub use crate::maps::AddressClass;
+pub use crate::module::Module;
+
+// Guards against unwinding forever through a corrupted stack
+const MAX_FRAMES: usize = 512;
+
+/// A debugging session: the traced process, or a core file of one, together with the debug
+/// information of the objects loaded into it
+pub struct Debugger {
+    pub(crate) target: Box<dyn Target>,
+    // The executable, followed by the shared libraries in the order they were loaded
+    modules: Vec<Module>,
+    breakpoints: Vec<Breakpoint>,
+    next_breakpoint_id: usize,
+    // Address of the r_debug structure of the dynamic linker, once known
+    r_debug: Option<u64>,
+    // Where the dynamic linker reports changes to the set of loaded objects. kitt keeps a
+    // breakpoint site there, which is never reported to the user.
+    rendezvous_address: Option<u64>,
+    // The temporary breakpoint deleted when it stopped the process at its last stop
+    temporary_hit: Option<Breakpoint>,
+    // The signals which stop the process, or all of them when not set. Others are passed to
+    // the process without stopping.
+    signal_catch: Option<Vec<Signal>>,
+    // The value history and convenience variables of expressions
+    pub(crate) variables: Variables,
+    // The siginfo of the last stop, for $_siginfo
+    siginfo: Option<Vec<u8>>,
+}
+
+/// Which variables of a frame to list
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum VariableKind {
+    /// The local variables
+    Locals,
+    /// The parameters
+    Arguments,
+}
+
+/// A variable together with the module whose debug information describes it
+#[derive(Clone)]
+pub struct Variable {
+    module: usize,
+    pub(crate) die: Die,
+}
+
+/// A frame of the call stack, innermost first
+pub struct Frame {
+    /// The address the frame is executing, or returns to in outer frames
+    pub pc: u64,
+    /// Canonical frame address, when call frame information covers the pc
+    pub cfa: Option<u64>,
+    /// The registers as unwound to this frame. Only those which the call frame information
+    /// restores are meaningful in outer frames.
+    pub registers: Registers,
+}
+
+// Locations which are addresses rather than names, written as `0x1234` or `*0x1234`
+fn parse_address_location(location: &str) -> Option<Result<u64>> {
+    let address = location.strip_prefix('*').unwrap_or(location);
+    let hex = address.strip_prefix("0x")?;
+    Some(u64::from_str_radix(hex, 16).map_err(|err| anyhow!("invalid address {location}: {err}")))
+}
+
+impl Debugger {
+    /// Starts a session, loading the symbols of the executable and the dynamic linker
+    pub fn new(target: impl Target + 'static) -> Result<Self> {
+        let modules = Self::load_modules(&target)?;
+        let mut debugger = Self {
+            target: Box::new(target),
+            modules,
+            breakpoints: Vec::new(),
+            next_breakpoint_id: 1,
+            r_debug: None,
+            rendezvous_address: None,
+            temporary_hit: None,
+            signal_catch: None,
+            variables: Variables::default(),
+            siginfo: None,
+        };
+        debugger.set_up_rendezvous()?;
+        Ok(debugger)
+    }
+
+    // The executable and the dynamic linker, which the kernel maps before the program starts
+    pub(crate) fn load_modules(target: &dyn Target) -> Result<Vec<Module>> {
+        let executable = Module::load(target.executable_path()?, 0)?;
+        let auxv = target.read_auxv()?;
+
+        let mut modules = vec![executable];
+        if modules[0].elf.is_position_independent() {
+            let entry = auxv
+                .get(&AT_ENTRY)
+                .ok_or(anyhow!("auxiliary vector has no entry point"))?;
+            modules[0].load_bias = entry - modules[0].elf.entry();
+        }
+        // The dynamic linker is mapped by the kernel along with the executable, before any other
+        // library is loaded
+        if let Some(interpreter) = modules[0].elf.interpreter()
+            && let Some(&base) = auxv.get(&AT_BASE)
+            && base != 0
+        {
+            modules.push(Module::load(interpreter, base)?);
+        }
+        Ok(modules)
+    }
+
+    // After an exec the process runs a new program, so the modules are loaded again and the
+    // breakpoints on names are resolved against it. Breakpoints on addresses become pending.
+    fn reload_after_exec(&mut self) -> Result<()> {
+        self.modules = Self::load_modules(&*self.target)?;
+        self.r_debug = None;
+        self.rendezvous_address = None;
+        for breakpoint in &mut self.breakpoints {
+            breakpoint.addresses.clear();
+        }
+        self.set_up_rendezvous()?;
+        for module in 0..self.modules.len() {
+            self.resolve_breakpoints_in(module)?;
+        }
+        Ok(())
+    }
+
+    fn interpreter(&self) -> Option<&Module> {
+        self.modules.get(1)
+    }
+
+    // Finds the breakpoint address of the dynamic linker. When attaching to a running process
+    // the linker has already published r_debug and loaded the libraries, otherwise its
+    // notification function is found by name.
+    fn set_up_rendezvous(&mut self) -> Result<()> {
+        self.r_debug = solib::find_r_debug(&*self.target, &self.modules[0])?;
+        let address = match self.r_debug {
+            Some(r_debug) => Some(solib::read_r_debug(&*self.target, r_debug)?.brk),
+            None => self.interpreter().and_then(|interpreter| {
+                let symbols = interpreter.symbols.lookup(solib::DEBUG_STATE_SYMBOL);
+                symbols.first().map(|s| s.address + interpreter.load_bias)
+            }),
+        };
+        // A core file never runs, so it has no use for the site
+        if let Some(address) = address.filter(|&address| address != 0)
+            && self.target.can_resume()
+        {
+            self.target.add_breakpoint_site(address)?;
+            self.rendezvous_address = Some(address);
+        }
+        if self.r_debug.is_some() {
+            self.update_shared_libraries()?;
+        }
+        Ok(())
+    }
+
+    // Brings the modules in line with the link map of the dynamic linker
+    fn update_shared_libraries(&mut self) -> Result<()> {
+        if self.r_debug.is_none() {
+            self.r_debug = match solib::find_r_debug(&*self.target, &self.modules[0])? {
+                Some(address) => Some(address),
+                None => self.interpreter().and_then(|interpreter| {
+                    let symbols = interpreter.symbols.lookup(solib::R_DEBUG_SYMBOL);
+                    symbols.first().map(|s| s.address + interpreter.load_bias)
+                }),
+            };
+        }
+        let Some(address) = self.r_debug else {
+            return Ok(());
+        };
+        let r_debug = solib::read_r_debug(&*self.target, address)?;
+        if r_debug.state != RT_CONSISTENT {
+            return Ok(());
+        }
+
+        let entries = solib::read_link_map(&*self.target, &r_debug)?;
+        for index in (1..self.modules.len()).rev() {
+            let bias = self.modules[index].load_bias;
+            if !entries.iter().any(|entry| entry.load_bias == bias) {
+                self.unload_module(index)?;
+            }
+        }
+        // The executable is the entry without a name. Objects which cannot be read from disk,
+        // such as the vDSO, are left out.
+        for entry in entries.iter().filter(|entry| !entry.path.is_empty()) {
+            if self.modules[1..]
+                .iter()
+                .any(|module| module.load_bias == entry.load_bias)
+            {
+                continue;
+            }
+            if let Ok(module) = Module::load(&entry.path, entry.load_bias) {
+                self.modules.push(module);
+                self.resolve_breakpoints_in(self.modules.len() - 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn unload_module(&mut self, index: usize) -> Result<()> {
+        let range = self.modules[index].address_range();
+        for breakpoint in &mut self.breakpoints {
+            for &address in breakpoint.addresses.iter().filter(|a| range.contains(a)) {
+                // The code is gone, so there is no byte to restore
+                self.target.forget_breakpoint_site(address);
+            }
+            breakpoint
+                .addresses
+                .retain(|address| !range.contains(address));
+        }
+        self.modules.remove(index);
+        Ok(())
+    }
+
+    /// The shared libraries loaded into the program, in load order
+    pub fn shared_libraries(&self) -> &[Module] {
+        &self.modules[1..]
+    }
+
+    fn module_for(&self, address: u64) -> Option<usize> {
+        self.modules
+            .iter()
+            .position(|module| module.contains(address))
+    }
+
+    /// The program counter of the stopped thread
+    pub fn pc(&self) -> Result<u64> {
+        let rip = self.target.registers().read_by_id(RegisterId::RIP)?;
+        Ok(u64::from_le_bytes(rip.widen()[..8].try_into()?))
+    }
+
+    /// Resumes the process until it stops for a reason the user cares about. Stops at the
+    /// rendezvous breakpoint update the shared libraries and carry on, as do hits of breakpoints
+    /// whose condition fails or which are being ignored.
+    pub fn continue_execution(&mut self) -> Result<StopReason> {
+        let reason = self.resume_until(|_, _| Ok(false))?;
+        self.record_stop(reason)
+    }
+
+    /// Executes a single instruction of the stopped thread
+    pub fn step_instruction(&mut self) -> Result<StopReason> {
+        self.temporary_hit = None;
+        let reason = self.target.step_instruction()?;
+        if reason.event() == Some(ProcessEvent::Exec) {
+            self.reload_after_exec()?;
+        }
+        self.record_stop(reason)
+    }
+
+    // Sets $_exitcode when the process exits, and keeps the siginfo of the signal it stops with
+    // for $_siginfo
+    fn record_stop(&mut self, reason: StopReason) -> Result<StopReason> {
+        if let Some(code) = reason.exit_code() {
+            let value = expression::Value::new(ValueType::INT, code.to_le_bytes().to_vec());
+            self.variables.set("_exitcode", &value)?;
+        }
+        let process = self.target.process().filter(|_| reason.is_stopped());
+        self.siginfo = process.and_then(|process| process.siginfo().ok());
+        Ok(reason)
+    }
+
+    // The siginfo of the last stop, as a siginfo_t where the debug information has the type
+    // and otherwise as an array of ints. The type is looked up only when asked for, as that
+    // searches every module.
+    fn siginfo_value(&self) -> Option<expression::Value> {
+        let siginfo = self.siginfo.clone()?;
+        let ints = ValueType::Array {
+            element: Box::new(ValueType::INT),
+            count: siginfo.len() as u64 / 4,
+        };
+        let ty = self.lookup_type("siginfo_t").unwrap_or(ints);
+        Some(expression::Value::new(ty, siginfo))
+    }
+
+    /// Executes a single instruction, running a call it makes through to the return, as nexti
+    /// does. A call is told by the return address it pushes, which follows the call instruction.
+    pub fn step_over_instruction(&mut self) -> Result<StopReason> {
+        let rsp = lookup_register_info_by_id(RegisterId::RSP)?;
+        let (pc, sp) = (self.pc()?, self.target.registers().read_as_u64(rsp)?);
+        let reason = self.step_instruction()?;
+        if !reason.is_stopped() || self.target.registers().read_as_u64(rsp)? != sp - 8 {
+            return Ok(reason);
+        }
+        let pushed = u64::from_le_bytes(self.target.read_memory(sp - 8, 8)?.try_into().unwrap());
+        // x86 instructions are at most 15 bytes long
+        if pushed > pc && pushed - pc <= 15 && self.pc()? != pushed {
+            return self.run_to(&format!("*{pushed:#x}"), false);
+        }
+        Ok(reason)
+    }
+
+    // Like continue_execution, but also stops when `is_target` accepts the pc of a stop at a
+    // breakpoint site which no enabled breakpoint owns
+    fn resume_until(
+        &mut self,
+        mut is_target: impl FnMut(&Self, u64) -> Result<bool>,
+    ) -> Result<StopReason> {
+        self.temporary_hit = None;
+        loop {
+            self.target.resume()?;
+            let reason = self.target.wait_on_signal()?;
+            if !reason.is_stopped() {
+                return Ok(reason);
+            }
+            if reason.event() == Some(ProcessEvent::Exec) {
+                self.reload_after_exec()?;
+            }
+            if reason.event().is_some() {
+                return Ok(reason);
+            }
+            if let Some(signal) = reason.signal()
+                && reason.is_stopped()
+                && !self.catches_signal(signal)
+            {
+                continue;
+            }
+            if let Some(stop) = reason.syscall() {
+                if self.target.syscall_catch_policy().catches(stop.number()) {
+                    return Ok(reason);
+                }
+                continue;
+            }
+            let pc = self.pc()?;
+            if self.breakpoint_at(pc).is_some() {
+                if self.record_hit(pc)? || is_target(self, pc)? {
+                    return Ok(reason);
+                }
+                continue;
+            }
+            if Some(pc) == self.rendezvous_address {
+                self.update_shared_libraries()?;
+                if !is_target(self, pc)? {
+                    continue;
+                }
+            } else if self.target.has_breakpoint_site(pc) && !is_target(self, pc)? {
+                continue;
+            }
+            return Ok(reason);
+        }
+    }
+
+    /// Adds system calls to those which stop the process on entry and exit, or all of them when
+    /// no numbers are given
+    pub fn catch_syscalls(&mut self, numbers: Option<Vec<u64>>) -> Result<()> {
+        let policy = match (self.target.syscall_catch_policy(), numbers) {
+            (SyscallCatchPolicy::All, _) | (_, None) => SyscallCatchPolicy::All,
+            (SyscallCatchPolicy::Some(caught), Some(mut numbers)) => {
+                numbers.extend(caught);
+                numbers.sort();
+                numbers.dedup();
+                SyscallCatchPolicy::Some(numbers)
+            }
+            (SyscallCatchPolicy::None, Some(numbers)) => SyscallCatchPolicy::Some(numbers),
+        };
+        self.target.set_syscall_catch_policy(policy)
+    }
+
+    // SIGTRAP is how breakpoints and stepping stop the process and SIGINT is the user asking
+    // for a stop, so both always stop it
+    fn catches_signal(&self, signal: Signal) -> bool {
+        match &self.signal_catch {
+            _ if signal == Signal::SIGTRAP || signal == Signal::SIGINT => true,
+            Some(signals) => signals.contains(&signal),
+            None => true,
+        }
+    }
+
+    /// The signals which stop the program, or None when every signal does
+    pub fn signal_catch(&self) -> Option<&[Signal]> {
+        self.signal_catch.as_deref()
+    }
+
+    /// Adds to the signals which stop the process, which until then is every signal
+    pub fn catch_signals(&mut self, signals: &[Signal]) {
+        let caught = self.signal_catch.get_or_insert_with(Vec::new);
+        for &signal in signals {
+            if !caught.contains(&signal) {
+                caught.push(signal);
+            }
+        }
+    }
+
+    /// Goes back to stopping at every signal
+    pub fn clear_signal_catch(&mut self) {
+        self.signal_catch = None;
+    }
+
+    /// Stops catching system calls
+    pub fn clear_syscall_catch(&mut self) -> Result<()> {
+        self.target
+            .set_syscall_catch_policy(SyscallCatchPolicy::None)
+    }
+
+    // Counts a hit of the breakpoints at the pc, returning whether any of them stops the
+    // process. Conditions are evaluated against the registers and memory of the stopped
+    // process, and an error in one stops it like gdb does. Temporary breakpoints which stop
+    // the process are deleted.
+    fn record_hit(&mut self, pc: u64) -> Result<bool> {
+        let mut stop = false;
+        let mut temporary = None;
+        for index in 0..self.breakpoints.len() {
+            let breakpoint = &self.breakpoints[index];
+            if !breakpoint.enabled || !breakpoint.addresses.contains(&pc) {
+                continue;
+            }
+            if let Some(condition) = breakpoint.condition.clone() {
+                let id = breakpoint.id;
+                let holds = expression::evaluate_condition(&condition, self).map_err(|err| {
+                    anyhow!("error in testing the condition of breakpoint {id}: {err}")
+                })?;
+                if !holds {
+                    continue;
+                }
+            }
+            let breakpoint = &mut self.breakpoints[index];
+            breakpoint.hit_count += 1;
+            if breakpoint.ignore_count > 0 {
+                breakpoint.ignore_count -= 1;
+            } else {
+                stop = true;
+                if breakpoint.temporary && temporary.is_none() {
+                    temporary = Some(breakpoint.id);
+                }
+            }
+        }
+        if let Some(id) = temporary {
+            let index = self.breakpoint_index(id)?;
+            self.remove_sites(index)?;
+            self.temporary_hit = Some(self.breakpoints.remove(index));
+        }
+        Ok(stop)
+    }
+
+    /// Runs until the pc reaches a location or the current function returns, whichever comes
+    /// first. With `any_frame` unset, as for `until`, the location only counts in the current
+    /// frame or one of its callers, so that recursive calls run past it. `advance` sets it.
+    pub fn run_to(&mut self, location: &str, any_frame: bool) -> Result<StopReason> {
+        let addresses = self.resolve_location(location)?;
+        if addresses.is_empty() {
+            bail!("no function named {location}");
+        }
+        let frames = self.backtrace()?;
+        let frame_cfa = frames[0].cfa;
+        // After the return the stack pointer is back at the canonical frame address
+        let return_address = match (frame_cfa, frames.get(1)) {
+            (Some(cfa), Some(caller)) => Some((caller.pc, cfa)),
+            _ => None,
+        };
+
+        let mut sites: Vec<u64> = addresses.clone();
+        sites.extend(return_address.map(|(address, _)| address));
+        sites.retain(|&address| !self.target.has_breakpoint_site(address));
+        sites.dedup();
+        for &address in &sites {
+            self.target.add_breakpoint_site(address)?;
+        }
+
+        let reason = self.resume_until(|debugger, pc| {
+            if addresses.contains(&pc) {
+                let outer = match (debugger.current_cfa(), frame_cfa) {
+                    (Some(cfa), Some(frame_cfa)) => cfa >= frame_cfa,
+                    _ => true,
+                };
+                if any_frame || outer {
+                    return Ok(true);
+                }
+            }
+            if let Some((address, cfa)) = return_address
+                && pc == address
+            {
+                let sp = debugger
+                    .target
+                    .registers()
+                    .read_as_u64(lookup_register_info_by_id(RegisterId::RSP)?)?;
+                return Ok(sp >= cfa);
+            }
+            Ok(false)
+        });
+        // The process may have exited, in which case there is nothing left to restore
+        if !matches!(&reason, Ok(reason) if !reason.is_stopped()) {
+            for address in sites {
+                self.target.remove_breakpoint_site(address)?;
+            }
+        }
+        self.record_stop(reason?)
+    }
+
+    // The module of the current pc, which is the one with the debug information of the
+    // current function
+    fn current_module(&self) -> Result<usize> {
+        let pc = self.pc()?;
+        self.module_for(pc)
+            .ok_or_else(|| anyhow!("no object file is loaded at {pc:#x}"))
+    }
+
+    // The context for evaluating an expression of `die`
+    fn eval_context<'a>(
+        &'a self,
+        module: &'a Module,
+        die: &'a Die,
+        frame_base: Option<u64>,
+    ) -> EvalContext<'a> {
+        let mut ctx = EvalContext::new(&*self.target, self.target.registers());
+        ctx.load_bias = module.load_bias;
+        ctx.frame_base = frame_base;
+        ctx.cfa = self.current_cfa();
+        // Entry registers stay unset, so DW_OP_entry_value is refused. Unwinding cannot stand in
+        // for them: the argument registers it names are caller-saved, and the CFI of the caller
+        // does not recover their values at the call.
+        ctx.debug_info = Some(DebugInfo {
+            dwarf: &module.dwarf,
+            die,
+        });
+        ctx
+    }
+
+    fn current_cfa(&self) -> Option<u64> {
+        let pc = self.pc().ok()?;
+        let module = &self.modules[self.module_for(pc)?];
+        let unwound = module.cfi.unwind(
+            module.file_address(pc),
+            self.target.registers(),
+            &*self.target,
+            module.load_bias,
+        );
+        Some(unwound.ok()??.cfa)
+    }
+
+    fn current_function(&self) -> Result<(usize, Die)> {
+        let pc = self.pc()?;
+        let index = self.current_module()?;
+        let function = self.modules[index]
+            .dwarf
+            .function_containing(self.modules[index].file_address(pc))?
+            .ok_or(anyhow!("no debug information for the function at {pc:#x}"))?;
+        Ok((index, function))
+    }
+
+    fn frame_base(&self, module: &Module, function: &Die) -> Result<Option<u64>> {
+        let pc = module.file_address(self.pc()?);
+        let Some(expr) = module
+            .dwarf
+            .location_expression(function, DW_AT_FRAME_BASE, pc)?
+        else {
+            return Ok(None);
+        };
+        let ctx = self.eval_context(module, function, None);
+        match evaluate(&expr, &ctx)? {
+            Location::Address(address) => Ok(Some(address)),
+            Location::Register(id) => Ok(Some(ctx.read_register(id)?)),
+            other => bail!("unsupported frame base location {other:?}"),
+        }
+    }
+
+    // The arguments and the locals in scope of the current function
+    fn current_frame_variables(&self) -> Result<(Vec<Variable>, Vec<Variable>)> {
+        let (module, function) = self.current_function()?;
+        let pc = self.modules[module].file_address(self.pc()?);
+        let (params, locals) = self.modules[module].dwarf.frame_variables(&function, pc)?;
+        let wrap = |dies: Vec<Die>| {
+            dies.into_iter()
+                .map(|die| Variable { module, die })
+                .collect()
+        };
+        Ok((wrap(params), wrap(locals)))
+    }
+
+    /// The variables of the current frame's function
+    pub fn frame_variables(&self, kind: VariableKind) -> Result<Vec<Variable>> {
+        let (params, locals) = self.current_frame_variables()?;
+        Ok(match kind {
+            VariableKind::Locals => locals,
+            VariableKind::Arguments => params,
+        })
+    }
+
+    // Looks a variable up by name, preferring the innermost local, then the arguments of the
+    // current function and finally variables with static storage, first in the current module
+    fn lookup_variable(&self, name: &str) -> Result<Option<Variable>> {
+        if let Ok((params, locals)) = self.current_frame_variables() {
+            for variable in locals.iter().rev().chain(params.iter()) {
+                let dwarf = &self.modules[variable.module].dwarf;
+                if dwarf.name(&variable.die)?.as_deref() == Some(name) {
+                    return Ok(Some(variable.clone()));
+                }
+            }
+        }
+
+        let mut order: Vec<usize> = (0..self.modules.len()).collect();
+        if let Ok(current) = self.current_module() {
+            order.retain(|&index| index != current);
+            order.insert(0, current);
+        }
+        for &module in &order {
+            if let Some(die) = self.modules[module].dwarf.find_global_variable(name)? {
+                return Ok(Some(Variable { module, die }));
+            }
+        }
+        // Qualified and mangled names are matched through the symbol table
+        for &module in &order {
+            let dwarf = &self.modules[module].dwarf;
+            for symbol in self.modules[module].symbols.lookup(name) {
+                if symbol.kind == SymbolKind::Object
+                    && let Some(die) = dwarf.find_global_variable_by_linkage_name(&symbol.name)?
+                {
+                    return Ok(Some(Variable { module, die }));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Finds a variable by name, in the current scope first and then among the globals
+    pub fn find_variable(&self, name: &str) -> Result<Variable> {
+        self.lookup_variable(name)?
+            .ok_or_else(|| anyhow!("no variable named {name} in the current scope"))
+    }
+
+    // Evaluates the location of a variable and reads its value, or returns None when the
+    // variable was optimized out at the current pc
+    fn read_variable(&self, variable: &Variable) -> Result<Option<(Type, Location, Vec<u8>)>> {
+        let module = &self.modules[variable.module];
+        let (dwarf, die) = (&module.dwarf, &variable.die);
+        let name = dwarf.name(die)?.unwrap_or("<anonymous>".to_string());
+        let ty = dwarf
+            .type_of(die)?
+            .ok_or(anyhow!("variable {name} has no type"))?;
+
+        let pc = module.file_address(self.pc()?);
+        let Some(expr) = dwarf.location_expression(die, DW_AT_LOCATION, pc)? else {
+            return Ok(None);
+        };
+
+        // Variables with static storage need no frame, so a frame base which cannot be computed
+        // is only reported if the expression turns out to need it
+        let frame_base = match self.current_function() {
+            Ok((index, function)) if index == variable.module => self.frame_base(module, &function),
+            _ => Ok(None),
+        };
+        let ctx = self.eval_context(module, die, *frame_base.as_ref().unwrap_or(&None));
+        let location = match &frame_base {
+            Ok(_) => evaluate(&expr, &ctx)?,
+            Err(frame_err) => evaluate(&expr, &ctx).map_err(|err| anyhow!("{err}: {frame_err}"))?,
+        };
+        let size = dwarf.type_size(&ty)? as usize;
+        let data = read_location(&location, size, &ctx)?;
+        Ok(Some((ty, location, data)))
+    }
+
+    /// What is being debugged
+    pub fn target(&self) -> &dyn Target {
+        &*self.target
+    }
+
+    /// What is being debugged, to change
+    pub fn target_mut(&mut self) -> &mut dyn Target {
+        &mut *self.target
+    }
+
+    /// The value history and convenience variables of expressions
+    pub fn variables(&self) -> &Variables {
+        &self.variables
+    }
+
+    /// The value history and convenience variables of expressions, to change
+    pub fn variables_mut(&mut self) -> &mut Variables {
+        &mut self.variables
+    }
+
+    /// Evaluates an expression as print does. Assignments write to the program.
+    pub fn evaluate(&mut self, text: &str) -> Result<expression::Value> {
+        expression::evaluate(text, self)
+    }
+
+    /// Renders a value as print shows it
+    pub fn format_value(&self, value: &expression::Value) -> Result<String> {
+        expression::format_value(value, self)
+    }
+
+    /// The name of a type, in C syntax
+    pub fn type_name(&self, ty: &ValueType) -> Result<String> {
+        expression::type_name(ty, self)
+    }
+
+    /// The address a value refers to, for commands such as x which take one: the value of a
+    /// pointer or integer, or else where the value is stored
+    pub fn value_address(&self, value: &expression::Value) -> Result<u64> {
+        expression::value_address(value, self)
+    }
+
+    /// Renders a variable as `name = value`
+    pub fn format_variable(&self, variable: &Variable) -> Result<String> {
+        let (name, value) = self.variable_name_and_value(variable)?;
+        Ok(format!("{name} = {value}"))
+    }
+
+    /// The name of a variable and its value as rendered by format_variable
+    pub fn variable_name_and_value(&self, variable: &Variable) -> Result<(String, String)> {
+        let dwarf = &self.modules[variable.module].dwarf;
+        let name = dwarf
+            .name(&variable.die)?
+            .unwrap_or("<anonymous>".to_string());
+        let Some((ty, location, data)) = self.read_variable(variable)? else {
+            return Ok((name, "<optimized out>".to_string()));
+        };
+        // Composites with some pieces optimized out show the rest, with the bytes missing
+        let missing = location.optimized_out();
+        if missing.iter().map(|range| range.len()).sum::<usize>() >= data.len() {
+            return Ok((name, "<optimized out>".to_string()));
+        }
+        let mut value = dwarf.format_value(&ty, &data, 0)?;
+        if !missing.is_empty() {
+            let ranges: Vec<String> = missing
+                .iter()
+                .map(|range| format!("{}..{}", range.start, range.end))
+                .collect();
+            value = format!("{value} <bytes {} optimized out>", ranges.join(", "));
+        }
+        // As with C, pointers are printed along with the type pointed to
+        if let TypeKind::Pointer { .. } = dwarf.strip_type(&ty)?.kind {
+            Ok((name, format!("({}) {value}", dwarf.type_name(&ty)?)))
+        } else {
+            Ok((name, value))
+        }
+    }
+
+    /// Describes a runtime address as `0x... in function+offset`, naming the library when the
+    /// address is outside of the executable
+    pub fn describe_address(&self, address: u64) -> String {
+        self.describe(address, address)
+    }
+
+    /// Describes the pc of a frame. The pc of callers is a return address, which is looked up
+    /// one byte earlier so that it is attributed to the function making the call.
+    pub fn describe_frame(&self, index: usize, frame: &Frame) -> String {
+        let lookup = if index == 0 {
+            frame.pc
+        } else {
+            frame.pc.saturating_sub(1)
+        };
+        self.describe(frame.pc, lookup)
+    }
+
+    fn describe(&self, address: u64, lookup: u64) -> String {
+        let Some(index) = self.module_for(lookup) else {
+            return format!("{address:#018x} in ??");
+        };
+        let module = &self.modules[index];
+        let file_address = module.file_address(lookup);
+        let mut description = match module.symbols.symbol_containing(file_address) {
+            Some(symbol) if symbol.address == module.file_address(address) => {
+                format!("{address:#018x} in {}", symbol.display_name())
+            }
+            Some(symbol) => format!(
+                "{address:#018x} in {}+{}",
+                symbol.display_name(),
+                module.file_address(address) - symbol.address
+            ),
+            None => format!("{address:#018x} in ??"),
+        };
+        if index != 0 {
+            description += &format!(" from {}", module.path.display());
+        }
+        description
+    }
+
+    /// Describes the symbol covering an address as gdb does, as `name + offset in section .text`
+    pub fn symbol_at(&self, address: u64) -> Option<String> {
+        let index = self.module_for(address)?;
+        let module = &self.modules[index];
+        let file_address = module.file_address(address);
+        let symbol = module.symbols.symbol_containing(file_address)?;
+        let mut description = match file_address - symbol.address {
+            0 => symbol.display_name().to_string(),
+            offset => format!("{} + {offset}", symbol.display_name()),
+        };
+        if let Some(section) = module.elf.section_containing(file_address) {
+            description += &format!(" in section {section}");
+        }
+        if index != 0 {
+            description += &format!(" of {}", module.path.display());
+        }
+        Some(description)
+    }
+
+    /// The name of the symbol covering an address, in whichever object it is
+    pub fn function_name(&self, address: u64) -> Option<String> {
+        let module = &self.modules[self.module_for(address)?];
+        let symbol = module
+            .symbols
+            .symbol_containing(module.file_address(address))?;
+        Some(symbol.display_name().to_string())
+    }
+
+    /// The runtime address of a function or object symbol of any loaded object
+    pub fn symbol_address(&self, name: &str) -> Option<u64> {
+        self.modules.iter().find_map(|module| {
+            let symbol = module.symbols.lookup(name).into_iter().next()?;
+            Some(symbol.address + module.load_bias)
+        })
+    }
+
+    /// The names of the symbols of every loaded object, as they are shown
+    pub fn symbol_names(&self) -> impl Iterator<Item = &str> {
+        self.modules
+            .iter()
+            .flat_map(|module| module.symbols.names())
+    }
+
+    /// The mapped regions of the program's address space
+    pub fn memory_regions(&self) -> Result<Vec<MemoryRegion>> {
+        self.target.memory_regions()
+    }
+
+    /// Works out what an address points into: a section of a loaded object, the heap, the stack
+    /// of a thread or some other mapping
+    pub fn classify_address(&self, address: u64) -> Result<AddressClass> {
+        let regions = self.target.memory_regions()?;
+        let Some(region) = regions.iter().find(|region| region.contains(address)) else {
+            return Ok(AddressClass::Unmapped);
+        };
+
+        if let Some(index) = self.module_for(address) {
+            let module = &self.modules[index];
+            if let Some(section) = module.elf.section_containing(module.file_address(address)) {
+                return Ok(AddressClass::Section {
+                    section: section.to_string(),
+                    path: module.path.display().to_string(),
+                });
+            }
+        }
+        match region.path.as_str() {
+            "[heap]" => return Ok(AddressClass::Heap),
+            "[stack]" => return Ok(AddressClass::Stack { thread: 1 }),
+            _ => {}
+        }
+        // The stacks of other threads are anonymous mappings, recognised by the stack pointer
+        // of the thread being inside them
+        for (index, tid) in self.target.threads()?.into_iter().enumerate() {
+            if let Ok(Some(sp)) = self.target.thread_stack_pointer(tid)
+                && region.contains(sp)
+            {
+                return Ok(AddressClass::Stack { thread: index + 1 });
+            }
+        }
+
+        Ok(if region.path.starts_with('[') {
+            AddressClass::Special(region.path.clone())
+        } else if region.is_file_backed() {
+            AddressClass::MappedFile {
+                path: region.path.clone(),
+                offset: region.offset + (address - region.range.start),
+            }
+        } else {
+            AddressClass::Anonymous
+        })
+    }
+
+    /// Walks the call stack using the call frame information of the loaded objects. The walk
+    /// ends at the outermost frame or at the first pc without call frame information.
+    pub fn backtrace(&self) -> Result<Vec<Frame>> {
+        let mut frames = Vec::new();
+        let mut registers = self.target.registers().clone();
+        while frames.len() < MAX_FRAMES {
+            let pc = registers.read_as_u64(lookup_register_info_by_id(RegisterId::RIP)?)?;
+            // The pc of callers is a return address, which may belong to the next function when
+            // the call is the last instruction of the caller
+            let lookup_pc = if frames.is_empty() {
+                pc
+            } else {
+                pc.saturating_sub(1)
+            };
+            let unwound = self.module_for(lookup_pc).and_then(|index| {
+                let module = &self.modules[index];
+                module
+                    .cfi
+                    .unwind(
+                        module.file_address(lookup_pc),
+                        &registers,
+                        &*self.target,
+                        module.load_bias,
+                    )
+                    .unwrap_or(None)
+            });
+
+            let Some(unwound) = unwound else {
+                frames.push(Frame {
+                    pc,
+                    cfa: None,
+                    registers,
+                });
+                break;
+            };
+            frames.push(Frame {
+                pc,
+                cfa: Some(unwound.cfa),
+                registers,
+            });
+            let Some(caller) = unwound.caller else {
+                break;
+            };
+            registers = caller;
+            let caller_pc = registers.read_as_u64(lookup_register_info_by_id(RegisterId::RIP)?)?;
+            if caller_pc == 0 {
+                break;
+            }
+        }
+        Ok(frames)
+    }
+
+    // The runtime addresses of the functions of a module with the given name, which may be
+    // mangled or demangled
+    fn function_addresses(module: &Module, name: &str) -> Vec<u64> {
+        module
+            .symbols
+            .lookup(name)
+            .into_iter()
+            .filter(|symbol| symbol.kind == SymbolKind::Function)
+            .map(|symbol| symbol.address + module.load_bias)
+            .collect()
+    }
+
+    // Resolves a location given by the user to runtime addresses. A name which matches no
+    // function resolves to nothing, since a library loaded later may define it.
+    fn resolve_location(&self, location: &str) -> Result<Vec<u64>> {
+        if let Some(address) = parse_address_location(location) {
+            return Ok(vec![address?]);
+        }
+        Ok(self
+            .modules
+            .iter()
+            .flat_map(|module| Self::function_addresses(module, location))
+            .collect())
+    }
+
+    // Adds the functions of a newly loaded module to the breakpoints on names, which resolves
+    // pending breakpoints
+    fn resolve_breakpoints_in(&mut self, module: usize) -> Result<()> {
+        for breakpoint in &mut self.breakpoints {
+            if parse_address_location(&breakpoint.location).is_some() {
+                continue;
+            }
+            let mut addresses =
+                Self::function_addresses(&self.modules[module], &breakpoint.location);
+            addresses.retain(|address| !breakpoint.addresses.contains(address));
+            if breakpoint.enabled {
+                for &address in &addresses {
+                    self.target.add_breakpoint_site(address)?;
+                }
+            }
+            breakpoint.addresses.extend(addresses);
+        }
+        Ok(())
+    }
+
+    /// Sets a breakpoint, which is pending when its location does not resolve yet
+    pub fn set_breakpoint(&mut self, location: &str) -> Result<&Breakpoint> {
+        let addresses = self.resolve_location(location)?;
+        for &address in &addresses {
+            self.target.add_breakpoint_site(address)?;
+        }
+        self.breakpoints.push(Breakpoint::new(
+            self.next_breakpoint_id,
+            location,
+            addresses,
+        ));
+        self.next_breakpoint_id += 1;
+        Ok(&self.breakpoints[self.breakpoints.len() - 1])
+    }
+
+    /// Sets a breakpoint which deletes itself the first time it stops the process
+    pub fn set_temporary_breakpoint(&mut self, location: &str) -> Result<&Breakpoint> {
+        self.set_breakpoint(location)?;
+        let index = self.breakpoints.len() - 1;
+        self.breakpoints[index].temporary = true;
+        Ok(&self.breakpoints[index])
+    }
+
+    /// The breakpoints, in the order they were set
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// The enabled breakpoint which has a site at the address
+    pub fn breakpoint_at(&self, address: u64) -> Option<&Breakpoint> {
+        self.breakpoints
+            .iter()
+            .find(|bp| bp.enabled && bp.addresses.contains(&address))
+    }
+
+    /// The breakpoint the process is stopped at, including a temporary one deleted by the stop
+    pub fn stopped_breakpoint(&self) -> Option<&Breakpoint> {
+        let pc = self.pc().ok()?;
+        self.breakpoint_at(pc).or_else(|| {
+            self.temporary_hit
+                .as_ref()
+                .filter(|bp| bp.addresses.contains(&pc))
+        })
+    }
+
+    fn breakpoint_index(&self, id: usize) -> Result<usize> {
+        self.breakpoints
+            .iter()
+            .position(|bp| bp.id == id)
+            .ok_or_else(|| anyhow!("no breakpoint number {id}"))
+    }
+
+    // Removes the sites of a breakpoint which neither another enabled breakpoint nor the
+    // rendezvous with the dynamic linker shares
+    fn remove_sites(&mut self, index: usize) -> Result<()> {
+        for address in self.breakpoints[index].addresses.clone() {
+            let shared = self
+                .breakpoints
+                .iter()
+                .enumerate()
+                .any(|(i, bp)| i != index && bp.enabled && bp.addresses.contains(&address));
+            if !shared && Some(address) != self.rendezvous_address {
+                self.target.remove_breakpoint_site(address)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes a breakpoint, taking out its sites
+    pub fn delete_breakpoint(&mut self, id: usize) -> Result<()> {
+        let index = self.breakpoint_index(id)?;
+        if self.breakpoints[index].enabled {
+            self.remove_sites(index)?;
+        }
+        self.breakpoints.remove(index);
+        Ok(())
+    }
+
+    /// The most recently set breakpoint, which commands without a breakpoint number refer to
+    pub fn last_breakpoint_id(&self) -> Option<usize> {
+        self.breakpoints.last().map(|bp| bp.id)
+    }
+
+    /// Sets or clears the condition of a breakpoint
+    pub fn set_breakpoint_condition(&mut self, id: usize, condition: Option<String>) -> Result<()> {
+        let index = self.breakpoint_index(id)?;
+        self.breakpoints[index].condition = condition;
+        Ok(())
+    }
+
+    /// Sets how many upcoming hits of a breakpoint to carry on from
+    pub fn set_breakpoint_ignore_count(&mut self, id: usize, count: usize) -> Result<()> {
+        let index = self.breakpoint_index(id)?;
+        self.breakpoints[index].ignore_count = count;
+        Ok(())
+    }
+
+    /// Sets the commands run when a breakpoint stops the program
+    pub fn set_breakpoint_commands(&mut self, id: usize, commands: Vec<String>) -> Result<()> {
+        let index = self.breakpoint_index(id)?;
+        self.breakpoints[index].commands = commands;
+        Ok(())
+    }
+
+    /// Enables or disables a breakpoint, adding or taking out its sites
+    pub fn set_breakpoint_enabled(&mut self, id: usize, enabled: bool) -> Result<()> {
+        let index = self.breakpoint_index(id)?;
+        if self.breakpoints[index].enabled == enabled {
+            return Ok(());
+        }
+        if enabled {
+            for &address in &self.breakpoints[index].addresses {
+                self.target.add_breakpoint_site(address)?;
+            }
+        } else {
+            self.remove_sites(index)?;
+        }
+        self.breakpoints[index].enabled = enabled;
+        Ok(())
+    }
+}
+
+impl ExpressionContext for Debugger {
+    fn registers(&self) -> &Registers {
+        self.target.registers()
+    }
+
+    fn write_register(&mut self, info: &'static RegisterInfo, data: &[u8]) -> Result<()> {
+        self.target.write_register(info, data)
+    }
+
+    fn read_memory(&self, address: u64, size: usize) -> Result<Vec<u8>> {
+        self.target.read_memory(address, size)
+    }
+
+    fn write_memory(&mut self, address: u64, data: &[u8]) -> Result<()> {
+        self.target.write_memory(address, data)
+    }
+
+    // Variables described by the debug information, and otherwise symbols, whose type is
+    // unknown unless they are functions
+    fn variable(&self, name: &str) -> Result<Option<expression::Value>> {
+        if let Some(variable) = self.lookup_variable(name)? {
+            let Some((ty, location, data)) = self.read_variable(&variable)? else {
+                bail!("{name} has been optimized out");
+            };
+            let place = match location {
+                Location::Address(address) => Some(Place::Memory(address)),
+                Location::Register(id) => {
+                    Some(Place::Register(lookup_register_by_dwarf(id as i32)?))
+                }
+                _ => None,
+            };
+            return Ok(Some(expression::Value {
+                ty: ValueType::Debug {
+                    module: variable.module,
+                    ty: ty.id,
+                },
+                data,
+                place,
+            }));
+        }
+        for module in &self.modules {
+            if let Some(symbol) = module.symbols.lookup(name).first() {
+                let ty = match symbol.kind {
+                    SymbolKind::Function => ValueType::Function,
+                    SymbolKind::Object => ValueType::Void,
+                };
+                let address = symbol.address + module.load_bias;
+                return Ok(Some(expression::Value {
+                    ty,
+                    data: Vec::new(),
+                    place: Some(Place::Memory(address)),
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    fn lookup_type(&self, name: &str) -> Option<ValueType> {
+        self.modules.iter().enumerate().find_map(|(module, m)| {
+            let ty = m.dwarf.find_type(name).ok()??;
+            Some(ValueType::Debug { module, ty })
+        })
+    }
+
+    fn dwarf(&self, module: usize) -> Option<&Dwarf> {
+        self.modules.get(module).map(|module| &module.dwarf)
+    }
+
+    fn convenience(&self, name: &str) -> Option<expression::Value> {
+        match name {
+            "_siginfo" => self.siginfo_value(),
+            _ => self.variables.get(name),
+        }
+    }
+
+    fn set_convenience(&mut self, name: &str, value: &expression::Value) -> Result<()> {
+        self.variables.set(name, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::debugger::{Debugger, VariableKind};
+    use crate::expression::{evaluate, format_value};
+    use crate::maps::AddressClass;
+    use crate::module::Module;
+    use crate::process::{DebugProcess, EventCatch, Process, ProcessEvent, SyscallStop};
+    use crate::reginfo::{lookup_register_info_by_id, RegisterId};
+    use crate::solib::read_c_string;
+    use crate::syscalls::syscall_number;
+    use crate::target::mock::MockTarget;
+    use nix::sys::signal::Signal;
+
+    // Runs the variables test program up to the int3 in its `inspect` function
+    fn stopped_in_inspect() -> Debugger {
+        let process = Process::launch("target/debug/variables", DebugProcess::YES);
+        assert!(process.is_ok());
+
+        let mut debugger = Debugger::new(process.unwrap()).unwrap();
+        debugger.continue_execution().unwrap();
+        debugger
+    }
+
+    #[test]
+    fn arguments_of_current_frame() {
+        let debugger = stopped_in_inspect();
+        let args = debugger.frame_variables(VariableKind::Arguments).unwrap();
+        assert_eq!(args.len(), 2);
+        assert!(debugger
+            .format_variable(&args[0])
+            .is_ok_and(|s| s.starts_with("shape = (&variables::Shape) 0x")));
+        assert_eq!(debugger.format_variable(&args[1]).unwrap(), "factor = 7");
+    }
+
+    #[test]
+    fn nested_structures_are_printed() {
+        let debugger = stopped_in_inspect();
+        let local_shape = debugger.find_variable("local_shape").unwrap();
+        let printed = debugger.format_variable(&local_shape).unwrap();
+        let expected = "local_shape = {
+  origin = {
+    x = -1,
+    y = 2,
+  },
+  colour = Blue,
+  corners = [1, 2, 3],
+  scale = 1.5,
+  label = Some {
+    __0 = 9,
+  },
+}";
+        assert_eq!(printed, expected);
+    }
+
+    #[test]
+    fn locals_and_statics() {
+        let debugger = stopped_in_inspect();
+        let total = debugger.find_variable("total").unwrap();
+        assert_eq!(debugger.format_variable(&total).unwrap(), "total = 31");
+
+        let counter = debugger.find_variable("COUNTER").unwrap();
+        assert_eq!(debugger.format_variable(&counter).unwrap(), "COUNTER = 17");
+
+        assert!(debugger
+            .find_variable("no_such_variable")
+            .is_err_and(|err| err.to_string().contains("no variable named")));
+    }
+
+    #[test]
+    fn breakpoints_by_demangled_name() {
+        let process = Process::launch("target/debug/variables", DebugProcess::YES).unwrap();
+        let mut debugger = Debugger::new(process).unwrap();
+        // Names which resolve to nothing stay pending, as a library may define them later
+        let pending = debugger
+            .set_breakpoint("variables::no_such_function")
+            .unwrap();
+        assert!(pending.addresses.is_empty());
+
+        let address = debugger
+            .set_breakpoint("variables::inspect")
+            .unwrap()
+            .addresses[0];
+        assert!(debugger
+            .describe_address(address)
+            .ends_with(" in variables::inspect"));
+
+        assert!(debugger.continue_execution().unwrap().is_stopped());
+        assert_eq!(debugger.pc().unwrap(), address);
+        assert_eq!(debugger.breakpoint_at(address).unwrap().id, 2);
+
+        let frames: Vec<_> = debugger
+            .backtrace()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .map(|(index, frame)| debugger.describe_frame(index, frame))
+            .collect();
+        assert!(frames[0].ends_with(" in variables::inspect"));
+        assert!(frames[1].contains(" in variables::main+"));
+
+        // Continuing steps over the breakpoint, up to the int3 in the body of the function
+        debugger.continue_execution().unwrap();
+        let pc = debugger.pc().unwrap();
+        assert!(debugger
+            .describe_address(pc)
+            .contains(" in variables::inspect+"));
+        assert!(debugger.breakpoint_at(pc).is_none());
+    }
+
+    #[test]
+    fn memory_is_read_and_written_around_breakpoint_sites() {
+        let process = Process::launch("target/debug/variables", DebugProcess::YES).unwrap();
+        let mut debugger = Debugger::new(process).unwrap();
+        let address = debugger
+            .set_breakpoint("variables::inspect")
+            .unwrap()
+            .addresses[0];
+        let raw = |debugger: &Debugger| {
+            let process = debugger.target.process().unwrap();
+            process.read_memory(address, 1).unwrap()[0]
+        };
+        assert_eq!(raw(&debugger), 0xcc);
+        let original = debugger.target.read_memory(address, 1).unwrap()[0];
+        assert_ne!(original, 0xcc);
+
+        // A write over the site keeps the int3, and the site restores what was written
+        debugger.target.write_memory(address, &[0x90]).unwrap();
+        assert_eq!(raw(&debugger), 0xcc);
+        assert_eq!(debugger.target.read_memory(address, 1).unwrap(), [0x90]);
+        debugger.delete_breakpoint(1).unwrap();
+        assert_eq!(raw(&debugger), 0x90);
+    }
+
+    #[test]
+    fn statics_by_qualified_and_mangled_names() {
+        let debugger = stopped_in_inspect();
+        let counter = debugger.find_variable("variables::COUNTER").unwrap();
+        assert_eq!(debugger.format_variable(&counter).unwrap(), "COUNTER = 17");
+
+        let mangled = debugger.modules[0].symbols.lookup("variables::COUNTER")[0]
+            .name
+            .clone();
+        let counter = debugger.find_variable(&mangled).unwrap();
+        assert_eq!(debugger.format_variable(&counter).unwrap(), "COUNTER = 17");
+    }
+
+    #[test]
+    fn pending_breakpoints_resolve_when_libraries_load() {
+        let process = Process::launch("target/debug/libraries", DebugProcess::YES).unwrap();
+        let mut debugger = Debugger::new(process).unwrap();
+        let loaded = |debugger: &Debugger| {
+            debugger
+                .shared_libraries()
+                .iter()
+                .any(|library| library.path.ends_with("libm.so.6"))
+        };
+        assert!(!loaded(&debugger));
+        assert!(debugger
+            .set_breakpoint("cbrt")
+            .unwrap()
+            .addresses
+            .is_empty());
+
+        // The breakpoint resolves once dlopen brings libm in
+        debugger.continue_execution().unwrap();
+        assert!(loaded(&debugger));
+        let pc = debugger.pc().unwrap();
+        assert_eq!(debugger.breakpoint_at(pc).unwrap().id, 1);
+        assert!(debugger.describe_address(pc).contains(" in cbrt from "));
+
+        // And goes back to pending once dlclose unloads it
+        debugger.continue_execution().unwrap();
+        assert!(!loaded(&debugger));
+        assert!(debugger.breakpoints()[0].addresses.is_empty());
+    }
+
+    #[test]
+    fn addresses_are_classified() {
+        let debugger = stopped_in_inspect();
+        let pc = debugger.pc().unwrap();
+        let in_text = |class: AddressClass| {
+            matches!(class, AddressClass::Section { section, path }
+                if section == ".text" && path.ends_with("variables"))
+        };
+        assert!(in_text(debugger.classify_address(pc).unwrap()));
+        assert!(debugger
+            .symbol_at(pc)
+            .unwrap()
+            .starts_with("variables::inspect + "));
+
+        let counter = debugger.symbol_address("variables::COUNTER").unwrap();
+        assert!(matches!(
+            debugger.classify_address(counter).unwrap(),
+            AddressClass::Section { section, .. } if section == ".rodata"
+        ));
+
+        let rsp = lookup_register_info_by_id(RegisterId::RSP).unwrap();
+        let sp = debugger.target.registers().read_as_u64(rsp).unwrap();
+        assert_eq!(
+            debugger.classify_address(sp).unwrap(),
+            AddressClass::Stack { thread: 1 }
+        );
+        assert_eq!(
+            debugger.classify_address(0).unwrap(),
+            AddressClass::Unmapped
+        );
+
+        let regions = debugger.memory_regions().unwrap();
+        assert!(regions
+            .iter()
+            .any(|region| region.path == "[stack]" && region.rss.is_some()));
+    }
+
+    #[test]
+    fn expressions_over_variables() {
+        let mut debugger = stopped_in_inspect();
+        let mut print = |text: &str| {
+            let value = evaluate(text, &mut debugger).unwrap();
+            format_value(&value, &debugger).unwrap()
+        };
+        assert_eq!(print("local_shape.origin.y * factor"), "14");
+        assert_eq!(print("shape->origin.x + shape.corners[2]"), "33");
+        assert_eq!(print("local_shape.corners"), "[1, 2, 3]");
+        assert_eq!(print("local_shape.scale * 2"), "3");
+        assert_eq!(print("local_shape.colour"), "Blue");
+        assert_eq!(print("*(u32*)&COUNTER"), "17");
+        assert_eq!(
+            print("&local_shape.corners[1] - &local_shape.corners[0]"),
+            "1"
+        );
+        assert!(print("&local_shape").starts_with("(Shape *) 0x"));
+        assert!(print("(variables::Point *)&local_shape.origin").starts_with("(Point *) 0x"));
+        assert!(print("variables::inspect").starts_with("{function} 0x"));
+
+        assert_eq!(print("total = total + 1"), "32");
+        let total = debugger.find_variable("total").unwrap();
+        assert_eq!(debugger.format_variable(&total).unwrap(), "total = 32");
+        assert!(evaluate("local_shape.nothing", &mut debugger).is_err());
+    }
+
+    #[test]
+    fn conditions_and_ignore_counts() {
+        let launch = || {
+            let process = Process::launch("target/debug/variables", DebugProcess::YES).unwrap();
+            let mut debugger = Debugger::new(process).unwrap();
+            let id = debugger.set_breakpoint("variables::inspect").unwrap().id;
+            (debugger, id)
+        };
+        // Runs to the int3 in the body of inspect, past the breakpoint on its entry
+        let passes_breakpoint = |debugger: &mut Debugger| {
+            assert!(debugger.continue_execution().unwrap().is_stopped());
+            debugger.breakpoint_at(debugger.pc().unwrap()).is_none()
+        };
+
+        // factor is passed in rsi
+        let (mut debugger, id) = launch();
+        debugger
+            .set_breakpoint_condition(id, Some("$rsi == 7".to_string()))
+            .unwrap();
+        assert!(!passes_breakpoint(&mut debugger));
+        assert_eq!(debugger.breakpoints()[0].hit_count, 1);
+
+        let (mut debugger, id) = launch();
+        debugger
+            .set_breakpoint_condition(id, Some("$rsi != 7".to_string()))
+            .unwrap();
+        assert!(passes_breakpoint(&mut debugger));
+        assert_eq!(debugger.breakpoints()[0].hit_count, 0);
+
+        let (mut debugger, id) = launch();
+        debugger.set_breakpoint_ignore_count(id, 1).unwrap();
+        assert!(passes_breakpoint(&mut debugger));
+        let breakpoint = &debugger.breakpoints()[0];
+        assert_eq!((breakpoint.hit_count, breakpoint.ignore_count), (1, 0));
+
+        let (mut debugger, id) = launch();
+        debugger
+            .set_breakpoint_condition(id, Some("no_such_variable == 1".to_string()))
+            .unwrap();
+        assert!(debugger
+            .continue_execution()
+            .is_err_and(|err| err.to_string().contains("condition of breakpoint 1")));
+    }
+
+    #[test]
+    fn temporary_breakpoints_and_running_to_locations() {
+        let process = Process::launch("target/debug/variables", DebugProcess::YES).unwrap();
+        let mut debugger = Debugger::new(process).unwrap();
+        let id = debugger
+            .set_temporary_breakpoint("variables::inspect")
+            .unwrap()
+            .id;
+        debugger.continue_execution().unwrap();
+        assert_eq!(debugger.stopped_breakpoint().unwrap().id, id);
+        assert!(debugger.breakpoints().is_empty());
+        let inspect = debugger.pc().unwrap();
+        assert!(!debugger.target.has_breakpoint_site(inspect));
+
+        // Runs past the int3 in inspect, then to the return into main since inspect does not
+        // reach the C main again
+        debugger.continue_execution().unwrap();
+        assert!(debugger
+            .run_to("variables::no_such_function", false)
+            .is_err());
+        assert!(debugger.run_to("main", false).unwrap().is_stopped());
+        let pc = debugger.pc().unwrap();
+        assert!(debugger
+            .describe_address(pc)
+            .contains(" in variables::main+"));
+        assert!(debugger.stopped_breakpoint().is_none());
+        assert!(!debugger.target.has_breakpoint_site(pc));
+
+        let process = Process::launch("target/debug/variables", DebugProcess::YES).unwrap();
+        let mut debugger = Debugger::new(process).unwrap();
+        assert!(debugger
+            .run_to("variables::inspect", true)
+            .unwrap()
+            .is_stopped());
+        assert!(debugger
+            .describe_address(debugger.pc().unwrap())
+            .ends_with(" in variables::inspect"));
+    }
+
+    #[test]
+    fn syscalls_are_caught_on_entry_and_exit() {
+        let process = Process::launch("target/debug/variables", DebugProcess::YES).unwrap();
+        let mut debugger = Debugger::new(process).unwrap();
+        let openat = syscall_number("openat").unwrap();
+        debugger.catch_syscalls(Some(vec![openat])).unwrap();
+
+        let entry = debugger.continue_execution().unwrap().syscall();
+        let Some(SyscallStop::Entry { number, args }) = entry else {
+            panic!("expected a syscall entry, got {entry:?}");
+        };
+        assert_eq!(number, openat);
+        // The dynamic linker opens libraries by absolute path
+        let path = read_c_string(&*debugger.target, args[1]).unwrap();
+        assert!(path.starts_with('/'));
+
+        let exit = debugger.continue_execution().unwrap().syscall();
+        assert!(matches!(exit, Some(SyscallStop::Exit { number, .. }) if number == openat));
+
+        // Without catchpoints the program runs on to the int3 in inspect
+        debugger.clear_syscall_catch().unwrap();
+        let reason = debugger.continue_execution().unwrap();
+        assert!(reason.is_stopped() && reason.syscall().is_none());
+    }
+
+    #[test]
+    fn process_events_and_signals_are_caught() {
+        let process = Process::launch("target/debug/lifecycle", DebugProcess::YES).unwrap();
+        let mut debugger = Debugger::new(process).unwrap();
+        let events = EventCatch {
+            fork: true,
+            vfork: true,
+            clone: true,
+            exec: true,
+            exit: true,
+        };
+        debugger.target.set_event_catch(events).unwrap();
+        debugger.catch_signals(&[Signal::SIGUSR1]);
+
+        let mut next_event = || debugger.continue_execution().unwrap().event();
+        assert!(matches!(next_event(), Some(ProcessEvent::Clone(_))));
+        assert!(matches!(next_event(), Some(ProcessEvent::Fork(_))));
+        // The SIGCHLD of the forked child is passed on without stopping
+        let reason = debugger.continue_execution().unwrap();
+        assert_eq!(reason.signal(), Some(Signal::SIGUSR1));
+        // The debug information of the libc crate describes siginfo_t
+        let signo = evaluate("$_siginfo.si_signo", &mut debugger).unwrap();
+        assert_eq!(format_value(&signo, &debugger).unwrap(), "10");
+
+        debugger.set_breakpoint("lifecycle::main").unwrap();
+        assert_eq!(
+            debugger.continue_execution().unwrap().event(),
+            Some(ProcessEvent::Exec)
+        );
+        // The breakpoint is resolved again in the new image
+        assert_eq!(debugger.breakpoints()[0].addresses.len(), 1);
+        assert!(debugger.continue_execution().unwrap().is_stopped());
+        assert_eq!(debugger.stopped_breakpoint().unwrap().id, 1);
+
+        // At the exit event the process can still be inspected
+        let reason = debugger.continue_execution().unwrap();
+        assert_eq!(reason.to_string(), "stopped with cause exit with code 3");
+        assert!(debugger.backtrace().unwrap().len() > 1);
+        assert_eq!(debugger.continue_execution().unwrap().exit_code(), Some(3));
+        let code = evaluate("$_exitcode", &mut debugger).unwrap();
+        assert_eq!(format_value(&code, &debugger).unwrap(), "3");
+    }
+
+    #[test]
+    fn breakpoints_on_a_mock_target() {
+        let bias = 0x5555_0000_0000;
+        let module = Module::load("target/debug/variables", bias).unwrap();
+        let inspect = module.symbols.lookup("variables::inspect")[0].address + bias;
+        let mut target = MockTarget::new("target/debug/variables", bias).unwrap();
+        target.set_register(RegisterId::RDI, 3).unwrap();
+        target.queue_stop(inspect);
+        target.queue_stop(inspect);
+        target.queue_stop(inspect + 4);
+        let mut debugger = Debugger::new(target).unwrap();
+        let original = debugger.target.read_memory(inspect, 1).unwrap();
+
+        let id = debugger
+            .set_breakpoint(&format!("*{inspect:#x}"))
+            .unwrap()
+            .id;
+        assert!(debugger.target.has_breakpoint_site(inspect));
+        assert_eq!(debugger.target.read_memory(inspect, 1).unwrap(), original);
+        debugger.set_breakpoint_ignore_count(id, 1).unwrap();
+        debugger
+            .set_breakpoint_condition(id, Some("$rdi == 3".to_string()))
+            .unwrap();
+
+        // The first hit is ignored and the second stops
+        let reason = debugger.continue_execution().unwrap();
+        assert!(reason.is_stopped());
+        assert_eq!(debugger.pc().unwrap(), inspect);
+        assert_eq!(debugger.breakpoints()[0].hit_count, 2);
+
+        assert!(debugger.step_instruction().unwrap().is_stopped());
+        assert_eq!(debugger.pc().unwrap(), inspect + 4);
+        assert_eq!(debugger.continue_execution().unwrap().exit_code(), Some(0));
+
+        debugger.delete_breakpoint(id).unwrap();
+        assert_eq!(debugger.target.read_memory(inspect, 1).unwrap(), original);
+    }
+}
@@ -0,0 +1,506 @@
+use crate::dwarf::consts::*;
+use crate::dwarf::expr::{evaluate, EvalContext, Location, MemoryReader};
+use crate::dwarf::Cursor;
+use crate::elf::Elf;
+use crate::reginfo::lookup_register_by_dwarf;
+use crate::registers::Registers;
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::rc::Rc;
+
+// The DWARF numbers of the registers the unwinder treats specially on x86-64
+const RSP: u16 = 7;
+const RETURN_ADDRESS: u16 = 16;
+
+// How to recover the value a register had in the caller
+#[derive(Clone, Debug)]
+enum Rule {
+    Undefined,
+    SameValue,
+    // Saved at the given offset from the CFA
+    Offset(i64),
+    // The value is the CFA plus the given offset
+    ValOffset(i64),
+    Register(u16),
+    // Saved at the address computed by the expression
+    Expression(Vec<u8>),
+    ValExpression(Vec<u8>),
+}
+
+#[derive(Clone, Debug)]
+enum CfaRule {
+    RegisterOffset(u16, i64),
+    Expression(Vec<u8>),
+}
+
+// A row of the table described by the call frame instructions: how to find the CFA and the
+// saved registers at one pc
+#[derive(Clone, Debug)]
+struct Row {
+    cfa: CfaRule,
+    registers: HashMap<u16, Rule>,
+}
+
+struct Cie {
+    code_alignment: u64,
+    data_alignment: i64,
+    return_address_register: u16,
+    fde_encoding: u8,
+    has_augmentation_data: bool,
+    instructions: Range<usize>,
+}
+
+struct Fde {
+    cie: usize,
+    pcs: Range<u64>,
+    instructions: Range<usize>,
+}
+
+pub struct UnwoundFrame {
+    /// Canonical frame address: the value of the stack pointer before the call instruction
+    pub cfa: u64,
+    /// The registers of the caller, absent for the outermost frame
+    pub caller: Option<Registers>,
+}
+
+/// The call frame information of an object file, read from .eh_frame, which unlike
+/// .debug_frame is present even without debug information
+pub struct CallFrameInfo {
+    elf: Rc<Elf>,
+    // Link time address of .eh_frame, which pc relative pointers are relative to
+    section_address: u64,
+    cies: HashMap<usize, Cie>,
+    // Sorted by start address
+    fdes: Vec<Fde>,
+}
+
+fn read_encoded_pointer(cursor: &mut Cursor, encoding: u8, section_address: u64) -> Result<u64> {
+    if encoding == DW_EH_PE_OMIT {
+        return Ok(0);
+    }
+    let field_address = section_address + cursor.position() as u64;
+    let value = match encoding & 0x0f {
+        DW_EH_PE_ABSPTR | DW_EH_PE_UDATA8 | DW_EH_PE_SDATA8 => cursor.u64()?,
+        DW_EH_PE_ULEB128 => cursor.uleb128()?,
+        DW_EH_PE_UDATA2 => cursor.u16()? as u64,
+        DW_EH_PE_UDATA4 => cursor.u32()? as u64,
+        DW_EH_PE_SLEB128 => cursor.sleb128()? as u64,
+        DW_EH_PE_SDATA2 => cursor.u16()? as i16 as u64,
+        DW_EH_PE_SDATA4 => cursor.u32()? as i32 as u64,
+        other => bail!("unknown pointer format {other:#x}"),
+    };
+    match encoding & 0x70 {
+        0 => {}
+        DW_EH_PE_PCREL => return Ok(field_address.wrapping_add(value)),
+        other => bail!("unsupported pointer base {other:#x}"),
+    }
+    if encoding & DW_EH_PE_INDIRECT != 0 {
+        bail!("indirect pointers are not supported");
+    }
+    Ok(value)
+}
+
+impl CallFrameInfo {
+    pub fn new(elf: Rc<Elf>) -> Result<Self> {
+        let mut cfi = Self {
+            section_address: elf.section(".eh_frame").map_or(0, |s| s.sh_addr),
+            elf,
+            cies: HashMap::new(),
+            fdes: Vec::new(),
+        };
+        cfi.parse()?;
+        cfi.fdes.sort_by_key(|fde| fde.pcs.start);
+        Ok(cfi)
+    }
+
+    fn section(&self) -> &[u8] {
+        self.elf.section_data(".eh_frame").unwrap_or_default()
+    }
+
+    fn parse(&mut self) -> Result<()> {
+        let elf = self.elf.clone();
+        let data = elf.section_data(".eh_frame").unwrap_or_default();
+        let mut offset = 0;
+        while offset < data.len() {
+            let mut cursor = Cursor::at(data, offset);
+            let (length, _) = cursor.initial_length()?;
+            // A zero length entry terminates the section
+            if length == 0 {
+                break;
+            }
+            let end = cursor.position() + length as usize;
+            let id_position = cursor.position();
+            let id = cursor.u32()? as usize;
+            if id == 0 {
+                let cie = self.parse_cie(&mut cursor, end)?;
+                self.cies.insert(offset, cie);
+            } else {
+                // The id of an entry is the distance back to its CIE
+                let cie_offset = id_position
+                    .checked_sub(id)
+                    .ok_or_else(|| anyhow!("FDE at {offset:#x} has a bad CIE pointer"))?;
+                if !self.cies.contains_key(&cie_offset) {
+                    let mut cie_cursor = Cursor::at(data, cie_offset);
+                    let (cie_length, _) = cie_cursor.initial_length()?;
+                    let cie_end = cie_cursor.position() + cie_length as usize;
+                    cie_cursor.skip(4)?;
+                    let cie = self.parse_cie(&mut cie_cursor, cie_end)?;
+                    self.cies.insert(cie_offset, cie);
+                }
+                let fde = self.parse_fde(&mut cursor, end, cie_offset)?;
+                self.fdes.push(fde);
+            }
+            offset = end;
+        }
+        Ok(())
+    }
+
+    fn parse_cie(&self, cursor: &mut Cursor, end: usize) -> Result<Cie> {
+        let version = cursor.u8()?;
+        let augmentation = cursor.cstr()?.to_string();
+        if augmentation.contains("eh") {
+            cursor.skip(8)?;
+        }
+        let code_alignment = cursor.uleb128()?;
+        let data_alignment = cursor.sleb128()?;
+        let return_address_register = if version == 1 {
+            cursor.u8()? as u16
+        } else {
+            cursor.uleb128()? as u16
+        };
+
+        let mut cie = Cie {
+            code_alignment,
+            data_alignment,
+            return_address_register,
+            fde_encoding: DW_EH_PE_ABSPTR,
+            has_augmentation_data: augmentation.starts_with('z'),
+            instructions: 0..0,
+        };
+        if cie.has_augmentation_data {
+            let length = cursor.uleb128()? as usize;
+            let data_end = cursor.position() + length;
+            for c in augmentation.chars().skip(1) {
+                match c {
+                    'L' => {
+                        cursor.u8()?;
+                    }
+                    'P' => {
+                        let encoding = cursor.u8()?;
+                        read_encoded_pointer(cursor, encoding, self.section_address)?;
+                    }
+                    'R' => cie.fde_encoding = cursor.u8()?,
+                    // Signal handler frames, which kitt unwinds like any other
+                    'S' => {}
+                    _ => break,
+                }
+            }
+            cursor.skip(data_end.saturating_sub(cursor.position()))?;
+        }
+        cie.instructions = cursor.position()..end;
+        Ok(cie)
+    }
+
+    fn parse_fde(&self, cursor: &mut Cursor, end: usize, cie_offset: usize) -> Result<Fde> {
+        let cie = &self.cies[&cie_offset];
+        let start = read_encoded_pointer(cursor, cie.fde_encoding, self.section_address)?;
+        // The range is a plain length, only the format of the encoding applies to it
+        let length = read_encoded_pointer(cursor, cie.fde_encoding & 0x0f, 0)?;
+        if cie.has_augmentation_data {
+            let length = cursor.uleb128()? as usize;
+            cursor.skip(length)?;
+        }
+        Ok(Fde {
+            cie: cie_offset,
+            pcs: start..start.wrapping_add(length),
+            instructions: cursor.position()..end,
+        })
+    }
+
+    fn fde_for(&self, pc: u64) -> Option<&Fde> {
+        let index = self.fdes.partition_point(|fde| fde.pcs.start <= pc);
+        let fde = self.fdes.get(index.checked_sub(1)?)?;
+        fde.pcs.contains(&pc).then_some(fde)
+    }
+
+    // Runs call frame instructions, up to the row which applies to `pc`
+    fn execute(
+        &self,
+        cie: &Cie,
+        instructions: Range<usize>,
+        mut location: u64,
+        pc: u64,
+        initial: Option<&Row>,
+        row: &mut Row,
+    ) -> Result<()> {
+        let data = &self.section()[instructions];
+        let mut cursor = Cursor::new(data);
+        let mut remembered = Vec::new();
+        let factored = |offset: u64| offset as i64 * cie.data_alignment;
+        let restore = |row: &mut Row, register: u16| match initial
+            .and_then(|initial| initial.registers.get(&register))
+        {
+            Some(rule) => row.registers.insert(register, rule.clone()),
+            None => row.registers.remove(&register),
+        };
+
+        while !cursor.is_at_end() {
+            let op = cursor.u8()?;
+            let advance = match op & 0xc0 {
+                DW_CFA_ADVANCE_LOC => Some((op & 0x3f) as u64),
+                DW_CFA_OFFSET => {
+                    let offset = factored(cursor.uleb128()?);
+                    row.registers
+                        .insert((op & 0x3f) as u16, Rule::Offset(offset));
+                    None
+                }
+                DW_CFA_RESTORE => {
+                    restore(row, (op & 0x3f) as u16);
+                    None
+                }
+                _ => match op {
+                    DW_CFA_NOP => None,
+                    DW_CFA_SET_LOC => {
+                        location = read_encoded_pointer(
+                            &mut cursor,
+                            cie.fde_encoding,
+                            self.section_address,
+                        )?;
+                        if location > pc {
+                            return Ok(());
+                        }
+                        None
+                    }
+                    DW_CFA_ADVANCE_LOC1 => Some(cursor.u8()? as u64),
+                    DW_CFA_ADVANCE_LOC2 => Some(cursor.u16()? as u64),
+                    DW_CFA_ADVANCE_LOC4 => Some(cursor.u32()? as u64),
+                    DW_CFA_OFFSET_EXTENDED => {
+                        let register = cursor.uleb128()? as u16;
+                        let offset = factored(cursor.uleb128()?);
+                        row.registers.insert(register, Rule::Offset(offset));
+                        None
+                    }
+                    DW_CFA_OFFSET_EXTENDED_SF => {
+                        let register = cursor.uleb128()? as u16;
+                        let offset = cursor.sleb128()? * cie.data_alignment;
+                        row.registers.insert(register, Rule::Offset(offset));
+                        None
+                    }
+                    DW_CFA_GNU_NEGATIVE_OFFSET_EXTENDED => {
+                        let register = cursor.uleb128()? as u16;
+                        let offset = -factored(cursor.uleb128()?);
+                        row.registers.insert(register, Rule::Offset(offset));
+                        None
+                    }
+                    DW_CFA_VAL_OFFSET => {
+                        let register = cursor.uleb128()? as u16;
+                        let offset = factored(cursor.uleb128()?);
+                        row.registers.insert(register, Rule::ValOffset(offset));
+                        None
+                    }
+                    DW_CFA_VAL_OFFSET_SF => {
+                        let register = cursor.uleb128()? as u16;
+                        let offset = cursor.sleb128()? * cie.data_alignment;
+                        row.registers.insert(register, Rule::ValOffset(offset));
+                        None
+                    }
+                    DW_CFA_RESTORE_EXTENDED => {
+                        restore(row, cursor.uleb128()? as u16);
+                        None
+                    }
+                    DW_CFA_UNDEFINED => {
+                        row.registers
+                            .insert(cursor.uleb128()? as u16, Rule::Undefined);
+                        None
+                    }
+                    DW_CFA_SAME_VALUE => {
+                        row.registers
+                            .insert(cursor.uleb128()? as u16, Rule::SameValue);
+                        None
+                    }
+                    DW_CFA_REGISTER => {
+                        let register = cursor.uleb128()? as u16;
+                        let source = cursor.uleb128()? as u16;
+                        row.registers.insert(register, Rule::Register(source));
+                        None
+                    }
+                    DW_CFA_EXPRESSION | DW_CFA_VAL_EXPRESSION => {
+                        let register = cursor.uleb128()? as u16;
+                        let length = cursor.uleb128()? as usize;
+                        let expr = cursor.bytes(length)?.to_vec();
+                        let rule = if op == DW_CFA_EXPRESSION {
+                            Rule::Expression(expr)
+                        } else {
+                            Rule::ValExpression(expr)
+                        };
+                        row.registers.insert(register, rule);
+                        None
+                    }
+                    DW_CFA_REMEMBER_STATE => {
+                        remembered.push(row.clone());
+                        None
+                    }
+                    DW_CFA_RESTORE_STATE => {
+                        *row = remembered
+                            .pop()
+                            .ok_or_else(|| anyhow!("DW_CFA_restore_state without saved state"))?;
+                        None
+                    }
+                    DW_CFA_DEF_CFA | DW_CFA_DEF_CFA_SF => {
+                        let register = cursor.uleb128()? as u16;
+                        let offset = if op == DW_CFA_DEF_CFA {
+                            cursor.uleb128()? as i64
+                        } else {
+                            cursor.sleb128()? * cie.data_alignment
+                        };
+                        row.cfa = CfaRule::RegisterOffset(register, offset);
+                        None
+                    }
+                    DW_CFA_DEF_CFA_REGISTER => {
+                        let register = cursor.uleb128()? as u16;
+                        let CfaRule::RegisterOffset(_, offset) = row.cfa else {
+                            bail!("DW_CFA_def_cfa_register applied to a CFA expression");
+                        };
+                        row.cfa = CfaRule::RegisterOffset(register, offset);
+                        None
+                    }
+                    DW_CFA_DEF_CFA_OFFSET | DW_CFA_DEF_CFA_OFFSET_SF => {
+                        let offset = if op == DW_CFA_DEF_CFA_OFFSET {
+                            cursor.uleb128()? as i64
+                        } else {
+                            cursor.sleb128()? * cie.data_alignment
+                        };
+                        let CfaRule::RegisterOffset(register, _) = row.cfa else {
+                            bail!("DW_CFA_def_cfa_offset applied to a CFA expression");
+                        };
+                        row.cfa = CfaRule::RegisterOffset(register, offset);
+                        None
+                    }
+                    DW_CFA_DEF_CFA_EXPRESSION => {
+                        let length = cursor.uleb128()? as usize;
+                        row.cfa = CfaRule::Expression(cursor.bytes(length)?.to_vec());
+                        None
+                    }
+                    DW_CFA_GNU_ARGS_SIZE => {
+                        cursor.uleb128()?;
+                        None
+                    }
+                    other => bail!("unknown call frame instruction {other:#x}"),
+                },
+            };
+
+            if let Some(delta) = advance {
+                location += delta * cie.code_alignment;
+                if location > pc {
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The row of the table for a link time pc
+    fn row_for(&self, pc: u64) -> Result<Option<(Row, &Cie)>> {
+        let Some(fde) = self.fde_for(pc) else {
+            return Ok(None);
+        };
+        let cie = &self.cies[&fde.cie];
+        let mut row = Row {
+            cfa: CfaRule::RegisterOffset(RSP, 8),
+            registers: HashMap::new(),
+        };
+        self.execute(cie, cie.instructions.clone(), 0, u64::MAX, None, &mut row)?;
+        let initial = row.clone();
+        self.execute(
+            cie,
+            fde.instructions.clone(),
+            fde.pcs.start,
+            pc,
+            Some(&initial),
+            &mut row,
+        )?;
+        Ok(Some((row, cie)))
+    }
+
+    /// Recovers the registers of the caller of the frame with the given registers. `pc` is the
+    /// link time address to look up, which for frames other than the innermost should point into
+    /// the call instruction rather than at the return address.
+    pub fn unwind(
+        &self,
+        pc: u64,
+        registers: &Registers,
+        memory: &dyn MemoryReader,
+        load_bias: u64,
+    ) -> Result<Option<UnwoundFrame>> {
+        let Some((row, cie)) = self.row_for(pc)? else {
+            return Ok(None);
+        };
+
+        let mut ctx = EvalContext::new(memory, registers);
+        ctx.load_bias = load_bias;
+        let cfa = match &row.cfa {
+            CfaRule::RegisterOffset(register, offset) => {
+                ctx.read_register(*register)?.wrapping_add(*offset as u64)
+            }
+            CfaRule::Expression(expr) => match evaluate(expr, &ctx)? {
+                Location::Address(address) => address,
+                other => bail!("unexpected CFA location {other:?}"),
+            },
+        };
+        ctx.cfa = Some(cfa);
+
+        let return_address = cie.return_address_register;
+        if matches!(
+            row.registers.get(&return_address),
+            None | Some(Rule::Undefined)
+        ) {
+            return Ok(Some(UnwoundFrame { cfa, caller: None }));
+        }
+
+        // By convention the stack pointer of the caller is the CFA, the other registers keep
+        // their values unless a rule says otherwise
+        let mut caller = registers.clone();
+        caller.set_cached_u64(lookup_register_by_dwarf(RSP as i32)?, cfa);
+        for (&register, rule) in &row.registers {
+            let value = match rule {
+                Rule::Undefined | Rule::SameValue => continue,
+                Rule::Offset(offset) => {
+                    let bytes = memory.read_memory(cfa.wrapping_add(*offset as u64), 8)?;
+                    u64::from_le_bytes(bytes.try_into().unwrap_or_default())
+                }
+                Rule::ValOffset(offset) => cfa.wrapping_add(*offset as u64),
+                Rule::Register(source) => ctx.read_register(*source)?,
+                Rule::Expression(expr) | Rule::ValExpression(expr) => {
+                    // The CFA is pushed onto the stack before the expression runs
+                    let mut with_cfa = vec![DW_OP_CALL_FRAME_CFA];
+                    with_cfa.extend(expr);
+                    let address = match evaluate(&with_cfa, &ctx)? {
+                        Location::Address(address) => address,
+                        other => bail!("unexpected register location {other:?}"),
+                    };
+                    if let Rule::ValExpression(_) = rule {
+                        address
+                    } else {
+                        let bytes = memory.read_memory(address, 8)?;
+                        u64::from_le_bytes(bytes.try_into().unwrap_or_default())
+                    }
+                }
+            };
+            // The return address lands in the pc of the caller
+            let register = if register == return_address {
+                RETURN_ADDRESS
+            } else {
+                register
+            };
+            if let Ok(info) = lookup_register_by_dwarf(register as i32) {
+                caller.set_cached_u64(info, value);
+            }
+        }
+
+        Ok(Some(UnwoundFrame {
+            cfa,
+            caller: Some(caller),
+        }))
+    }
+}
@@ -0,0 +1,282 @@
+// Constants from the DWARF 5 specification, section 7. Only the values kitt understands are
+// listed here.
+
+// Unit header types
+pub const DW_UT_COMPILE: u8 = 0x01;
+pub const DW_UT_TYPE: u8 = 0x02;
+pub const DW_UT_PARTIAL: u8 = 0x03;
+pub const DW_UT_SKELETON: u8 = 0x04;
+pub const DW_UT_SPLIT_COMPILE: u8 = 0x05;
+pub const DW_UT_SPLIT_TYPE: u8 = 0x06;
+
+// Tags
+pub const DW_TAG_ARRAY_TYPE: u64 = 0x01;
+pub const DW_TAG_CLASS_TYPE: u64 = 0x02;
+pub const DW_TAG_ENUMERATION_TYPE: u64 = 0x04;
+pub const DW_TAG_FORMAL_PARAMETER: u64 = 0x05;
+pub const DW_TAG_LEXICAL_BLOCK: u64 = 0x0b;
+pub const DW_TAG_MEMBER: u64 = 0x0d;
+pub const DW_TAG_POINTER_TYPE: u64 = 0x0f;
+pub const DW_TAG_REFERENCE_TYPE: u64 = 0x10;
+pub const DW_TAG_STRUCTURE_TYPE: u64 = 0x13;
+pub const DW_TAG_SUBROUTINE_TYPE: u64 = 0x15;
+pub const DW_TAG_TYPEDEF: u64 = 0x16;
+pub const DW_TAG_UNION_TYPE: u64 = 0x17;
+pub const DW_TAG_PTR_TO_MEMBER_TYPE: u64 = 0x1f;
+pub const DW_TAG_SUBRANGE_TYPE: u64 = 0x21;
+pub const DW_TAG_BASE_TYPE: u64 = 0x24;
+pub const DW_TAG_CONST_TYPE: u64 = 0x26;
+pub const DW_TAG_ENUMERATOR: u64 = 0x28;
+pub const DW_TAG_SUBPROGRAM: u64 = 0x2e;
+pub const DW_TAG_VARIANT: u64 = 0x19;
+pub const DW_TAG_VARIABLE: u64 = 0x34;
+pub const DW_TAG_VOLATILE_TYPE: u64 = 0x35;
+pub const DW_TAG_RESTRICT_TYPE: u64 = 0x37;
+pub const DW_TAG_NAMESPACE: u64 = 0x39;
+pub const DW_TAG_UNSPECIFIED_TYPE: u64 = 0x3b;
+pub const DW_TAG_RVALUE_REFERENCE_TYPE: u64 = 0x42;
+pub const DW_TAG_ATOMIC_TYPE: u64 = 0x47;
+pub const DW_TAG_VARIANT_PART: u64 = 0x33;
+
+// Attributes
+pub const DW_AT_SIBLING: u64 = 0x01;
+pub const DW_AT_LOCATION: u64 = 0x02;
+pub const DW_AT_NAME: u64 = 0x03;
+pub const DW_AT_BYTE_SIZE: u64 = 0x0b;
+pub const DW_AT_BIT_OFFSET: u64 = 0x0c;
+pub const DW_AT_BIT_SIZE: u64 = 0x0d;
+pub const DW_AT_LOW_PC: u64 = 0x11;
+pub const DW_AT_HIGH_PC: u64 = 0x12;
+pub const DW_AT_DISCR: u64 = 0x15;
+pub const DW_AT_DISCR_VALUE: u64 = 0x16;
+pub const DW_AT_CONST_VALUE: u64 = 0x1c;
+pub const DW_AT_UPPER_BOUND: u64 = 0x2f;
+pub const DW_AT_ABSTRACT_ORIGIN: u64 = 0x31;
+pub const DW_AT_COUNT: u64 = 0x37;
+pub const DW_AT_DATA_MEMBER_LOCATION: u64 = 0x38;
+pub const DW_AT_DECLARATION: u64 = 0x3c;
+pub const DW_AT_ENCODING: u64 = 0x3e;
+pub const DW_AT_FRAME_BASE: u64 = 0x40;
+pub const DW_AT_SPECIFICATION: u64 = 0x47;
+pub const DW_AT_TYPE: u64 = 0x49;
+pub const DW_AT_RANGES: u64 = 0x55;
+pub const DW_AT_DATA_BIT_OFFSET: u64 = 0x6b;
+pub const DW_AT_LINKAGE_NAME: u64 = 0x6e;
+pub const DW_AT_STR_OFFSETS_BASE: u64 = 0x72;
+pub const DW_AT_ADDR_BASE: u64 = 0x73;
+pub const DW_AT_RNGLISTS_BASE: u64 = 0x74;
+pub const DW_AT_LOCLISTS_BASE: u64 = 0x8c;
+pub const DW_AT_MIPS_LINKAGE_NAME: u64 = 0x2007;
+pub const DW_AT_GNU_ADDR_BASE: u64 = 0x2133;
+
+// Attribute forms
+pub const DW_FORM_ADDR: u64 = 0x01;
+pub const DW_FORM_BLOCK2: u64 = 0x03;
+pub const DW_FORM_BLOCK4: u64 = 0x04;
+pub const DW_FORM_DATA2: u64 = 0x05;
+pub const DW_FORM_DATA4: u64 = 0x06;
+pub const DW_FORM_DATA8: u64 = 0x07;
+pub const DW_FORM_STRING: u64 = 0x08;
+pub const DW_FORM_BLOCK: u64 = 0x09;
+pub const DW_FORM_BLOCK1: u64 = 0x0a;
+pub const DW_FORM_DATA1: u64 = 0x0b;
+pub const DW_FORM_FLAG: u64 = 0x0c;
+pub const DW_FORM_SDATA: u64 = 0x0d;
+pub const DW_FORM_STRP: u64 = 0x0e;
+pub const DW_FORM_UDATA: u64 = 0x0f;
+pub const DW_FORM_REF_ADDR: u64 = 0x10;
+pub const DW_FORM_REF1: u64 = 0x11;
+pub const DW_FORM_REF2: u64 = 0x12;
+pub const DW_FORM_REF4: u64 = 0x13;
+pub const DW_FORM_REF8: u64 = 0x14;
+pub const DW_FORM_REF_UDATA: u64 = 0x15;
+pub const DW_FORM_INDIRECT: u64 = 0x16;
+pub const DW_FORM_SEC_OFFSET: u64 = 0x17;
+pub const DW_FORM_EXPRLOC: u64 = 0x18;
+pub const DW_FORM_FLAG_PRESENT: u64 = 0x19;
+pub const DW_FORM_STRX: u64 = 0x1a;
+pub const DW_FORM_ADDRX: u64 = 0x1b;
+pub const DW_FORM_REF_SUP4: u64 = 0x1c;
+pub const DW_FORM_STRP_SUP: u64 = 0x1d;
+pub const DW_FORM_DATA16: u64 = 0x1e;
+pub const DW_FORM_LINE_STRP: u64 = 0x1f;
+pub const DW_FORM_REF_SIG8: u64 = 0x20;
+pub const DW_FORM_IMPLICIT_CONST: u64 = 0x21;
+pub const DW_FORM_LOCLISTX: u64 = 0x22;
+pub const DW_FORM_RNGLISTX: u64 = 0x23;
+pub const DW_FORM_REF_SUP8: u64 = 0x24;
+pub const DW_FORM_STRX1: u64 = 0x25;
+pub const DW_FORM_STRX2: u64 = 0x26;
+pub const DW_FORM_STRX3: u64 = 0x27;
+pub const DW_FORM_STRX4: u64 = 0x28;
+pub const DW_FORM_ADDRX1: u64 = 0x29;
+pub const DW_FORM_ADDRX2: u64 = 0x2a;
+pub const DW_FORM_ADDRX3: u64 = 0x2b;
+pub const DW_FORM_ADDRX4: u64 = 0x2c;
+pub const DW_FORM_GNU_ADDR_INDEX: u64 = 0x1f01;
+pub const DW_FORM_GNU_STR_INDEX: u64 = 0x1f02;
+pub const DW_FORM_GNU_REF_ALT: u64 = 0x1f20;
+pub const DW_FORM_GNU_STRP_ALT: u64 = 0x1f21;
+
+// Base type encodings
+pub const DW_ATE_ADDRESS: u64 = 0x01;
+pub const DW_ATE_BOOLEAN: u64 = 0x02;
+pub const DW_ATE_FLOAT: u64 = 0x04;
+pub const DW_ATE_SIGNED: u64 = 0x05;
+pub const DW_ATE_SIGNED_CHAR: u64 = 0x06;
+pub const DW_ATE_UNSIGNED: u64 = 0x07;
+pub const DW_ATE_UNSIGNED_CHAR: u64 = 0x08;
+pub const DW_ATE_UTF: u64 = 0x10;
+
+// Range list entries (DWARF 5)
+pub const DW_RLE_END_OF_LIST: u8 = 0x00;
+pub const DW_RLE_BASE_ADDRESSX: u8 = 0x01;
+pub const DW_RLE_STARTX_ENDX: u8 = 0x02;
+pub const DW_RLE_STARTX_LENGTH: u8 = 0x03;
+pub const DW_RLE_OFFSET_PAIR: u8 = 0x04;
+pub const DW_RLE_BASE_ADDRESS: u8 = 0x05;
+pub const DW_RLE_START_END: u8 = 0x06;
+pub const DW_RLE_START_LENGTH: u8 = 0x07;
+
+// Location list entries (DWARF 5)
+pub const DW_LLE_END_OF_LIST: u8 = 0x00;
+pub const DW_LLE_BASE_ADDRESSX: u8 = 0x01;
+pub const DW_LLE_STARTX_ENDX: u8 = 0x02;
+pub const DW_LLE_STARTX_LENGTH: u8 = 0x03;
+pub const DW_LLE_OFFSET_PAIR: u8 = 0x04;
+pub const DW_LLE_DEFAULT_LOCATION: u8 = 0x05;
+pub const DW_LLE_BASE_ADDRESS: u8 = 0x06;
+pub const DW_LLE_START_END: u8 = 0x07;
+pub const DW_LLE_START_LENGTH: u8 = 0x08;
+
+// Location expression operations
+pub const DW_OP_ADDR: u8 = 0x03;
+pub const DW_OP_DEREF: u8 = 0x06;
+pub const DW_OP_CONST1U: u8 = 0x08;
+pub const DW_OP_CONST1S: u8 = 0x09;
+pub const DW_OP_CONST2U: u8 = 0x0a;
+pub const DW_OP_CONST2S: u8 = 0x0b;
+pub const DW_OP_CONST4U: u8 = 0x0c;
+pub const DW_OP_CONST4S: u8 = 0x0d;
+pub const DW_OP_CONST8U: u8 = 0x0e;
+pub const DW_OP_CONST8S: u8 = 0x0f;
+pub const DW_OP_CONSTU: u8 = 0x10;
+pub const DW_OP_CONSTS: u8 = 0x11;
+pub const DW_OP_DUP: u8 = 0x12;
+pub const DW_OP_DROP: u8 = 0x13;
+pub const DW_OP_OVER: u8 = 0x14;
+pub const DW_OP_PICK: u8 = 0x15;
+pub const DW_OP_SWAP: u8 = 0x16;
+pub const DW_OP_ROT: u8 = 0x17;
+pub const DW_OP_XDEREF: u8 = 0x18;
+pub const DW_OP_ABS: u8 = 0x19;
+pub const DW_OP_AND: u8 = 0x1a;
+pub const DW_OP_DIV: u8 = 0x1b;
+pub const DW_OP_MINUS: u8 = 0x1c;
+pub const DW_OP_MOD: u8 = 0x1d;
+pub const DW_OP_MUL: u8 = 0x1e;
+pub const DW_OP_NEG: u8 = 0x1f;
+pub const DW_OP_NOT: u8 = 0x20;
+pub const DW_OP_OR: u8 = 0x21;
+pub const DW_OP_PLUS: u8 = 0x22;
+pub const DW_OP_PLUS_UCONST: u8 = 0x23;
+pub const DW_OP_SHL: u8 = 0x24;
+pub const DW_OP_SHR: u8 = 0x25;
+pub const DW_OP_SHRA: u8 = 0x26;
+pub const DW_OP_XOR: u8 = 0x27;
+pub const DW_OP_BRA: u8 = 0x28;
+pub const DW_OP_EQ: u8 = 0x29;
+pub const DW_OP_GE: u8 = 0x2a;
+pub const DW_OP_GT: u8 = 0x2b;
+pub const DW_OP_LE: u8 = 0x2c;
+pub const DW_OP_LT: u8 = 0x2d;
+pub const DW_OP_NE: u8 = 0x2e;
+pub const DW_OP_SKIP: u8 = 0x2f;
+pub const DW_OP_LIT0: u8 = 0x30;
+pub const DW_OP_LIT31: u8 = 0x4f;
+pub const DW_OP_REG0: u8 = 0x50;
+pub const DW_OP_REG31: u8 = 0x6f;
+pub const DW_OP_BREG0: u8 = 0x70;
+pub const DW_OP_BREG31: u8 = 0x8f;
+pub const DW_OP_REGX: u8 = 0x90;
+pub const DW_OP_FBREG: u8 = 0x91;
+pub const DW_OP_BREGX: u8 = 0x92;
+pub const DW_OP_PIECE: u8 = 0x93;
+pub const DW_OP_DEREF_SIZE: u8 = 0x94;
+pub const DW_OP_XDEREF_SIZE: u8 = 0x95;
+pub const DW_OP_NOP: u8 = 0x96;
+pub const DW_OP_PUSH_OBJECT_ADDRESS: u8 = 0x97;
+pub const DW_OP_CALL2: u8 = 0x98;
+pub const DW_OP_CALL4: u8 = 0x99;
+pub const DW_OP_CALL_REF: u8 = 0x9a;
+pub const DW_OP_FORM_TLS_ADDRESS: u8 = 0x9b;
+pub const DW_OP_CALL_FRAME_CFA: u8 = 0x9c;
+pub const DW_OP_BIT_PIECE: u8 = 0x9d;
+pub const DW_OP_IMPLICIT_VALUE: u8 = 0x9e;
+pub const DW_OP_STACK_VALUE: u8 = 0x9f;
+pub const DW_OP_IMPLICIT_POINTER: u8 = 0xa0;
+pub const DW_OP_ADDRX: u8 = 0xa1;
+pub const DW_OP_CONSTX: u8 = 0xa2;
+pub const DW_OP_ENTRY_VALUE: u8 = 0xa3;
+pub const DW_OP_CONST_TYPE: u8 = 0xa4;
+pub const DW_OP_REGVAL_TYPE: u8 = 0xa5;
+pub const DW_OP_DEREF_TYPE: u8 = 0xa6;
+pub const DW_OP_XDEREF_TYPE: u8 = 0xa7;
+pub const DW_OP_CONVERT: u8 = 0xa8;
+pub const DW_OP_REINTERPRET: u8 = 0xa9;
+pub const DW_OP_GNU_PUSH_TLS_ADDRESS: u8 = 0xe0;
+pub const DW_OP_GNU_IMPLICIT_POINTER: u8 = 0xf2;
+pub const DW_OP_GNU_ENTRY_VALUE: u8 = 0xf3;
+pub const DW_OP_GNU_CONST_TYPE: u8 = 0xf4;
+pub const DW_OP_GNU_REGVAL_TYPE: u8 = 0xf5;
+pub const DW_OP_GNU_DEREF_TYPE: u8 = 0xf6;
+pub const DW_OP_GNU_CONVERT: u8 = 0xf7;
+pub const DW_OP_GNU_REINTERPRET: u8 = 0xf9;
+pub const DW_OP_GNU_PARAMETER_REF: u8 = 0xfa;
+pub const DW_OP_GNU_ADDR_INDEX: u8 = 0xfb;
+pub const DW_OP_GNU_CONST_INDEX: u8 = 0xfc;
+
+// Call frame instructions. The first three carry an operand in their low six bits.
+pub const DW_CFA_ADVANCE_LOC: u8 = 0x40;
+pub const DW_CFA_OFFSET: u8 = 0x80;
+pub const DW_CFA_RESTORE: u8 = 0xc0;
+pub const DW_CFA_NOP: u8 = 0x00;
+pub const DW_CFA_SET_LOC: u8 = 0x01;
+pub const DW_CFA_ADVANCE_LOC1: u8 = 0x02;
+pub const DW_CFA_ADVANCE_LOC2: u8 = 0x03;
+pub const DW_CFA_ADVANCE_LOC4: u8 = 0x04;
+pub const DW_CFA_OFFSET_EXTENDED: u8 = 0x05;
+pub const DW_CFA_RESTORE_EXTENDED: u8 = 0x06;
+pub const DW_CFA_UNDEFINED: u8 = 0x07;
+pub const DW_CFA_SAME_VALUE: u8 = 0x08;
+pub const DW_CFA_REGISTER: u8 = 0x09;
+pub const DW_CFA_REMEMBER_STATE: u8 = 0x0a;
+pub const DW_CFA_RESTORE_STATE: u8 = 0x0b;
+pub const DW_CFA_DEF_CFA: u8 = 0x0c;
+pub const DW_CFA_DEF_CFA_REGISTER: u8 = 0x0d;
+pub const DW_CFA_DEF_CFA_OFFSET: u8 = 0x0e;
+pub const DW_CFA_DEF_CFA_EXPRESSION: u8 = 0x0f;
+pub const DW_CFA_EXPRESSION: u8 = 0x10;
+pub const DW_CFA_OFFSET_EXTENDED_SF: u8 = 0x11;
+pub const DW_CFA_DEF_CFA_SF: u8 = 0x12;
+pub const DW_CFA_DEF_CFA_OFFSET_SF: u8 = 0x13;
+pub const DW_CFA_VAL_OFFSET: u8 = 0x14;
+pub const DW_CFA_VAL_OFFSET_SF: u8 = 0x15;
+pub const DW_CFA_VAL_EXPRESSION: u8 = 0x16;
+pub const DW_CFA_GNU_ARGS_SIZE: u8 = 0x2e;
+pub const DW_CFA_GNU_NEGATIVE_OFFSET_EXTENDED: u8 = 0x2f;
+
+// Pointer encodings of .eh_frame, from the Linux Standard Base. The low four bits give the
+// format of the value and the next three what it is relative to.
+pub const DW_EH_PE_ABSPTR: u8 = 0x00;
+pub const DW_EH_PE_ULEB128: u8 = 0x01;
+pub const DW_EH_PE_UDATA2: u8 = 0x02;
+pub const DW_EH_PE_UDATA4: u8 = 0x03;
+pub const DW_EH_PE_UDATA8: u8 = 0x04;
+pub const DW_EH_PE_SLEB128: u8 = 0x09;
+pub const DW_EH_PE_SDATA2: u8 = 0x0a;
+pub const DW_EH_PE_SDATA4: u8 = 0x0b;
+pub const DW_EH_PE_SDATA8: u8 = 0x0c;
+pub const DW_EH_PE_PCREL: u8 = 0x10;
+pub const DW_EH_PE_INDIRECT: u8 = 0x80;
+pub const DW_EH_PE_OMIT: u8 = 0xff;
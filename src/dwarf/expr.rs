@@ -0,0 +1,881 @@
+use crate::dwarf::consts::*;
+use crate::dwarf::{AttrValue, Cursor, Die, Dwarf};
+use crate::process::Process;
+use crate::reginfo::lookup_register_by_dwarf;
+use crate::registers::Registers;
+use anyhow::{anyhow, bail, Result};
+use std::ops::Range;
+
+// Guards against expressions which branch backwards forever
+const MAX_OPERATIONS: usize = 100_000;
+
+/// Expressions read the memory of the program being debugged through this trait, so that they
+/// can be evaluated against a live process as well as against synthetic memory in tests.
+pub trait MemoryReader {
+    /// Reads memory as the program has it, without the int3s of breakpoint sites
+    fn read_memory(&self, address: u64, amount: usize) -> Result<Vec<u8>>;
+}
+
+impl MemoryReader for Process {
+    fn read_memory(&self, address: u64, amount: usize) -> Result<Vec<u8>> {
+        self.read_memory_without_sites(address, amount)
+    }
+}
+
+/// Where the value of an object described by a location expression lives
+#[derive(Clone, Debug, PartialEq)]
+pub enum Location {
+    Address(u64),
+    Register(u16),
+    Value(u64),
+    Implicit(Vec<u8>),
+    /// A pointer to an object which was optimized away: the entry of that object and the byte
+    /// offset into it
+    ImplicitPointer {
+        die_offset: usize,
+        offset: i64,
+    },
+    Pieces(Vec<Piece>),
+    /// Nothing is known about the object, it was optimized out
+    Empty,
+}
+
+impl Location {
+    /// The bytes of an object which were optimized out, for composites with pieces that have no
+    /// location. Bytes holding only part of such a piece count as optimized out.
+    pub fn optimized_out(&self) -> Vec<Range<usize>> {
+        let Location::Pieces(pieces) = self else {
+            return Vec::new();
+        };
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        let mut bit = 0;
+        for piece in pieces {
+            if piece.location == Location::Empty {
+                let range = (bit / 8) as usize..(bit + piece.bit_size).div_ceil(8) as usize;
+                match ranges.last_mut() {
+                    Some(last) if last.end >= range.start => last.end = range.end,
+                    _ => ranges.push(range),
+                }
+            }
+            bit += piece.bit_size;
+        }
+        ranges
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Piece {
+    pub location: Location,
+    pub bit_size: u64,
+    /// Offset of the piece within the value at the location, used by DW_OP_bit_piece
+    pub bit_offset: u64,
+}
+
+/// The debug information an expression came from. It is needed by the operations which refer to
+/// other entries or to indexed addresses.
+pub struct DebugInfo<'a> {
+    pub dwarf: &'a Dwarf,
+    pub die: &'a Die,
+}
+
+pub struct EvalContext<'a> {
+    pub memory: &'a dyn MemoryReader,
+    pub registers: &'a Registers,
+    /// Difference between runtime and link time addresses of the object file
+    pub load_bias: u64,
+    pub frame_base: Option<u64>,
+    /// Canonical frame address of the current frame, for DW_OP_call_frame_cfa
+    pub cfa: Option<u64>,
+    /// Register values on entry to the current function, for DW_OP_entry_value
+    pub entry_registers: Option<&'a Registers>,
+    pub object_address: Option<u64>,
+    pub debug_info: Option<DebugInfo<'a>>,
+}
+
+impl<'a> EvalContext<'a> {
+    pub fn new(memory: &'a dyn MemoryReader, registers: &'a Registers) -> Self {
+        Self {
+            memory,
+            registers,
+            load_bias: 0,
+            frame_base: None,
+            cfa: None,
+            entry_registers: None,
+            object_address: None,
+            debug_info: None,
+        }
+    }
+
+    pub fn read_register(&self, dwarf_id: u16) -> Result<u64> {
+        read_register(self.registers, dwarf_id)
+    }
+
+    fn debug_info(&self, op: &str) -> Result<&DebugInfo<'a>> {
+        self.debug_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("{op} needs the debug information of the expression"))
+    }
+}
+
+fn read_register(registers: &Registers, dwarf_id: u16) -> Result<u64> {
+    let info = lookup_register_by_dwarf(dwarf_id as i32)?;
+    registers.read_as_u64(info)
+}
+
+fn truncate(value: u64, size: u64) -> u64 {
+    if size >= 8 {
+        value
+    } else {
+        value & ((1u64 << (size * 8)) - 1)
+    }
+}
+
+fn sign_extend(value: u64, size: u64) -> u64 {
+    if size >= 8 {
+        return value;
+    }
+    let shift = 64 - size * 8;
+    (((value << shift) as i64) >> shift) as u64
+}
+
+struct Evaluator<'c, 'a> {
+    ctx: &'c EvalContext<'a>,
+    stack: Vec<u64>,
+    pieces: Vec<Piece>,
+    // Set by the operations which describe where the object is rather than compute its address
+    location: Option<Location>,
+    operations: usize,
+}
+
+impl Evaluator<'_, '_> {
+    fn pop(&mut self) -> Result<u64> {
+        self.stack
+            .pop()
+            .ok_or_else(|| anyhow!("DWARF expression stack underflow"))
+    }
+
+    fn peek(&self, depth: usize) -> Result<u64> {
+        self.stack
+            .len()
+            .checked_sub(depth + 1)
+            .map(|index| self.stack[index])
+            .ok_or_else(|| anyhow!("DWARF expression stack underflow"))
+    }
+
+    fn binary(&mut self, f: impl Fn(u64, u64) -> Result<u64>) -> Result<()> {
+        let rhs = self.pop()?;
+        let lhs = self.pop()?;
+        self.stack.push(f(lhs, rhs)?);
+        Ok(())
+    }
+
+    fn compare(&mut self, f: impl Fn(i64, i64) -> bool) -> Result<()> {
+        self.binary(|lhs, rhs| Ok(f(lhs as i64, rhs as i64) as u64))
+    }
+
+    fn read_sized(&self, address: u64, size: u64) -> Result<u64> {
+        if size > 8 {
+            bail!("cannot dereference {size} bytes onto the expression stack");
+        }
+        let bytes = self.ctx.memory.read_memory(address, size as usize)?;
+        let mut buf = [0u8; 8];
+        buf[..bytes.len()].copy_from_slice(&bytes);
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    // Size and signedness of the base type at a unit relative offset, used by the typed
+    // operations of DWARF 5
+    fn base_type(&self, unit_offset: u64) -> Result<(u64, bool)> {
+        // An offset of zero denotes the generic type, an integer the size of an address
+        if unit_offset == 0 {
+            return Ok((8, false));
+        }
+        let info = self.ctx.debug_info("typed operation")?;
+        let die = info.dwarf.unit_relative_die(info.die, unit_offset)?;
+        if die.tag != DW_TAG_BASE_TYPE {
+            bail!("typed operation refers to a non base type");
+        }
+        let size = die.attr(DW_AT_BYTE_SIZE).and_then(AttrValue::as_u64);
+        let encoding = die.attr(DW_AT_ENCODING).and_then(AttrValue::as_u64);
+        let signed = matches!(encoding, Some(DW_ATE_SIGNED) | Some(DW_ATE_SIGNED_CHAR));
+        Ok((size.unwrap_or(8), signed))
+    }
+
+    fn convert(&self, value: u64, unit_offset: u64) -> Result<u64> {
+        let (size, signed) = self.base_type(unit_offset)?;
+        let value = truncate(value, size);
+        Ok(if signed {
+            sign_extend(value, size)
+        } else {
+            value
+        })
+    }
+
+    // Runs the expression of another entry on the current stack, for DW_OP_call*
+    fn call(&mut self, die: Die) -> Result<()> {
+        match die.attr(DW_AT_LOCATION) {
+            None => Ok(()),
+            Some(AttrValue::Block(expr)) => self.run(&expr.clone()),
+            Some(_) => bail!("DW_OP_call of an entry with a location list is not supported"),
+        }
+    }
+
+    fn entry_value(&mut self, expr: &[u8]) -> Result<()> {
+        let registers = self.ctx.entry_registers.ok_or_else(|| {
+            anyhow!(
+                "DW_OP_entry_value is not supported: the registers at function entry are unknown"
+            )
+        })?;
+
+        // The common form names a register directly, which stands for its value at entry
+        let mut cursor = Cursor::new(expr);
+        let op = cursor.u8()?;
+        let register = match op {
+            DW_OP_REG0..=DW_OP_REG31 if cursor.is_at_end() => Some((op - DW_OP_REG0) as u16),
+            DW_OP_REGX => {
+                let id = cursor.uleb128()? as u16;
+                cursor.is_at_end().then_some(id)
+            }
+            _ => None,
+        };
+        if let Some(register) = register {
+            self.stack.push(read_register(registers, register)?);
+            return Ok(());
+        }
+
+        let ctx = EvalContext {
+            memory: self.ctx.memory,
+            registers,
+            load_bias: self.ctx.load_bias,
+            frame_base: None,
+            cfa: None,
+            entry_registers: None,
+            object_address: None,
+            debug_info: None,
+        };
+        match evaluate(expr, &ctx)? {
+            Location::Address(value) | Location::Value(value) => self.stack.push(value),
+            other => bail!("unexpected entry value location {other:?}"),
+        }
+        Ok(())
+    }
+
+    fn finish_piece(&mut self, bit_size: u64, bit_offset: u64) -> Result<()> {
+        let location = match self.location.take() {
+            Some(location) => location,
+            None if self.stack.is_empty() => Location::Empty,
+            None => Location::Address(self.pop()?),
+        };
+        self.pieces.push(Piece {
+            location,
+            bit_size,
+            bit_offset,
+        });
+        Ok(())
+    }
+
+    fn run(&mut self, expr: &[u8]) -> Result<()> {
+        let mut cursor = Cursor::new(expr);
+        let ctx = self.ctx;
+
+        while !cursor.is_at_end() {
+            self.operations += 1;
+            if self.operations > MAX_OPERATIONS {
+                bail!("DWARF expression did not terminate");
+            }
+
+            let op = cursor.u8()?;
+            if self.location.is_some() && !matches!(op, DW_OP_PIECE | DW_OP_BIT_PIECE) {
+                bail!("operation {op:#x} follows a location description");
+            }
+
+            match op {
+                DW_OP_ADDR => self.stack.push(cursor.u64()?.wrapping_add(ctx.load_bias)),
+                DW_OP_ADDRX | DW_OP_GNU_ADDR_INDEX | DW_OP_CONSTX | DW_OP_GNU_CONST_INDEX => {
+                    let index = cursor.uleb128()?;
+                    let info = ctx.debug_info("DW_OP_addrx")?;
+                    let value = info.dwarf.indexed_address(info.die, index)?;
+                    // DW_OP_constx values are not addresses, and are not relocated
+                    if matches!(op, DW_OP_ADDRX | DW_OP_GNU_ADDR_INDEX) {
+                        self.stack.push(value.wrapping_add(ctx.load_bias));
+                    } else {
+                        self.stack.push(value);
+                    }
+                }
+                DW_OP_CONST1U => self.stack.push(cursor.fixed(1)?),
+                DW_OP_CONST1S => self.stack.push(cursor.fixed(1)? as i8 as u64),
+                DW_OP_CONST2U => self.stack.push(cursor.fixed(2)?),
+                DW_OP_CONST2S => self.stack.push(cursor.fixed(2)? as i16 as u64),
+                DW_OP_CONST4U => self.stack.push(cursor.fixed(4)?),
+                DW_OP_CONST4S => self.stack.push(cursor.fixed(4)? as i32 as u64),
+                DW_OP_CONST8U | DW_OP_CONST8S => self.stack.push(cursor.u64()?),
+                DW_OP_CONSTU => self.stack.push(cursor.uleb128()?),
+                DW_OP_CONSTS => self.stack.push(cursor.sleb128()? as u64),
+                DW_OP_LIT0..=DW_OP_LIT31 => self.stack.push((op - DW_OP_LIT0) as u64),
+
+                DW_OP_DUP => self.stack.push(self.peek(0)?),
+                DW_OP_DROP => {
+                    self.pop()?;
+                }
+                DW_OP_OVER => self.stack.push(self.peek(1)?),
+                DW_OP_PICK => {
+                    let index = cursor.u8()? as usize;
+                    self.stack.push(self.peek(index)?);
+                }
+                DW_OP_SWAP => {
+                    let top = self.pop()?;
+                    let second = self.pop()?;
+                    self.stack.extend([top, second]);
+                }
+                DW_OP_ROT => {
+                    let top = self.pop()?;
+                    let second = self.pop()?;
+                    let third = self.pop()?;
+                    self.stack.extend([top, third, second]);
+                }
+
+                DW_OP_DEREF | DW_OP_XDEREF => {
+                    let address = self.pop()?;
+                    // The address space identifier is ignored, x86-64 has only one
+                    if op == DW_OP_XDEREF {
+                        self.pop()?;
+                    }
+                    self.stack.push(self.read_sized(address, 8)?);
+                }
+                DW_OP_DEREF_SIZE | DW_OP_XDEREF_SIZE => {
+                    let size = cursor.u8()? as u64;
+                    let address = self.pop()?;
+                    if op == DW_OP_XDEREF_SIZE {
+                        self.pop()?;
+                    }
+                    self.stack.push(self.read_sized(address, size)?);
+                }
+                DW_OP_DEREF_TYPE | DW_OP_GNU_DEREF_TYPE | DW_OP_XDEREF_TYPE => {
+                    let size = cursor.u8()? as u64;
+                    let type_offset = cursor.uleb128()?;
+                    let address = self.pop()?;
+                    if op == DW_OP_XDEREF_TYPE {
+                        self.pop()?;
+                    }
+                    let value = self.read_sized(address, size)?;
+                    self.stack.push(self.convert(value, type_offset)?);
+                }
+
+                DW_OP_ABS => {
+                    let value = self.pop()? as i64;
+                    self.stack.push(value.wrapping_abs() as u64);
+                }
+                DW_OP_NEG => {
+                    let value = self.pop()? as i64;
+                    self.stack.push(value.wrapping_neg() as u64);
+                }
+                DW_OP_NOT => {
+                    let value = self.pop()?;
+                    self.stack.push(!value);
+                }
+                DW_OP_AND => self.binary(|lhs, rhs| Ok(lhs & rhs))?,
+                DW_OP_OR => self.binary(|lhs, rhs| Ok(lhs | rhs))?,
+                DW_OP_XOR => self.binary(|lhs, rhs| Ok(lhs ^ rhs))?,
+                DW_OP_PLUS => self.binary(|lhs, rhs| Ok(lhs.wrapping_add(rhs)))?,
+                DW_OP_MINUS => self.binary(|lhs, rhs| Ok(lhs.wrapping_sub(rhs)))?,
+                DW_OP_MUL => self.binary(|lhs, rhs| Ok(lhs.wrapping_mul(rhs)))?,
+                DW_OP_DIV => self.binary(|lhs, rhs| {
+                    if rhs == 0 {
+                        bail!("division by zero in DWARF expression");
+                    }
+                    Ok((lhs as i64).wrapping_div(rhs as i64) as u64)
+                })?,
+                DW_OP_MOD => self.binary(|lhs, rhs| {
+                    if rhs == 0 {
+                        bail!("division by zero in DWARF expression");
+                    }
+                    Ok(lhs % rhs)
+                })?,
+                DW_OP_SHL => {
+                    self.binary(|lhs, rhs| Ok(lhs.checked_shl(rhs as u32).unwrap_or(0)))?
+                }
+                DW_OP_SHR => {
+                    self.binary(|lhs, rhs| Ok(lhs.checked_shr(rhs as u32).unwrap_or(0)))?
+                }
+                DW_OP_SHRA => self.binary(|lhs, rhs| Ok(((lhs as i64) >> rhs.min(63)) as u64))?,
+                DW_OP_PLUS_UCONST => {
+                    let value = self.pop()?;
+                    self.stack.push(value.wrapping_add(cursor.uleb128()?));
+                }
+
+                DW_OP_EQ => self.compare(|lhs, rhs| lhs == rhs)?,
+                DW_OP_NE => self.compare(|lhs, rhs| lhs != rhs)?,
+                DW_OP_GE => self.compare(|lhs, rhs| lhs >= rhs)?,
+                DW_OP_GT => self.compare(|lhs, rhs| lhs > rhs)?,
+                DW_OP_LE => self.compare(|lhs, rhs| lhs <= rhs)?,
+                DW_OP_LT => self.compare(|lhs, rhs| lhs < rhs)?,
+                DW_OP_SKIP | DW_OP_BRA => {
+                    let offset = cursor.fixed(2)? as i16 as i64;
+                    let taken = op == DW_OP_SKIP || self.pop()? != 0;
+                    if taken {
+                        let target = cursor.position() as i64 + offset;
+                        if target < 0 || target as usize > expr.len() {
+                            bail!("branch target {target} is outside of the expression");
+                        }
+                        cursor = Cursor::at(expr, target as usize);
+                    }
+                }
+
+                DW_OP_REG0..=DW_OP_REG31 => {
+                    self.location = Some(Location::Register((op - DW_OP_REG0) as u16))
+                }
+                DW_OP_REGX => self.location = Some(Location::Register(cursor.uleb128()? as u16)),
+                DW_OP_BREG0..=DW_OP_BREG31 => {
+                    let value = ctx.read_register((op - DW_OP_BREG0) as u16)?;
+                    self.stack
+                        .push(value.wrapping_add(cursor.sleb128()? as u64));
+                }
+                DW_OP_BREGX => {
+                    let value = ctx.read_register(cursor.uleb128()? as u16)?;
+                    self.stack
+                        .push(value.wrapping_add(cursor.sleb128()? as u64));
+                }
+                DW_OP_REGVAL_TYPE | DW_OP_GNU_REGVAL_TYPE => {
+                    let value = ctx.read_register(cursor.uleb128()? as u16)?;
+                    let type_offset = cursor.uleb128()?;
+                    self.stack.push(self.convert(value, type_offset)?);
+                }
+                DW_OP_FBREG => {
+                    let frame_base = ctx
+                        .frame_base
+                        .ok_or_else(|| anyhow!("no frame base available for DW_OP_fbreg"))?;
+                    self.stack
+                        .push(frame_base.wrapping_add(cursor.sleb128()? as u64));
+                }
+                DW_OP_CALL_FRAME_CFA => {
+                    let cfa = ctx
+                        .cfa
+                        .ok_or_else(|| anyhow!("no canonical frame address available"))?;
+                    self.stack.push(cfa);
+                }
+                DW_OP_PUSH_OBJECT_ADDRESS => {
+                    let address = ctx
+                        .object_address
+                        .ok_or_else(|| anyhow!("no object address available"))?;
+                    self.stack.push(address);
+                }
+
+                DW_OP_CONST_TYPE | DW_OP_GNU_CONST_TYPE => {
+                    let type_offset = cursor.uleb128()?;
+                    let size = cursor.u8()? as usize;
+                    let bytes = cursor.bytes(size)?;
+                    if size > 8 {
+                        bail!("typed constants wider than 8 bytes are not supported");
+                    }
+                    let mut buf = [0u8; 8];
+                    buf[..size].copy_from_slice(bytes);
+                    let value = self.convert(u64::from_le_bytes(buf), type_offset)?;
+                    self.stack.push(value);
+                }
+                DW_OP_CONVERT | DW_OP_GNU_CONVERT | DW_OP_REINTERPRET | DW_OP_GNU_REINTERPRET => {
+                    let type_offset = cursor.uleb128()?;
+                    let value = self.pop()?;
+                    self.stack.push(self.convert(value, type_offset)?);
+                }
+
+                DW_OP_CALL2 | DW_OP_CALL4 | DW_OP_CALL_REF => {
+                    let info = ctx.debug_info("DW_OP_call")?;
+                    let die = match op {
+                        DW_OP_CALL2 => info.dwarf.unit_relative_die(info.die, cursor.fixed(2)?)?,
+                        DW_OP_CALL4 => info.dwarf.unit_relative_die(info.die, cursor.fixed(4)?)?,
+                        _ => info.dwarf.die_at(cursor.fixed(4)? as usize)?,
+                    };
+                    self.call(die)?;
+                }
+                DW_OP_ENTRY_VALUE | DW_OP_GNU_ENTRY_VALUE => {
+                    let len = cursor.uleb128()? as usize;
+                    let sub_expr = cursor.bytes(len)?;
+                    self.entry_value(sub_expr)?;
+                }
+
+                DW_OP_STACK_VALUE => self.location = Some(Location::Value(self.pop()?)),
+                DW_OP_IMPLICIT_VALUE => {
+                    let len = cursor.uleb128()? as usize;
+                    self.location = Some(Location::Implicit(cursor.bytes(len)?.to_vec()));
+                }
+                DW_OP_IMPLICIT_POINTER | DW_OP_GNU_IMPLICIT_POINTER => {
+                    // The reference is 4 bytes in the 32 bit DWARF format
+                    let die_offset = cursor.fixed(4)? as usize;
+                    let offset = cursor.sleb128()?;
+                    self.location = Some(Location::ImplicitPointer { die_offset, offset });
+                }
+                DW_OP_PIECE => {
+                    let size = cursor.uleb128()?;
+                    self.finish_piece(size * 8, 0)?;
+                }
+                DW_OP_BIT_PIECE => {
+                    let bit_size = cursor.uleb128()?;
+                    let bit_offset = cursor.uleb128()?;
+                    self.finish_piece(bit_size, bit_offset)?;
+                }
+                DW_OP_NOP => {}
+
+                DW_OP_FORM_TLS_ADDRESS | DW_OP_GNU_PUSH_TLS_ADDRESS => {
+                    bail!("thread local storage is not supported")
+                }
+                DW_OP_GNU_PARAMETER_REF => bail!("DW_OP_GNU_parameter_ref is not supported"),
+                other => bail!("unknown DWARF expression operation {other:#x}"),
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn evaluate(expr: &[u8], ctx: &EvalContext) -> Result<Location> {
+    let mut evaluator = Evaluator {
+        ctx,
+        stack: Vec::new(),
+        pieces: Vec::new(),
+        location: None,
+        operations: 0,
+    };
+    evaluator.run(expr)?;
+
+    if !evaluator.pieces.is_empty() {
+        return Ok(Location::Pieces(evaluator.pieces));
+    }
+    match evaluator.location {
+        Some(location) => Ok(location),
+        None if evaluator.stack.is_empty() => Ok(Location::Empty),
+        None => Ok(Location::Address(evaluator.pop()?)),
+    }
+}
+
+// Copies `bit_size` bits starting at `src_bit` of `src` into `dst` starting at `dst_bit`
+fn copy_bits(dst: &mut [u8], dst_bit: u64, src: &[u8], src_bit: u64, bit_size: u64) {
+    for i in 0..bit_size {
+        let (s, d) = (src_bit + i, dst_bit + i);
+        let Some(byte) = src.get((s / 8) as usize) else {
+            return;
+        };
+        let bit = (byte >> (s % 8)) & 1;
+        if let Some(target) = dst.get_mut((d / 8) as usize) {
+            *target = (*target & !(1 << (d % 8))) | (bit << (d % 8));
+        }
+    }
+}
+
+/// Reads `size` bytes of the object at the given location. Pieces without a location are left
+/// as zeros, see [`Location::optimized_out`].
+pub fn read_location(location: &Location, size: usize, ctx: &EvalContext) -> Result<Vec<u8>> {
+    let mut data = match location {
+        Location::Address(address) => ctx.memory.read_memory(*address, size)?,
+        Location::Register(id) => {
+            let info = lookup_register_by_dwarf(*id as i32)?;
+            let mut bytes = ctx.registers.read(info)?.widen().to_vec();
+            bytes.truncate(info.size);
+            bytes
+        }
+        Location::Value(value) => value.to_le_bytes().to_vec(),
+        Location::Implicit(bytes) => bytes.clone(),
+        Location::ImplicitPointer { .. } => bail!("<synthetic pointer>"),
+        Location::Empty => bail!("<optimized out>"),
+        Location::Pieces(pieces) => {
+            let mut data = vec![0u8; size];
+            let mut bit = 0;
+            for piece in pieces {
+                if piece.location == Location::Empty {
+                    bit += piece.bit_size;
+                    continue;
+                }
+                let needed = (piece.bit_offset + piece.bit_size).div_ceil(8) as usize;
+                let bytes = read_location(&piece.location, needed, ctx)?;
+                copy_bits(&mut data, bit, &bytes, piece.bit_offset, piece.bit_size);
+                bit += piece.bit_size;
+            }
+            data
+        }
+    };
+    data.resize(size, 0);
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dwarf::consts::*;
+    use crate::dwarf::expr::{evaluate, read_location, EvalContext, Location, MemoryReader};
+    use crate::registers::Registers;
+    use anyhow::{bail, Result};
+
+    // A single region of memory starting at `base`
+    struct FakeMemory {
+        base: u64,
+        data: Vec<u8>,
+    }
+
+    impl MemoryReader for FakeMemory {
+        fn read_memory(&self, address: u64, amount: usize) -> Result<Vec<u8>> {
+            let start = address.wrapping_sub(self.base) as usize;
+            match self.data.get(start..start + amount) {
+                Some(bytes) => Ok(bytes.to_vec()),
+                None => bail!("address {address:#x} is not mapped"),
+            }
+        }
+    }
+
+    const BASE: u64 = 0x1000;
+
+    fn memory() -> FakeMemory {
+        let mut data = vec![0u8; 64];
+        data[..8].copy_from_slice(&0xdead_beef_u64.to_le_bytes());
+        data[8..16].copy_from_slice(&(BASE + 16).to_le_bytes());
+        data[16..20].copy_from_slice(&[1, 2, 3, 4]);
+        FakeMemory { base: BASE, data }
+    }
+
+    fn registers_with(rsp: u64, rdi: u64) -> Registers {
+        let mut registers = Registers::default();
+        let mut user = registers.user_data();
+        user.regs.rsp = rsp;
+        user.regs.rdi = rdi;
+        registers.set_user_data(user);
+        registers
+    }
+
+    fn value_of(expr: &[u8]) -> Result<u64> {
+        let memory = memory();
+        let registers = registers_with(BASE, 7);
+        let ctx = EvalContext::new(&memory, &registers);
+        match evaluate(expr, &ctx)? {
+            Location::Value(value) | Location::Address(value) => Ok(value),
+            other => bail!("unexpected location {other:?}"),
+        }
+    }
+
+    #[test]
+    fn arithmetic_and_stack_operations() {
+        let lit = |n: u8| DW_OP_LIT0 + n;
+        assert_eq!(value_of(&[lit(6), lit(7), DW_OP_MUL]).unwrap(), 42);
+        assert_eq!(
+            value_of(&[lit(3), lit(5), DW_OP_MINUS]).unwrap(),
+            -2i64 as u64
+        );
+        assert_eq!(
+            value_of(&[DW_OP_CONST1S, 0xf9, lit(2), DW_OP_DIV]).unwrap(),
+            -3i64 as u64
+        );
+        assert_eq!(value_of(&[lit(17), lit(5), DW_OP_MOD]).unwrap(), 2);
+        assert_eq!(
+            value_of(&[DW_OP_CONST1S, 0xf0, lit(2), DW_OP_SHRA]).unwrap(),
+            -4i64 as u64
+        );
+        assert_eq!(value_of(&[DW_OP_CONST1S, 0xf0, DW_OP_ABS]).unwrap(), 16);
+        assert_eq!(value_of(&[lit(1), lit(2), lit(3), DW_OP_ROT]).unwrap(), 2);
+        assert_eq!(value_of(&[lit(1), lit(2), DW_OP_OVER]).unwrap(), 1);
+        assert_eq!(
+            value_of(&[lit(1), lit(2), lit(3), DW_OP_PICK, 2]).unwrap(),
+            1
+        );
+        assert_eq!(
+            value_of(&[lit(1), lit(2), DW_OP_SWAP, DW_OP_DROP]).unwrap(),
+            2
+        );
+        assert_eq!(value_of(&[lit(2), lit(3), DW_OP_LT]).unwrap(), 1);
+        assert_eq!(
+            value_of(&[
+                DW_OP_CONSTU,
+                0xe5,
+                0x8e,
+                0x26,
+                DW_OP_PLUS_UCONST,
+                1,
+                DW_OP_STACK_VALUE
+            ])
+            .unwrap(),
+            624486
+        );
+    }
+
+    #[test]
+    fn branches() {
+        // Counts down from 3 to 0 with a backwards branch
+        let expr = [
+            DW_OP_LIT0 + 3,
+            DW_OP_LIT0 + 1,
+            DW_OP_MINUS,
+            DW_OP_DUP,
+            DW_OP_BRA,
+            0xfa,
+            0xff,
+        ];
+        assert_eq!(value_of(&expr).unwrap(), 0);
+
+        let expr = [DW_OP_SKIP, 1, 0, DW_OP_LIT0 + 9, DW_OP_LIT0 + 4];
+        assert_eq!(value_of(&expr).unwrap(), 4);
+
+        let expr = [DW_OP_SKIP, 0xfd, 0xff];
+        assert!(value_of(&expr).is_err_and(|err| err.to_string().contains("did not terminate")));
+    }
+
+    #[test]
+    fn registers_and_memory() {
+        // rsp points at the start of memory, which holds 0xdeadbeef
+        assert_eq!(
+            value_of(&[DW_OP_BREG0 + 7, 0, DW_OP_DEREF]).unwrap(),
+            0xdead_beef
+        );
+        assert_eq!(value_of(&[DW_OP_BREGX, 5, 3]).unwrap(), 10);
+        assert_eq!(
+            value_of(&[DW_OP_BREG0 + 7, 16, DW_OP_DEREF_SIZE, 2]).unwrap(),
+            0x0201
+        );
+        // Follow the pointer stored at offset 8
+        assert_eq!(
+            value_of(&[DW_OP_BREG0 + 7, 8, DW_OP_DEREF, DW_OP_DEREF_SIZE, 1]).unwrap(),
+            1
+        );
+        assert!(value_of(&[DW_OP_LIT0, DW_OP_DEREF]).is_err());
+
+        let memory = memory();
+        let registers = registers_with(BASE, 7);
+        let ctx = EvalContext::new(&memory, &registers);
+        assert_eq!(
+            evaluate(&[DW_OP_REG0 + 5], &ctx).unwrap(),
+            Location::Register(5)
+        );
+        assert_eq!(
+            evaluate(&[DW_OP_REGX, 16], &ctx).unwrap(),
+            Location::Register(16)
+        );
+        assert!(evaluate(&[DW_OP_REG0, DW_OP_LIT0], &ctx).is_err());
+    }
+
+    #[test]
+    fn frame_base_and_cfa() {
+        let memory = memory();
+        let registers = registers_with(BASE, 7);
+        let mut ctx = EvalContext::new(&memory, &registers);
+        assert!(evaluate(&[DW_OP_FBREG, 0x10], &ctx).is_err());
+        assert!(evaluate(&[DW_OP_CALL_FRAME_CFA], &ctx).is_err());
+
+        ctx.frame_base = Some(0x2000);
+        ctx.cfa = Some(0x3000);
+        assert_eq!(
+            evaluate(&[DW_OP_FBREG, 0x70], &ctx).unwrap(),
+            Location::Address(0x2000 - 16)
+        );
+        assert_eq!(
+            evaluate(&[DW_OP_CALL_FRAME_CFA, DW_OP_CONST1U, 8, DW_OP_MINUS], &ctx).unwrap(),
+            Location::Address(0x3000 - 8)
+        );
+    }
+
+    #[test]
+    fn address_is_relocated_by_load_bias() {
+        let memory = memory();
+        let registers = registers_with(BASE, 7);
+        let mut ctx = EvalContext::new(&memory, &registers);
+        ctx.load_bias = 0x5000;
+        let mut expr = vec![DW_OP_ADDR];
+        expr.extend(0x100u64.to_le_bytes());
+        assert_eq!(evaluate(&expr, &ctx).unwrap(), Location::Address(0x5100));
+    }
+
+    #[test]
+    fn implicit_values() {
+        let memory = memory();
+        let registers = registers_with(BASE, 7);
+        let ctx = EvalContext::new(&memory, &registers);
+
+        let location = evaluate(&[DW_OP_IMPLICIT_VALUE, 2, 0x34, 0x12], &ctx).unwrap();
+        assert_eq!(location, Location::Implicit(vec![0x34, 0x12]));
+        assert_eq!(
+            read_location(&location, 4, &ctx).unwrap(),
+            [0x34, 0x12, 0, 0]
+        );
+
+        let location = evaluate(&[DW_OP_LIT0 + 5, DW_OP_STACK_VALUE], &ctx).unwrap();
+        assert_eq!(read_location(&location, 2, &ctx).unwrap(), [5, 0]);
+
+        let location = evaluate(&[], &ctx).unwrap();
+        assert_eq!(location, Location::Empty);
+        assert!(read_location(&location, 4, &ctx).is_err());
+    }
+
+    #[test]
+    fn pieces_are_assembled() {
+        let memory = memory();
+        let registers = registers_with(BASE, 0x0605);
+        let ctx = EvalContext::new(&memory, &registers);
+
+        // Two bytes from rdi followed by two bytes of memory at BASE + 16
+        let expr = [
+            DW_OP_REG0 + 5,
+            DW_OP_PIECE,
+            2,
+            DW_OP_BREG0 + 7,
+            16,
+            DW_OP_PIECE,
+            2,
+        ];
+        let location = evaluate(&expr, &ctx).unwrap();
+        assert!(matches!(&location, Location::Pieces(pieces) if pieces.len() == 2));
+        assert_eq!(read_location(&location, 4, &ctx).unwrap(), [5, 6, 1, 2]);
+
+        // A nibble from each of two constants
+        let expr = [
+            DW_OP_LIT0 + 0xa,
+            DW_OP_STACK_VALUE,
+            DW_OP_BIT_PIECE,
+            4,
+            0,
+            DW_OP_CONST1U,
+            0x30,
+            DW_OP_STACK_VALUE,
+            DW_OP_BIT_PIECE,
+            4,
+            4,
+        ];
+        let location = evaluate(&expr, &ctx).unwrap();
+        assert_eq!(read_location(&location, 1, &ctx).unwrap(), [0x3a]);
+
+        // Pieces without a location are optimized out, and leave the others readable
+        let location = evaluate(&[DW_OP_PIECE, 4], &ctx).unwrap();
+        assert_eq!(read_location(&location, 4, &ctx).unwrap(), [0; 4]);
+        assert_eq!(location.optimized_out().first(), Some(&(0..4)));
+        let expr = [DW_OP_PIECE, 2, DW_OP_REG0 + 5, DW_OP_PIECE, 2];
+        let location = evaluate(&expr, &ctx).unwrap();
+        assert_eq!(read_location(&location, 4, &ctx).unwrap(), [0, 0, 5, 6]);
+        assert_eq!(location.optimized_out().first(), Some(&(0..2)));
+        assert!(Location::Address(BASE).optimized_out().is_empty());
+    }
+
+    #[test]
+    fn entry_values() {
+        let memory = memory();
+        let registers = registers_with(BASE, 7);
+        let entry = registers_with(BASE, 99);
+        let mut ctx = EvalContext::new(&memory, &registers);
+
+        let expr = [DW_OP_ENTRY_VALUE, 1, DW_OP_REG0 + 5, DW_OP_STACK_VALUE];
+        assert!(evaluate(&expr, &ctx).is_err_and(|err| err.to_string().contains("not supported")));
+
+        ctx.entry_registers = Some(&entry);
+        assert_eq!(evaluate(&expr, &ctx).unwrap(), Location::Value(99));
+
+        let expr = [
+            DW_OP_GNU_ENTRY_VALUE,
+            2,
+            DW_OP_BREG0 + 5,
+            1,
+            DW_OP_STACK_VALUE,
+        ];
+        assert_eq!(evaluate(&expr, &ctx).unwrap(), Location::Value(100));
+    }
+
+    #[test]
+    fn malformed_expressions() {
+        assert!(
+            value_of(&[DW_OP_PLUS]).is_err_and(|err| err.to_string().contains("stack underflow"))
+        );
+        assert!(value_of(&[0xff]).is_err_and(|err| err.to_string().contains("unknown")));
+        assert!(value_of(&[DW_OP_CONST4U, 1]).is_err());
+        assert!(value_of(&[DW_OP_LIT0 + 1, DW_OP_LIT0, DW_OP_DIV]).is_err());
+        assert!(value_of(&[DW_OP_ADDRX, 0]).is_err());
+    }
+}
@@ -0,0 +1,1017 @@
+use crate::dwarf::consts::*;
+use crate::dwarf::types::TypeRef;
+use crate::elf::Elf;
+use anyhow::{anyhow, bail, Result};
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::rc::Rc;
+
+pub mod cfi;
+pub mod consts;
+pub mod expr;
+pub mod types;
+
+// A forward only reader over the bytes of a DWARF section
+#[derive(Clone)]
+pub(crate) struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub fn at(data: &'a [u8], pos: usize) -> Self {
+        Self { data, pos }
+    }
+
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub fn is_at_end(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    pub fn bytes(&mut self, count: usize) -> Result<&'a [u8]> {
+        let end = self.pos + count;
+        if end > self.data.len() {
+            bail!("unexpected end of DWARF data at offset {:#x}", self.pos);
+        }
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub fn skip(&mut self, count: usize) -> Result<()> {
+        self.bytes(count).map(|_| ())
+    }
+
+    /// Reads an unsigned little endian integer of `size` bytes, where size is at most 8
+    pub fn fixed(&mut self, size: usize) -> Result<u64> {
+        let bytes = self.bytes(size)?;
+        Ok(bytes
+            .iter()
+            .rev()
+            .fold(0u64, |acc, &b| (acc << 8) | b as u64))
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16> {
+        Ok(self.fixed(2)? as u16)
+    }
+
+    pub fn u32(&mut self) -> Result<u32> {
+        Ok(self.fixed(4)? as u32)
+    }
+
+    pub fn u64(&mut self) -> Result<u64> {
+        self.fixed(8)
+    }
+
+    pub fn uleb128(&mut self) -> Result<u64> {
+        let mut result = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            if shift < 64 {
+                result |= ((byte & 0x7f) as u64) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+        }
+    }
+
+    pub fn sleb128(&mut self) -> Result<i64> {
+        let mut result = 0i64;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            if shift < 64 {
+                result |= ((byte & 0x7f) as i64) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    result |= -1i64 << shift;
+                }
+                return Ok(result);
+            }
+        }
+    }
+
+    pub fn cstr(&mut self) -> Result<&'a str> {
+        let rest = &self.data[self.pos.min(self.data.len())..];
+        let len = rest
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| anyhow!("unterminated string at offset {:#x}", self.pos))?;
+        let s = std::str::from_utf8(&rest[..len])?;
+        self.pos += len + 1;
+        Ok(s)
+    }
+
+    /// Reads an initial length field, returning the length and the size of offsets (4 or 8)
+    /// used by the section contents that follow.
+    pub fn initial_length(&mut self) -> Result<(u64, u8)> {
+        let length = self.u32()?;
+        if length == 0xffff_ffff {
+            Ok((self.u64()?, 8))
+        } else {
+            Ok((length as u64, 4))
+        }
+    }
+}
+
+// Every form is decoded, though not every value is used
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub enum AttrValue {
+    Address(u64),
+    AddressIndex(u64),
+    Block(Vec<u8>),
+    Constant(u64),
+    SignedConstant(i64),
+    Flag(bool),
+    /// An absolute offset into .debug_info
+    Reference(usize),
+    TypeSignature(u64),
+    SectionOffset(u64),
+    LocationListIndex(u64),
+    RangeListIndex(u64),
+    InlineString(String),
+    StringOffset(u64),
+    LineStringOffset(u64),
+    StringIndex(u64),
+    /// Forms which reference supplementary object files
+    Unsupported(u64),
+}
+
+impl AttrValue {
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            AttrValue::Constant(v) | AttrValue::SectionOffset(v) => Some(*v),
+            AttrValue::SignedConstant(v) => Some(*v as u64),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            AttrValue::Constant(v) => Some(*v as i64),
+            AttrValue::SignedConstant(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+struct AttrSpec {
+    name: u64,
+    form: u64,
+    implicit_const: i64,
+}
+
+struct Abbrev {
+    tag: u64,
+    has_children: bool,
+    specs: Vec<AttrSpec>,
+}
+
+type AbbrevTable = HashMap<u64, Abbrev>;
+
+fn parse_abbrev_table(data: &[u8], offset: usize) -> Result<AbbrevTable> {
+    let mut cursor = Cursor::at(data, offset);
+    let mut table = HashMap::new();
+    loop {
+        let code = cursor.uleb128()?;
+        if code == 0 {
+            break;
+        }
+        let tag = cursor.uleb128()?;
+        let has_children = cursor.u8()? != 0;
+        let mut specs = Vec::new();
+        loop {
+            let name = cursor.uleb128()?;
+            let form = cursor.uleb128()?;
+            if name == 0 && form == 0 {
+                break;
+            }
+            let implicit_const = if form == DW_FORM_IMPLICIT_CONST {
+                cursor.sleb128()?
+            } else {
+                0
+            };
+            specs.push(AttrSpec {
+                name,
+                form,
+                implicit_const,
+            });
+        }
+        table.insert(
+            code,
+            Abbrev {
+                tag,
+                has_children,
+                specs,
+            },
+        );
+    }
+    Ok(table)
+}
+
+pub(crate) struct Unit {
+    offset: usize,
+    end: usize,
+    version: u16,
+    address_size: u8,
+    offset_size: u8,
+    abbrevs: Rc<AbbrevTable>,
+    root_offset: usize,
+    str_offsets_base: u64,
+    addr_base: u64,
+    rnglists_base: u64,
+    loclists_base: u64,
+    base_address: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Die {
+    pub offset: usize,
+    unit: usize,
+    pub tag: u64,
+    has_children: bool,
+    attributes: Vec<(u64, AttrValue)>,
+    // Offset of the first byte after this entry's attributes, which is where its first child or
+    // next sibling begins
+    next: usize,
+}
+
+impl Die {
+    pub fn attr(&self, name: u64) -> Option<&AttrValue> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v)
+    }
+
+    pub fn has_attr(&self, name: u64) -> bool {
+        self.attr(name).is_some()
+    }
+}
+
+struct FunctionRange {
+    range: Range<u64>,
+    die_offset: usize,
+}
+
+pub struct Dwarf {
+    elf: Rc<Elf>,
+    units: Vec<Unit>,
+    functions: OnceCell<Vec<FunctionRange>>,
+}
+
+impl Dwarf {
+    pub fn new(elf: Rc<Elf>) -> Result<Self> {
+        let mut dwarf = Self {
+            elf,
+            units: Vec::new(),
+            functions: OnceCell::new(),
+        };
+        dwarf.units = dwarf.parse_units()?;
+        Ok(dwarf)
+    }
+
+    fn section(&self, name: &str) -> &[u8] {
+        self.elf.section_data(name).unwrap_or_default()
+    }
+
+    fn parse_units(&self) -> Result<Vec<Unit>> {
+        let info = self.section(".debug_info");
+        let abbrev_data = self.section(".debug_abbrev");
+        let mut abbrev_cache: HashMap<u64, Rc<AbbrevTable>> = HashMap::new();
+        let mut units = Vec::new();
+
+        let mut cursor = Cursor::new(info);
+        while !cursor.is_at_end() {
+            let offset = cursor.position();
+            let (length, offset_size) = cursor.initial_length()?;
+            let end = cursor.position() + length as usize;
+            let version = cursor.u16()?;
+
+            let (unit_type, address_size, abbrev_offset) = if version >= 5 {
+                let unit_type = cursor.u8()?;
+                let address_size = cursor.u8()?;
+                let abbrev_offset = cursor.fixed(offset_size as usize)?;
+                (unit_type, address_size, abbrev_offset)
+            } else {
+                let abbrev_offset = cursor.fixed(offset_size as usize)?;
+                let address_size = cursor.u8()?;
+                (DW_UT_COMPILE, address_size, abbrev_offset)
+            };
+
+            match unit_type {
+                DW_UT_COMPILE | DW_UT_PARTIAL => {}
+                DW_UT_SKELETON | DW_UT_SPLIT_COMPILE => cursor.skip(8)?,
+                DW_UT_TYPE | DW_UT_SPLIT_TYPE => cursor.skip(8 + offset_size as usize)?,
+                other => bail!("unknown unit type {other:#x} at offset {offset:#x}"),
+            }
+
+            let abbrevs = match abbrev_cache.get(&abbrev_offset) {
+                Some(table) => table.clone(),
+                None => {
+                    let table = Rc::new(parse_abbrev_table(abbrev_data, abbrev_offset as usize)?);
+                    abbrev_cache.insert(abbrev_offset, table.clone());
+                    table
+                }
+            };
+
+            units.push(Unit {
+                offset,
+                end,
+                version,
+                address_size,
+                offset_size,
+                abbrevs,
+                root_offset: cursor.position(),
+                str_offsets_base: 0,
+                addr_base: 0,
+                rnglists_base: 0,
+                loclists_base: 0,
+                base_address: 0,
+            });
+            cursor = Cursor::at(info, end);
+        }
+
+        // The bases of the indexed sections are attributes of the root DIE, and are needed before
+        // any indexed form in the unit can be resolved.
+        for (index, unit) in units.iter_mut().enumerate() {
+            let Some(root) = self.parse_die_in(unit, index, unit.root_offset)? else {
+                continue;
+            };
+            let base = |name| root.attr(name).and_then(AttrValue::as_u64);
+            unit.str_offsets_base = base(DW_AT_STR_OFFSETS_BASE).unwrap_or(0);
+            unit.addr_base = base(DW_AT_ADDR_BASE)
+                .or(base(DW_AT_GNU_ADDR_BASE))
+                .unwrap_or(0);
+            unit.rnglists_base = base(DW_AT_RNGLISTS_BASE).unwrap_or(0);
+            unit.loclists_base = base(DW_AT_LOCLISTS_BASE).unwrap_or(0);
+            unit.base_address = match root.attr(DW_AT_LOW_PC) {
+                Some(AttrValue::Address(address)) => *address,
+                Some(AttrValue::AddressIndex(i)) => self.read_indexed_address(unit, *i)?,
+                _ => 0,
+            };
+        }
+
+        Ok(units)
+    }
+
+    fn unit(&self, index: usize) -> &Unit {
+        &self.units[index]
+    }
+
+    fn unit_index_for_offset(&self, offset: usize) -> Result<usize> {
+        let index = self.units.partition_point(|u| u.end <= offset);
+        match self.units.get(index) {
+            Some(unit) if unit.offset <= offset => Ok(index),
+            _ => bail!("no unit contains .debug_info offset {offset:#x}"),
+        }
+    }
+
+    fn read_attr_value(
+        &self,
+        cursor: &mut Cursor,
+        unit: &Unit,
+        form: u64,
+        implicit_const: i64,
+    ) -> Result<AttrValue> {
+        let offset_size = unit.offset_size as usize;
+        let value = match form {
+            DW_FORM_ADDR => AttrValue::Address(cursor.fixed(unit.address_size as usize)?),
+            DW_FORM_BLOCK1 => {
+                let len = cursor.u8()? as usize;
+                AttrValue::Block(cursor.bytes(len)?.to_vec())
+            }
+            DW_FORM_BLOCK2 => {
+                let len = cursor.u16()? as usize;
+                AttrValue::Block(cursor.bytes(len)?.to_vec())
+            }
+            DW_FORM_BLOCK4 => {
+                let len = cursor.u32()? as usize;
+                AttrValue::Block(cursor.bytes(len)?.to_vec())
+            }
+            DW_FORM_BLOCK | DW_FORM_EXPRLOC => {
+                let len = cursor.uleb128()? as usize;
+                AttrValue::Block(cursor.bytes(len)?.to_vec())
+            }
+            DW_FORM_DATA16 => AttrValue::Block(cursor.bytes(16)?.to_vec()),
+            DW_FORM_DATA1 => AttrValue::Constant(cursor.fixed(1)?),
+            DW_FORM_DATA2 => AttrValue::Constant(cursor.fixed(2)?),
+            DW_FORM_DATA4 => AttrValue::Constant(cursor.fixed(4)?),
+            DW_FORM_DATA8 => AttrValue::Constant(cursor.fixed(8)?),
+            DW_FORM_UDATA => AttrValue::Constant(cursor.uleb128()?),
+            DW_FORM_SDATA => AttrValue::SignedConstant(cursor.sleb128()?),
+            DW_FORM_IMPLICIT_CONST => AttrValue::SignedConstant(implicit_const),
+            DW_FORM_FLAG => AttrValue::Flag(cursor.u8()? != 0),
+            DW_FORM_FLAG_PRESENT => AttrValue::Flag(true),
+            DW_FORM_REF1 => AttrValue::Reference(unit.offset + cursor.fixed(1)? as usize),
+            DW_FORM_REF2 => AttrValue::Reference(unit.offset + cursor.fixed(2)? as usize),
+            DW_FORM_REF4 => AttrValue::Reference(unit.offset + cursor.fixed(4)? as usize),
+            DW_FORM_REF8 => AttrValue::Reference(unit.offset + cursor.fixed(8)? as usize),
+            DW_FORM_REF_UDATA => AttrValue::Reference(unit.offset + cursor.uleb128()? as usize),
+            DW_FORM_REF_ADDR => {
+                // DWARF 2 encoded this form with the size of an address
+                let size = if unit.version <= 2 {
+                    unit.address_size as usize
+                } else {
+                    offset_size
+                };
+                AttrValue::Reference(cursor.fixed(size)? as usize)
+            }
+            DW_FORM_REF_SIG8 => AttrValue::TypeSignature(cursor.u64()?),
+            DW_FORM_SEC_OFFSET => AttrValue::SectionOffset(cursor.fixed(offset_size)?),
+            DW_FORM_LOCLISTX => AttrValue::LocationListIndex(cursor.uleb128()?),
+            DW_FORM_RNGLISTX => AttrValue::RangeListIndex(cursor.uleb128()?),
+            DW_FORM_STRING => AttrValue::InlineString(cursor.cstr()?.to_string()),
+            DW_FORM_STRP => AttrValue::StringOffset(cursor.fixed(offset_size)?),
+            DW_FORM_LINE_STRP => AttrValue::LineStringOffset(cursor.fixed(offset_size)?),
+            DW_FORM_STRX | DW_FORM_GNU_STR_INDEX => AttrValue::StringIndex(cursor.uleb128()?),
+            DW_FORM_STRX1 => AttrValue::StringIndex(cursor.fixed(1)?),
+            DW_FORM_STRX2 => AttrValue::StringIndex(cursor.fixed(2)?),
+            DW_FORM_STRX3 => AttrValue::StringIndex(cursor.fixed(3)?),
+            DW_FORM_STRX4 => AttrValue::StringIndex(cursor.fixed(4)?),
+            DW_FORM_ADDRX | DW_FORM_GNU_ADDR_INDEX => AttrValue::AddressIndex(cursor.uleb128()?),
+            DW_FORM_ADDRX1 => AttrValue::AddressIndex(cursor.fixed(1)?),
+            DW_FORM_ADDRX2 => AttrValue::AddressIndex(cursor.fixed(2)?),
+            DW_FORM_ADDRX3 => AttrValue::AddressIndex(cursor.fixed(3)?),
+            DW_FORM_ADDRX4 => AttrValue::AddressIndex(cursor.fixed(4)?),
+            DW_FORM_REF_SUP4 => AttrValue::Unsupported(cursor.fixed(4)?),
+            DW_FORM_REF_SUP8 => AttrValue::Unsupported(cursor.fixed(8)?),
+            DW_FORM_STRP_SUP | DW_FORM_GNU_REF_ALT | DW_FORM_GNU_STRP_ALT => {
+                AttrValue::Unsupported(cursor.fixed(offset_size)?)
+            }
+            DW_FORM_INDIRECT => {
+                let form = cursor.uleb128()?;
+                let implicit_const = if form == DW_FORM_IMPLICIT_CONST {
+                    cursor.sleb128()?
+                } else {
+                    0
+                };
+                self.read_attr_value(cursor, unit, form, implicit_const)?
+            }
+            other => bail!("unknown attribute form {other:#x}"),
+        };
+        Ok(value)
+    }
+
+    // Parses the entry at `offset`. A null entry, which terminates a list of siblings, is
+    // returned as None.
+    fn parse_die_in(&self, unit: &Unit, unit_index: usize, offset: usize) -> Result<Option<Die>> {
+        let mut cursor = Cursor::at(self.section(".debug_info"), offset);
+        let code = cursor.uleb128()?;
+        if code == 0 {
+            return Ok(None);
+        }
+
+        let abbrev = unit
+            .abbrevs
+            .get(&code)
+            .ok_or_else(|| anyhow!("unknown abbreviation code {code} at offset {offset:#x}"))?;
+        let mut attributes = Vec::with_capacity(abbrev.specs.len());
+        for spec in &abbrev.specs {
+            let value = self.read_attr_value(&mut cursor, unit, spec.form, spec.implicit_const)?;
+            attributes.push((spec.name, value));
+        }
+
+        Ok(Some(Die {
+            offset,
+            unit: unit_index,
+            tag: abbrev.tag,
+            has_children: abbrev.has_children,
+            attributes,
+            next: cursor.position(),
+        }))
+    }
+
+    pub fn die_at(&self, offset: usize) -> Result<Die> {
+        let index = self.unit_index_for_offset(offset)?;
+        self.parse_die_in(self.unit(index), index, offset)?
+            .ok_or_else(|| anyhow!("null entry at offset {offset:#x}"))
+    }
+
+    pub fn compile_units(&self) -> Result<Vec<Die>> {
+        let mut roots = Vec::new();
+        for (index, unit) in self.units.iter().enumerate() {
+            if let Some(root) = self.parse_die_in(unit, index, unit.root_offset)? {
+                roots.push(root);
+            }
+        }
+        Ok(roots)
+    }
+
+    // Returns the offset of the first byte after the entry and all of its descendants
+    fn skip_die(&self, die: &Die) -> Result<usize> {
+        if !die.has_children {
+            return Ok(die.next);
+        }
+        if let Some(AttrValue::Reference(sibling)) = die.attr(DW_AT_SIBLING) {
+            return Ok(*sibling);
+        }
+
+        let unit = self.unit(die.unit);
+        let mut offset = die.next;
+        while let Some(child) = self.parse_die_in(unit, die.unit, offset)? {
+            offset = self.skip_die(&child)?;
+        }
+        // step over the null entry terminating the children
+        Ok(offset + 1)
+    }
+
+    pub fn children(&self, die: &Die) -> Result<Vec<Die>> {
+        let mut children = Vec::new();
+        if !die.has_children {
+            return Ok(children);
+        }
+
+        let unit = self.unit(die.unit);
+        let mut offset = die.next;
+        while offset < unit.end {
+            let Some(child) = self.parse_die_in(unit, die.unit, offset)? else {
+                break;
+            };
+            offset = self.skip_die(&child)?;
+            children.push(child);
+        }
+        Ok(children)
+    }
+
+    pub fn address_size(&self, die: &Die) -> u8 {
+        self.unit(die.unit).address_size
+    }
+
+    fn read_indexed_address(&self, unit: &Unit, index: u64) -> Result<u64> {
+        let section = self.section(".debug_addr");
+        let size = unit.address_size as usize;
+        let mut cursor = Cursor::at(section, unit.addr_base as usize + index as usize * size);
+        cursor.fixed(size)
+    }
+
+    /// An entry of .debug_addr, indexed relative to the address base of the unit of `die`
+    pub fn indexed_address(&self, die: &Die, index: u64) -> Result<u64> {
+        self.read_indexed_address(self.unit(die.unit), index)
+    }
+
+    /// The entry at an offset relative to the start of the unit containing `die`
+    pub fn unit_relative_die(&self, die: &Die, offset: u64) -> Result<Die> {
+        self.die_at(self.unit(die.unit).offset + offset as usize)
+    }
+
+    fn read_string_at(&self, section: &str, offset: u64) -> Result<String> {
+        let mut cursor = Cursor::at(self.section(section), offset as usize);
+        Ok(cursor.cstr()?.to_string())
+    }
+
+    pub fn attr_string(&self, die: &Die, name: u64) -> Result<Option<String>> {
+        let s = match die.attr(name) {
+            None => return Ok(None),
+            Some(AttrValue::InlineString(s)) => s.clone(),
+            Some(AttrValue::StringOffset(offset)) => self.read_string_at(".debug_str", *offset)?,
+            Some(AttrValue::LineStringOffset(offset)) => {
+                self.read_string_at(".debug_line_str", *offset)?
+            }
+            Some(AttrValue::StringIndex(index)) => {
+                let unit = self.unit(die.unit);
+                let size = unit.offset_size as usize;
+                let mut cursor = Cursor::at(
+                    self.section(".debug_str_offsets"),
+                    unit.str_offsets_base as usize + *index as usize * size,
+                );
+                let offset = cursor.fixed(size)?;
+                self.read_string_at(".debug_str", offset)?
+            }
+            Some(other) => bail!("attribute {name:#x} is not a string: {other:?}"),
+        };
+        Ok(Some(s))
+    }
+
+    pub fn attr_address(&self, die: &Die, name: u64) -> Result<Option<u64>> {
+        match die.attr(name) {
+            None => Ok(None),
+            Some(AttrValue::Address(address)) => Ok(Some(*address)),
+            Some(AttrValue::AddressIndex(index)) => Ok(Some(
+                self.read_indexed_address(self.unit(die.unit), *index)?,
+            )),
+            Some(other) => bail!("attribute {name:#x} is not an address: {other:?}"),
+        }
+    }
+
+    pub fn attr_reference(&self, die: &Die, name: u64) -> Result<Option<Die>> {
+        match die.attr(name) {
+            None => Ok(None),
+            Some(AttrValue::Reference(offset)) => Ok(Some(self.die_at(*offset)?)),
+            Some(AttrValue::TypeSignature(_)) => bail!("type units are not supported"),
+            Some(other) => bail!("attribute {name:#x} is not a reference: {other:?}"),
+        }
+    }
+
+    /// The name of an entry, following the declarations an out of line definition refers to
+    pub fn name(&self, die: &Die) -> Result<Option<String>> {
+        self.inherited_string(die, &[DW_AT_NAME])
+    }
+
+    /// The mangled name of an entry. Older compilers use the attribute from the MIPS vendor range.
+    pub fn linkage_name(&self, die: &Die) -> Result<Option<String>> {
+        self.inherited_string(die, &[DW_AT_LINKAGE_NAME, DW_AT_MIPS_LINKAGE_NAME])
+    }
+
+    // A string attribute of an entry, or of the declaration or abstract instance it completes
+    fn inherited_string(&self, die: &Die, names: &[u64]) -> Result<Option<String>> {
+        for &name in names {
+            if let Some(value) = self.attr_string(die, name)? {
+                return Ok(Some(value));
+            }
+        }
+        for origin in [DW_AT_SPECIFICATION, DW_AT_ABSTRACT_ORIGIN] {
+            if let Some(origin) = self.attr_reference(die, origin)? {
+                return self.inherited_string(&origin, names);
+            }
+        }
+        Ok(None)
+    }
+
+    /// The address ranges covered by an entry, as link time addresses
+    pub fn pc_ranges(&self, die: &Die) -> Result<Vec<Range<u64>>> {
+        if let Some(low) = self.attr_address(die, DW_AT_LOW_PC)? {
+            let high = match die.attr(DW_AT_HIGH_PC) {
+                Some(AttrValue::Constant(length)) => low + length,
+                Some(AttrValue::SignedConstant(length)) => low + *length as u64,
+                Some(_) => self.attr_address(die, DW_AT_HIGH_PC)?.unwrap_or(low),
+                None => low + 1,
+            };
+            let range = low..high;
+            return Ok(vec![range]);
+        }
+
+        let unit = self.unit(die.unit);
+        match die.attr(DW_AT_RANGES) {
+            None => Ok(Vec::new()),
+            Some(AttrValue::RangeListIndex(index)) => {
+                let offset =
+                    self.read_list_offset(".debug_rnglists", unit.rnglists_base, *index)?;
+                self.read_rnglist(unit, offset)
+            }
+            Some(AttrValue::SectionOffset(offset)) | Some(AttrValue::Constant(offset)) => {
+                if unit.version >= 5 {
+                    self.read_rnglist(unit, *offset)
+                } else {
+                    self.read_debug_ranges(unit, *offset)
+                }
+            }
+            Some(other) => bail!("unexpected form for DW_AT_ranges: {other:?}"),
+        }
+    }
+
+    // Resolves an index into the offsets table which follows the header of a DWARF 5 range or
+    // location list section
+    fn read_list_offset(&self, section: &str, base: u64, index: u64) -> Result<u64> {
+        // The offsets are relative to the base, and are 4 bytes wide in the 32 bit format
+        let mut cursor = Cursor::at(self.section(section), base as usize + index as usize * 4);
+        Ok(base + cursor.u32()? as u64)
+    }
+
+    fn read_debug_ranges(&self, unit: &Unit, offset: u64) -> Result<Vec<Range<u64>>> {
+        let mut cursor = Cursor::at(self.section(".debug_ranges"), offset as usize);
+        let mut base = unit.base_address;
+        let mut ranges = Vec::new();
+        loop {
+            let start = cursor.u64()?;
+            let end = cursor.u64()?;
+            match (start, end) {
+                (0, 0) => break,
+                (u64::MAX, address) => base = address,
+                (start, end) => ranges.push(base + start..base + end),
+            }
+        }
+        Ok(ranges)
+    }
+
+    fn read_rnglist(&self, unit: &Unit, offset: u64) -> Result<Vec<Range<u64>>> {
+        let mut cursor = Cursor::at(self.section(".debug_rnglists"), offset as usize);
+        let size = unit.address_size as usize;
+        let mut base = unit.base_address;
+        let mut ranges = Vec::new();
+        loop {
+            match cursor.u8()? {
+                DW_RLE_END_OF_LIST => break,
+                DW_RLE_BASE_ADDRESSX => {
+                    base = self.read_indexed_address(unit, cursor.uleb128()?)?
+                }
+                DW_RLE_STARTX_ENDX => {
+                    let start = self.read_indexed_address(unit, cursor.uleb128()?)?;
+                    let end = self.read_indexed_address(unit, cursor.uleb128()?)?;
+                    ranges.push(start..end);
+                }
+                DW_RLE_STARTX_LENGTH => {
+                    let start = self.read_indexed_address(unit, cursor.uleb128()?)?;
+                    ranges.push(start..start + cursor.uleb128()?);
+                }
+                DW_RLE_OFFSET_PAIR => {
+                    let start = cursor.uleb128()?;
+                    let end = cursor.uleb128()?;
+                    ranges.push(base + start..base + end);
+                }
+                DW_RLE_BASE_ADDRESS => base = cursor.fixed(size)?,
+                DW_RLE_START_END => ranges.push(cursor.fixed(size)?..cursor.fixed(size)?),
+                DW_RLE_START_LENGTH => {
+                    let start = cursor.fixed(size)?;
+                    ranges.push(start..start + cursor.uleb128()?);
+                }
+                other => bail!("unknown range list entry {other:#x}"),
+            }
+        }
+        Ok(ranges)
+    }
+
+    /// Returns the location expression of an entry which is valid at the link time address `pc`.
+    /// Entries described by a location list may have no location at a given pc.
+    pub fn location_expression(&self, die: &Die, name: u64, pc: u64) -> Result<Option<Vec<u8>>> {
+        let unit = self.unit(die.unit);
+        match die.attr(name) {
+            None => Ok(None),
+            Some(AttrValue::Block(expr)) => Ok(Some(expr.clone())),
+            Some(AttrValue::LocationListIndex(index)) => {
+                let offset =
+                    self.read_list_offset(".debug_loclists", unit.loclists_base, *index)?;
+                self.read_loclist(unit, offset, pc)
+            }
+            Some(AttrValue::SectionOffset(offset)) | Some(AttrValue::Constant(offset)) => {
+                if unit.version >= 5 {
+                    self.read_loclist(unit, *offset, pc)
+                } else {
+                    self.read_debug_loc(unit, *offset, pc)
+                }
+            }
+            Some(other) => bail!("unexpected form for location: {other:?}"),
+        }
+    }
+
+    fn read_debug_loc(&self, unit: &Unit, offset: u64, pc: u64) -> Result<Option<Vec<u8>>> {
+        let mut cursor = Cursor::at(self.section(".debug_loc"), offset as usize);
+        let mut base = unit.base_address;
+        loop {
+            let start = cursor.u64()?;
+            let end = cursor.u64()?;
+            match (start, end) {
+                (0, 0) => return Ok(None),
+                (u64::MAX, address) => base = address,
+                (start, end) => {
+                    let len = cursor.u16()? as usize;
+                    let expr = cursor.bytes(len)?;
+                    if (base + start..base + end).contains(&pc) {
+                        return Ok(Some(expr.to_vec()));
+                    }
+                }
+            }
+        }
+    }
+
+    fn read_loclist(&self, unit: &Unit, offset: u64, pc: u64) -> Result<Option<Vec<u8>>> {
+        let mut cursor = Cursor::at(self.section(".debug_loclists"), offset as usize);
+        let size = unit.address_size as usize;
+        let mut base = unit.base_address;
+        let mut default = None;
+        loop {
+            let range = match cursor.u8()? {
+                DW_LLE_END_OF_LIST => return Ok(default),
+                DW_LLE_BASE_ADDRESSX => {
+                    base = self.read_indexed_address(unit, cursor.uleb128()?)?;
+                    continue;
+                }
+                DW_LLE_BASE_ADDRESS => {
+                    base = cursor.fixed(size)?;
+                    continue;
+                }
+                DW_LLE_STARTX_ENDX => {
+                    let start = self.read_indexed_address(unit, cursor.uleb128()?)?;
+                    start..self.read_indexed_address(unit, cursor.uleb128()?)?
+                }
+                DW_LLE_STARTX_LENGTH => {
+                    let start = self.read_indexed_address(unit, cursor.uleb128()?)?;
+                    start..start + cursor.uleb128()?
+                }
+                DW_LLE_OFFSET_PAIR => {
+                    let start = cursor.uleb128()?;
+                    base + start..base + cursor.uleb128()?
+                }
+                DW_LLE_DEFAULT_LOCATION => {
+                    let len = cursor.uleb128()? as usize;
+                    default = Some(cursor.bytes(len)?.to_vec());
+                    continue;
+                }
+                DW_LLE_START_END => cursor.fixed(size)?..cursor.fixed(size)?,
+                DW_LLE_START_LENGTH => {
+                    let start = cursor.fixed(size)?;
+                    start..start + cursor.uleb128()?
+                }
+                other => bail!("unknown location list entry {other:#x}"),
+            };
+            let len = cursor.uleb128()? as usize;
+            let expr = cursor.bytes(len)?;
+            if range.contains(&pc) {
+                return Ok(Some(expr.to_vec()));
+            }
+        }
+    }
+
+    fn function_index(&self) -> Result<&Vec<FunctionRange>> {
+        if let Some(functions) = self.functions.get() {
+            return Ok(functions);
+        }
+
+        let mut functions = Vec::new();
+        for (index, unit) in self.units.iter().enumerate() {
+            // Walk every entry of the unit in order. Null entries are a single byte.
+            let mut offset = unit.root_offset;
+            while offset < unit.end {
+                let Some(die) = self.parse_die_in(unit, index, offset)? else {
+                    offset += 1;
+                    continue;
+                };
+                offset = die.next;
+                if die.tag != DW_TAG_SUBPROGRAM {
+                    continue;
+                }
+                for range in self.pc_ranges(&die)? {
+                    if !range.is_empty() {
+                        functions.push(FunctionRange {
+                            range,
+                            die_offset: die.offset,
+                        });
+                    }
+                }
+            }
+        }
+        functions.sort_by_key(|f| f.range.start);
+
+        Ok(self.functions.get_or_init(|| functions))
+    }
+
+    /// Finds the subprogram entry whose code contains the link time address `pc`
+    pub fn function_containing(&self, pc: u64) -> Result<Option<Die>> {
+        let functions = self.function_index()?;
+        let end = functions.partition_point(|f| f.range.start <= pc);
+        // Ranges of nested functions may overlap, so prefer the innermost (latest starting) one
+        for function in functions[..end].iter().rev() {
+            if function.range.contains(&pc) {
+                return Ok(Some(self.die_at(function.die_offset)?));
+            }
+        }
+        Ok(None)
+    }
+
+    fn contains_pc(&self, die: &Die, pc: u64) -> Result<bool> {
+        Ok(self.pc_ranges(die)?.iter().any(|r| r.contains(&pc)))
+    }
+
+    /// Collects the parameters of a function and the local variables in scope at `pc`, the
+    /// innermost scope's variables last
+    pub fn frame_variables(&self, function: &Die, pc: u64) -> Result<(Vec<Die>, Vec<Die>)> {
+        let mut params = Vec::new();
+        let mut locals = Vec::new();
+        for child in self.children(function)? {
+            match child.tag {
+                DW_TAG_FORMAL_PARAMETER => params.push(child),
+                DW_TAG_VARIABLE => locals.push(child),
+                DW_TAG_LEXICAL_BLOCK => self.collect_block_variables(&child, pc, &mut locals)?,
+                _ => {}
+            }
+        }
+        Ok((params, locals))
+    }
+
+    fn collect_block_variables(&self, block: &Die, pc: u64, locals: &mut Vec<Die>) -> Result<()> {
+        // Blocks without ranges span the whole of their parent
+        if (block.has_attr(DW_AT_LOW_PC) || block.has_attr(DW_AT_RANGES))
+            && !self.contains_pc(block, pc)?
+        {
+            return Ok(());
+        }
+        for child in self.children(block)? {
+            match child.tag {
+                DW_TAG_VARIABLE => locals.push(child),
+                DW_TAG_LEXICAL_BLOCK => self.collect_block_variables(&child, pc, locals)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds a variable with static storage by name, searching the top level of every unit and
+    /// the namespaces nested in it
+    pub fn find_global_variable(&self, name: &str) -> Result<Option<Die>> {
+        self.find_global_variable_by(|die| Ok(self.name(die)?.as_deref() == Some(name)))
+    }
+
+    /// Finds a variable with static storage by its mangled name
+    pub fn find_global_variable_by_linkage_name(&self, name: &str) -> Result<Option<Die>> {
+        self.find_global_variable_by(|die| Ok(self.linkage_name(die)?.as_deref() == Some(name)))
+    }
+
+    fn find_global_variable_by(
+        &self,
+        matches: impl Fn(&Die) -> Result<bool>,
+    ) -> Result<Option<Die>> {
+        for root in self.compile_units()? {
+            if let Some(die) = self.find_variable_in_scope(&root, &matches)? {
+                return Ok(Some(die));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Finds the definition of a named type, searching the top level of every unit and the
+    /// namespaces nested in it. The name may be qualified with its namespaces.
+    pub fn find_type(&self, name: &str) -> Result<Option<TypeRef>> {
+        for root in self.compile_units()? {
+            if let Some(ty) = self.find_type_in_scope(&root, "", name)? {
+                return Ok(Some(ty));
+            }
+        }
+        Ok(None)
+    }
+
+    fn find_type_in_scope(&self, scope: &Die, prefix: &str, name: &str) -> Result<Option<TypeRef>> {
+        for child in self.children(scope)? {
+            match child.tag {
+                DW_TAG_STRUCTURE_TYPE
+                | DW_TAG_UNION_TYPE
+                | DW_TAG_CLASS_TYPE
+                | DW_TAG_ENUMERATION_TYPE
+                | DW_TAG_TYPEDEF
+                | DW_TAG_BASE_TYPE
+                    if !child.has_attr(DW_AT_DECLARATION) =>
+                {
+                    if let Some(own) = self.name(&child)?
+                        && (own == name || format!("{prefix}{own}") == name)
+                    {
+                        return Ok(Some(TypeRef(child.offset)));
+                    }
+                }
+                DW_TAG_NAMESPACE => {
+                    let namespace = self.name(&child)?.unwrap_or_default();
+                    let prefix = format!("{prefix}{namespace}::");
+                    if let Some(ty) = self.find_type_in_scope(&child, &prefix, name)? {
+                        return Ok(Some(ty));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+
+    fn find_variable_in_scope(
+        &self,
+        scope: &Die,
+        matches: &impl Fn(&Die) -> Result<bool>,
+    ) -> Result<Option<Die>> {
+        for child in self.children(scope)? {
+            match child.tag {
+                DW_TAG_VARIABLE if child.has_attr(DW_AT_LOCATION) && matches(&child)? => {
+                    return Ok(Some(child));
+                }
+                DW_TAG_NAMESPACE => {
+                    if let Some(die) = self.find_variable_in_scope(&child, matches)? {
+                        return Ok(Some(die));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dwarf::Cursor;
+
+    #[test]
+    fn leb128_decoding() {
+        let data = [0xe5, 0x8e, 0x26, 0x7f, 0x80, 0x7f, 0x02];
+        let mut cursor = Cursor::new(&data);
+        assert_eq!(cursor.uleb128().unwrap(), 624485);
+        assert_eq!(cursor.sleb128().unwrap(), -1);
+        assert_eq!(cursor.sleb128().unwrap(), -128);
+        assert_eq!(cursor.sleb128().unwrap(), 2);
+        assert!(cursor.is_at_end());
+        assert!(cursor.u8().is_err());
+    }
+}
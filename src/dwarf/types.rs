@@ -0,0 +1,572 @@
+use crate::dwarf::consts::*;
+use crate::dwarf::{AttrValue, Die, Dwarf};
+use anyhow::{bail, Result};
+use bytemuck::pod_read_unaligned;
+use std::fmt::Write;
+
+// Arrays longer than this are truncated when printed
+const MAX_PRINTED_ELEMENTS: usize = 200;
+
+/// Types are identified by the offset of their entry in .debug_info
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TypeRef(pub usize);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CompositeKind {
+    Struct,
+    Union,
+    Class,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Qualifier {
+    Const,
+    Volatile,
+    Restrict,
+    Atomic,
+}
+
+#[derive(Clone, Debug)]
+pub struct Member {
+    pub name: Option<String>,
+    pub ty: TypeRef,
+    pub offset: u64,
+    /// Bit fields store the offset in bits from the byte at `offset`, and their width
+    pub bits: Option<(u64, u64)>,
+}
+
+/// A Rust style tagged union. The discriminant is read to select which variant is active.
+#[derive(Clone, Debug)]
+pub struct VariantPart {
+    pub discriminant: Option<Member>,
+    pub variants: Vec<Variant>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Variant {
+    /// None for the default variant
+    pub discriminant_value: Option<u64>,
+    pub members: Vec<Member>,
+}
+
+#[derive(Clone, Debug)]
+pub enum TypeKind {
+    Void,
+    Base {
+        encoding: u64,
+        size: u64,
+    },
+    /// Pointers and C++ references
+    Pointer {
+        pointee: Option<TypeRef>,
+        size: u64,
+        is_reference: bool,
+    },
+    Composite {
+        kind: CompositeKind,
+        size: u64,
+        members: Vec<Member>,
+        variant_part: Option<VariantPart>,
+    },
+    Enum {
+        size: u64,
+        underlying: Option<TypeRef>,
+        enumerators: Vec<(String, i64)>,
+    },
+    Array {
+        element: TypeRef,
+        // The number of elements in each dimension, if known
+        counts: Vec<Option<u64>>,
+    },
+    Typedef {
+        target: Option<TypeRef>,
+    },
+    Qualified {
+        qualifier: Qualifier,
+        target: Option<TypeRef>,
+    },
+    Function,
+}
+
+#[derive(Clone, Debug)]
+pub struct Type {
+    pub id: TypeRef,
+    pub name: Option<String>,
+    pub kind: TypeKind,
+}
+
+pub fn read_uint(data: &[u8]) -> u64 {
+    data.iter()
+        .take(8)
+        .rev()
+        .fold(0u64, |acc, &b| (acc << 8) | b as u64)
+}
+
+pub fn read_int(data: &[u8]) -> i64 {
+    let size = data.len().min(8);
+    if size == 0 {
+        return 0;
+    }
+    let shift = 64 - size as u32 * 8;
+    ((read_uint(data) << shift) as i64) >> shift
+}
+
+impl Dwarf {
+    fn type_ref(&self, die: &Die) -> Option<TypeRef> {
+        match die.attr(DW_AT_TYPE) {
+            Some(AttrValue::Reference(offset)) => Some(TypeRef(*offset)),
+            _ => None,
+        }
+    }
+
+    /// The type of an entry. Definitions which complete a declaration, such as C++ static
+    /// members and namespace variables, carry the type on the declaration.
+    pub fn type_of(&self, die: &Die) -> Result<Option<Type>> {
+        if let Some(id) = self.type_ref(die) {
+            return Ok(Some(self.resolve_type(id)?));
+        }
+        match self.attr_reference(die, DW_AT_SPECIFICATION)? {
+            Some(declaration) => self.type_of(&declaration),
+            None => Ok(None),
+        }
+    }
+
+    fn member(&self, die: &Die) -> Result<Member> {
+        let Some(ty) = self.type_ref(die) else {
+            bail!("member at {:#x} has no type", die.offset);
+        };
+        let mut offset = match die.attr(DW_AT_DATA_MEMBER_LOCATION) {
+            Some(AttrValue::Block(expr)) => {
+                // Older producers describe the offset as DW_OP_plus_uconst <offset>
+                let mut cursor = super::Cursor::new(expr);
+                if cursor.u8()? != DW_OP_PLUS_UCONST {
+                    bail!("unsupported member location expression");
+                }
+                cursor.uleb128()?
+            }
+            Some(value) => value.as_u64().unwrap_or(0),
+            None => 0,
+        };
+
+        let mut bits = None;
+        if let Some(bit_size) = die.attr(DW_AT_BIT_SIZE).and_then(AttrValue::as_u64) {
+            let data_bit_offset = die.attr(DW_AT_DATA_BIT_OFFSET).and_then(AttrValue::as_u64);
+            let bit_offset = match data_bit_offset {
+                Some(bit_offset) => offset * 8 + bit_offset,
+                None => {
+                    // DWARF 2 style bit offsets count from the most significant bit of the
+                    // storage unit
+                    let storage = match die.attr(DW_AT_BYTE_SIZE).and_then(AttrValue::as_u64) {
+                        Some(size) => size,
+                        None => self.type_size(&self.resolve_type(ty)?)?,
+                    };
+                    let legacy = die.attr(DW_AT_BIT_OFFSET).and_then(AttrValue::as_u64);
+                    offset * 8 + storage * 8 - legacy.unwrap_or(0) - bit_size
+                }
+            };
+            offset = bit_offset / 8;
+            bits = Some((bit_offset % 8, bit_size));
+        }
+
+        Ok(Member {
+            name: self.name(die)?,
+            ty,
+            offset,
+            bits,
+        })
+    }
+
+    fn variant_part(&self, die: &Die) -> Result<VariantPart> {
+        let discriminant = match self.attr_reference(die, DW_AT_DISCR)? {
+            Some(member) => Some(self.member(&member)?),
+            None => None,
+        };
+        let mut variants = Vec::new();
+        for child in self.children(die)? {
+            if child.tag != DW_TAG_VARIANT {
+                continue;
+            }
+            let mut members = Vec::new();
+            for member in self.children(&child)? {
+                if member.tag == DW_TAG_MEMBER {
+                    members.push(self.member(&member)?);
+                }
+            }
+            variants.push(Variant {
+                discriminant_value: child.attr(DW_AT_DISCR_VALUE).and_then(AttrValue::as_u64),
+                members,
+            });
+        }
+        Ok(VariantPart {
+            discriminant,
+            variants,
+        })
+    }
+
+    pub fn resolve_type(&self, id: TypeRef) -> Result<Type> {
+        let die = self.die_at(id.0)?;
+        let size = die.attr(DW_AT_BYTE_SIZE).and_then(AttrValue::as_u64);
+        let kind = match die.tag {
+            DW_TAG_BASE_TYPE => TypeKind::Base {
+                encoding: die
+                    .attr(DW_AT_ENCODING)
+                    .and_then(AttrValue::as_u64)
+                    .unwrap_or(0),
+                size: size.unwrap_or(0),
+            },
+            DW_TAG_UNSPECIFIED_TYPE => TypeKind::Void,
+            DW_TAG_POINTER_TYPE
+            | DW_TAG_REFERENCE_TYPE
+            | DW_TAG_RVALUE_REFERENCE_TYPE
+            | DW_TAG_PTR_TO_MEMBER_TYPE => TypeKind::Pointer {
+                pointee: self.type_ref(&die),
+                size: size.unwrap_or(self.address_size(&die) as u64),
+                is_reference: die.tag != DW_TAG_POINTER_TYPE,
+            },
+            DW_TAG_STRUCTURE_TYPE | DW_TAG_UNION_TYPE | DW_TAG_CLASS_TYPE => {
+                let kind = match die.tag {
+                    DW_TAG_STRUCTURE_TYPE => CompositeKind::Struct,
+                    DW_TAG_UNION_TYPE => CompositeKind::Union,
+                    _ => CompositeKind::Class,
+                };
+                let mut members = Vec::new();
+                let mut variant_part = None;
+                for child in self.children(&die)? {
+                    match child.tag {
+                        // Static members have no location within the object
+                        DW_TAG_MEMBER if !child.has_attr(DW_AT_DECLARATION) => {
+                            members.push(self.member(&child)?)
+                        }
+                        DW_TAG_VARIANT_PART => variant_part = Some(self.variant_part(&child)?),
+                        _ => {}
+                    }
+                }
+                TypeKind::Composite {
+                    kind,
+                    size: size.unwrap_or(0),
+                    members,
+                    variant_part,
+                }
+            }
+            DW_TAG_ENUMERATION_TYPE => {
+                let mut enumerators = Vec::new();
+                for child in self.children(&die)? {
+                    if child.tag == DW_TAG_ENUMERATOR {
+                        let name = self.name(&child)?.unwrap_or_default();
+                        let value = child.attr(DW_AT_CONST_VALUE).and_then(AttrValue::as_i64);
+                        enumerators.push((name, value.unwrap_or(0)));
+                    }
+                }
+                TypeKind::Enum {
+                    size: size.unwrap_or(4),
+                    underlying: self.type_ref(&die),
+                    enumerators,
+                }
+            }
+            DW_TAG_ARRAY_TYPE => {
+                let Some(element) = self.type_ref(&die) else {
+                    bail!("array type at {:#x} has no element type", die.offset);
+                };
+                let mut counts = Vec::new();
+                for child in self.children(&die)? {
+                    if child.tag != DW_TAG_SUBRANGE_TYPE {
+                        continue;
+                    }
+                    let count = match child.attr(DW_AT_COUNT).and_then(AttrValue::as_u64) {
+                        Some(count) => Some(count),
+                        None => child
+                            .attr(DW_AT_UPPER_BOUND)
+                            .and_then(AttrValue::as_i64)
+                            .map(|upper| (upper + 1).max(0) as u64),
+                    };
+                    counts.push(count);
+                }
+                if counts.is_empty() {
+                    counts.push(None);
+                }
+                TypeKind::Array { element, counts }
+            }
+            DW_TAG_TYPEDEF => TypeKind::Typedef {
+                target: self.type_ref(&die),
+            },
+            DW_TAG_CONST_TYPE | DW_TAG_VOLATILE_TYPE | DW_TAG_RESTRICT_TYPE
+            | DW_TAG_ATOMIC_TYPE => {
+                let qualifier = match die.tag {
+                    DW_TAG_CONST_TYPE => Qualifier::Const,
+                    DW_TAG_VOLATILE_TYPE => Qualifier::Volatile,
+                    DW_TAG_RESTRICT_TYPE => Qualifier::Restrict,
+                    _ => Qualifier::Atomic,
+                };
+                TypeKind::Qualified {
+                    qualifier,
+                    target: self.type_ref(&die),
+                }
+            }
+            DW_TAG_SUBROUTINE_TYPE => TypeKind::Function,
+            other => bail!("unsupported type tag {other:#x} at {:#x}", die.offset),
+        };
+
+        Ok(Type {
+            id,
+            name: self.name(&die)?,
+            kind,
+        })
+    }
+
+    /// Removes typedefs and qualifiers, leaving the type which determines the representation
+    pub fn strip_type(&self, ty: &Type) -> Result<Type> {
+        let mut ty = ty.clone();
+        loop {
+            match ty.kind {
+                TypeKind::Typedef { target } | TypeKind::Qualified { target, .. } => match target {
+                    Some(target) => ty = self.resolve_type(target)?,
+                    None => {
+                        return Ok(Type {
+                            id: ty.id,
+                            name: Some("void".to_string()),
+                            kind: TypeKind::Void,
+                        })
+                    }
+                },
+                _ => return Ok(ty),
+            }
+        }
+    }
+
+    pub fn type_size(&self, ty: &Type) -> Result<u64> {
+        let size = match &self.strip_type(ty)?.kind {
+            TypeKind::Void | TypeKind::Function => 0,
+            TypeKind::Base { size, .. }
+            | TypeKind::Pointer { size, .. }
+            | TypeKind::Composite { size, .. }
+            | TypeKind::Enum { size, .. } => *size,
+            TypeKind::Array { element, counts } => {
+                let element_size = self.type_size(&self.resolve_type(*element)?)?;
+                counts
+                    .iter()
+                    .fold(element_size, |acc, count| acc * count.unwrap_or(0))
+            }
+            TypeKind::Typedef { .. } | TypeKind::Qualified { .. } => unreachable!(),
+        };
+        Ok(size)
+    }
+
+    /// A C like spelling of the type, used when no name is recorded for it
+    pub fn type_name(&self, ty: &Type) -> Result<String> {
+        if let Some(name) = &ty.name {
+            return Ok(name.clone());
+        }
+        let target_name = |target: &Option<TypeRef>| -> Result<String> {
+            match target {
+                Some(target) => self.type_name(&self.resolve_type(*target)?),
+                None => Ok("void".to_string()),
+            }
+        };
+        let name = match &ty.kind {
+            TypeKind::Void => "void".to_string(),
+            TypeKind::Function => "<function>".to_string(),
+            TypeKind::Base { .. } => "<unnamed>".to_string(),
+            TypeKind::Pointer {
+                pointee,
+                is_reference,
+                ..
+            } => {
+                let sigil = if *is_reference { "&" } else { "*" };
+                format!("{}{sigil}", target_name(pointee)?)
+            }
+            TypeKind::Composite { kind, .. } => match kind {
+                CompositeKind::Struct => "struct <anonymous>".to_string(),
+                CompositeKind::Union => "union <anonymous>".to_string(),
+                CompositeKind::Class => "class <anonymous>".to_string(),
+            },
+            TypeKind::Enum { .. } => "enum <anonymous>".to_string(),
+            TypeKind::Array { element, counts } => {
+                let mut name = self.type_name(&self.resolve_type(*element)?)?;
+                for count in counts {
+                    match count {
+                        Some(count) => write!(name, "[{count}]")?,
+                        None => name.push_str("[]"),
+                    }
+                }
+                name
+            }
+            TypeKind::Typedef { target } => target_name(target)?,
+            TypeKind::Qualified { qualifier, target } => {
+                let qualifier = match qualifier {
+                    Qualifier::Const => "const",
+                    Qualifier::Volatile => "volatile",
+                    Qualifier::Restrict => "restrict",
+                    Qualifier::Atomic => "_Atomic",
+                };
+                format!("{qualifier} {}", target_name(target)?)
+            }
+        };
+        Ok(name)
+    }
+
+    /// Renders the bytes of an object of the given type. Nested aggregates are printed over
+    /// multiple lines, indented by `indent` levels.
+    pub fn format_value(&self, ty: &Type, data: &[u8], indent: usize) -> Result<String> {
+        let ty = self.strip_type(ty)?;
+        let formatted = match &ty.kind {
+            TypeKind::Void => "<void>".to_string(),
+            TypeKind::Function => "<function>".to_string(),
+            TypeKind::Base { encoding, size } => format_base(*encoding, &data[..*size as usize]),
+            TypeKind::Pointer { size, .. } => format!("{:#x}", read_uint(&data[..*size as usize])),
+            TypeKind::Enum {
+                size, enumerators, ..
+            } => {
+                let value = read_int(&data[..*size as usize]);
+                match enumerators.iter().find(|(_, v)| *v == value) {
+                    Some((name, _)) => name.clone(),
+                    None => format!("{value}"),
+                }
+            }
+            TypeKind::Array { element, counts } => {
+                let element = self.resolve_type(*element)?;
+                self.format_array(&element, counts, data, indent)?
+            }
+            TypeKind::Composite {
+                members,
+                variant_part,
+                ..
+            } => {
+                let mut members = members.clone();
+                if let Some(variant_part) = variant_part
+                    && let Some(variant) = self.active_variant(variant_part, data)?
+                {
+                    // A Rust enum variant is a single member whose type is named after the
+                    // variant and holds its fields
+                    if let [member] = variant.members.as_slice() {
+                        let member_type = self.resolve_type(member.ty)?;
+                        let start = member.offset as usize;
+                        let end = start + self.type_size(&member_type)? as usize;
+                        let value = self.format_value(&member_type, &data[start..end], indent)?;
+                        return Ok(format!("{} {value}", self.type_name(&member_type)?));
+                    }
+                    members.extend(variant.members.iter().cloned());
+                }
+                self.format_members(&members, data, indent)?
+            }
+            TypeKind::Typedef { .. } | TypeKind::Qualified { .. } => unreachable!(),
+        };
+        Ok(formatted)
+    }
+
+    fn active_variant<'v>(
+        &self,
+        variant_part: &'v VariantPart,
+        data: &[u8],
+    ) -> Result<Option<&'v Variant>> {
+        let Some(discriminant) = &variant_part.discriminant else {
+            return Ok(variant_part.variants.first());
+        };
+        let size = self.type_size(&self.resolve_type(discriminant.ty)?)? as usize;
+        let start = discriminant.offset as usize;
+        let value = read_uint(&data[start..start + size]);
+        let variant = variant_part
+            .variants
+            .iter()
+            .find(|v| v.discriminant_value == Some(value))
+            .or(variant_part
+                .variants
+                .iter()
+                .find(|v| v.discriminant_value.is_none()));
+        Ok(variant)
+    }
+
+    fn format_members(&self, members: &[Member], data: &[u8], indent: usize) -> Result<String> {
+        if members.is_empty() {
+            return Ok("{}".to_string());
+        }
+
+        let padding = "  ".repeat(indent + 1);
+        let mut out = String::from("{\n");
+        for member in members {
+            let member_type = self.resolve_type(member.ty)?;
+            let start = member.offset as usize;
+            let value = match member.bits {
+                Some((bit_offset, bit_size)) => {
+                    let storage = &data[start..data.len().min(start + 8)];
+                    let bits = read_uint(storage) >> bit_offset;
+                    let mask = if bit_size >= 64 {
+                        u64::MAX
+                    } else {
+                        (1 << bit_size) - 1
+                    };
+                    format!("{}", bits & mask)
+                }
+                None => {
+                    let end = start + self.type_size(&member_type)? as usize;
+                    if end > data.len() {
+                        bail!("member extends past the end of its parent");
+                    }
+                    self.format_value(&member_type, &data[start..end], indent + 1)?
+                }
+            };
+            let name = member.name.as_deref().unwrap_or("<anonymous>");
+            writeln!(out, "{padding}{name} = {value},")?;
+        }
+        write!(out, "{}}}", "  ".repeat(indent))?;
+        Ok(out)
+    }
+
+    fn format_array(
+        &self,
+        element: &Type,
+        counts: &[Option<u64>],
+        data: &[u8],
+        indent: usize,
+    ) -> Result<String> {
+        let Some((count, inner_counts)) = counts.split_first() else {
+            return self.format_value(element, data, indent);
+        };
+        let count = count.unwrap_or(0) as usize;
+
+        let element_size = inner_counts
+            .iter()
+            .fold(self.type_size(element)?, |acc, c| acc * c.unwrap_or(0))
+            as usize;
+        let mut items = Vec::new();
+        for i in 0..count.min(MAX_PRINTED_ELEMENTS) {
+            let start = i * element_size;
+            let item = &data[start..start + element_size];
+            items.push(self.format_array(element, inner_counts, item, indent)?);
+        }
+        if count > MAX_PRINTED_ELEMENTS {
+            items.push("...".to_string());
+        }
+        Ok(format!("[{}]", items.join(", ")))
+    }
+}
+
+fn format_base(encoding: u64, data: &[u8]) -> String {
+    match encoding {
+        DW_ATE_BOOLEAN => format!("{}", read_uint(data) != 0),
+        DW_ATE_FLOAT if data.len() == 4 => format!("{}", pod_read_unaligned::<f32>(data)),
+        DW_ATE_FLOAT if data.len() == 8 => format!("{}", pod_read_unaligned::<f64>(data)),
+        DW_ATE_SIGNED => format!("{}", read_int(data)),
+        DW_ATE_UNSIGNED => format!("{}", read_uint(data)),
+        DW_ATE_SIGNED_CHAR | DW_ATE_UNSIGNED_CHAR if data.len() == 1 => {
+            let value = data[0];
+            let number = if encoding == DW_ATE_SIGNED_CHAR {
+                format!("{}", value as i8)
+            } else {
+                format!("{value}")
+            };
+            format!("{number} {:?}", value as char)
+        }
+        DW_ATE_UTF => match char::from_u32(read_uint(data) as u32) {
+            Some(c) => format!("{c:?}"),
+            None => format!("{}", read_uint(data)),
+        },
+        DW_ATE_ADDRESS => format!("{:#x}", read_uint(data)),
+        _ => format!(
+            "0x{}",
+            data.iter()
+                .rev()
+                .map(|b| format!("{b:02x}"))
+                .collect::<String>()
+        ),
+    }
+}
@@ -0,0 +1,187 @@
+use anyhow::{anyhow, bail, Result};
+use nix::libc::{Elf64_Ehdr, Elf64_Phdr, Elf64_Shdr, Elf64_Sym, ET_DYN, PT_INTERP, PT_LOAD};
+use std::collections::HashMap;
+use std::fs;
+use std::mem;
+use std::ops::Range;
+use std::path::Path;
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const ELFCLASS64: u8 = 2;
+const SHT_NOBITS: u32 = 8;
+const SHF_ALLOC: u64 = 2;
+
+// Reads a plain C struct out of a byte buffer. The structs read this way are the libc ELF
+// definitions, which are valid for any bit pattern.
+pub(crate) fn read_struct<T: Copy>(data: &[u8], offset: usize) -> Result<T> {
+    let end = offset
+        .checked_add(mem::size_of::<T>())
+        .ok_or_else(|| anyhow!("offset {offset:#x} overflows"))?;
+    if end > data.len() {
+        bail!(
+            "read of {} bytes at {offset:#x} is out of bounds",
+            mem::size_of::<T>()
+        );
+    }
+    Ok(unsafe { std::ptr::read_unaligned(data[offset..].as_ptr() as *const T) })
+}
+
+// The bytes of a plain C struct, the reverse of read_struct
+pub(crate) fn struct_bytes<T: Copy>(value: &T) -> Vec<u8> {
+    let pointer = value as *const T as *const u8;
+    unsafe { std::slice::from_raw_parts(pointer, mem::size_of::<T>()) }.to_vec()
+}
+
+pub struct Elf {
+    data: Vec<u8>,
+    header: Elf64_Ehdr,
+    program_headers: Vec<Elf64_Phdr>,
+    section_headers: Vec<Elf64_Shdr>,
+    section_names: HashMap<String, usize>,
+}
+
+impl Elf {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data = fs::read(path)?;
+        Self::parse(path, data)
+    }
+
+    fn parse(path: &Path, data: Vec<u8>) -> Result<Self> {
+        if !data.starts_with(ELF_MAGIC) {
+            bail!("{} is not an ELF file", path.display());
+        }
+
+        let header: Elf64_Ehdr = read_struct(&data, 0)?;
+        if header.e_ident[4] != ELFCLASS64 {
+            bail!("{} is not a 64 bit ELF file", path.display());
+        }
+
+        let mut program_headers = Vec::with_capacity(header.e_phnum as usize);
+        for i in 0..header.e_phnum as usize {
+            let offset = header.e_phoff as usize + i * header.e_phentsize as usize;
+            program_headers.push(read_struct::<Elf64_Phdr>(&data, offset)?);
+        }
+
+        let mut section_headers = Vec::with_capacity(header.e_shnum as usize);
+        for i in 0..header.e_shnum as usize {
+            let offset = header.e_shoff as usize + i * header.e_shentsize as usize;
+            section_headers.push(read_struct::<Elf64_Shdr>(&data, offset)?);
+        }
+
+        let mut elf = Self {
+            data,
+            header,
+            program_headers,
+            section_headers,
+            section_names: HashMap::new(),
+        };
+
+        if let Some(names) = elf.section_headers.get(header.e_shstrndx as usize) {
+            let names_offset = names.sh_offset as usize;
+            for (index, section) in elf.section_headers.iter().enumerate() {
+                let name = elf.string_at(names_offset + section.sh_name as usize);
+                elf.section_names.insert(name.to_string(), index);
+            }
+        }
+
+        Ok(elf)
+    }
+
+    // Reads a NUL terminated string starting at the given file offset
+    fn string_at(&self, offset: usize) -> &str {
+        let bytes = self.data.get(offset..).unwrap_or_default();
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        std::str::from_utf8(&bytes[..end]).unwrap_or_default()
+    }
+
+    pub fn entry(&self) -> u64 {
+        self.header.e_entry
+    }
+
+    pub fn is_position_independent(&self) -> bool {
+        self.header.e_type == ET_DYN
+    }
+
+    // The link time addresses spanned by the loadable segments
+    pub fn load_range(&self) -> Range<u64> {
+        let segments = self.program_headers.iter().filter(|p| p.p_type == PT_LOAD);
+        let start = segments.clone().map(|p| p.p_vaddr).min().unwrap_or(0);
+        let end = segments.map(|p| p.p_vaddr + p.p_memsz).max().unwrap_or(0);
+        start..end
+    }
+
+    // The link time address and the contents in the file of each loadable segment, along with
+    // its size in memory, which is larger than the file contents when it has a .bss
+    #[cfg(test)]
+    pub fn load_segments(&self) -> impl Iterator<Item = (u64, &[u8], u64)> {
+        self.program_headers
+            .iter()
+            .filter(|p| p.p_type == PT_LOAD)
+            .map(|p| {
+                let start = p.p_offset as usize;
+                let data = self.data.get(start..start + p.p_filesz as usize);
+                (p.p_vaddr, data.unwrap_or_default(), p.p_memsz)
+            })
+    }
+
+    // The path of the dynamic linker requested by a dynamically linked executable
+    pub fn interpreter(&self) -> Option<&str> {
+        let segment = self
+            .program_headers
+            .iter()
+            .find(|p| p.p_type == PT_INTERP)?;
+        Some(self.string_at(segment.p_offset as usize))
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Elf64_Shdr> {
+        self.section_names
+            .get(name)
+            .map(|&index| &self.section_headers[index])
+    }
+
+    // The name of the section which is loaded at a link time address
+    pub fn section_containing(&self, address: u64) -> Option<&str> {
+        self.section_names
+            .iter()
+            .find(|&(_, &index)| {
+                let section = &self.section_headers[index];
+                section.sh_flags & SHF_ALLOC != 0
+                    && (section.sh_addr..section.sh_addr + section.sh_size).contains(&address)
+            })
+            .map(|(name, _)| name.as_str())
+    }
+
+    // The contents of a section as stored in the file. Sections which occupy no space in the
+    // file, such as .bss, are returned as empty slices.
+    pub fn section_data(&self, name: &str) -> Option<&[u8]> {
+        let section = self.section(name)?;
+        if section.sh_type == SHT_NOBITS {
+            return Some(&[]);
+        }
+        let start = section.sh_offset as usize;
+        self.data.get(start..start + section.sh_size as usize)
+    }
+
+    // The symbols of the file along with their names, taken from .symtab or, for stripped
+    // files, from .dynsym
+    pub fn symbols(&self) -> Result<Vec<(String, Elf64_Sym)>> {
+        let Some(table) = self.section(".symtab").or_else(|| self.section(".dynsym")) else {
+            return Ok(Vec::new());
+        };
+        let names = self
+            .section_headers
+            .get(table.sh_link as usize)
+            .ok_or_else(|| anyhow!("symbol table has no string table"))?;
+
+        let count = table.sh_size as usize / mem::size_of::<Elf64_Sym>();
+        let mut symbols = Vec::with_capacity(count);
+        for i in 0..count {
+            let offset = table.sh_offset as usize + i * mem::size_of::<Elf64_Sym>();
+            let symbol: Elf64_Sym = read_struct(&self.data, offset)?;
+            let name = self.string_at(names.sh_offset as usize + symbol.st_name as usize);
+            symbols.push((name.to_string(), symbol));
+        }
+        Ok(symbols)
+    }
+}
@@ -0,0 +1,781 @@
+use crate::dwarf::consts::*;
+use crate::dwarf::types::{read_int, read_uint, TypeKind};
+use crate::dwarf::Dwarf;
+use crate::expression::parse::{parse, BinaryOp, Expr, TypeName, UnaryOp};
+use crate::expression::{builtin_type, ExpressionContext, Place, Value, ValueType};
+use crate::reginfo::{lookup_register_info_by_name, RegisterFormat, RegisterId, RegisterInfo};
+use anyhow::{anyhow, bail, Result};
+use bytemuck::pod_read_unaligned;
+
+fn register_named(name: &str) -> Option<&'static RegisterInfo> {
+    lookup_register_info_by_name(&name.to_ascii_uppercase()).ok()
+}
+
+const UNKNOWN_TYPE: &str = "value has unknown type; cast it to its declared type";
+
+// The number held by a scalar value. Integers keep their bits sign or zero extended to 64
+// bits, along with whether they are signed.
+#[derive(Copy, Clone, Debug)]
+enum Number {
+    Int(u64, bool),
+    Float(f64),
+}
+
+fn dwarf(ctx: &dyn ExpressionContext, module: usize) -> Result<&Dwarf> {
+    ctx.dwarf(module)
+        .ok_or_else(|| anyhow!("no debug information for module {module}"))
+}
+
+// Converts types from the debug information which have a built in equivalent, and strips
+// typedefs and qualifiers from the rest, so that only composite types remain as debug types
+fn resolve(ctx: &dyn ExpressionContext, ty: &ValueType) -> Result<ValueType> {
+    let &ValueType::Debug { module, ty: id } = ty else {
+        return Ok(ty.clone());
+    };
+    let dwarf = dwarf(ctx, module)?;
+    let stripped = dwarf.strip_type(&dwarf.resolve_type(id)?)?;
+    let debug = |id| ValueType::Debug { module, ty: id };
+    Ok(match stripped.kind {
+        TypeKind::Void => ValueType::Void,
+        TypeKind::Function => ValueType::Function,
+        TypeKind::Base { encoding, size } => {
+            let size = size as usize;
+            match encoding {
+                DW_ATE_BOOLEAN => ValueType::Bool,
+                DW_ATE_FLOAT => ValueType::Float { size },
+                DW_ATE_SIGNED | DW_ATE_SIGNED_CHAR => ValueType::Int { size, signed: true },
+                DW_ATE_UNSIGNED | DW_ATE_UNSIGNED_CHAR | DW_ATE_UTF | DW_ATE_ADDRESS => {
+                    ValueType::Int {
+                        size,
+                        signed: false,
+                    }
+                }
+                _ => debug(stripped.id),
+            }
+        }
+        TypeKind::Pointer { pointee, .. } => {
+            ValueType::Pointer(Box::new(pointee.map_or(ValueType::Void, debug)))
+        }
+        TypeKind::Enum {
+            size, underlying, ..
+        } => match underlying {
+            Some(underlying) => resolve(ctx, &debug(underlying))?,
+            None => ValueType::Int {
+                size: size as usize,
+                signed: false,
+            },
+        },
+        TypeKind::Array { element, counts } => {
+            counts
+                .iter()
+                .rev()
+                .fold(debug(element), |element, count| ValueType::Array {
+                    element: Box::new(element),
+                    count: count.unwrap_or(0),
+                })
+        }
+        TypeKind::Composite { .. } => debug(stripped.id),
+        TypeKind::Typedef { .. } | TypeKind::Qualified { .. } => unreachable!(),
+    })
+}
+
+fn size_of(ctx: &dyn ExpressionContext, ty: &ValueType) -> Result<u64> {
+    Ok(match ty {
+        // As in gdb, arithmetic on pointers to void and to functions counts in bytes
+        ValueType::Void | ValueType::Function | ValueType::Bool | ValueType::Char => 1,
+        ValueType::Int { size, .. } | ValueType::Float { size } => *size as u64,
+        ValueType::Pointer(_) => 8,
+        ValueType::Array { element, count } => size_of(ctx, element)? * count,
+        &ValueType::Debug { module, ty } => {
+            let dwarf = dwarf(ctx, module)?;
+            dwarf.type_size(&dwarf.resolve_type(ty)?)?
+        }
+    })
+}
+
+fn is_scalar(ty: &ValueType) -> bool {
+    matches!(
+        ty,
+        ValueType::Bool
+            | ValueType::Char
+            | ValueType::Int { .. }
+            | ValueType::Float { .. }
+            | ValueType::Pointer(_)
+    )
+}
+
+fn place_address(value: &Value) -> Option<u64> {
+    match value.place {
+        Some(Place::Memory(address)) => Some(address),
+        _ => None,
+    }
+}
+
+fn number(ctx: &dyn ExpressionContext, value: &Value) -> Result<Number> {
+    let ty = resolve(ctx, &value.ty)?;
+    let data = &value.data;
+    let size = size_of(ctx, &ty)? as usize;
+    if is_scalar(&ty) && data.len() < size {
+        bail!("value of {size} bytes holds only {} bytes", data.len());
+    }
+    Ok(match ty {
+        ValueType::Bool => Number::Int((data[0] != 0) as u64, false),
+        ValueType::Char => Number::Int(data[0] as i8 as u64, true),
+        ValueType::Int { size, signed: true } => Number::Int(read_int(&data[..size]) as u64, true),
+        ValueType::Int { size, .. } => Number::Int(read_uint(&data[..size]), false),
+        ValueType::Float { size: 4 } => Number::Float(pod_read_unaligned::<f32>(&data[..4]) as f64),
+        ValueType::Float { size: 8 } => Number::Float(pod_read_unaligned(&data[..8])),
+        ValueType::Pointer(_) => Number::Int(read_uint(&data[..8]), false),
+        // Arrays and functions decay to their address
+        ValueType::Array { .. } | ValueType::Function if place_address(value).is_some() => {
+            Number::Int(place_address(value).unwrap_or_default(), false)
+        }
+        ValueType::Void => bail!(UNKNOWN_TYPE),
+        other => bail!("{} is not a number", type_name(&other, ctx)?),
+    })
+}
+
+// Truncates integer bits to a size and extends them back to 64 bits
+fn fit(bits: u64, size: usize, signed: bool) -> u64 {
+    if size >= 8 {
+        return bits;
+    }
+    let shift = 64 - size as u32 * 8;
+    if signed {
+        (((bits << shift) as i64) >> shift) as u64
+    } else {
+        (bits << shift) >> shift
+    }
+}
+
+// The bytes of a number converted to a scalar type
+fn encode(ty: &ValueType, number: Number) -> Result<Vec<u8>> {
+    let bits = match number {
+        Number::Int(bits, _) => bits,
+        Number::Float(value) if value < 0.0 => value as i64 as u64,
+        Number::Float(value) => value as u64,
+    };
+    let float = match number {
+        Number::Int(bits, true) => bits as i64 as f64,
+        Number::Int(bits, false) => bits as f64,
+        Number::Float(value) => value,
+    };
+    Ok(match ty {
+        ValueType::Bool => vec![(bits != 0 || float != 0.0) as u8],
+        ValueType::Char => vec![bits as u8],
+        ValueType::Int { size, .. } => bits.to_le_bytes()[..*size].to_vec(),
+        ValueType::Float { size: 4 } => (float as f32).to_le_bytes().to_vec(),
+        ValueType::Float { size: 8 } => float.to_le_bytes().to_vec(),
+        ValueType::Pointer(_) => bits.to_le_bytes().to_vec(),
+        _ => bail!("cannot convert a number to {ty:?}"),
+    })
+}
+
+fn int_value(ty: ValueType, bits: u64) -> Result<Value> {
+    Ok(Value::new(
+        ty.clone(),
+        encode(&ty, Number::Int(bits, false))?,
+    ))
+}
+
+fn truthy(ctx: &dyn ExpressionContext, value: &Value) -> Result<bool> {
+    Ok(match number(ctx, value)? {
+        Number::Int(bits, _) => bits != 0,
+        Number::Float(value) => value != 0.0,
+    })
+}
+
+// Converts a value to another type, as a C cast does. Values in memory can be reinterpreted
+// as any type, which is how symbols without debug information are given one.
+fn convert(ctx: &dyn ExpressionContext, value: &Value, ty: &ValueType) -> Result<Value> {
+    let target = resolve(ctx, ty)?;
+    if value.ty == ValueType::Void
+        && let Some(address) = place_address(value)
+    {
+        let data = ctx.read_memory(address, size_of(ctx, &target)? as usize)?;
+        return Ok(Value {
+            ty: ty.clone(),
+            data,
+            place: value.place,
+        });
+    }
+    if is_scalar(&target) {
+        return Ok(Value::new(
+            ty.clone(),
+            encode(&target, number(ctx, value)?)?,
+        ));
+    }
+    if size_of(ctx, &target)? == value.data.len() as u64 {
+        return Ok(Value {
+            ty: ty.clone(),
+            data: value.data.clone(),
+            place: value.place,
+        });
+    }
+    bail!(
+        "cannot convert {} to {}",
+        type_name(&value.ty, ctx)?,
+        type_name(ty, ctx)?
+    )
+}
+
+// The type integer operands are converted to by the usual arithmetic conversions of C, in
+// which everything narrower than int is promoted to int
+fn common_int_type(left: &ValueType, right: &ValueType) -> ValueType {
+    let promote = |ty: &ValueType| match *ty {
+        ValueType::Int { size, signed } if size >= 4 => (size, signed),
+        _ => (4, true),
+    };
+    let (left_size, left_signed) = promote(left);
+    let (right_size, right_signed) = promote(right);
+    let (size, signed) = if left_size == right_size {
+        (left_size, left_signed && right_signed)
+    } else if left_size > right_size {
+        (left_size, left_signed)
+    } else {
+        (right_size, right_signed)
+    };
+    ValueType::Int { size, signed }
+}
+
+fn compare<T: PartialOrd>(op: BinaryOp, left: T, right: T) -> bool {
+    match op {
+        BinaryOp::Lt => left < right,
+        BinaryOp::Le => left <= right,
+        BinaryOp::Gt => left > right,
+        BinaryOp::Ge => left >= right,
+        BinaryOp::Eq => left == right,
+        _ => left != right,
+    }
+}
+
+fn is_comparison(op: BinaryOp) -> bool {
+    matches!(
+        op,
+        BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge | BinaryOp::Eq | BinaryOp::Ne
+    )
+}
+
+struct Evaluator<'a> {
+    ctx: &'a mut dyn ExpressionContext,
+}
+
+impl Evaluator<'_> {
+    fn eval(&mut self, expr: &Expr) -> Result<Value> {
+        match expr {
+            &Expr::Integer { value, unsigned } => {
+                let ty = match (unsigned, value) {
+                    (false, 0..=0x7fff_ffff) => ValueType::INT,
+                    (true, 0..=0xffff_ffff) => ValueType::Int {
+                        size: 4,
+                        signed: false,
+                    },
+                    (false, 0..=0x7fff_ffff_ffff_ffff) => ValueType::LONG,
+                    _ => ValueType::UNSIGNED_LONG,
+                };
+                int_value(ty, value)
+            }
+            Expr::Float(value) => Ok(Value::new(ValueType::DOUBLE, value.to_le_bytes().to_vec())),
+            Expr::Char(value) => Ok(Value::new(ValueType::Char, vec![*value])),
+            Expr::Name(name) => self
+                .ctx
+                .variable(name)?
+                .ok_or_else(|| anyhow!("no symbol \"{name}\" in current context")),
+            Expr::Dollar(name) => self.dollar(name),
+            Expr::Unary(op, operand) => {
+                let operand = self.eval(operand)?;
+                self.unary(*op, operand)
+            }
+            Expr::Binary(BinaryOp::And, left, right) => {
+                let result = self.truthy(left)? && self.truthy(right)?;
+                int_value(ValueType::INT, result as u64)
+            }
+            Expr::Binary(BinaryOp::Or, left, right) => {
+                let result = self.truthy(left)? || self.truthy(right)?;
+                int_value(ValueType::INT, result as u64)
+            }
+            Expr::Binary(op, left, right) => {
+                let left = self.eval(left)?;
+                let right = self.eval(right)?;
+                self.binary(*op, left, right)
+            }
+            Expr::Cast(name, operand) => {
+                let ty = self.named_type(name)?;
+                let operand = self.eval(operand)?;
+                convert(self.ctx, &operand, &ty)
+            }
+            Expr::Index(base, index) => {
+                let base = self.eval(base)?;
+                let index = self.eval(index)?;
+                let Number::Int(index, _) = number(self.ctx, &index)? else {
+                    bail!("array index must be an integer");
+                };
+                self.index(base, index as i64)
+            }
+            Expr::Member(base, name) => {
+                let base = self.eval(base)?;
+                self.member(base, name)
+            }
+            Expr::Arrow(base, name) => {
+                let base = self.eval(base)?;
+                if !matches!(resolve(self.ctx, &base.ty)?, ValueType::Pointer(_)) {
+                    bail!("the left operand of -> must be a pointer");
+                }
+                let target = self.deref(base)?;
+                self.member(target, name)
+            }
+            Expr::Assign(target, value) => {
+                if let Expr::Dollar(name) = &**target
+                    && register_named(name).is_none()
+                {
+                    let value = self.eval(value)?;
+                    self.ctx.set_convenience(name, &value)?;
+                    return Ok(value);
+                }
+                let target = self.eval(target)?;
+                let value = self.eval(value)?;
+                self.assign(target, value)
+            }
+        }
+    }
+
+    fn truthy(&mut self, expr: &Expr) -> Result<bool> {
+        let value = self.eval(expr)?;
+        truthy(self.ctx, &value)
+    }
+
+    fn named_type(&self, name: &TypeName) -> Result<ValueType> {
+        let base = match builtin_type(&name.name) {
+            Some(ty) => ty,
+            None => {
+                let bare = ["struct ", "union ", "enum ", "class "]
+                    .iter()
+                    .find_map(|keyword| name.name.strip_prefix(keyword))
+                    .unwrap_or(&name.name);
+                self.ctx
+                    .lookup_type(bare)
+                    .ok_or_else(|| anyhow!("no type named {}", name.name))?
+            }
+        };
+        Ok((0..name.pointers).fold(base, |ty, _| ty.pointer_to()))
+    }
+
+    // Registers come before convenience variables of the same name, as in gdb
+    fn dollar(&self, name: &str) -> Result<Value> {
+        match register_named(name) {
+            Some(info) => self.register(info),
+            None => (self.ctx.convenience(name))
+                .ok_or_else(|| anyhow!("no register, variable or history value ${name}")),
+        }
+    }
+
+    fn register(&self, info: &'static RegisterInfo) -> Result<Value> {
+        let data = self.ctx.registers().read(info)?.widen()[..info.size].to_vec();
+        let ty = match (info.id, info.format) {
+            (RegisterId::RIP, _) => ValueType::Function.pointer_to(),
+            (RegisterId::RSP | RegisterId::RBP, _) => ValueType::Void.pointer_to(),
+            (_, RegisterFormat::Uint) => ValueType::Int {
+                size: info.size,
+                signed: true,
+            },
+            (_, RegisterFormat::DoubleFloat | RegisterFormat::LongDouble) => ValueType::DOUBLE,
+            (_, RegisterFormat::Vector) => ValueType::Array {
+                element: Box::new(ValueType::Int {
+                    size: 1,
+                    signed: false,
+                }),
+                count: info.size as u64,
+            },
+        };
+        let size = size_of(self.ctx, &ty)? as usize;
+        Ok(Value {
+            ty,
+            data: data[..size].to_vec(),
+            place: Some(Place::Register(info)),
+        })
+    }
+
+    fn unary(&mut self, op: UnaryOp, operand: Value) -> Result<Value> {
+        match op {
+            UnaryOp::Deref => self.deref(operand),
+            UnaryOp::AddressOf => match place_address(&operand) {
+                Some(address) => int_value(operand.ty.pointer_to(), address),
+                None => bail!("cannot take the address of a value which is not in memory"),
+            },
+            UnaryOp::Not => {
+                let result = !truthy(self.ctx, &operand)?;
+                int_value(ValueType::INT, result as u64)
+            }
+            UnaryOp::Negate | UnaryOp::Complement => {
+                let ty = resolve(self.ctx, &operand.ty)?;
+                match (number(self.ctx, &operand)?, op) {
+                    (Number::Float(value), UnaryOp::Negate) => {
+                        Ok(Value::new(ty.clone(), encode(&ty, Number::Float(-value))?))
+                    }
+                    (Number::Int(bits, _), _) if !matches!(ty, ValueType::Pointer(_)) => {
+                        let ty = common_int_type(&ty, &ty);
+                        let bits = match op {
+                            UnaryOp::Negate => bits.wrapping_neg(),
+                            _ => !bits,
+                        };
+                        int_value(ty, bits)
+                    }
+                    _ => bail!("invalid operand for unary {op:?}"),
+                }
+            }
+        }
+    }
+
+    fn read_object(&self, ty: ValueType, address: u64) -> Result<Value> {
+        let target = resolve(self.ctx, &ty)?;
+        let data = match target {
+            ValueType::Void => bail!("attempt to take contents of a void pointer"),
+            ValueType::Function => Vec::new(),
+            _ => {
+                let size = size_of(self.ctx, &target)? as usize;
+                self.ctx.read_memory(address, size)?
+            }
+        };
+        Ok(Value {
+            ty,
+            data,
+            place: Some(Place::Memory(address)),
+        })
+    }
+
+    fn deref(&self, value: Value) -> Result<Value> {
+        match resolve(self.ctx, &value.ty)? {
+            ValueType::Pointer(pointee) => {
+                let address = read_uint(&value.data[..8]);
+                self.read_object(*pointee, address)
+            }
+            ValueType::Array { .. } => self.index(value, 0),
+            _ => bail!("attempt to take contents of a non-pointer value"),
+        }
+    }
+
+    fn index(&self, base: Value, index: i64) -> Result<Value> {
+        match resolve(self.ctx, &base.ty)? {
+            ValueType::Array { element, count } => {
+                let size = size_of(self.ctx, &element)?;
+                let offset = (index as u64).wrapping_mul(size);
+                if let Some(address) = place_address(&base) {
+                    return self.read_object(*element, address.wrapping_add(offset));
+                }
+                if index < 0 || index as u64 >= count {
+                    bail!("index {index} is out of bounds for an array of {count} elements");
+                }
+                let start = offset as usize;
+                Ok(Value::new(
+                    *element,
+                    base.data[start..start + size as usize].to_vec(),
+                ))
+            }
+            ValueType::Pointer(pointee) => {
+                let size = size_of(self.ctx, &resolve(self.ctx, &pointee)?)?;
+                let address = read_uint(&base.data[..8]);
+                let address = address.wrapping_add((index as u64).wrapping_mul(size));
+                self.read_object(*pointee, address)
+            }
+            other => bail!(
+                "cannot subscript a value of type {}",
+                type_name(&other, self.ctx)?
+            ),
+        }
+    }
+
+    // Member access, which like in Rust looks through a pointer to a structure
+    fn member(&self, base: Value, name: &str) -> Result<Value> {
+        let mut base = base;
+        if let ValueType::Pointer(_) = resolve(self.ctx, &base.ty)? {
+            base = self.deref(base)?;
+        }
+        let ValueType::Debug { module, ty } = resolve(self.ctx, &base.ty)? else {
+            bail!("{} has no members", type_name(&base.ty, self.ctx)?);
+        };
+        let dwarf = dwarf(self.ctx, module)?;
+        let TypeKind::Composite { members, .. } = dwarf.resolve_type(ty)?.kind else {
+            bail!("{} has no members", type_name(&base.ty, self.ctx)?);
+        };
+        let member = members
+            .iter()
+            .find(|member| member.name.as_deref() == Some(name))
+            .ok_or_else(|| anyhow!("there is no member named {name}"))?;
+
+        let ty = ValueType::Debug {
+            module,
+            ty: member.ty,
+        };
+        let size = size_of(self.ctx, &ty)? as usize;
+        let start = member.offset as usize;
+        if let Some((bit_offset, bit_size)) = member.bits {
+            let storage = &base.data[start..base.data.len().min(start + 8)];
+            let bits = read_uint(storage) >> bit_offset;
+            let bits = bits & u64::MAX.checked_shr(64 - bit_size as u32).unwrap_or(0);
+            return Ok(Value::new(ty, bits.to_le_bytes()[..size].to_vec()));
+        }
+        let data = base
+            .data
+            .get(start..start + size)
+            .ok_or_else(|| anyhow!("member {name} extends past the end of its parent"))?
+            .to_vec();
+        Ok(Value {
+            ty,
+            data,
+            place: place_address(&base).map(|address| Place::Memory(address + member.offset)),
+        })
+    }
+
+    fn binary(&self, op: BinaryOp, left: Value, right: Value) -> Result<Value> {
+        let decay = |ty: ValueType, value: &Value| match ty {
+            ValueType::Array { element, .. } if place_address(value).is_some() => {
+                ValueType::Pointer(element)
+            }
+            ty => ty,
+        };
+        let left_type = decay(resolve(self.ctx, &left.ty)?, &left);
+        let right_type = decay(resolve(self.ctx, &right.ty)?, &right);
+        let left_number = number(self.ctx, &left)?;
+        let right_number = number(self.ctx, &right)?;
+
+        // Pointer arithmetic counts in elements
+        let pointee_size = |ty: &ValueType| -> Result<Option<u64>> {
+            match ty {
+                ValueType::Pointer(pointee) => {
+                    Ok(Some(size_of(self.ctx, &resolve(self.ctx, pointee)?)?))
+                }
+                _ => Ok(None),
+            }
+        };
+        let left_pointee = pointee_size(&left_type)?;
+        let right_pointee = pointee_size(&right_type)?;
+        if let (Number::Int(left_bits, _), Number::Int(right_bits, _)) = (left_number, right_number)
+            && (left_pointee.is_some() || right_pointee.is_some())
+            && !is_comparison(op)
+        {
+            return match (op, left_pointee, right_pointee) {
+                (BinaryOp::Add, Some(size), None) => int_value(
+                    left_type,
+                    left_bits.wrapping_add(right_bits.wrapping_mul(size)),
+                ),
+                (BinaryOp::Add, None, Some(size)) => int_value(
+                    right_type,
+                    right_bits.wrapping_add(left_bits.wrapping_mul(size)),
+                ),
+                (BinaryOp::Sub, Some(size), None) => int_value(
+                    left_type,
+                    left_bits.wrapping_sub(right_bits.wrapping_mul(size)),
+                ),
+                (BinaryOp::Sub, Some(size), Some(_)) => {
+                    let difference = left_bits.wrapping_sub(right_bits) as i64;
+                    int_value(ValueType::LONG, (difference / size.max(1) as i64) as u64)
+                }
+                _ => bail!("invalid operands to {op:?} on pointers"),
+            };
+        }
+
+        match (left_number, right_number) {
+            (Number::Int(left_bits, _), Number::Int(right_bits, _)) => {
+                let ty = match op {
+                    BinaryOp::Shl | BinaryOp::Shr => common_int_type(&left_type, &left_type),
+                    _ => common_int_type(&left_type, &right_type),
+                };
+                let ValueType::Int { size, signed } = ty else {
+                    unreachable!()
+                };
+                let left = fit(left_bits, size, signed);
+                let right = fit(right_bits, size, signed);
+                if is_comparison(op) {
+                    let result = if signed {
+                        compare(op, left as i64, right as i64)
+                    } else {
+                        compare(op, left, right)
+                    };
+                    return int_value(ValueType::INT, result as u64);
+                }
+                if matches!(op, BinaryOp::Div | BinaryOp::Rem) && right == 0 {
+                    bail!("division by zero");
+                }
+                let bits = match op {
+                    BinaryOp::Add => left.wrapping_add(right),
+                    BinaryOp::Sub => left.wrapping_sub(right),
+                    BinaryOp::Mul => left.wrapping_mul(right),
+                    BinaryOp::Div if signed => (left as i64).wrapping_div(right as i64) as u64,
+                    BinaryOp::Div => left / right,
+                    BinaryOp::Rem if signed => (left as i64).wrapping_rem(right as i64) as u64,
+                    BinaryOp::Rem => left % right,
+                    BinaryOp::Shl => left.wrapping_shl(right as u32),
+                    BinaryOp::Shr if signed => (left as i64).wrapping_shr(right as u32) as u64,
+                    BinaryOp::Shr => left.wrapping_shr(right as u32),
+                    BinaryOp::BitAnd => left & right,
+                    BinaryOp::BitXor => left ^ right,
+                    BinaryOp::BitOr => left | right,
+                    _ => unreachable!(),
+                };
+                int_value(ty, bits)
+            }
+            (left_number, right_number) => {
+                let float = |number| match number {
+                    Number::Int(bits, true) => bits as i64 as f64,
+                    Number::Int(bits, false) => bits as f64,
+                    Number::Float(value) => value,
+                };
+                let (left, right) = (float(left_number), float(right_number));
+                if is_comparison(op) {
+                    return int_value(ValueType::INT, compare(op, left, right) as u64);
+                }
+                let result = match op {
+                    BinaryOp::Add => left + right,
+                    BinaryOp::Sub => left - right,
+                    BinaryOp::Mul => left * right,
+                    BinaryOp::Div => left / right,
+                    _ => bail!("invalid operands to {op:?} on floating point values"),
+                };
+                // Arithmetic on two floats stays in float, anything wider goes to double
+                let ty = match (&left_type, &right_type) {
+                    (ValueType::Float { size: 4 }, ValueType::Float { size: 4 })
+                    | (ValueType::Float { size: 4 }, ValueType::Int { .. })
+                    | (ValueType::Int { .. }, ValueType::Float { size: 4 }) => {
+                        ValueType::Float { size: 4 }
+                    }
+                    _ => ValueType::DOUBLE,
+                };
+                Ok(Value::new(ty.clone(), encode(&ty, Number::Float(result))?))
+            }
+        }
+    }
+
+    fn assign(&mut self, target: Value, value: Value) -> Result<Value> {
+        let place = target
+            .place
+            .ok_or_else(|| anyhow!("left operand of assignment is not an lvalue"))?;
+        if target.ty == ValueType::Void {
+            bail!(UNKNOWN_TYPE);
+        }
+        let converted = convert(self.ctx, &value, &target.ty)?;
+        match place {
+            Place::Memory(address) => self.ctx.write_memory(address, &converted.data)?,
+            Place::Register(info) => self.ctx.write_register(info, &converted.data)?,
+        }
+        Ok(Value {
+            ty: target.ty,
+            data: converted.data,
+            place: Some(place),
+        })
+    }
+}
+
+/// Parses and evaluates an expression. Assignments write to the process.
+pub fn evaluate(text: &str, ctx: &mut dyn ExpressionContext) -> Result<Value> {
+    let expr = parse(text, &|name| {
+        builtin_type(name).is_some() || ctx.lookup_type(name).is_some()
+    })?;
+    Evaluator { ctx }.eval(&expr)
+}
+
+/// Evaluates an expression for whether it holds, as for the condition of a breakpoint
+pub fn evaluate_condition(text: &str, ctx: &mut dyn ExpressionContext) -> Result<bool> {
+    let value = evaluate(text, ctx)?;
+    truthy(ctx, &value)
+}
+
+/// The address an expression refers to, for commands such as x which take one: the value of a
+/// pointer or integer, or else where the value is stored
+pub fn value_address(value: &Value, ctx: &dyn ExpressionContext) -> Result<u64> {
+    let ty = resolve(ctx, &value.ty)?;
+    if matches!(ty, ValueType::Pointer(_) | ValueType::Int { .. }) {
+        return match number(ctx, value)? {
+            Number::Int(bits, _) => Ok(bits),
+            Number::Float(_) => unreachable!(),
+        };
+    }
+    place_address(value).ok_or_else(|| anyhow!("value does not refer to memory"))
+}
+
+pub fn type_name(ty: &ValueType, ctx: &dyn ExpressionContext) -> Result<String> {
+    Ok(match ty {
+        ValueType::Void => "void".to_string(),
+        ValueType::Bool => "bool".to_string(),
+        ValueType::Char => "char".to_string(),
+        &ValueType::Int { size, signed } => {
+            let name = match size {
+                1 => "char",
+                2 => "short",
+                4 => "int",
+                _ => "long",
+            };
+            let sign = match (size, signed) {
+                (1, true) => "signed ",
+                (_, true) => "",
+                _ => "unsigned ",
+            };
+            format!("{sign}{name}")
+        }
+        ValueType::Float { size: 4 } => "float".to_string(),
+        ValueType::Float { .. } => "double".to_string(),
+        ValueType::Pointer(pointee) => {
+            let pointee = type_name(pointee, ctx)?;
+            if pointee.ends_with('*') {
+                format!("{pointee}*")
+            } else {
+                format!("{pointee} *")
+            }
+        }
+        ValueType::Array { element, count } => format!("{} [{count}]", type_name(element, ctx)?),
+        ValueType::Function => "function".to_string(),
+        &ValueType::Debug { module, ty } => {
+            let dwarf = dwarf(ctx, module)?;
+            dwarf.type_name(&dwarf.resolve_type(ty)?)?
+        }
+    })
+}
+
+/// Renders a value. Pointers are printed along with the type pointed to, as with variables.
+pub fn format_value(value: &Value, ctx: &dyn ExpressionContext) -> Result<String> {
+    let data = &value.data;
+    let size = size_of(ctx, &value.ty)? as usize;
+    if data.len() < size && !matches!(value.ty, ValueType::Void | ValueType::Function) {
+        bail!("value of {size} bytes holds only {} bytes", data.len());
+    }
+    Ok(match &value.ty {
+        ValueType::Void => bail!(UNKNOWN_TYPE),
+        ValueType::Function => match place_address(value) {
+            Some(address) => format!("{{function}} {address:#x}"),
+            None => "{function}".to_string(),
+        },
+        ValueType::Bool => format!("{}", data[0] != 0),
+        ValueType::Char => format!("{} {:?}", data[0] as i8, data[0] as char),
+        ValueType::Int { signed: true, .. } => format!("{}", read_int(&data[..size])),
+        ValueType::Int { .. } => format!("{}", read_uint(&data[..size])),
+        ValueType::Float { size: 4 } => format!("{}", pod_read_unaligned::<f32>(&data[..4])),
+        ValueType::Float { .. } => format!("{}", pod_read_unaligned::<f64>(&data[..8])),
+        ValueType::Pointer(_) => format!(
+            "({}) {:#x}",
+            type_name(&value.ty, ctx)?,
+            read_uint(&data[..8])
+        ),
+        ValueType::Array { element, count } => {
+            let element_size = size_of(ctx, element)? as usize;
+            let mut items = Vec::new();
+            for index in 0..*count as usize {
+                let start = index * element_size;
+                let item = Value::new(
+                    (**element).clone(),
+                    data[start..start + element_size].to_vec(),
+                );
+                items.push(format_value(&item, ctx)?);
+            }
+            format!("[{}]", items.join(", "))
+        }
+        &ValueType::Debug { module, ty } => {
+            let dwarf = dwarf(ctx, module)?;
+            let ty = dwarf.resolve_type(ty)?;
+            let formatted = dwarf.format_value(&ty, data, 0)?;
+            if let TypeKind::Pointer { .. } = dwarf.strip_type(&ty)?.kind {
+                format!("({}) {formatted}", dwarf.type_name(&ty)?)
+            } else {
+                formatted
+            }
+        }
+    })
+}
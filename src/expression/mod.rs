@@ -0,0 +1,401 @@
+//! Values of the C like expressions `print`, `x`, `set var` and breakpoint conditions take,
+//! which are evaluated through [`Debugger::evaluate`](crate::Debugger::evaluate).
+
+use crate::dwarf::Dwarf;
+use crate::reginfo::RegisterInfo;
+use crate::registers::Registers;
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+
+mod eval;
+mod parse;
+
+pub use crate::dwarf::types::TypeRef;
+pub(crate) use eval::{evaluate, evaluate_condition, format_value, type_name, value_address};
+
+// The C like expressions accepted by print, x, set var and breakpoint conditions. Values are
+// typed, either with one of a handful of built in C types or with a type from the debug
+// information, so that arithmetic and printing follow the rules of the language.
+
+/// The type of a value
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValueType {
+    /// No value, as the result of an assignment to void
+    Void,
+    /// A C `_Bool` or Rust `bool`
+    Bool,
+    /// Printed along with the character
+    Char,
+    /// An integer
+    Int {
+        /// Its size in bytes
+        size: usize,
+        /// Whether it is signed
+        signed: bool,
+    },
+    /// A float or double
+    Float {
+        /// Its size in bytes
+        size: usize,
+    },
+    /// A pointer to the given type
+    Pointer(Box<ValueType>),
+    /// An array
+    Array {
+        /// The type of the elements
+        element: Box<ValueType>,
+        /// The number of elements
+        count: u64,
+    },
+    /// Code, which has an address but no value
+    Function,
+    /// A type from the debug information of a module
+    Debug {
+        /// The module, as an index into the executable and then the shared libraries
+        module: usize,
+        /// The type in the module's debug information
+        ty: TypeRef,
+    },
+}
+
+impl ValueType {
+    /// A C int
+    pub const INT: ValueType = ValueType::Int {
+        size: 4,
+        signed: true,
+    };
+    /// A C long
+    pub const LONG: ValueType = ValueType::Int {
+        size: 8,
+        signed: true,
+    };
+    /// A C unsigned long
+    pub const UNSIGNED_LONG: ValueType = ValueType::Int {
+        size: 8,
+        signed: false,
+    };
+    /// A C double
+    pub const DOUBLE: ValueType = ValueType::Float { size: 8 };
+
+    /// A pointer to this type
+    pub fn pointer_to(self) -> ValueType {
+        ValueType::Pointer(Box::new(self))
+    }
+}
+
+// The C and Rust spellings of the built in types, as used in casts
+fn builtin_type(name: &str) -> Option<ValueType> {
+    let int = |size, signed| Some(ValueType::Int { size, signed });
+    match name {
+        "void" => Some(ValueType::Void),
+        "bool" | "_Bool" => Some(ValueType::Bool),
+        "char" => Some(ValueType::Char),
+        "signed char" | "int8_t" | "i8" => int(1, true),
+        "unsigned char" | "uint8_t" | "u8" => int(1, false),
+        "short" | "short int" | "signed short" | "int16_t" | "i16" => int(2, true),
+        "unsigned short" | "unsigned short int" | "uint16_t" | "u16" => int(2, false),
+        "int" | "signed" | "signed int" | "int32_t" | "i32" => int(4, true),
+        "unsigned" | "unsigned int" | "uint32_t" | "u32" => int(4, false),
+        "long" | "long int" | "signed long" | "long long" | "long long int" | "int64_t" | "i64"
+        | "ssize_t" | "intptr_t" | "isize" => int(8, true),
+        "unsigned long" | "unsigned long int" | "unsigned long long" | "uint64_t" | "u64"
+        | "size_t" | "uintptr_t" | "usize" => int(8, false),
+        "float" | "f32" => Some(ValueType::Float { size: 4 }),
+        "double" | "f64" => Some(ValueType::DOUBLE),
+        _ => None,
+    }
+}
+
+/// Where a value lives, for values which can be assigned to or have their address taken
+#[derive(Copy, Clone, Debug)]
+pub enum Place {
+    /// Memory at an address
+    Memory(u64),
+    /// A register of the stopped thread
+    Register(&'static RegisterInfo),
+}
+
+/// A value an expression evaluated to
+#[derive(Clone, Debug)]
+pub struct Value {
+    /// Its type
+    pub ty: ValueType,
+    /// The bytes of the value, in target byte order
+    pub data: Vec<u8>,
+    /// Where the value was read from, if anywhere
+    pub place: Option<Place>,
+}
+
+impl Value {
+    /// A value which lives nowhere, such as the result of arithmetic
+    pub fn new(ty: ValueType, data: Vec<u8>) -> Self {
+        Self {
+            ty,
+            data,
+            place: None,
+        }
+    }
+}
+
+/// The values expressions name with `$` other than registers. Printed values are kept in a
+/// history as `$1`, `$2` and on, with `$` for the last, `$$` for the one before and `$$<n>` for
+/// the one n back from the last. Convenience variables such as `$base` are set by assignment,
+/// and some, such as `$_exitcode`, by the debugger.
+#[derive(Default)]
+pub struct Variables {
+    history: Vec<Value>,
+    variables: BTreeMap<String, Value>,
+}
+
+impl Variables {
+    /// Adds a value to the history, returning its number
+    pub fn record(&mut self, value: &Value) -> usize {
+        self.history.push(detached(value));
+        self.history.len()
+    }
+
+    /// A value by the name after the `$`
+    pub fn get(&self, name: &str) -> Option<Value> {
+        let back = |count: usize| {
+            let index = self.history.len().checked_sub(count + 1)?;
+            Some(self.history[index].clone())
+        };
+        if let Some(count) = name.strip_prefix('$') {
+            return back(if count.is_empty() {
+                1
+            } else {
+                count.parse().ok()?
+            });
+        }
+        if name.is_empty() {
+            return back(0);
+        }
+        if let Ok(number) = name.parse::<usize>() {
+            return self.history.get(number.checked_sub(1)?).cloned();
+        }
+        self.variables.get(name).cloned()
+    }
+
+    /// Sets a convenience variable. Values in the history cannot be changed.
+    pub fn set(&mut self, name: &str, value: &Value) -> Result<()> {
+        if name.is_empty() || name.starts_with(|c: char| c == '$' || c.is_ascii_digit()) {
+            bail!("${name} is in the value history, which cannot be assigned to");
+        }
+        self.variables.insert(name.to_string(), detached(value));
+        Ok(())
+    }
+
+    /// The convenience variables, by name
+    pub fn variables(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.variables
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+}
+
+// A copy of a value which no longer refers to a register, as the register changes when the
+// program runs. Values read from memory keep their address, so that `&$1` still works.
+fn detached(value: &Value) -> Value {
+    let place = match value.place {
+        Some(Place::Memory(address)) => Some(Place::Memory(address)),
+        _ => None,
+    };
+    Value {
+        place,
+        ..value.clone()
+    }
+}
+
+/// What expressions are evaluated against: the registers and memory of the stopped process and
+/// the variables and types of its debug information
+pub(crate) trait ExpressionContext {
+    fn registers(&self) -> &Registers;
+    fn write_register(&mut self, info: &'static RegisterInfo, data: &[u8]) -> Result<()>;
+    fn read_memory(&self, address: u64, size: usize) -> Result<Vec<u8>>;
+    fn write_memory(&mut self, address: u64, data: &[u8]) -> Result<()>;
+    /// A variable, or failing that a symbol, by name
+    fn variable(&self, name: &str) -> Result<Option<Value>>;
+    /// A type from the debug information by name
+    fn lookup_type(&self, name: &str) -> Option<ValueType>;
+    fn dwarf(&self, module: usize) -> Option<&Dwarf>;
+    /// A convenience variable or value from the history, by the name after the `$`
+    fn convenience(&self, name: &str) -> Option<Value>;
+    fn set_convenience(&mut self, name: &str, value: &Value) -> Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dwarf::Dwarf;
+    use crate::expression::{
+        evaluate, evaluate_condition, format_value, ExpressionContext, Place, Value, ValueType,
+        Variables,
+    };
+    use crate::reginfo::{lookup_register_info_by_name, RegisterInfo};
+    use crate::registers::Registers;
+    use anyhow::{anyhow, Result};
+    use std::collections::HashMap;
+
+    // Memory of 64 bytes at 0x1000 holding the values 0, 1, 2.., and a global `g` of unknown
+    // type at 0x1010
+    struct FakeContext {
+        registers: Registers,
+        memory: Vec<u8>,
+        written: HashMap<&'static str, Vec<u8>>,
+        variables: Variables,
+    }
+
+    const BASE: u64 = 0x1000;
+
+    impl FakeContext {
+        fn new() -> Self {
+            let mut registers = Registers::default();
+            registers.set_cached_u64(lookup_register_info_by_name("RSP").unwrap(), BASE + 8);
+            registers.set_cached_u64(lookup_register_info_by_name("RDI").unwrap(), 3);
+            Self {
+                registers,
+                memory: (0..64).collect(),
+                written: HashMap::new(),
+                variables: Variables::default(),
+            }
+        }
+    }
+
+    impl ExpressionContext for FakeContext {
+        fn registers(&self) -> &Registers {
+            &self.registers
+        }
+
+        fn write_register(&mut self, info: &'static RegisterInfo, data: &[u8]) -> Result<()> {
+            let mut bytes = [0; 8];
+            bytes[..data.len()].copy_from_slice(data);
+            self.registers
+                .set_cached_u64(info, u64::from_le_bytes(bytes));
+            Ok(())
+        }
+
+        fn read_memory(&self, address: u64, size: usize) -> Result<Vec<u8>> {
+            let start = address
+                .checked_sub(BASE)
+                .ok_or_else(|| anyhow!("cannot access {address:#x}"))?
+                as usize;
+            self.memory
+                .get(start..start + size)
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| anyhow!("cannot access {address:#x}"))
+        }
+
+        fn write_memory(&mut self, address: u64, data: &[u8]) -> Result<()> {
+            let start = (address - BASE) as usize;
+            self.memory[start..start + data.len()].copy_from_slice(data);
+            self.written.insert("memory", data.to_vec());
+            Ok(())
+        }
+
+        fn variable(&self, name: &str) -> Result<Option<Value>> {
+            Ok((name == "g").then(|| Value {
+                ty: ValueType::Void,
+                data: Vec::new(),
+                place: Some(Place::Memory(BASE + 0x10)),
+            }))
+        }
+
+        fn lookup_type(&self, _name: &str) -> Option<ValueType> {
+            None
+        }
+
+        fn dwarf(&self, _module: usize) -> Option<&Dwarf> {
+            None
+        }
+
+        fn convenience(&self, name: &str) -> Option<Value> {
+            self.variables.get(name)
+        }
+
+        fn set_convenience(&mut self, name: &str, value: &Value) -> Result<()> {
+            self.variables.set(name, value)
+        }
+    }
+
+    fn print(ctx: &mut FakeContext, text: &str) -> String {
+        let value = evaluate(text, ctx).unwrap();
+        format_value(&value, ctx).unwrap()
+    }
+
+    #[test]
+    fn arithmetic_follows_c_rules() {
+        let mut ctx = FakeContext::new();
+        assert_eq!(print(&mut ctx, "1 + 2 * 3"), "7");
+        assert_eq!(print(&mut ctx, "-7 / 2"), "-3");
+        assert_eq!(print(&mut ctx, "-7 % 3"), "-1");
+        assert_eq!(print(&mut ctx, "(long)1 << 40"), "1099511627776");
+        assert_eq!(print(&mut ctx, "(unsigned char)300"), "44");
+        assert_eq!(print(&mut ctx, "(unsigned)-1"), "4294967295");
+        assert_eq!(print(&mut ctx, "0u - 1 > 0"), "1");
+        assert_eq!(print(&mut ctx, "7 / 2.0"), "3.5");
+        assert_eq!(print(&mut ctx, "(int)2.75 + (float)0.5"), "2.5");
+        assert_eq!(print(&mut ctx, "'a' + 1"), "98");
+        assert_eq!(print(&mut ctx, "(char)98"), "98 'b'");
+        assert_eq!(print(&mut ctx, "!0 && (2 | 4) == 6 && ~0 == -1"), "1");
+        assert!(evaluate("1 / 0", &mut ctx).is_err());
+    }
+
+    #[test]
+    fn registers_and_memory() {
+        let mut ctx = FakeContext::new();
+        assert_eq!(print(&mut ctx, "$rdi"), "3");
+        assert_eq!(print(&mut ctx, "$rsp"), "(void *) 0x1008");
+        assert!(evaluate_condition("$rdi == 3", &mut ctx).unwrap());
+        assert!(!evaluate_condition("$rdi == 4", &mut ctx).unwrap());
+        assert_eq!(
+            print(&mut ctx, "(uint32_t*)$rsp"),
+            "(unsigned int *) 0x1008"
+        );
+        assert_eq!(print(&mut ctx, "*(uint32_t*)$rsp"), "185207048");
+        assert_eq!(print(&mut ctx, "*(unsigned char*)$rsp"), "8");
+        assert_eq!(print(&mut ctx, "((unsigned char*)$rsp)[3]"), "11");
+        assert_eq!(print(&mut ctx, "*((unsigned char*)$rsp + 2)"), "10");
+        assert_eq!(print(&mut ctx, "(u16*)$rsp - (u16*)0x1000"), "4");
+        assert!(evaluate("$nosuch", &mut ctx).is_err());
+        assert!(evaluate("*$rdi", &mut ctx).is_err());
+
+        // Symbols without debug information must be cast to a type before use
+        assert_eq!(print(&mut ctx, "&g"), "(void *) 0x1010");
+        assert_eq!(print(&mut ctx, "(unsigned char)g"), "16");
+        assert!(evaluate("g + 1", &mut ctx).is_err());
+    }
+
+    #[test]
+    fn assignment_writes_through_places() {
+        let mut ctx = FakeContext::new();
+        assert_eq!(print(&mut ctx, "$rdi = $rdi * 2"), "6");
+        assert_eq!(print(&mut ctx, "$rdi"), "6");
+
+        assert_eq!(print(&mut ctx, "*(u16*)0x1000 = 0x1234"), "4660");
+        assert_eq!(ctx.written["memory"], vec![0x34, 0x12]);
+        assert_eq!(print(&mut ctx, "*(u16*)0x1000"), "4660");
+        assert!(evaluate("1 = 2", &mut ctx).is_err());
+    }
+
+    #[test]
+    fn convenience_variables_and_history_keep_their_types() {
+        let mut ctx = FakeContext::new();
+        assert_eq!(print(&mut ctx, "$base = $sp"), "(void *) 0x1008");
+        assert_eq!(print(&mut ctx, "*(unsigned char*)($base + 1)"), "9");
+        assert_eq!(print(&mut ctx, "$fp = 0x1000"), "(void *) 0x1000");
+        assert_eq!(print(&mut ctx, "$rbp"), "(void *) 0x1000");
+
+        for text in ["(char)97", "7 / 2.0", "(u16*)$rsp"] {
+            let value = evaluate(text, &mut ctx).unwrap();
+            ctx.variables.record(&value);
+        }
+        assert_eq!(print(&mut ctx, "$1"), "97 'a'");
+        assert_eq!(print(&mut ctx, "$2 * 2"), "7");
+        assert_eq!(print(&mut ctx, "$"), "(unsigned short *) 0x1008");
+        assert_eq!(print(&mut ctx, "*$"), "2312");
+        assert_eq!(print(&mut ctx, "$$"), "3.5");
+        assert_eq!(print(&mut ctx, "$$2 + 1"), "98");
+        assert!(evaluate("$4", &mut ctx).is_err());
+        assert!(evaluate("$1 = 2", &mut ctx).is_err());
+        assert!(evaluate("$unset", &mut ctx).is_err());
+    }
+}
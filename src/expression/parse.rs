@@ -0,0 +1,501 @@
+use anyhow::{anyhow, bail, Result};
+
+// Words which can only start a type name, which is how a cast such as `(unsigned long)x` is
+// told apart from a parenthesised expression
+const TYPE_KEYWORDS: &[&str] = &[
+    "void", "bool", "_Bool", "char", "short", "int", "long", "signed", "unsigned", "float",
+    "double", "struct", "union", "enum", "class", "const", "volatile",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Integer { value: u64, unsigned: bool },
+    Float(f64),
+    Char(u8),
+    Identifier(String),
+    // `$name`
+    Dollar(String),
+    Punct(&'static str),
+}
+
+// Longer operators come first so that `<=` is not read as `<` followed by `=`
+const PUNCTUATION: &[&str] = &[
+    "->", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "!",
+    "~", "&", "|", "^", "(", ")", "[", "]", ".", "=",
+];
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum UnaryOp {
+    Negate,
+    Not,
+    Complement,
+    Deref,
+    AddressOf,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Shl,
+    Shr,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    BitAnd,
+    BitXor,
+    BitOr,
+    And,
+    Or,
+}
+
+/// A type as written in a cast, such as `unsigned int` or `struct point *`
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeName {
+    pub name: String,
+    pub pointers: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Integer {
+        value: u64,
+        unsigned: bool,
+    },
+    Float(f64),
+    Char(u8),
+    /// A variable or symbol, possibly qualified as in `ns::value`
+    Name(String),
+    /// A register, convenience variable or value from the history, such as `$rax`, `$base` or
+    /// `$1`, named without the `$`
+    Dollar(String),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Cast(TypeName, Box<Expr>),
+    Index(Box<Expr>, Box<Expr>),
+    Member(Box<Expr>, String),
+    Arrow(Box<Expr>, String),
+    Assign(Box<Expr>, Box<Expr>),
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn parse_number(text: &str) -> Result<Token> {
+    let invalid = || anyhow!("invalid number {text}");
+    let lower = text.to_ascii_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x") {
+        let digits = hex.trim_end_matches(['u', 'l']);
+        let value = u64::from_str_radix(digits, 16).map_err(|_| invalid())?;
+        return Ok(Token::Integer {
+            value,
+            unsigned: hex[digits.len()..].contains('u'),
+        });
+    }
+    if lower.contains(['.', 'e']) {
+        let digits = lower.trim_end_matches('f');
+        return Ok(Token::Float(digits.parse().map_err(|_| invalid())?));
+    }
+    let digits = lower.trim_end_matches(['u', 'l']);
+    let value = if digits.len() > 1 && digits.starts_with('0') {
+        u64::from_str_radix(&digits[1..], 8)
+    } else {
+        digits.parse()
+    };
+    Ok(Token::Integer {
+        value: value.map_err(|_| invalid())?,
+        unsigned: lower[digits.len()..].contains('u'),
+    })
+}
+
+fn parse_escape(c: char) -> Result<u8> {
+    Ok(match c {
+        'n' => b'\n',
+        't' => b'\t',
+        'r' => b'\r',
+        '0' => 0,
+        '\\' => b'\\',
+        '\'' => b'\'',
+        '"' => b'"',
+        other => bail!("unknown escape sequence \\{other}"),
+    })
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_ascii_alphanumeric()
+                    || chars[i] == '.'
+                    || (matches!(chars[i], '+' | '-')
+                        && matches!(chars[i - 1], 'e' | 'E')
+                        && !chars[start..i].iter().any(|c| matches!(c, 'x' | 'X'))))
+            {
+                i += 1;
+            }
+            let number: String = chars[start..i].iter().collect();
+            tokens.push(parse_number(&number)?);
+        } else if is_identifier_start(c) || c == '$' {
+            let start = i;
+            i += 1;
+            // `$$` and `$$<n>` count back through the value history
+            if c == '$' && chars.get(i) == Some(&'$') {
+                i += 1;
+            }
+            // Qualified names such as `variables::COUNTER` are read as a single name
+            loop {
+                while i < chars.len() && is_identifier_char(chars[i]) {
+                    i += 1;
+                }
+                if chars.get(i) == Some(&':')
+                    && chars.get(i + 1) == Some(&':')
+                    && chars.get(i + 2).is_some_and(|&c| is_identifier_start(c))
+                {
+                    i += 2;
+                    continue;
+                }
+                break;
+            }
+            let word: String = chars[start..i].iter().collect();
+            match word.strip_prefix('$') {
+                Some(name) => tokens.push(Token::Dollar(name.to_string())),
+                None => tokens.push(Token::Identifier(word)),
+            }
+        } else if c == '\'' {
+            let (value, length) = match (chars.get(i + 1), chars.get(i + 2)) {
+                (Some('\\'), Some(&escaped)) => (parse_escape(escaped)?, 4),
+                (Some(&c), _) if c.is_ascii() => (c as u8, 3),
+                _ => bail!("invalid character literal"),
+            };
+            if chars.get(i + length - 1) != Some(&'\'') {
+                bail!("unterminated character literal");
+            }
+            tokens.push(Token::Char(value));
+            i += length;
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            let punct = PUNCTUATION
+                .iter()
+                .find(|p| rest.starts_with(**p))
+                .ok_or_else(|| anyhow!("unexpected character {c:?} in expression"))?;
+            tokens.push(Token::Punct(punct));
+            i += punct.len();
+        }
+    }
+    Ok(tokens)
+}
+
+// The binary operators by precedence, loosest first
+const BINARY_LEVELS: &[&[(&str, BinaryOp)]] = &[
+    &[("||", BinaryOp::Or)],
+    &[("&&", BinaryOp::And)],
+    &[("|", BinaryOp::BitOr)],
+    &[("^", BinaryOp::BitXor)],
+    &[("&", BinaryOp::BitAnd)],
+    &[("==", BinaryOp::Eq), ("!=", BinaryOp::Ne)],
+    &[
+        ("<", BinaryOp::Lt),
+        ("<=", BinaryOp::Le),
+        (">", BinaryOp::Gt),
+        (">=", BinaryOp::Ge),
+    ],
+    &[("<<", BinaryOp::Shl), (">>", BinaryOp::Shr)],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+    &[
+        ("*", BinaryOp::Mul),
+        ("/", BinaryOp::Div),
+        ("%", BinaryOp::Rem),
+    ],
+];
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    // Whether a name is a type, for names which are not keywords
+    is_type: &'a dyn Fn(&str) -> bool,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn peek_punct(&self, punct: &str) -> bool {
+        matches!(self.peek(), Some(Token::Punct(p)) if *p == punct)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        if self.peek_punct(punct) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<()> {
+        if !self.eat(punct) {
+            bail!("expected {punct} in expression");
+        }
+        Ok(())
+    }
+
+    fn assignment(&mut self) -> Result<Expr> {
+        let target = self.binary(0)?;
+        if self.eat("=") {
+            let value = self.assignment()?;
+            return Ok(Expr::Assign(Box::new(target), Box::new(value)));
+        }
+        Ok(target)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr> {
+        if level == BINARY_LEVELS.len() {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        'operators: loop {
+            for &(punct, op) in BINARY_LEVELS[level] {
+                if self.eat(punct) {
+                    let right = self.binary(level + 1)?;
+                    left = Expr::Binary(op, Box::new(left), Box::new(right));
+                    continue 'operators;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        let op = match self.peek() {
+            Some(Token::Punct("-")) => Some(UnaryOp::Negate),
+            Some(Token::Punct("+")) => {
+                self.position += 1;
+                return self.unary();
+            }
+            Some(Token::Punct("!")) => Some(UnaryOp::Not),
+            Some(Token::Punct("~")) => Some(UnaryOp::Complement),
+            Some(Token::Punct("*")) => Some(UnaryOp::Deref),
+            Some(Token::Punct("&")) => Some(UnaryOp::AddressOf),
+            _ => None,
+        };
+        if let Some(op) = op {
+            self.position += 1;
+            return Ok(Expr::Unary(op, Box::new(self.unary()?)));
+        }
+        if self.peek_punct("(")
+            && let Some(ty) = self.cast_type()?
+        {
+            return Ok(Expr::Cast(ty, Box::new(self.unary()?)));
+        }
+        self.postfix()
+    }
+
+    // Reads `(type)` when the parenthesis starts a cast, leaving the position alone otherwise
+    fn cast_type(&mut self) -> Result<Option<TypeName>> {
+        let Some(Token::Identifier(first)) = self.tokens.get(self.position + 1) else {
+            return Ok(None);
+        };
+        if !TYPE_KEYWORDS.contains(&first.as_str()) && !(self.is_type)(first) {
+            return Ok(None);
+        }
+        self.position += 1;
+        let mut words = Vec::new();
+        while let Some(Token::Identifier(word)) = self.peek() {
+            words.push(word.clone());
+            self.position += 1;
+        }
+        let mut pointers = 0;
+        while self.eat("*") {
+            pointers += 1;
+        }
+        self.expect(")")?;
+        Ok(Some(TypeName {
+            name: words.join(" "),
+            pointers,
+        }))
+    }
+
+    fn postfix(&mut self) -> Result<Expr> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.eat("[") {
+                let index = self.assignment()?;
+                self.expect("]")?;
+                expr = Expr::Index(Box::new(expr), Box::new(index));
+            } else if self.eat(".") {
+                expr = Expr::Member(Box::new(expr), self.member_name()?);
+            } else if self.eat("->") {
+                expr = Expr::Arrow(Box::new(expr), self.member_name()?);
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    // Member names, which for tuple fields may be numbers
+    fn member_name(&mut self) -> Result<String> {
+        match self.next() {
+            Some(Token::Identifier(name)) => Ok(name),
+            Some(Token::Integer { value, .. }) => Ok(format!("__{value}")),
+            _ => bail!("expected a member name"),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::Integer { value, unsigned }) => Ok(Expr::Integer { value, unsigned }),
+            Some(Token::Float(value)) => Ok(Expr::Float(value)),
+            Some(Token::Char(value)) => Ok(Expr::Char(value)),
+            Some(Token::Identifier(name)) => Ok(Expr::Name(name)),
+            Some(Token::Dollar(name)) => Ok(Expr::Dollar(name)),
+            Some(Token::Punct("(")) => {
+                let expr = self.assignment()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(Token::Punct(punct)) => bail!("unexpected {punct} in expression"),
+            None => bail!("incomplete expression"),
+        }
+    }
+}
+
+/// Parses a C like expression. Casts to types which are not spelled with a keyword are
+/// recognised through `is_type`.
+pub fn parse(text: &str, is_type: &dyn Fn(&str) -> bool) -> Result<Expr> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        position: 0,
+        is_type,
+    };
+    let expr = parser.assignment()?;
+    if parser.position < parser.tokens.len() {
+        bail!(
+            "unexpected {:?} in expression",
+            parser.tokens[parser.position]
+        );
+    }
+    Ok(expr)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expression::parse::{parse, BinaryOp, Expr, TypeName, UnaryOp};
+
+    fn parse_c(text: &str) -> Expr {
+        parse(text, &|name| name == "uint32_t").unwrap()
+    }
+
+    fn int(value: u64) -> Box<Expr> {
+        Box::new(Expr::Integer {
+            value,
+            unsigned: false,
+        })
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        assert_eq!(
+            parse_c("1 + 2 * 3 - 4"),
+            Expr::Binary(
+                BinaryOp::Sub,
+                Box::new(Expr::Binary(
+                    BinaryOp::Add,
+                    int(1),
+                    Box::new(Expr::Binary(BinaryOp::Mul, int(2), int(3)))
+                )),
+                int(4)
+            )
+        );
+        assert_eq!(
+            parse_c("a == 1 && b"),
+            Expr::Binary(
+                BinaryOp::And,
+                Box::new(Expr::Binary(
+                    BinaryOp::Eq,
+                    Box::new(Expr::Name("a".to_string())),
+                    int(1)
+                )),
+                Box::new(Expr::Name("b".to_string()))
+            )
+        );
+        assert!(matches!(parse_c("x = y = 0x10"), Expr::Assign(_, value)
+            if matches!(*value, Expr::Assign(..))));
+    }
+
+    #[test]
+    fn casts_and_postfix_operators() {
+        assert_eq!(
+            parse_c("*(uint32_t*)$rsp"),
+            Expr::Unary(
+                UnaryOp::Deref,
+                Box::new(Expr::Cast(
+                    TypeName {
+                        name: "uint32_t".to_string(),
+                        pointers: 1
+                    },
+                    Box::new(Expr::Dollar("rsp".to_string()))
+                ))
+            )
+        );
+        assert!(matches!(
+            parse_c("(unsigned long)x"),
+            Expr::Cast(TypeName { name, pointers: 0 }, _) if name == "unsigned long"
+        ));
+        // Not a type, so a parenthesised expression
+        assert!(matches!(
+            parse_c("(x) - 1"),
+            Expr::Binary(BinaryOp::Sub, ..)
+        ));
+        assert!(matches!(
+            parse_c("&ns::shape->corners[2].x"),
+            Expr::Unary(UnaryOp::AddressOf, inner) if matches!(*inner, Expr::Member(..))
+        ));
+        assert_eq!(
+            parse_c("pair.0"),
+            Expr::Member(Box::new(Expr::Name("pair".to_string())), "__0".to_string())
+        );
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(*int(255), parse_c("0xff"));
+        assert_eq!(*int(8), parse_c("010"));
+        assert_eq!(
+            parse_c("3u"),
+            Expr::Integer {
+                value: 3,
+                unsigned: true
+            }
+        );
+        assert_eq!(parse_c("1.5e-1"), Expr::Float(0.15));
+        assert_eq!(parse_c("'\\n'"), Expr::Char(b'\n'));
+        assert!(parse("1 +", &|_| false).is_err());
+        assert!(parse("(1", &|_| false).is_err());
+        assert!(parse("1 2", &|_| false).is_err());
+        assert!(parse("0xzz", &|_| false).is_err());
+    }
+}
@@ -0,0 +1,56 @@
+//! kitt's debugging engine for x86-64 Linux, as used by the `kitt` debugger and `kitt-server`.
+//!
+//! [`Process`] launches or attaches to a program under ptrace and controls it: resuming,
+//! stepping, breakpoint sites, hardware stoppoints, memory, registers and the reasons it
+//! stopped. [`Debugger`] adds symbols, breakpoints, backtraces and variables on top of any
+//! [`Target`], which is a live [`Process`], a core file or a remote stub.
+//!
+//! ```no_run
+//! use kitt::{DebugProcess, Debugger, Process, RegisterId, lookup_register_info_by_id};
+//!
+//! let process = Process::launch("/bin/true", DebugProcess::YES)?;
+//! let mut debugger = Debugger::new(process)?;
+//! debugger.set_breakpoint("main")?;
+//! let reason = debugger.continue_execution()?;
+//! println!("{reason}");
+//! let rsp = lookup_register_info_by_id(RegisterId::RSP)?;
+//! let value = debugger.target().registers().read_as_u64(rsp)?;
+//! println!("rsp = {value:#x}");
+//! # anyhow::Ok(())
+//! ```
+//!
+//! The public modules and the items re-exported here are the stable API, which the `kitt`
+//! binaries use like any other program would. The DWARF reader, the symbol tables and the
+//! parsing of memory maps are internal, and only the types the API hands out, such as
+//! [`target::MemoryRegion`] and [`debugger::Module`], are re-exported from them.
+
+#![warn(missing_docs)]
+
+pub mod breakpoint;
+pub mod corefile;
+pub mod debugger;
+pub mod expression;
+pub mod process;
+pub mod reginfo;
+pub mod registers;
+pub mod rsp;
+pub mod syscalls;
+pub mod target;
+
+pub(crate) mod dwarf;
+mod elf;
+pub(crate) mod maps;
+pub(crate) mod module;
+mod reg_macros;
+pub(crate) mod solib;
+pub(crate) mod symbols;
+
+pub use breakpoint::Breakpoint;
+pub use debugger::Debugger;
+pub use process::{DebugProcess, Process, ProcessEvent, StopReason, SyscallStop};
+pub use reginfo::{
+    lookup_register_by_dwarf, lookup_register_info_by_id, lookup_register_info_by_name,
+    RegisterFormat, RegisterId, RegisterInfo, RegisterKind,
+};
+pub use registers::{Registers, Value};
+pub use target::Target;
@@ -1,16 +1,32 @@
-use crate::process::{DebugProcess, Process};
-use anyhow::Result;
+use crate::commands::{parse_signal, Arguments, Command, Kind, Param};
+use crate::completion::KittHelper;
+use crate::console::Console;
+use anyhow::{anyhow, bail, Result};
+use kitt::breakpoint::Breakpoint;
+use kitt::corefile::CoreFile;
+use kitt::debugger::{Debugger, VariableKind};
+use kitt::expression::ValueType;
+use kitt::process::{DebugProcess, Process, SyscallCatchPolicy, SyscallStop};
+use kitt::reginfo::{registers_of_kind, RegisterInfo, RegisterKind};
+use kitt::rsp::RemoteTarget;
+use kitt::target;
+use kitt::{corefile, expression, reginfo, syscalls};
 use nix::unistd::Pid;
 use rustyline::error::ReadlineError;
-use rustyline::history::History;
-use rustyline::DefaultEditor;
+use rustyline::history::{DefaultHistory, History};
+use rustyline::Editor;
+use serde_json::{json, Value};
 use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
 
-mod process;
-mod reginfo;
-mod registers;
-
-mod reg_macros;
+mod commands;
+mod completion;
+mod console;
+mod dap;
+mod script;
+mod user;
 
 fn attach(args: Vec<String>) -> Result<Process> {
     if args.len() == 2 && args[0] == "-p" {
@@ -25,44 +41,1217 @@ fn attach(args: Vec<String>) -> Result<Process> {
     }
 }
 
-fn handle_command(process: &mut Process, line: &str) -> Result<()> {
-    let tokens: Vec<_> = line.split_ascii_whitespace().collect();
-    let command = tokens[0];
+// Loads a core file for post-mortem debugging, with the executable given after it or found
+// from the files mapped into the process
+fn open_core(console: &mut Console, args: &[String]) -> Result<Debugger> {
+    let (path, executable) = match args {
+        [path] => (path, None),
+        [path, executable] => (path, Some(PathBuf::from(executable))),
+        _ => bail!("usage: kitt --core <core file> [<program>]"),
+    };
+    let core = CoreFile::open(Path::new(path), executable)?;
+    if !core.command().is_empty() {
+        writeln!(console, "Core was generated by `{}'.", core.command())?;
+    }
+    if let Some(signal) = core.signal() {
+        writeln!(console, "Program terminated with signal {signal}.")?;
+    }
+    let debugger = Debugger::new(core)?;
+    writeln!(console, "#0  {}", debugger.describe_address(debugger.pc()?))?;
+    Ok(debugger)
+}
+
+// Debugs a process served by a remote stub, with the local copy of the program given after the
+// address or found through the stub
+fn connect_remote(console: &mut Console, args: &[&str]) -> Result<Debugger> {
+    let (address, executable) = match args {
+        [address] => (address, None),
+        [address, executable] => (address, Some(PathBuf::from(executable))),
+        _ => bail!("usage: target remote <host:port|unix:<path>> [<program>]"),
+    };
+    let target = RemoteTarget::connect(address, executable)?;
+    writeln!(console, "Remote debugging using {address}")?;
+    let debugger = Debugger::new(target)?;
+    writeln!(console, "#0  {}", debugger.describe_address(debugger.pc()?))?;
+    Ok(debugger)
+}
 
-    if "continue".starts_with(command) {
-        process.resume()?;
-        let reason = process.wait_on_signal()?;
-        println!("process id {} {}", process.pid, reason);
+fn print_variables(console: &mut Console, debugger: &Debugger, kind: VariableKind) -> Result<()> {
+    let variables = debugger.frame_variables(kind)?;
+    if variables.is_empty() {
+        match kind {
+            VariableKind::Locals => writeln!(console, "no locals")?,
+            VariableKind::Arguments => writeln!(console, "no arguments")?,
+        }
+    }
+    let mut results = Vec::new();
+    for variable in variables {
+        let (name, value) = debugger.variable_name_and_value(&variable)?;
+        writeln!(console, "{name} = {value}")?;
+        results.push(json!({"name": name, "value": value}));
     }
+    console.set_result(json!({"variables": results}));
+    Ok(())
+}
 
+// The general purpose registers, or those named
+fn info_registers(console: &mut Console, debugger: &mut Debugger, args: &Arguments) -> Result<()> {
+    let mut infos: Vec<&RegisterInfo> = (args.all("register"))
+        .map(|name| reginfo::lookup_register_info_by_name(&name.to_uppercase()))
+        .collect::<Result<_>>()?;
+    if infos.is_empty() {
+        infos = registers_of_kind(RegisterKind::GeneralPurpose).collect();
+    }
+    let registers = debugger.target().registers();
+    let mut results = serde_json::Map::new();
+    for info in infos {
+        let name = info.name.to_lowercase();
+        let value = registers.format(info)?;
+        writeln!(console, "{name:<10}{value}")?;
+        results.insert(name, json!(value));
+    }
+    console.set_result(json!({"registers": results}));
     Ok(())
 }
 
-fn handle_command_and_report_errors(process: &mut Process, command: &str) {
-    if let Err(err) = handle_command(process, command) {
-        println!("{err}");
+fn print_shared_libraries(console: &mut Console, debugger: &Debugger) -> Result<()> {
+    let libraries = debugger.shared_libraries();
+    if libraries.is_empty() {
+        writeln!(console, "no shared libraries loaded")?;
+        return Ok(());
+    }
+    writeln!(
+        console,
+        "{:<18}  {:<18}  {:<10}  Shared Object Library",
+        "From", "To", "Syms Read"
+    )?;
+    for library in libraries {
+        let text = library.text_range().unwrap_or(0..0);
+        // As in gdb, an asterisk marks libraries without debug information
+        let symbols = if library.has_debug_info() {
+            "Yes"
+        } else {
+            "Yes (*)"
+        };
+        writeln!(
+            console,
+            "{:#018x}  {:#018x}  {symbols:<10}  {}",
+            text.start,
+            text.end,
+            library.path.display()
+        )?;
+    }
+    Ok(())
+}
+
+fn print_mappings(console: &mut Console, debugger: &Debugger) -> Result<()> {
+    writeln!(
+        console,
+        "{:>18} {:>18} {:>10} {:>10} {:>10}  Perms  objfile",
+        "Start Addr", "End Addr", "Size", "Offset", "Rss"
+    )?;
+    for region in debugger.memory_regions()? {
+        let rss = region
+            .rss
+            .map(|rss| format!("{}K", rss / 1024))
+            .unwrap_or_default();
+        writeln!(
+            console,
+            "{:>#18x} {:>#18x} {:>#10x} {:>#10x} {rss:>10}  {}   {}",
+            region.range.start,
+            region.range.end,
+            region.range.end - region.range.start,
+            region.offset,
+            region.permissions,
+            region.path
+        )?;
+    }
+    Ok(())
+}
+
+// An address given as hex, as decimal or as the name of a symbol
+fn parse_address(debugger: &Debugger, text: &str) -> Result<u64> {
+    if let Some(hex) = text.strip_prefix("0x") {
+        return Ok(u64::from_str_radix(hex, 16)?);
+    }
+    if let Ok(address) = text.parse() {
+        return Ok(address);
+    }
+    debugger
+        .symbol_address(text)
+        .ok_or_else(|| anyhow!("no symbol named {text}"))
+}
+
+fn info_symbol(console: &mut Console, debugger: &mut Debugger, args: &Arguments) -> Result<()> {
+    let address = parse_address(debugger, args.text("address")?)?;
+    match debugger.symbol_at(address) {
+        Some(symbol) => writeln!(console, "{symbol}")?,
+        None => writeln!(
+            console,
+            "No symbol matches {address:#x}, which is {}",
+            debugger.classify_address(address)?
+        )?,
+    }
+    Ok(())
+}
+
+fn info_address(console: &mut Console, debugger: &mut Debugger, args: &Arguments) -> Result<()> {
+    let location = args.text("address")?;
+    let address = parse_address(debugger, location)?;
+    let class = debugger.classify_address(address)?;
+    if location.starts_with(|c: char| c.is_ascii_digit()) {
+        writeln!(console, "{address:#x} is {class}")?;
+    } else {
+        writeln!(console, "{location} is at {address:#x}, {class}")?;
+    }
+    Ok(())
+}
+
+// Sets a breakpoint from the arguments `<location> [if <condition>]`
+fn set_breakpoint(
+    console: &mut Console,
+    debugger: &mut Debugger,
+    args: &Arguments,
+    temporary: bool,
+) -> Result<()> {
+    let location = args.text("location")?;
+    let condition = args.get("condition").map(str::to_string);
+    let breakpoint = match temporary {
+        true => debugger.set_temporary_breakpoint(location)?,
+        false => debugger.set_breakpoint(location)?,
+    };
+    let (id, kind, addresses) = (
+        breakpoint.id,
+        breakpoint.kind(),
+        breakpoint.addresses.clone(),
+    );
+    debugger.set_breakpoint_condition(id, condition)?;
+    let breakpoint = debugger.breakpoints().iter().find(|b| b.id == id);
+    if let Some(breakpoint) = breakpoint {
+        console.set_result(json!({"breakpoint": breakpoint_json(breakpoint)}));
+    }
+    if addresses.is_empty() {
+        writeln!(
+            console,
+            "{kind} {id} ({location}) pending on a future library load"
+        )?;
+    }
+    for address in addresses {
+        writeln!(
+            console,
+            "{kind} {id} at {}",
+            debugger.describe_address(address)
+        )?;
+    }
+    Ok(())
+}
+
+// A breakpoint as the JSON interpreter describes it
+fn breakpoint_json(breakpoint: &Breakpoint) -> Value {
+    let addresses: Vec<String> = (breakpoint.addresses.iter())
+        .map(|address| format!("{address:#x}"))
+        .collect();
+    json!({
+        "id": breakpoint.id,
+        "location": breakpoint.location,
+        "addresses": addresses,
+        "enabled": breakpoint.enabled,
+        "temporary": breakpoint.temporary,
+        "condition": breakpoint.condition,
+        "hit_count": breakpoint.hit_count,
+        "ignore_count": breakpoint.ignore_count,
+        "commands": breakpoint.commands,
+    })
+}
+
+fn list_breakpoints(console: &mut Console, debugger: &Debugger) -> Result<()> {
+    let breakpoints: Vec<Value> = debugger.breakpoints().iter().map(breakpoint_json).collect();
+    console.set_result(json!({"breakpoints": breakpoints}));
+    if debugger.breakpoints().is_empty() {
+        writeln!(console, "no breakpoints")?;
+    }
+    for breakpoint in debugger.breakpoints() {
+        let enabled = if breakpoint.enabled { "y" } else { "n" };
+        let temporary = if breakpoint.temporary {
+            "  (temporary)"
+        } else {
+            ""
+        };
+        writeln!(
+            console,
+            "{}  {enabled}  {}{temporary}",
+            breakpoint.id, breakpoint.location
+        )?;
+        if breakpoint.addresses.is_empty() {
+            writeln!(console, "      <PENDING>")?;
+        }
+        for &address in &breakpoint.addresses {
+            writeln!(console, "      {}", debugger.describe_address(address))?;
+        }
+        if let Some(condition) = &breakpoint.condition {
+            writeln!(console, "      stop only if {condition}")?;
+        }
+        match breakpoint.hit_count {
+            0 => {}
+            1 => writeln!(console, "      breakpoint already hit 1 time")?,
+            hits => writeln!(console, "      breakpoint already hit {hits} times")?,
+        }
+        if breakpoint.ignore_count > 0 {
+            writeln!(
+                console,
+                "      ignore next {} hits",
+                breakpoint.ignore_count
+            )?;
+        }
+        for command in &breakpoint.commands {
+            writeln!(console, "        {command}")?;
+        }
+    }
+    Ok(())
+}
+
+fn print_catchpoints(console: &mut Console, debugger: &Debugger) -> Result<()> {
+    let events = debugger.target().event_catch();
+    let caught = [
+        (events.fork, "fork"),
+        (events.vfork, "vfork"),
+        (events.clone, "clone"),
+        (events.exec, "exec"),
+        (events.exit, "exit"),
+    ];
+    for (_, name) in caught.iter().filter(|(caught, _)| *caught) {
+        writeln!(console, "{name}")?;
+    }
+    match debugger.target().syscall_catch_policy() {
+        SyscallCatchPolicy::None => {}
+        SyscallCatchPolicy::All => writeln!(console, "syscall <any syscall>")?,
+        SyscallCatchPolicy::Some(numbers) => {
+            let names: Vec<String> = numbers
+                .iter()
+                .map(|&number| match syscalls::syscall_name(number) {
+                    Some(name) => format!("{name} ({number})"),
+                    None => number.to_string(),
+                })
+                .collect();
+            writeln!(console, "syscall {}", names.join(", "))?;
+        }
+    }
+    match debugger.signal_catch() {
+        Some(signals) => {
+            let names: Vec<&str> = signals.iter().map(|signal| signal.as_str()).collect();
+            writeln!(console, "signal {}", names.join(", "))?;
+        }
+        None => writeln!(console, "signal <any signal>")?,
+    }
+    Ok(())
+}
+
+fn catch_syscalls(_: &mut Console, debugger: &mut Debugger, args: &Arguments) -> Result<()> {
+    let mut numbers = Vec::new();
+    for call in args.all("call") {
+        numbers.extend(syscalls::parse_syscalls(call)?);
+    }
+    debugger.catch_syscalls((!numbers.is_empty()).then_some(numbers))
+}
+
+fn catch_signals(_: &mut Console, debugger: &mut Debugger, args: &Arguments) -> Result<()> {
+    let signals = args
+        .all("signal")
+        .map(parse_signal)
+        .collect::<Result<Vec<_>>>()?;
+    match signals.is_empty() {
+        true => debugger.clear_signal_catch(),
+        false => debugger.catch_signals(&signals),
+    }
+    Ok(())
+}
+
+// Catches or stops catching a process event such as fork
+fn catch_event(debugger: &mut Debugger, name: &str, caught: bool) -> Result<()> {
+    let mut events = debugger.target().event_catch().clone();
+    *events
+        .caught_mut(name)
+        .ok_or_else(|| anyhow!("no catchpoint for {name}"))? = caught;
+    debugger.target_mut().set_event_catch(events)
+}
+
+fn delete_catchpoint(_: &mut Console, debugger: &mut Debugger, args: &Arguments) -> Result<()> {
+    match args.text("kind")? {
+        "syscall" => debugger.clear_syscall_catch(),
+        "signal" => {
+            debugger.clear_signal_catch();
+            Ok(())
+        }
+        name => catch_event(debugger, name, false),
+    }
+}
+
+// The format of the x command, given as /<count><format><size> with each part optional
+struct ExamineFormat {
+    count: usize,
+    format: char,
+    size: usize,
+}
+
+fn parse_examine_format(spec: &str) -> Result<ExamineFormat> {
+    let digits = spec.chars().take_while(char::is_ascii_digit).count();
+    let mut format = ExamineFormat {
+        count: if digits == 0 {
+            1
+        } else {
+            spec[..digits].parse()?
+        },
+        format: 'x',
+        size: 0,
+    };
+    for letter in spec[digits..].chars() {
+        match letter {
+            'x' | 'd' | 'u' | 'o' | 't' | 'c' | 's' => format.format = letter,
+            'b' => format.size = 1,
+            'h' => format.size = 2,
+            'w' => format.size = 4,
+            'g' => format.size = 8,
+            _ => bail!("invalid format letter {letter} for x"),
+        }
+    }
+    if format.size == 0 {
+        format.size = if format.format == 'c' { 1 } else { 4 };
+    }
+    Ok(format)
+}
+
+fn format_memory_unit(format: char, data: &[u8]) -> String {
+    let mut bytes = [0; 8];
+    bytes[..data.len()].copy_from_slice(data);
+    let bits = u64::from_le_bytes(bytes);
+    // Sign extends from the size of the unit
+    let shift = 64 - data.len() as u32 * 8;
+    match format {
+        'd' => format!("{}", ((bits << shift) as i64) >> shift),
+        'u' => format!("{bits}"),
+        'o' => format!("{bits:#o}"),
+        't' => format!("{bits:0width$b}", width = data.len() * 8),
+        'c' => format!("{} {:?}", bits as u8 as i8, bits as u8 as char),
+        _ => format!("{bits:#0width$x}", width = data.len() * 2 + 2),
+    }
+}
+
+// Prints memory starting at the address an expression refers to, in the manner of gdb's x
+fn examine_memory(
+    console: &mut Console,
+    debugger: &mut Debugger,
+    spec: &str,
+    text: &str,
+) -> Result<()> {
+    let format = parse_examine_format(spec)?;
+    let value = debugger.evaluate(text)?;
+    let mut address = debugger.value_address(&value)?;
+
+    let mut units = Vec::new();
+    if format.format == 's' {
+        let mut last = address;
+        for _ in 0..format.count {
+            let string = target::read_c_string(debugger.target(), address)?;
+            writeln!(console, "{address:#x}:	{string:?}")?;
+            units.push(json!({"address": format!("{address:#x}"), "value": string}));
+            last = address;
+            address += string.len() as u64 + 1;
+        }
+        console.set_result(json!({"units": units}));
+        return set_last_examined(debugger, ValueType::Char, last);
+    }
+
+    let per_line = if format.size <= 2 {
+        8
+    } else {
+        16 / format.size
+    };
+    let data = debugger
+        .target()
+        .read_memory(address, format.count * format.size)?;
+    for line in data.chunks(per_line * format.size) {
+        let texts: Vec<String> = line
+            .chunks(format.size)
+            .map(|unit| format_memory_unit(format.format, unit))
+            .collect();
+        writeln!(console, "{address:#x}:\t{}", texts.join("\t"))?;
+        for (index, text) in texts.into_iter().enumerate() {
+            let unit_address = address + (index * format.size) as u64;
+            units.push(json!({"address": format!("{unit_address:#x}"), "value": text}));
+        }
+        address += line.len() as u64;
+    }
+    console.set_result(json!({"units": units}));
+    let unit = ValueType::Int {
+        size: format.size,
+        signed: false,
+    };
+    set_last_examined(debugger, unit, address - format.size as u64)
+}
+
+// Sets $_ to the address of the last unit x printed, as a pointer to the unit
+fn set_last_examined(debugger: &mut Debugger, unit: ValueType, address: u64) -> Result<()> {
+    let value = expression::Value::new(unit.pointer_to(), address.to_le_bytes().to_vec());
+    debugger.variables_mut().set("_", &value)
+}
+
+fn print_backtrace(console: &mut Console, debugger: &Debugger) -> Result<()> {
+    let mut frames = Vec::new();
+    for (index, frame) in debugger.backtrace()?.iter().enumerate() {
+        writeln!(
+            console,
+            "#{index:<2} {}",
+            debugger.describe_frame(index, frame)
+        )?;
+        // Callers are named by the call, just before the return address
+        let lookup = if index == 0 {
+            frame.pc
+        } else {
+            frame.pc.saturating_sub(1)
+        };
+        frames.push(json!({
+            "level": index,
+            "pc": format!("{:#x}", frame.pc),
+            "function": debugger.function_name(lookup),
+        }));
+    }
+    console.set_result(json!({"frames": frames}));
+    Ok(())
+}
+
+fn is_continue_command(console: &Console, line: &str) -> bool {
+    let line = console.user.expand(line);
+    let invocation = commands::parse(COMMANDS, &line);
+    invocation.is_ok_and(|invocation| invocation.is_some_and(|i| i.name() == "continue"))
+}
+
+// Continues the process and reports where it stops. When a breakpoint with commands stops it
+// they are run, and a `continue` among them resumes the process again rather than nesting.
+// Commands starting with `silent` do not report the stop.
+fn continue_and_report(console: &mut Console, debugger: &mut Debugger) -> Result<()> {
+    loop {
+        let reason = debugger.continue_execution()?;
+        let commands = match reason.is_stopped() {
+            true => debugger
+                .stopped_breakpoint()
+                .map(|bp| bp.commands.clone())
+                .unwrap_or_default(),
+            false => Vec::new(),
+        };
+        let silent = commands.first().is_some_and(|command| command == "silent");
+        if !silent {
+            console.report_stop(debugger, &reason)?;
+        }
+
+        let mut resume = false;
+        for command in commands.iter().skip(silent as usize) {
+            if is_continue_command(console, command) {
+                resume = true;
+                break;
+            }
+            handle_command_and_report_errors(console, debugger, command);
+        }
+        if !resume {
+            return Ok(());
+        }
+    }
+}
+
+// The breakpoint `commands` applies to: the one given, or else the last one set
+fn breakpoint_id(debugger: &Debugger, id: Option<&str>) -> Result<usize> {
+    let id = match id {
+        Some(id) => id.parse()?,
+        None => debugger
+            .last_breakpoint_id()
+            .ok_or_else(|| anyhow!("no breakpoints"))?,
+    };
+    if !debugger.breakpoints().iter().any(|bp| bp.id == id) {
+        bail!("no breakpoint number {id}");
+    }
+    Ok(id)
+}
+
+fn run_to(console: &mut Console, debugger: &mut Debugger, args: &Arguments) -> Result<()> {
+    let advance = args.name() == "advance";
+    let reason = debugger.run_to(args.text("location")?, advance)?;
+    console.report_stop(debugger, &reason)
+}
+
+fn step_instruction(console: &mut Console, debugger: &mut Debugger, _: &Arguments) -> Result<()> {
+    let reason = debugger.step_instruction()?;
+    console.report_stop(debugger, &reason)
+}
+
+fn step_over_instruction(
+    console: &mut Console,
+    debugger: &mut Debugger,
+    _: &Arguments,
+) -> Result<()> {
+    let reason = debugger.step_over_instruction()?;
+    console.report_stop(debugger, &reason)
+}
+
+fn print_expression(
+    console: &mut Console,
+    debugger: &mut Debugger,
+    args: &Arguments,
+) -> Result<()> {
+    let value = debugger.evaluate(args.text("expression")?)?;
+    let text = debugger.format_value(&value)?;
+    let number = debugger.variables_mut().record(&value);
+    writeln!(console, "${number} = {text}")?;
+    let ty = debugger.type_name(&value.ty)?;
+    console.set_result(json!({"history": number, "value": text, "type": ty}));
+    Ok(())
+}
+
+fn print_convenience_variables(
+    console: &mut Console,
+    debugger: &mut Debugger,
+    _: &Arguments,
+) -> Result<()> {
+    let variables: Vec<(String, expression::Value)> = (debugger.variables().variables())
+        .map(|(name, value)| (name.to_string(), value.clone()))
+        .collect();
+    if variables.is_empty() {
+        writeln!(console, "No convenience variables.")?;
+    }
+    for (name, value) in variables {
+        let text = debugger
+            .format_value(&value)
+            .unwrap_or_else(|err| format!("<{err}>"));
+        writeln!(console, "${name} = {text}")?;
+    }
+    Ok(())
+}
+
+fn ignore_breakpoint(
+    console: &mut Console,
+    debugger: &mut Debugger,
+    args: &Arguments,
+) -> Result<()> {
+    let (id, count) = (args.value("id")?, args.value("count")?);
+    debugger.set_breakpoint_ignore_count(id, count)?;
+    match count {
+        0 => writeln!(console, "Will stop next time breakpoint {id} is reached.")?,
+        1 => writeln!(console, "Will ignore next crossing of breakpoint {id}.")?,
+        _ => writeln!(
+            console,
+            "Will ignore next {count} crossings of breakpoint {id}."
+        )?,
+    }
+    Ok(())
+}
+
+fn generate_core(console: &mut Console, debugger: &mut Debugger, args: &Arguments) -> Result<()> {
+    let path = match args.get("file") {
+        Some(path) => path.to_string(),
+        None => format!("core.{}", debugger.target().pid()),
+    };
+    let process = debugger
+        .target()
+        .process()
+        .ok_or_else(|| anyhow!("the program is not being run"))?;
+    corefile::write_core(process, Path::new(&path))?;
+    writeln!(console, "Saved corefile {path}")?;
+    Ok(())
+}
+
+// The help for a command, where user commands describe themselves by what they run
+fn help(console: &mut Console, _: &mut Debugger, args: &Arguments) -> Result<()> {
+    let words: Vec<&str> = args.all("command").collect();
+    if let [name] = words[..] {
+        if let Some(lines) = console.user.definition(name) {
+            let text = format!("User command, running:\n  {}\n", lines.join("\n  "));
+            return Ok(write!(console, "{text}")?);
+        }
+        let command = console.user.expand(name);
+        if command != name {
+            return Ok(writeln!(console, "Alias for \"{command}\".")?);
+        }
+    }
+    write!(console, "{}", commands::help(COMMANDS, &words)?)?;
+    if words.is_empty() {
+        list_user_commands(console)?;
+    }
+    Ok(())
+}
+
+fn list_user_commands(console: &mut Console) -> Result<()> {
+    let mut text = String::new();
+    for (name, command) in console.user.aliases() {
+        text.push_str(&format!("{name} -- Alias for \"{command}\"\n"));
+    }
+    for name in console.user.definitions() {
+        text.push_str(&format!("{name} -- User command\n"));
+    }
+    if !text.is_empty() {
+        write!(console, "\nUser commands:\n\n{text}")?;
+    }
+    Ok(())
+}
+
+fn alias(console: &mut Console, _: &mut Debugger, args: &Arguments) -> Result<()> {
+    let Some(definition) = args.get("definition") else {
+        let aliases: Vec<String> = (console.user.aliases())
+            .map(|(name, command)| format!("{name} = {command}"))
+            .collect();
+        return Ok(aliases
+            .iter()
+            .try_for_each(|alias| writeln!(console, "{alias}"))?);
+    };
+    let Some((name, command)) = definition.split_once('=') else {
+        return Err(args.usage());
+    };
+    console.user.alias(COMMANDS, name.trim(), command.trim())
+}
+
+static COMMANDS: &[Command] = &[
+    Command::new(
+        "advance",
+        "Runs to a location, or until the current function returns\n\
+         Unlike until, the location may be in another function.",
+    )
+    .params(&[Param::word("location", Kind::Location)])
+    .run(run_to),
+    Command::new(
+        "alias",
+        "Names a command line, or lists the aliases\n\
+         As in alias where = backtrace. Words after an alias are added to the line it names. \
+         Put aliases in ~/.kittrc or .kittinit to have them in every session.",
+    )
+    .params(&[Param::optional_line("definition", Kind::Word)])
+    .run(alias),
+    Command::new("backtrace", "Prints the call stack")
+        .short(&["bt"])
+        .run(|console, debugger, _| print_backtrace(console, debugger)),
+    Command::new(
+        "break",
+        "Sets and manages breakpoints\n\
+         Breakpoints are numbered in the order they are set, and `break list` shows them.",
+    )
+    .short(&["b"])
+    .group(&[
+        Command::new(
+            "set",
+            "Sets a breakpoint\n\
+             A location is a function or symbol name, or an address as 0x1234 or *0x1234. With a \
+             condition the breakpoint only stops the program when the expression is true there.",
+        )
+        .params(&[
+            Param::word("location", Kind::Location),
+            Param::optional_line("condition", Kind::Condition),
+        ])
+        .run(|console, debugger, args| set_breakpoint(console, debugger, args, false)),
+        Command::new("list", "Lists the breakpoints")
+            .run(|console, debugger, _| list_breakpoints(console, debugger)),
+        Command::new("delete", "Deletes a breakpoint")
+            .params(&[Param::word("id", Kind::Breakpoint)])
+            .run(|_, debugger, args| debugger.delete_breakpoint(args.value("id")?)),
+        Command::new("enable", "Enables a breakpoint")
+            .params(&[Param::word("id", Kind::Breakpoint)])
+            .run(|_, debugger, args| debugger.set_breakpoint_enabled(args.value("id")?, true)),
+        Command::new(
+            "disable",
+            "Disables a breakpoint, which is kept but does not stop",
+        )
+        .params(&[Param::word("id", Kind::Breakpoint)])
+        .run(|_, debugger, args| debugger.set_breakpoint_enabled(args.value("id")?, false)),
+    ]),
+    Command::new(
+        "catch",
+        "Stops the program at system calls, signals and process events",
+    )
+    .group(&[
+        Command::new(
+            "syscall",
+            "Stops at the entry and exit of system calls\n\
+                 Calls are given by name or number, or as group:<group> such as group:file. \
+                 Without any every call is caught.",
+        )
+        .params(&[Param::words("call", Kind::Syscall)])
+        .run(catch_syscalls),
+        Command::new(
+            "signal",
+            "Stops when the program gets one of the signals\n\
+                 Without any signals every signal stops the program, which is the default.",
+        )
+        .params(&[Param::words("signal", Kind::Signal)])
+        .run(catch_signals),
+        Command::new("fork", "Stops when the program forks")
+            .run(|_, debugger, _| catch_event(debugger, "fork", true)),
+        Command::new("vfork", "Stops when the program vforks")
+            .run(|_, debugger, _| catch_event(debugger, "vfork", true)),
+        Command::new("clone", "Stops when the program clones a thread or process")
+            .run(|_, debugger, _| catch_event(debugger, "clone", true)),
+        Command::new("exec", "Stops when the program executes another")
+            .run(|_, debugger, _| catch_event(debugger, "exec", true)),
+        Command::new("exit", "Stops when the program is about to exit")
+            .run(|_, debugger, _| catch_event(debugger, "exit", true)),
+        Command::new("list", "Lists what is caught")
+            .run(|console, debugger, _| print_catchpoints(console, debugger)),
+        Command::new("delete", "Stops catching a kind of stop")
+            .params(&[Param::word(
+                "kind",
+                Kind::Choice(&[
+                    "syscall", "signal", "fork", "vfork", "clone", "exec", "exit",
+                ]),
+            )])
+            .run(delete_catchpoint),
+    ]),
+    Command::new(
+        "commands",
+        "Sets commands to run when a breakpoint stops the program\n\
+         The commands follow, one per line, up to a line saying end. They apply to the last \
+         breakpoint set when no id is given. A first command of silent keeps the stop from \
+         being reported, and continue resumes the program.",
+    )
+    .params(&[Param::optional("id", Kind::Breakpoint)])
+    .run(|_, _, _| bail!("commands can only be given at the prompt or in a command file")),
+    Command::new(
+        "condition",
+        "Sets the condition of a breakpoint, or removes it when none is given",
+    )
+    .params(&[
+        Param::word("id", Kind::Breakpoint),
+        Param::optional_line("expression", Kind::Expression),
+    ])
+    .run(|_, debugger, args| {
+        let condition = args.get("expression").map(str::to_string);
+        debugger.set_breakpoint_condition(args.value("id")?, condition)
+    }),
+    Command::new("continue", "Resumes the program until it stops")
+        .short(&["c"])
+        .run(|console, debugger, _| continue_and_report(console, debugger)),
+    Command::new(
+        "define",
+        "Defines a command running other commands\n\
+         The commands follow, one per line, up to a line saying end. In them $arg0, $arg1 and on \
+         are replaced by the arguments the command is given, and $argc by how many there are. \
+         Put definitions in ~/.kittrc or .kittinit to have them in every session.",
+    )
+    .params(&[Param::word("name", Kind::Word)])
+    .run(|_, _, _| bail!("define can only be given at the prompt or in a command file")),
+    Command::new(
+        "gcore",
+        "Saves a core file of the program, to core.<pid> by default",
+    )
+    .params(&[Param::optional("file", Kind::Path)])
+    .run(generate_core),
+    Command::new("help", "Lists the commands, or describes one")
+        .short(&["h"])
+        .params(&[Param::words("command", Kind::Command)])
+        .run(help),
+    Command::new("ignore", "Ignores the next hits of a breakpoint")
+        .params(&[
+            Param::word("id", Kind::Breakpoint),
+            Param::word("count", Kind::Number),
+        ])
+        .run(ignore_breakpoint),
+    Command::new("info", "Shows information about the program")
+        .short(&["i"])
+        .group(&[
+            Command::new("address", "Shows where an address or symbol is")
+                .params(&[Param::word("address", Kind::Address)])
+                .run(info_address),
+            Command::new("args", "Prints the arguments of the current function").run(
+                |console, debugger, _| print_variables(console, debugger, VariableKind::Arguments),
+            ),
+            Command::new("breakpoints", "Lists the breakpoints, as break list does")
+                .run(|console, debugger, _| list_breakpoints(console, debugger)),
+            Command::new(
+                "locals",
+                "Prints the local variables of the current function",
+            )
+            .run(|console, debugger, _| print_variables(console, debugger, VariableKind::Locals)),
+            Command::new("proc", "Shows information about the process").group(&[Command::new(
+                "mappings",
+                "Lists the memory mappings of the process",
+            )
+            .run(|console, debugger, _| print_mappings(console, debugger))]),
+            Command::new(
+                "registers",
+                "Prints the general purpose registers, or those named",
+            )
+            .params(&[Param::words("register", Kind::Register)])
+            .run(info_registers),
+            Command::new("sharedlibrary", "Lists the shared libraries loaded")
+                .run(|console, debugger, _| print_shared_libraries(console, debugger)),
+            Command::new("symbol", "Shows the symbol at an address")
+                .params(&[Param::word("address", Kind::Address)])
+                .run(info_symbol),
+            Command::new(
+                "convenience",
+                "Lists the convenience variables and their values",
+            )
+            .run(print_convenience_variables),
+        ]),
+    Command::new("nexti", "Steps one instruction, stepping over calls")
+        .short(&["ni"])
+        .run(step_over_instruction),
+    Command::new("print", "Prints the value of an expression")
+        .short(&["p"])
+        .params(&[Param::line("expression", Kind::Expression)])
+        .run(print_expression),
+    Command::new(
+        "set",
+        "Changes the program\n\
+         Convenience variables are set with set $name = <expression>, without variable.",
+    )
+    .group(&[Command::new(
+        "variable",
+        "Assigns to a variable or convenience variable, as in `set variable x = 3`",
+    )
+    .params(&[Param::line("assignment", Kind::Expression)])
+    .run(|_, debugger, args| {
+        debugger.evaluate(args.text("assignment")?)?;
+        Ok(())
+    })]),
+    Command::new(
+        "source",
+        "Runs the commands in a file, or a Rhai script when it ends in .rhai",
+    )
+    .params(&[Param::line("file", Kind::Path)])
+    .run(|console, debugger, args| source(console, debugger, Path::new(args.text("file")?))),
+    Command::new("stepi", "Steps one instruction")
+        .short(&["si"])
+        .run(step_instruction),
+    Command::new("target", "Connects to another target").group(&[Command::new(
+        "remote",
+        "Debugs a process served by a gdb remote stub such as gdbserver or kitt-server\n\
+         The current process is left running. The program is found through the stub when no \
+         local copy is given.",
+    )
+    .params(&[
+        Param::word("address", Kind::Word),
+        Param::optional("program", Kind::Path),
+    ])
+    .run(|console, debugger, args| {
+        let mut address: Vec<&str> = args.get("address").into_iter().collect();
+        address.extend(args.get("program"));
+        let remote = connect_remote(console, &address)?;
+        // A program kitt launched would otherwise be killed along with the old debugger
+        if let Some(process) = debugger.target_mut().process_mut() {
+            process.leave_running();
+        }
+        *debugger = remote;
+        Ok(())
+    })]),
+    Command::new(
+        "tbreak",
+        "Sets a breakpoint which is deleted when it first stops the program",
+    )
+    .params(&[
+        Param::word("location", Kind::Location),
+        Param::optional_line("condition", Kind::Condition),
+    ])
+    .run(|console, debugger, args| set_breakpoint(console, debugger, args, true)),
+    Command::new(
+        "until",
+        "Runs to a location in the current function, or until it returns",
+    )
+    .short(&["u"])
+    .params(&[Param::word("location", Kind::Location)])
+    .run(run_to),
+    Command::new(
+        "x",
+        "Examines memory at the address of an expression\n\
+         The format is /<count><format><size>, each part optional. Formats are x, d, u, o, t \
+         (binary), c (characters) and s (strings), and sizes b, h, w and g for 1, 2, 4 and 8 \
+         bytes.",
+    )
+    .with_format()
+    .params(&[Param::line("expression", Kind::Expression)])
+    .run(|console, debugger, args| {
+        let format = args.format().unwrap_or_default();
+        examine_memory(console, debugger, format, args.text("expression")?)
+    }),
+];
+
+// How deeply user commands may run one another
+const MAX_USER_DEPTH: usize = 64;
+
+fn handle_command(console: &mut Console, debugger: &mut Debugger, line: &str) -> Result<()> {
+    let mut line = console.user.expand(line);
+    // As in gdb, `set $name = ...` needs no `variable`
+    if let Some(rest) = line.strip_prefix("set ")
+        && rest.trim_start().starts_with('$')
+    {
+        line = format!("set variable {rest}");
+    }
+    if let Some(lines) = console.user.lines_for(&line) {
+        let lines = lines?;
+        if console.user.depth == MAX_USER_DEPTH {
+            bail!("user commands nested more than {MAX_USER_DEPTH} deep");
+        }
+        console.user.depth += 1;
+        let result = (lines.iter()).try_for_each(|line| handle_command(console, debugger, line));
+        console.user.depth -= 1;
+        return result;
+    }
+    match commands::parse(COMMANDS, &line)? {
+        Some(invocation) => invocation.run(console, debugger),
+        None => Ok(()),
+    }
+}
+
+// A command taking the lines which follow it, up to `end`
+enum Block {
+    // The commands of a breakpoint
+    Commands(usize),
+    Define(String),
+}
+
+impl Block {
+    fn prompt(&self) -> String {
+        let lines = match self {
+            Block::Commands(id) => format!("commands for breakpoint {id}"),
+            Block::Define(name) => format!("the commands \"{name}\" runs"),
+        };
+        format!("Type {lines}, one per line. End with a line saying just \"end\".")
+    }
+
+    fn finish(
+        self,
+        console: &mut Console,
+        debugger: &mut Debugger,
+        lines: Vec<String>,
+    ) -> Result<()> {
+        match self {
+            Block::Commands(id) => debugger.set_breakpoint_commands(id, lines),
+            Block::Define(name) => console.user.define(COMMANDS, &name, lines),
+        }
+    }
+}
+
+// The block a line starts, when it is `commands` or `define`
+fn block_for(console: &Console, debugger: &Debugger, line: &str) -> Option<Result<Block>> {
+    let line = console.user.expand(line);
+    let invocation = commands::parse(COMMANDS, &line).ok()??;
+    let arguments = &invocation.arguments;
+    match invocation.name() {
+        "commands" => Some(breakpoint_id(debugger, arguments.get("id")).map(Block::Commands)),
+        "define" => Some(arguments.text("name").and_then(|name| {
+            console.user.check_definition(COMMANDS, name)?;
+            Ok(Block::Define(name.to_string()))
+        })),
+        _ => None,
+    }
+}
+
+fn handle_command_and_report_errors(console: &mut Console, debugger: &mut Debugger, command: &str) {
+    if let Err(err) = handle_command(console, debugger, command) {
+        _ = writeln!(console, "{err}");
     }
 }
 
 const HISTORY_PATH: &str = ".kitt_hist";
 
-fn repl(process: &mut Process) -> Result<()> {
-    let mut editor = DefaultEditor::new()?;
+// Runs commands given as JSON requests, one per line, answering each with a JSON response on a
+// line of its own. A request is {"id": <any>, "command": "<command line>"}, with the lines after
+// `commands` or `define` given as "lines". The response is {"type": "response", "id",
+// "success", "output", "result"}, with an "error" on failure. The output is the text the
+// command printed, and the result the same as data, or null for commands which have none:
+//   print                         {"history": <n>, "value": "<text>", "type": "<type>"}
+//   x                             {"units": [{"address": "0x...", "value": "<text>"}]}
+//   info registers                {"registers": {"<name>": "<value>"}}
+//   info locals, info args        {"variables": [{"name", "value"}]}
+//   backtrace                     {"frames": [{"level", "pc": "0x...", "function"}]}
+//   break set, tbreak             {"breakpoint": <breakpoint>}
+//   break list, info breakpoints  {"breakpoints": [<breakpoint>]}
+// A breakpoint is {"id", "location", "addresses": ["0x..."], "enabled", "temporary",
+// "condition", "hit_count", "ignore_count", "commands": ["<command line>"]}, and a function
+// which is not known is null. The commands of user commands and of breakpoints run by a request
+// leave the result of the last of them which has one. Events for library loads and unloads and
+// for stops, described in console.rs, come before the response to the request causing them.
+fn interpret_json(
+    console: &mut Console,
+    debugger: &mut Debugger,
+    input: impl BufRead,
+    output: &mut impl Write,
+) -> Result<()> {
+    let startup = console.take_output();
+    console.take_result();
+    if !startup.is_empty() {
+        writeln!(
+            output,
+            "{}",
+            json!({"type": "event", "event": "output", "output": startup})
+        )?;
+    }
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let libraries = library_paths(debugger);
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(request) => {
+                let result = run_json_request(console, debugger, &request);
+                let mut response = json!({
+                    "type": "response",
+                    "id": request["id"],
+                    "success": result.is_ok(),
+                    "output": console.take_output(),
+                    "result": console.take_result(),
+                });
+                if let Err(err) = result {
+                    response["error"] = json!(err.to_string());
+                    response["result"] = Value::Null;
+                }
+                response
+            }
+            Err(err) => json!({
+                "type": "response",
+                "id": null,
+                "success": false,
+                "output": "",
+                "result": null,
+                "error": format!("invalid request: {err}"),
+            }),
+        };
+
+        let current = library_paths(debugger);
+        for path in current.iter().filter(|path| !libraries.contains(path)) {
+            writeln!(
+                output,
+                "{}",
+                json!({"type": "event", "event": "library-loaded", "path": path})
+            )?;
+        }
+        for path in libraries.iter().filter(|path| !current.contains(path)) {
+            writeln!(
+                output,
+                "{}",
+                json!({"type": "event", "event": "library-unloaded", "path": path})
+            )?;
+        }
+        for event in console.take_events() {
+            writeln!(output, "{event}")?;
+        }
+        writeln!(output, "{response}")?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+fn library_paths(debugger: &Debugger) -> Vec<String> {
+    debugger
+        .shared_libraries()
+        .iter()
+        .map(|library| library.path.display().to_string())
+        .collect()
+}
+
+fn run_json_request(console: &mut Console, debugger: &mut Debugger, request: &Value) -> Result<()> {
+    let command = request["command"]
+        .as_str()
+        .filter(|command| !command.trim().is_empty())
+        .ok_or_else(|| anyhow!("the request has no command"))?;
+    let Some(block) = block_for(console, debugger, command) else {
+        return handle_command(console, debugger, command);
+    };
+    let block = block?;
+    let lines = (request["lines"].as_array().into_iter().flatten())
+        .map(|line| line.as_str().map(str::to_string))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| anyhow!("the lines must be strings"))?;
+    block.finish(console, debugger, lines)
+}
+
+// Reads the lines of a block, one per line up to `end`. An empty list removes the commands of a
+// breakpoint.
+fn read_block(
+    console: &mut Console,
+    debugger: &mut Debugger,
+    editor: &mut Editor<KittHelper, DefaultHistory>,
+    block: Block,
+) -> Result<()> {
+    println!("{}", block.prompt());
+    let mut commands = Vec::new();
+    loop {
+        let line = editor.readline(">")?;
+        let line = line.trim();
+        if line == "end" {
+            break;
+        }
+        if !line.is_empty() {
+            commands.push(line.to_string());
+        }
+    }
+    block.finish(console, debugger, commands)
+}
+
+// Runs the commands of a file, one per line, up to the first which fails. Blank lines and those
+// starting with # are skipped, and `commands` and `define` take the lines which follow up to
+// `end`. Files ending in .rhai are scripts instead.
+fn source(console: &mut Console, debugger: &mut Debugger, path: &Path) -> Result<()> {
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "rhai")
+    {
+        return script::run(console, debugger, path);
+    }
+    let text =
+        fs::read_to_string(path).map_err(|err| anyhow!("cannot read {}: {err}", path.display()))?;
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()));
+    while let Some((number, line)) = lines.next() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let result = match block_for(console, debugger, line) {
+            Some(block) => block.and_then(|block| {
+                let commands = lines.by_ref().map(|(_, line)| line);
+                let commands = commands.take_while(|&line| line != "end");
+                block.finish(console, debugger, commands.map(str::to_string).collect())
+            }),
+            None => handle_command(console, debugger, line),
+        };
+        result.map_err(|err| anyhow!("{}:{number}: {err}", path.display()))?;
+    }
+    Ok(())
+}
+
+// The command files run at startup: ~/.kittrc and then .kittinit in the current directory
+fn startup_files() -> Vec<PathBuf> {
+    let home = env::var_os("HOME").map(|home| Path::new(&home).join(".kittrc"));
+    let project = Some(PathBuf::from(".kittinit"));
+    [home, project]
+        .into_iter()
+        .flatten()
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+fn repl(console: &mut Console, debugger: &mut Debugger) -> Result<()> {
+    let mut editor = Editor::new()?;
+    editor.set_helper(Some(KittHelper::new(COMMANDS)));
     _ = editor.load_history(HISTORY_PATH);
 
     loop {
+        if let Some(helper) = editor.helper_mut() {
+            helper.refresh(console, debugger);
+        }
         let line = editor.readline("kitt> ");
         match line {
-            Ok(line) if line == "" => {
+            Ok(line) if line.is_empty() => {
                 let history = editor.history();
                 if !history.is_empty() {
                     let last_cmd = &history[history.len() - 1];
-                    handle_command_and_report_errors(process, last_cmd);
+                    handle_command_and_report_errors(console, debugger, last_cmd);
                 }
             }
             Ok(line) => {
                 editor.add_history_entry(&line)?;
-                handle_command_and_report_errors(process, &line);
+                match block_for(console, debugger, &line) {
+                    Some(block) => block
+                        .and_then(|block| read_block(console, debugger, &mut editor, block))
+                        .unwrap_or_else(|err| println!("{err}")),
+                    None => handle_command_and_report_errors(console, debugger, &line),
+                }
             }
             Err(ReadlineError::Interrupted) => {
                 println!("Ctrl-C");
@@ -86,17 +1275,258 @@ fn repl(process: &mut Process) -> Result<()> {
     Ok(())
 }
 
+// Runs a program printing each system call it makes along with the result, in the manner of
+// strace. Calls are printed to stderr so that they mix with the output of the program.
+fn trace(args: &[String]) -> Result<()> {
+    let usage = || anyhow!("usage: kitt trace [-e <syscall>[,<syscall>...]] -- <program> [args]");
+    let separator = args.iter().position(|arg| arg == "--").ok_or_else(usage)?;
+    let (options, command) = (&args[..separator], &args[separator + 1..]);
+    let numbers = match options {
+        [] => None,
+        [flag, calls] if flag == "-e" => {
+            let mut numbers = Vec::new();
+            for call in calls.split(',') {
+                numbers.extend(syscalls::parse_syscalls(call)?);
+            }
+            Some(numbers)
+        }
+        _ => return Err(usage()),
+    };
+    let (program, program_args) = command.split_first().ok_or_else(usage)?;
+
+    let process = Process::launch_with_args(program, program_args, DebugProcess::YES)?;
+    let mut debugger = Debugger::new(process)?;
+    debugger.catch_syscalls(numbers)?;
+    // Whether the last call printed has yet to return
+    let mut in_call = false;
+    loop {
+        let reason = debugger.continue_execution()?;
+        match reason.syscall() {
+            Some(SyscallStop::Entry { number, args }) => {
+                eprint!(
+                    "{}",
+                    syscalls::format_call(number, &args, debugger.target())
+                );
+                in_call = true;
+                continue;
+            }
+            Some(SyscallStop::Exit { number, value }) => {
+                if in_call {
+                    eprintln!(" = {}", syscalls::format_return(number, value));
+                }
+                in_call = false;
+                continue;
+            }
+            None => {}
+        }
+        if in_call {
+            eprintln!(" = ?");
+            in_call = false;
+        }
+        if let Some(code) = reason.exit_code() {
+            eprintln!("+++ exited with {code} +++");
+            return Ok(());
+        }
+        match reason.signal() {
+            Some(signal) if !reason.is_stopped() => {
+                eprintln!("+++ killed by {signal} +++");
+                return Ok(());
+            }
+            Some(signal) => eprintln!("--- {signal} ---"),
+            None => {}
+        }
+    }
+}
+
 fn main() -> Result<()> {
-    let args: Vec<_> = env::args().collect();
+    let mut args: Vec<_> = env::args().collect();
+    // Options which come before the program
+    let (mut json, mut batch, mut script, mut command_files) = (false, false, None, Vec::new());
+    while args.len() > 1 {
+        match args[1].as_str() {
+            "--interpreter=json" => json = true,
+            "--batch" => batch = true,
+            "--script" | "-x" if args.len() > 2 => {
+                let path = args.remove(2);
+                match args[1] == "-x" {
+                    true => command_files.push(PathBuf::from(path)),
+                    false => script = Some(path),
+                }
+            }
+            "--script" | "-x" => bail!("usage: kitt {} <file> <program> [args]", args[1]),
+            _ => break,
+        }
+        args.remove(1);
+    }
     if args.len() == 1 {
         println!("no arguments given");
         std::process::exit(-1);
     }
+    if args[1] == "trace" {
+        return trace(&args[2..]);
+    }
+    if args[1] == "--dap" {
+        return dap::serve_stdio();
+    }
 
-    let mut process = attach(args.into_iter().skip(1).collect())?;
-    if let Err(err) = repl(&mut process) {
+    let (mut console, json_output) = match json {
+        true => (Console::json(), Some(console::take_stdout()?)),
+        false => (Console::terminal(), None),
+    };
+    let mut debugger = if args[1] == "--core" {
+        open_core(&mut console, &args[2..])?
+    } else if args[1] == "--remote" {
+        let args: Vec<&str> = args[2..].iter().map(String::as_str).collect();
+        connect_remote(&mut console, &args)?
+    } else {
+        let process = attach(args.into_iter().skip(1).collect())?;
+        Debugger::new(process)?
+    };
+    for path in startup_files().into_iter().chain(command_files) {
+        if let Err(err) = source(&mut console, &mut debugger, &path) {
+            writeln!(console, "{err}")?;
+            if batch {
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(path) = script {
+        return script::run(&mut console, &mut debugger, Path::new(&path));
+    }
+    if batch {
+        return Ok(());
+    }
+    if let Some(mut output) = json_output {
+        let stdin = io::stdin();
+        return interpret_json(&mut console, &mut debugger, stdin.lock(), &mut output);
+    }
+    if let Err(err) = repl(&mut console, &mut debugger) {
         println!("{err}");
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::console::Console;
+    use crate::{handle_command, interpret_json, source};
+    use kitt::debugger::Debugger;
+    use kitt::process::{DebugProcess, Process};
+    use kitt::rsp::Server;
+    use nix::sys::signal::{self, Signal};
+    use nix::sys::wait;
+    use serde_json::{json, Value};
+    use std::fs;
+    use std::io::Cursor;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn command_files_run_up_to_the_first_error() {
+        let process = Process::launch("target/debug/variables", DebugProcess::YES).unwrap();
+        let mut debugger = Debugger::new(process).unwrap();
+        let path = std::env::temp_dir().join(format!("kitt-{}.txt", std::process::id()));
+        let commands = "# a recipe\n\nbreak set variables::inspect\ncommands\nsilent\ninfo args\n\
+                        end\ncontinue\nprint nosuchvar\ncontinue\n";
+        fs::write(&path, commands).unwrap();
+        let mut console = Console::json();
+        let result = source(&mut console, &mut debugger, &path);
+        fs::remove_file(&path).unwrap();
+
+        let error = result.unwrap_err().to_string();
+        assert_eq!(
+            error,
+            format!(
+                "{}:9: no symbol \"nosuchvar\" in current context",
+                path.display()
+            )
+        );
+        let breakpoint = &debugger.breakpoints()[0];
+        assert_eq!(breakpoint.commands, ["silent", "info args"]);
+        assert_eq!(breakpoint.hit_count, 1);
+        assert!(console.take_output().contains("factor = "));
+    }
+
+    #[test]
+    fn json_responses_carry_results() {
+        let process = Process::launch("target/debug/variables", DebugProcess::YES).unwrap();
+        let mut debugger = Debugger::new(process).unwrap();
+        let commands = [
+            "break set variables::inspect",
+            "continue",
+            "backtrace",
+            "info registers rip rsp",
+            "print 1 + 2",
+            "x/2xg $rsp",
+            "info breakpoints",
+            "info args",
+            "break disable 1",
+        ];
+        let input: String = (commands.iter().enumerate())
+            .map(|(id, command)| format!("{}\n", json!({"id": id, "command": command})))
+            .collect();
+        let mut output = Vec::new();
+        let mut console = Console::json();
+        interpret_json(&mut console, &mut debugger, Cursor::new(input), &mut output).unwrap();
+
+        let responses: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .filter(|message| message["type"] == "response")
+            .collect();
+        assert!(
+            responses.iter().all(|r| r["success"] == true),
+            "{responses:?}"
+        );
+        let result = |id: usize| &responses[id]["result"];
+
+        let breakpoint = &result(0)["breakpoint"];
+        assert_eq!(breakpoint["id"], 1);
+        assert_eq!(breakpoint["location"], "variables::inspect");
+        let frames = &result(2)["frames"];
+        assert_eq!(frames[0]["function"], "variables::inspect");
+        assert_eq!(frames[1]["function"], "variables::main");
+        let registers = &result(3)["registers"];
+        assert_eq!(registers["rip"], frames[0]["pc"]);
+        assert_eq!(registers["rip"], breakpoint["addresses"][0]);
+        assert_eq!(
+            *result(4),
+            json!({"history": 1, "value": "3", "type": "int"})
+        );
+        let units = result(5)["units"].as_array().unwrap();
+        assert_eq!(units.len(), 2);
+        assert_eq!(units[0]["address"], registers["rsp"]);
+        assert_eq!(result(6)["breakpoints"][0]["hit_count"], 1);
+        assert_eq!(result(7)["variables"][0]["name"], "shape");
+        assert_eq!(*result(8), Value::Null);
+    }
+
+    #[test]
+    fn target_remote_leaves_the_current_program_running() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || {
+            let (connection, _) = listener.accept().unwrap();
+            let process = Process::launch("target/debug/variables", DebugProcess::YES).unwrap();
+            let mut server = Server::new(process, false, Box::new(connection)).unwrap();
+            server.serve().unwrap();
+        });
+
+        let process = Process::launch("target/debug/run-forever", DebugProcess::YES).unwrap();
+        let mut debugger = Debugger::new(process).unwrap();
+        let pid = debugger.target().pid();
+        let mut console = Console::json();
+        let command = format!("target remote {address}");
+        handle_command(&mut console, &mut debugger, &command).unwrap();
+        assert_ne!(debugger.target().pid(), pid);
+
+        // Had it been killed, it would have been reaped too
+        assert!(signal::kill(pid, None).is_ok());
+        signal::kill(pid, Signal::SIGKILL).unwrap();
+        wait::waitpid(pid, None).unwrap();
+        drop(debugger);
+        server.join().unwrap();
+    }
+}
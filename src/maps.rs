@@ -0,0 +1,263 @@
+use anyhow::{anyhow, bail, Result};
+use nix::unistd::Pid;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::ops::Range;
+
+// The memory regions of a process as listed by /proc/<pid>/maps and /proc/<pid>/smaps
+
+/// The access a memory region allows
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Permissions {
+    /// Readable
+    pub read: bool,
+    /// Writable
+    pub write: bool,
+    /// Executable
+    pub execute: bool,
+    /// Shared mappings are written as `s` and private copy on write mappings as `p`
+    pub shared: bool,
+}
+
+impl Display for Permissions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let flag = |set: bool, c: char| if set { c } else { '-' };
+        write!(
+            f,
+            "{}{}{}{}",
+            flag(self.read, 'r'),
+            flag(self.write, 'w'),
+            flag(self.execute, 'x'),
+            if self.shared { 's' } else { 'p' }
+        )
+    }
+}
+
+/// A mapping of the process's address space
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemoryRegion {
+    /// The addresses mapped
+    pub range: Range<u64>,
+    /// The access the mapping allows
+    pub permissions: Permissions,
+    /// Offset into the mapped file
+    pub offset: u64,
+    /// The device of the mapped file, as `major:minor` in hex
+    pub device: String,
+    /// The inode of the mapped file, or 0
+    pub inode: u64,
+    /// The mapped file, a pseudo path such as `[heap]` or `[stack]`, or empty for anonymous
+    /// mappings
+    pub path: String,
+    /// Resident set size in bytes, only known when read from smaps
+    pub rss: Option<u64>,
+}
+
+impl MemoryRegion {
+    /// Whether the region maps the address
+    pub fn contains(&self, address: u64) -> bool {
+        self.range.contains(&address)
+    }
+
+    /// Whether the region maps a file, rather than anonymous or special memory
+    pub fn is_file_backed(&self) -> bool {
+        self.inode != 0 && !self.path.is_empty()
+    }
+}
+
+// Splits off the next whitespace separated field
+fn next_field<'a>(rest: &mut &'a str) -> Option<&'a str> {
+    let trimmed = rest.trim_start();
+    if trimmed.is_empty() {
+        return None;
+    }
+    let end = trimmed
+        .find(|c: char| c.is_ascii_whitespace())
+        .unwrap_or(trimmed.len());
+    *rest = &trimmed[end..];
+    Some(&trimmed[..end])
+}
+
+fn parse_hex(field: &str) -> Result<u64> {
+    u64::from_str_radix(field, 16).map_err(|err| anyhow!("invalid number {field}: {err}"))
+}
+
+// Parses a line such as `7f0c1c000000-7f0c1c021000 rw-p 00000000 00:00 0    /usr/lib/libc.so.6`
+fn parse_region(line: &str) -> Result<MemoryRegion> {
+    let mut rest = line;
+    let mut field = || next_field(&mut rest).ok_or_else(|| anyhow!("truncated mapping {line}"));
+
+    let (start, end) = field()?
+        .split_once('-')
+        .ok_or_else(|| anyhow!("invalid address range in {line}"))?;
+    let perms = field()?.as_bytes();
+    if perms.len() != 4 {
+        bail!("invalid permissions in {line}");
+    }
+    let permissions = Permissions {
+        read: perms[0] == b'r',
+        write: perms[1] == b'w',
+        execute: perms[2] == b'x',
+        shared: perms[3] == b's',
+    };
+    let offset = parse_hex(field()?)?;
+    let device = field()?.to_string();
+    let inode = field()?.parse()?;
+
+    Ok(MemoryRegion {
+        range: parse_hex(start)?..parse_hex(end)?,
+        permissions,
+        offset,
+        device,
+        inode,
+        // Paths may contain spaces, so the path is everything after the inode
+        path: rest.trim().to_string(),
+        rss: None,
+    })
+}
+
+pub fn parse_maps(text: &str) -> Result<Vec<MemoryRegion>> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(parse_region)
+        .collect()
+}
+
+/// The smaps format follows each line of maps with `Key: value` lines, of which the resident
+/// set size is kept
+pub fn parse_smaps(text: &str) -> Result<Vec<MemoryRegion>> {
+    let mut regions: Vec<MemoryRegion> = Vec::new();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let mut rest = line;
+        let first = next_field(&mut rest).unwrap_or_default();
+        if !first.ends_with(':') {
+            regions.push(parse_region(line)?);
+            continue;
+        }
+        if first == "Rss:" {
+            let region = regions
+                .last_mut()
+                .ok_or_else(|| anyhow!("smaps field before the first mapping: {line}"))?;
+            let kb: u64 = next_field(&mut rest)
+                .ok_or_else(|| anyhow!("missing value in {line}"))?
+                .parse()?;
+            region.rss = Some(kb * 1024);
+        }
+    }
+    Ok(regions)
+}
+
+pub fn read_maps(pid: Pid) -> Result<Vec<MemoryRegion>> {
+    parse_maps(&fs::read_to_string(format!("/proc/{pid}/maps"))?)
+}
+
+pub fn read_smaps(pid: Pid) -> Result<Vec<MemoryRegion>> {
+    parse_smaps(&fs::read_to_string(format!("/proc/{pid}/smaps"))?)
+}
+
+/// What an address points into
+#[derive(Clone, Debug, PartialEq)]
+pub enum AddressClass {
+    /// A section of a loaded object, such as .text or .data
+    Section {
+        /// The name of the section
+        section: String,
+        /// The object
+        path: String,
+    },
+    /// The heap grown with brk
+    Heap,
+    /// Threads are numbered from 1, the main thread first
+    Stack {
+        /// The thread whose stack it is
+        thread: usize,
+    },
+    /// A mapped file outside of any section of a loaded object
+    MappedFile {
+        /// The file
+        path: String,
+        /// The offset of the address in the file
+        offset: u64,
+    },
+    /// Regions set up by the kernel, such as `[vdso]`
+    Special(String),
+    /// Anonymous memory, as from mmap
+    Anonymous,
+    /// No mapping
+    Unmapped,
+}
+
+impl Display for AddressClass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AddressClass::Section { section, path } => write!(f, "in section {section} of {path}"),
+            AddressClass::Heap => write!(f, "in the heap"),
+            AddressClass::Stack { thread } => write!(f, "on the stack of thread {thread}"),
+            AddressClass::MappedFile { path, offset } => {
+                write!(f, "in a mapping of {path} at file offset {offset:#x}")
+            }
+            AddressClass::Special(name) => write!(f, "in {name}"),
+            AddressClass::Anonymous => write!(f, "in an anonymous mapping"),
+            AddressClass::Unmapped => write!(f, "not mapped"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::maps::{parse_maps, parse_smaps, Permissions};
+
+    const MAPS: &str = "\
+55d6862f9000-55d6862fa000 r--p 00000000 08:01 1234                       /home/user/a program
+55d6862fa000-55d6862fb000 r-xp 00001000 08:01 1234                       /home/user/a program
+55d687a8e000-55d687aaf000 rw-p 00000000 00:00 0                          [heap]
+7f0c1c000000-7f0c1c021000 rw-s 00000000 00:00 0
+7ffd2d8c1000-7ffd2d8e2000 rw-p 00000000 00:00 0                          [stack]
+";
+
+    #[test]
+    fn maps_are_parsed() {
+        let regions = parse_maps(MAPS).unwrap();
+        assert_eq!(regions.len(), 5);
+
+        let text = &regions[1];
+        assert_eq!(text.range, 0x55d6862fa000..0x55d6862fb000);
+        assert_eq!(text.permissions.to_string(), "r-xp");
+        assert_eq!(text.offset, 0x1000);
+        assert_eq!(text.device, "08:01");
+        assert_eq!(text.inode, 1234);
+        assert_eq!(text.path, "/home/user/a program");
+        assert!(text.is_file_backed() && text.contains(0x55d6862fa123));
+
+        assert_eq!(regions[2].path, "[heap]");
+        assert!(!regions[2].is_file_backed());
+        let shared = Permissions {
+            read: true,
+            write: true,
+            execute: false,
+            shared: true,
+        };
+        assert_eq!(regions[3].permissions, shared);
+        assert_eq!(regions[3].path, "");
+        assert!(regions.iter().all(|region| region.rss.is_none()));
+    }
+
+    #[test]
+    fn smaps_record_resident_size() {
+        let smaps = "\
+55d687a8e000-55d687aaf000 rw-p 00000000 00:00 0                          [heap]
+Size:                132 kB
+Rss:                   8 kB
+VmFlags: rd wr mr mw me ac
+7ffd2d8c1000-7ffd2d8e2000 rw-p 00000000 00:00 0                          [stack]
+Size:                132 kB
+Rss:                  20 kB
+";
+        let regions = parse_smaps(smaps).unwrap();
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[0].rss, Some(8 * 1024));
+        assert_eq!(regions[1].path, "[stack]");
+        assert_eq!(regions[1].rss, Some(20 * 1024));
+        assert!(parse_smaps("Rss: 4 kB\n").is_err());
+    }
+}
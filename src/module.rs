@@ -0,0 +1,63 @@
+use crate::dwarf::cfi::CallFrameInfo;
+use crate::dwarf::Dwarf;
+use crate::elf::Elf;
+use crate::symbols::SymbolTable;
+use anyhow::Result;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// An object file mapped into the process, either the executable or a shared library, together
+/// with everything kitt reads from it
+pub struct Module {
+    /// The path the object was loaded from
+    pub path: PathBuf,
+    pub(crate) elf: Rc<Elf>,
+    pub(crate) dwarf: Dwarf,
+    pub(crate) symbols: SymbolTable,
+    pub(crate) cfi: CallFrameInfo,
+    /// Difference between the runtime and link time addresses of the object
+    pub load_bias: u64,
+}
+
+impl Module {
+    pub(crate) fn load(path: impl AsRef<Path>, load_bias: u64) -> Result<Self> {
+        let elf = Rc::new(Elf::open(&path)?);
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            dwarf: Dwarf::new(elf.clone())?,
+            symbols: SymbolTable::new(&elf)?,
+            cfi: CallFrameInfo::new(elf.clone())?,
+            elf,
+            load_bias,
+        })
+    }
+
+    /// The runtime addresses the object is mapped at
+    pub fn address_range(&self) -> Range<u64> {
+        let range = self.elf.load_range();
+        range.start + self.load_bias..range.end + self.load_bias
+    }
+
+    /// Whether the object is mapped at the address
+    pub fn contains(&self, address: u64) -> bool {
+        self.address_range().contains(&address)
+    }
+
+    /// The runtime addresses of the code of the object
+    pub fn text_range(&self) -> Option<Range<u64>> {
+        let text = self.elf.section(".text")?;
+        let start = text.sh_addr + self.load_bias;
+        Some(start..start + text.sh_size)
+    }
+
+    /// Whether the object has DWARF debug information
+    pub fn has_debug_info(&self) -> bool {
+        self.elf.section(".debug_info").is_some()
+    }
+
+    /// Converts a runtime address to the link time address the debug information describes
+    pub fn file_address(&self, address: u64) -> u64 {
+        address.wrapping_sub(self.load_bias)
+    }
+}
@@ -1,32 +1,251 @@
-use crate::reginfo::{lookup_register_info_by_id, RegisterId};
+//! Processes run or attached to under ptrace.
+
+use crate::elf::struct_bytes;
+use crate::reginfo::{lookup_register_info_by_id, RegisterId, RegisterInfo};
 use crate::registers::Registers;
-use anyhow::{bail, Result};
+use crate::syscalls::syscall_name;
+use anyhow::{anyhow, bail, Result};
+use bytemuck::{pod_read_unaligned, AnyBitPattern};
+use nix::libc;
 use nix::libc::{c_long, user_fpregs_struct, user_regs_struct};
 use nix::sys::ptrace::regset;
-use nix::sys::ptrace::AddressType;
+use nix::sys::ptrace::{AddressType, Event, Options};
 use nix::sys::signal::Signal;
-use nix::sys::wait::WaitStatus;
+use nix::sys::wait::{WaitPidFlag, WaitStatus};
 use nix::sys::{ptrace, signal, wait};
 use nix::unistd;
 use nix::unistd::{ForkResult, Pid};
 use std::cmp::PartialEq;
+use std::collections::HashMap;
 use std::ffi::CString;
 use std::fmt::{Display, Formatter};
+use std::fs::{self, File, OpenOptions};
 use std::io::{pipe, Read};
 use std::io::{PipeReader, Write};
+use std::mem;
+use std::os::unix::fs::FileExt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+const INT3: u8 = 0xcc;
+// si_code of the SIGTRAP raised by an int3
+const SI_KERNEL: i32 = 0x80;
+
+/// The registers of one thread of the process
+pub struct ThreadRegisters {
+    /// The thread ID
+    pub tid: Pid,
+    /// The general purpose registers
+    pub regs: user_regs_struct,
+    /// The x87 and SSE registers
+    pub fp_regs: user_fpregs_struct,
+    /// The signal the thread is stopped by, or 0 when it was only stopped to be inspected
+    pub signal: i32,
+}
+
+/// Interrupts a running process with SIGINT from another thread, marking the signal as the
+/// debugger's own so that it is not passed on to the process
+#[derive(Clone)]
+pub struct Interrupter {
+    pid: Pid,
+    sent: Arc<AtomicBool>,
+}
+
+impl Interrupter {
+    /// Sends the SIGINT
+    pub fn interrupt(&self) -> Result<()> {
+        self.sent.store(true, Ordering::Relaxed);
+        signal::kill(self.pid, Signal::SIGINT)?;
+        Ok(())
+    }
+}
 
+/// The threads of the process other than the traced one, held stopped until this is dropped,
+/// so that they do not change registers or memory while the process is being read
+pub struct StoppedThreads {
+    // Each thread with the signal it stopped with, or None until it has been waited for
+    threads: Vec<(Pid, Option<i32>)>,
+}
+
+impl Drop for StoppedThreads {
+    fn drop(&mut self) {
+        for &(tid, signal) in &self.threads {
+            // A thread must be in its stop for the detach to succeed
+            if signal.is_none() {
+                _ = wait::waitpid(tid, Some(WaitPidFlag::__WALL));
+            }
+            _ = ptrace::detach(tid, None);
+        }
+    }
+}
+
+/// What a hardware stoppoint stops on
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StoppointMode {
+    /// Executing the instruction at the address
+    Execute,
+    /// Writing to the address
+    Write,
+    /// Reading or writing the address
+    ReadWrite,
+}
+
+/// Whether the process runs, and how it ended if it did
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum ProcessState {
+    /// Stopped under ptrace, so it can be inspected
     Stopped,
+    /// Resumed and not yet waited on
     Running,
+    /// Exited with a code
     Exited,
+    /// Killed by a signal
     Terminated,
+    /// The program could not be executed
     FailedToLaunch,
 }
 
+/// A stop at the entry to or exit from a system call, reported with PTRACE_O_TRACESYSGOOD
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SyscallStop {
+    /// The arguments as passed in RDI, RSI, RDX, R10, R8 and R9
+    Entry {
+        /// The system call number, from ORIG_RAX
+        number: u64,
+        /// The arguments
+        args: [u64; 6],
+    },
+    /// Negative values in -4095..0 are errno values
+    Exit {
+        /// The system call number, from ORIG_RAX
+        number: u64,
+        /// The return value, from RAX
+        value: i64,
+    },
+}
+
+impl SyscallStop {
+    /// The number of the system call stopped at
+    pub fn number(&self) -> u64 {
+        match *self {
+            SyscallStop::Entry { number, .. } | SyscallStop::Exit { number, .. } => number,
+        }
+    }
+}
+
+/// Which system calls resuming with PTRACE_SYSCALL stops at
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum SyscallCatchPolicy {
+    /// No system calls, so the process resumes with PTRACE_CONT
+    #[default]
+    None,
+    /// The system calls of the given numbers
+    Some(Vec<u64>),
+    /// Every system call
+    All,
+}
+
+impl SyscallCatchPolicy {
+    /// Whether the system call of the given number is caught
+    pub fn catches(&self, number: u64) -> bool {
+        match self {
+            SyscallCatchPolicy::None => false,
+            SyscallCatchPolicy::Some(numbers) => numbers.contains(&number),
+            SyscallCatchPolicy::All => true,
+        }
+    }
+}
+
+/// A change in the life of the process, reported through the ptrace event options
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ProcessEvent {
+    /// The new process or thread, which kitt detaches from
+    Fork(Pid),
+    /// A vfork, with the new process
+    Vfork(Pid),
+    /// A new thread
+    Clone(Pid),
+    /// The process replaced its program with execve
+    Exec,
+    /// The process is about to exit, with the wait status it will exit with. Its registers and
+    /// memory can still be read.
+    Exit(i32),
+}
+
+impl Display for ProcessEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProcessEvent::Fork(child) => write!(f, "fork of process {child}"),
+            ProcessEvent::Vfork(child) => write!(f, "vfork of process {child}"),
+            ProcessEvent::Clone(thread) => write!(f, "clone of thread {thread}"),
+            ProcessEvent::Exec => write!(f, "exec"),
+            ProcessEvent::Exit(status) if libc::WIFSIGNALED(*status) => {
+                let signal = Signal::try_from(libc::WTERMSIG(*status));
+                match signal {
+                    Ok(signal) => write!(f, "exit by signal {signal}"),
+                    Err(_) => write!(f, "exit by signal {}", libc::WTERMSIG(*status)),
+                }
+            }
+            ProcessEvent::Exit(status) => {
+                write!(f, "exit with code {}", libc::WEXITSTATUS(*status))
+            }
+        }
+    }
+}
+
+/// The process events which stop the process
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EventCatch {
+    /// Stops at fork
+    pub fork: bool,
+    /// Stops at vfork
+    pub vfork: bool,
+    /// Stops when a thread is created
+    pub clone: bool,
+    /// Stops at a successful execve
+    pub exec: bool,
+    /// Stops just before the process exits
+    pub exit: bool,
+}
+
+impl EventCatch {
+    /// Whether the event of the given name is caught, as a flag to set
+    pub fn caught_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "fork" => Some(&mut self.fork),
+            "vfork" => Some(&mut self.vfork),
+            "clone" => Some(&mut self.clone),
+            "exec" => Some(&mut self.exec),
+            "exit" => Some(&mut self.exit),
+            _ => None,
+        }
+    }
+
+    fn options(&self) -> Options {
+        let mut options = Options::PTRACE_O_TRACESYSGOOD;
+        let events = [
+            (self.fork, Options::PTRACE_O_TRACEFORK),
+            (self.vfork, Options::PTRACE_O_TRACEVFORK),
+            (self.clone, Options::PTRACE_O_TRACECLONE),
+            (self.exec, Options::PTRACE_O_TRACEEXEC),
+            (self.exit, Options::PTRACE_O_TRACEEXIT),
+        ];
+        for (caught, option) in events {
+            if caught {
+                options |= option;
+            }
+        }
+        options
+    }
+}
+
+#[derive(Debug)]
 enum StopCause {
     Signal(Signal),
     Code(i32),
+    Syscall(SyscallStop),
+    Event(ProcessEvent),
 }
 
 impl Display for StopCause {
@@ -34,16 +253,28 @@ impl Display for StopCause {
         match self {
             StopCause::Signal(signal) => write!(f, "signal {signal}"),
             StopCause::Code(code) => write!(f, "exit code {code}"),
+            StopCause::Syscall(stop) => {
+                let name = syscall_name(stop.number()).unwrap_or("unknown");
+                match stop {
+                    SyscallStop::Entry { .. } => write!(f, "entry to syscall {name}"),
+                    SyscallStop::Exit { .. } => write!(f, "exit from syscall {name}"),
+                }
+            }
+            StopCause::Event(event) => write!(f, "{event}"),
         }
     }
 }
 
+/// Why the process last stopped, or how it ended
+#[derive(Debug)]
 pub struct StopReason {
     process_state: ProcessState,
     stop_cause: StopCause,
 }
 
 impl StopReason {
+    /// The reason for a status returned by waitpid, which must be an exit, a termination or a
+    /// signal stop
     pub fn new(wait_status: WaitStatus) -> Self {
         match wait_status {
             WaitStatus::Exited(_, code) => Self {
@@ -63,6 +294,45 @@ impl StopReason {
     }
 }
 
+impl StopReason {
+    /// Whether the process is stopped, rather than gone
+    pub fn is_stopped(&self) -> bool {
+        self.process_state == ProcessState::Stopped
+    }
+
+    /// The signal which stopped or terminated the process
+    pub fn signal(&self) -> Option<Signal> {
+        match self.stop_cause {
+            StopCause::Signal(signal) => Some(signal),
+            _ => None,
+        }
+    }
+
+    /// The system call the process stopped at the entry to or exit from
+    pub fn syscall(&self) -> Option<SyscallStop> {
+        match self.stop_cause {
+            StopCause::Syscall(stop) => Some(stop),
+            _ => None,
+        }
+    }
+
+    /// The process event the process stopped for
+    pub fn event(&self) -> Option<ProcessEvent> {
+        match self.stop_cause {
+            StopCause::Event(event) => Some(event),
+            _ => None,
+        }
+    }
+
+    /// The code the process exited with
+    pub fn exit_code(&self) -> Option<i32> {
+        match self.stop_cause {
+            StopCause::Code(code) if self.process_state == ProcessState::Exited => Some(code),
+            _ => None,
+        }
+    }
+}
+
 impl Display for StopReason {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self.process_state {
@@ -74,18 +344,24 @@ impl Display for StopReason {
     }
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(PartialEq)]
 enum TerminateOnEnd {
     YES,
     NO,
 }
 
+/// Whether a launched process is traced, or only run
+#[allow(clippy::upper_case_acronyms)]
 #[derive(PartialEq, Copy, Clone)]
 pub enum DebugProcess {
+    /// Traced, stopped before its first instruction
     YES,
+    /// Run untraced
     NO,
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(PartialEq, Debug)]
 enum IsAttached {
     YES,
@@ -101,12 +377,24 @@ impl From<DebugProcess> for IsAttached {
     }
 }
 
+/// A process under kitt's control, launched by it or attached to. A process kitt launched is
+/// killed when dropped, unless it was left running; one attached to is detached from.
 pub struct Process {
     pub(crate) pid: Pid,
     state: ProcessState,
     terminate_on_end: TerminateOnEnd,
     is_attached: IsAttached,
     registers: Registers,
+    // The original bytes under the int3 instructions inserted for breakpoints, by address
+    breakpoint_sites: HashMap<u64, u8>,
+    syscall_catch_policy: SyscallCatchPolicy,
+    event_catch: EventCatch,
+    // Syscall stops come in pairs, so the next one after an entry is the exit
+    expecting_syscall_exit: bool,
+    // A signal which stopped the process and is delivered to it when it resumes
+    pending_signal: Option<Signal>,
+    // Whether an Interrupter sent a SIGINT which has not stopped the process yet
+    interrupt_sent: Arc<AtomicBool>,
 }
 
 fn read_from_pipe(mut r: PipeReader) -> Result<String> {
@@ -123,10 +411,27 @@ impl Process {
             terminate_on_end,
             is_attached,
             registers: Default::default(),
+            breakpoint_sites: HashMap::new(),
+            syscall_catch_policy: SyscallCatchPolicy::None,
+            event_catch: EventCatch::default(),
+            expecting_syscall_exit: false,
+            pending_signal: None,
+            interrupt_sent: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    /// Runs the program at `path` without arguments
     pub fn launch(path: &str, debug_process: DebugProcess) -> Result<Self> {
+        Self::launch_with_args(path, &[], debug_process)
+    }
+
+    /// Runs the program at `path` with `args`, stopping it before its first instruction when
+    /// traced
+    pub fn launch_with_args(
+        path: &str,
+        args: &[String],
+        debug_process: DebugProcess,
+    ) -> Result<Self> {
         // O_CLOEXEC is set by `pipe_inner`
         let (reader, mut writer) = pipe()?;
         match unsafe { unistd::fork()? } {
@@ -143,6 +448,7 @@ impl Process {
 
                 if debug_process == DebugProcess::YES {
                     proc.wait_on_signal()?;
+                    ptrace::setoptions(proc.pid, proc.event_catch.options())?;
                 }
                 Ok(proc)
             }
@@ -155,7 +461,11 @@ impl Process {
                 }
 
                 let program = CString::new(path)?;
-                let result = unistd::execvp(&program, &[&program]);
+                let mut argv = vec![program.clone()];
+                for arg in args {
+                    argv.push(CString::new(arg.as_str())?);
+                }
+                let result = unistd::execvp(&program, &argv);
                 // If we reach here, it is because execvp failed. The result is guaranteed
                 // to contain an error.
                 write!(writer, "{}", result.err().unwrap())?;
@@ -165,43 +475,307 @@ impl Process {
         }
     }
 
+    /// Attaches to a running process, which stops it
     pub fn attach(pid: Pid) -> Result<Self> {
         // Calls PTRACE_ATTACH
         ptrace::attach(pid)?;
         let mut proc = Process::new(pid, TerminateOnEnd::NO, IsAttached::YES);
         proc.wait_on_signal()?;
+        ptrace::setoptions(pid, proc.event_catch.options())?;
         Ok(proc)
     }
 
-    // Resume the traced process with PTRACE_CONT
+    /// Resume the traced process with PTRACE_CONT, or with PTRACE_SYSCALL when system calls are
+    /// being caught
     pub fn resume(&mut self) -> Result<()> {
-        ptrace::cont(self.pid, None)?;
+        if self.breakpoint_sites.contains_key(&self.pc()?) {
+            self.step_over_breakpoint()?;
+        }
+        let signal = self.pending_signal.take();
+        if self.syscall_catch_policy == SyscallCatchPolicy::None {
+            self.expecting_syscall_exit = false;
+            ptrace::cont(self.pid, signal)?;
+        } else {
+            ptrace::syscall(self.pid, signal)?;
+        }
         self.state = ProcessState::Running;
         Ok(())
     }
 
-    // Waits on the pid. waitpid will block until the status of the watched process changes.
-    // The return value contains information about what changes were observed.
+    /// Something to interrupt the process with while another thread waits on it
+    pub fn interrupter(&self) -> Interrupter {
+        Interrupter {
+            pid: self.pid,
+            sent: self.interrupt_sent.clone(),
+        }
+    }
+
+    /// Replaces the signal to deliver when the process next resumes
+    pub fn set_pending_signal(&mut self, signal: Option<Signal>) {
+        self.pending_signal = signal;
+    }
+
+    /// Kills the process with SIGKILL and reaps it
+    pub fn kill(&mut self) -> Result<()> {
+        signal::kill(self.pid, Signal::SIGKILL)?;
+        wait::waitpid(self.pid, None)?;
+        self.state = ProcessState::Terminated;
+        Ok(())
+    }
+
+    /// Lets the process carry on once it is detached from, even if kitt launched it
+    pub fn leave_running(&mut self) {
+        self.terminate_on_end = TerminateOnEnd::NO;
+    }
+
+    /// Which system calls stop the process
+    pub fn syscall_catch_policy(&self) -> &SyscallCatchPolicy {
+        &self.syscall_catch_policy
+    }
+
+    /// Sets which system calls stop the process, from when it next resumes
+    pub fn set_syscall_catch_policy(&mut self, policy: SyscallCatchPolicy) {
+        self.syscall_catch_policy = policy;
+    }
+
+    /// Which process events stop the process
+    pub fn event_catch(&self) -> &EventCatch {
+        &self.event_catch
+    }
+
+    /// Sets which process events stop the process
+    pub fn set_event_catch(&mut self, events: EventCatch) -> Result<()> {
+        ptrace::setoptions(self.pid, events.options())?;
+        self.event_catch = events;
+        Ok(())
+    }
+
+    /// Waits on the pid. waitpid will block until the status of the watched process changes.
+    /// The return value contains information about what changes were observed.
     pub fn wait_on_signal(&mut self) -> Result<StopReason> {
         let wait_result = wait::waitpid(self.pid, None)?;
+        if let WaitStatus::PtraceSyscall(_) = wait_result {
+            self.state = ProcessState::Stopped;
+            self.read_all_registers()?;
+            return Ok(StopReason {
+                process_state: ProcessState::Stopped,
+                stop_cause: StopCause::Syscall(self.syscall_stop()?),
+            });
+        }
+
+        if let WaitStatus::PtraceEvent(_, _, event) = wait_result {
+            self.state = ProcessState::Stopped;
+            self.read_all_registers()?;
+            return Ok(StopReason {
+                process_state: ProcessState::Stopped,
+                stop_cause: StopCause::Event(self.process_event(event)?),
+            });
+        }
+
         let stop_reason = StopReason::new(wait_result);
         self.state = stop_reason.process_state;
+        // Signals the debugger uses itself are not passed on to the process, which includes a
+        // SIGINT only when an Interrupter sent it
+        let interrupt_sent = &self.interrupt_sent;
+        self.pending_signal = stop_reason
+            .signal()
+            .filter(|_| stop_reason.is_stopped())
+            .filter(|signal| ![Signal::SIGTRAP, Signal::SIGSTOP].contains(signal))
+            .filter(|&signal| {
+                signal != Signal::SIGINT || !interrupt_sent.swap(false, Ordering::Relaxed)
+            });
 
         if self.is_attached == IsAttached::YES && self.state == ProcessState::Stopped {
             self.read_all_registers()?;
+            self.rewind_past_breakpoint(&wait_result)?;
         }
 
         Ok(stop_reason)
     }
 
+    fn process_event(&mut self, event: i32) -> Result<ProcessEvent> {
+        let message = ptrace::getevent(self.pid)?;
+        let new_pid = Pid::from_raw(message as i32);
+        Ok(match event {
+            e if e == Event::PTRACE_EVENT_FORK as i32 => {
+                self.detach_child(new_pid, true)?;
+                ProcessEvent::Fork(new_pid)
+            }
+            e if e == Event::PTRACE_EVENT_VFORK as i32 => {
+                self.detach_child(new_pid, false)?;
+                ProcessEvent::Vfork(new_pid)
+            }
+            e if e == Event::PTRACE_EVENT_CLONE as i32 => {
+                self.detach_child(new_pid, false)?;
+                ProcessEvent::Clone(new_pid)
+            }
+            e if e == Event::PTRACE_EVENT_EXEC as i32 => {
+                // The old image is gone along with the int3s written into it
+                self.breakpoint_sites.clear();
+                self.expecting_syscall_exit = false;
+                ProcessEvent::Exec
+            }
+            e if e == Event::PTRACE_EVENT_EXIT as i32 => ProcessEvent::Exit(message as i32),
+            _ => bail!("unexpected ptrace event {event}"),
+        })
+    }
+
+    // kitt debugs a single process, so new processes and threads are let go. A forked child
+    // has its own copy of the memory, which would trap on the breakpoint sites once no longer
+    // traced, so the original bytes are put back in it first.
+    fn detach_child(&self, child: Pid, restore_sites: bool) -> Result<()> {
+        wait::waitpid(child, Some(WaitPidFlag::__WALL))?;
+        if restore_sites {
+            for (&address, &saved) in &self.breakpoint_sites {
+                let word = ptrace::read(child, address as AddressType)?;
+                let word = (word & !0xff) | saved as c_long;
+                ptrace::write(child, address as AddressType, word)?;
+            }
+        }
+        ptrace::detach(child, None)?;
+        Ok(())
+    }
+
+    // Decodes a syscall stop from the registers, which have just been read
+    fn syscall_stop(&mut self) -> Result<SyscallStop> {
+        let read =
+            |id| -> Result<u64> { self.registers.read_as_u64(lookup_register_info_by_id(id)?) };
+        let number = read(RegisterId::ORIG_RAX)?;
+        let stop = if self.expecting_syscall_exit {
+            SyscallStop::Exit {
+                number,
+                value: read(RegisterId::RAX)? as i64,
+            }
+        } else {
+            let mut args = [0; 6];
+            let registers = [
+                RegisterId::RDI,
+                RegisterId::RSI,
+                RegisterId::RDX,
+                RegisterId::R10,
+                RegisterId::R8,
+                RegisterId::R9,
+            ];
+            for (arg, id) in args.iter_mut().zip(registers) {
+                *arg = read(id)?;
+            }
+            SyscallStop::Entry { number, args }
+        };
+        self.expecting_syscall_exit = !self.expecting_syscall_exit;
+        Ok(stop)
+    }
+
+    // After an int3 of a breakpoint traps, the pc points just past it. Moving it back to the
+    // breakpoint address makes the stop look like it happened before the instruction there.
+    fn rewind_past_breakpoint(&mut self, wait_status: &WaitStatus) -> Result<()> {
+        let WaitStatus::Stopped(_, Signal::SIGTRAP) = wait_status else {
+            return Ok(());
+        };
+        let address = self.pc()?.wrapping_sub(1);
+        if self.breakpoint_sites.contains_key(&address)
+            && ptrace::getsiginfo(self.pid)?.si_code == SI_KERNEL
+        {
+            self.set_pc(address)?;
+        }
+        Ok(())
+    }
+
+    // Executes the instruction under the breakpoint at the pc with the original byte restored,
+    // then puts the int3 back
+    fn step_over_breakpoint(&mut self) -> Result<()> {
+        let address = self.pc()?;
+        let saved = self.breakpoint_sites[&address];
+        self.write_memory(address, &[saved])?;
+        ptrace::step(self.pid, None)?;
+        let status = wait::waitpid(self.pid, None)?;
+        if let WaitStatus::Stopped(..) = status {
+            self.write_memory(address, &[INT3])?;
+            self.read_all_registers()?;
+        } else {
+            bail!("process ended while stepping over the breakpoint at {address:#x}");
+        }
+        Ok(())
+    }
+
+    /// Executes a single instruction, which when the pc is at a breakpoint site is the one the
+    /// int3 replaced
+    pub fn step_instruction(&mut self) -> Result<StopReason> {
+        let address = self.pc()?;
+        let saved = self.breakpoint_sites.get(&address).copied();
+        if let Some(saved) = saved {
+            self.write_memory(address, &[saved])?;
+        }
+        ptrace::step(self.pid, self.pending_signal.take())?;
+        self.state = ProcessState::Running;
+        let reason = self.wait_on_signal()?;
+        if saved.is_some() && reason.is_stopped() {
+            self.write_memory(address, &[INT3])?;
+        }
+        Ok(reason)
+    }
+
+    /// The program counter, from the registers read at the last stop
+    pub fn pc(&self) -> Result<u64> {
+        self.registers
+            .read_as_u64(lookup_register_info_by_id(RegisterId::RIP)?)
+    }
+
+    /// Moves the program counter
+    pub fn set_pc(&mut self, pc: u64) -> Result<()> {
+        let mut user = self.registers.user_data();
+        user.regs.rip = pc;
+        self.write_gprs(user.regs)?;
+        self.registers.set_user_data(user);
+        Ok(())
+    }
+
+    /// The siginfo of the signal the process is stopped by, as the kernel lays it out
+    pub fn siginfo(&self) -> Result<Vec<u8>> {
+        Ok(struct_bytes(&ptrace::getsiginfo(self.pid)?))
+    }
+
+    /// Inserts an int3 at the address, saving the byte it replaces. Adding a site which already
+    /// exists does nothing.
+    pub fn add_breakpoint_site(&mut self, address: u64) -> Result<()> {
+        if self.breakpoint_sites.contains_key(&address) {
+            return Ok(());
+        }
+        let saved = self.read_memory(address, 1)?[0];
+        self.write_memory(address, &[INT3])?;
+        self.breakpoint_sites.insert(address, saved);
+        Ok(())
+    }
+
+    /// Takes out the int3 at the address, restoring the saved byte. Removing a site which does
+    /// not exist does nothing.
+    pub fn remove_breakpoint_site(&mut self, address: u64) -> Result<()> {
+        if let Some(saved) = self.breakpoint_sites.remove(&address) {
+            self.write_memory(address, &[saved])?;
+        }
+        Ok(())
+    }
+
+    /// Drops a site without restoring the original byte, for code which has been unmapped
+    pub fn forget_breakpoint_site(&mut self, address: u64) {
+        self.breakpoint_sites.remove(&address);
+    }
+
+    /// Whether there is a breakpoint site at the address
+    pub fn has_breakpoint_site(&self, address: u64) -> bool {
+        self.breakpoint_sites.contains_key(&address)
+    }
+
+    /// Reads the general purpose registers from the process
     pub fn read_registers(&mut self) -> Result<user_regs_struct> {
         Ok(ptrace::getregs(self.pid)?)
     }
 
+    /// Reads the x87 and SSE registers from the process
     pub fn read_fp_registers(&mut self) -> Result<user_fpregs_struct> {
         Ok(ptrace::getregset::<regset::NT_PRFPREG>(self.pid)?)
     }
 
+    /// Reads debug register DR`index` from the process
     pub fn read_debug_register(&mut self, index: u8) -> Result<u64> {
         let register_id = RegisterId::debug_register(index);
         let info = lookup_register_info_by_id(register_id)?;
@@ -209,6 +783,7 @@ impl Process {
         Ok(word as u64)
     }
 
+    /// Refreshes [`Process::registers`] from the process
     pub fn read_all_registers(&mut self) -> Result<()> {
         let mut u = self.registers.user_data();
 
@@ -222,20 +797,249 @@ impl Process {
         Ok(())
     }
 
+    /// Sets a hardware breakpoint or watchpoint in a free debug register, returning the index of
+    /// the register
+    pub fn set_hardware_stoppoint(
+        &mut self,
+        address: u64,
+        mode: StoppointMode,
+        size: usize,
+    ) -> Result<usize> {
+        let control = self.read_debug_register(7)?;
+        let Some(index) = (0..4).find(|index| control & (0b11 << (index * 2)) == 0) else {
+            bail!("all of the debug registers are in use");
+        };
+        // DR7 holds two enable bits for each register, and from bit 16 the kind of access and
+        // the length in four bits each. Instructions are always given a length of one.
+        let access = match mode {
+            StoppointMode::Execute => 0b00,
+            StoppointMode::Write => 0b01,
+            StoppointMode::ReadWrite => 0b11,
+        };
+        let length = match (mode, size) {
+            (StoppointMode::Execute, _) | (_, 1) => 0b00,
+            (_, 2) => 0b01,
+            (_, 4) => 0b11,
+            (_, 8) => 0b10,
+            _ => bail!("watchpoints must be 1, 2, 4 or 8 bytes long"),
+        };
+        if mode != StoppointMode::Execute && !address.is_multiple_of(size as u64) {
+            bail!("watchpoint at {address:#x} is not aligned to its size of {size}");
+        }
+        let register = lookup_register_info_by_id(RegisterId::debug_register(index as u8))?;
+        self.write_user_area(register.offset, address)?;
+        let shift = 16 + index * 4;
+        let control =
+            (control & !(0b1111 << shift)) | ((access | length << 2) << shift) | (1 << (index * 2));
+        self.write_debug_register(7, control)?;
+        Ok(index)
+    }
+
+    /// Disables the hardware stoppoint in debug register DR`index`
+    pub fn clear_hardware_stoppoint(&mut self, index: usize) -> Result<()> {
+        let control = self.read_debug_register(7)?;
+        let control = control & !(0b11 << (index * 2)) & !(0b1111 << (16 + index * 4));
+        self.write_debug_register(7, control)
+    }
+
+    /// The debug registers whose stoppoints caused the last stop, as flagged in DR6. The flags
+    /// are cleared, since the processor never clears them itself.
+    pub fn triggered_hardware_stoppoints(&mut self) -> Result<Vec<usize>> {
+        let status = self.read_debug_register(6)?;
+        self.write_debug_register(6, 0)?;
+        Ok((0..4).filter(|index| status & (1 << index) != 0).collect())
+    }
+
+    fn write_debug_register(&self, index: u8, value: u64) -> Result<()> {
+        let register = lookup_register_info_by_id(RegisterId::debug_register(index))?;
+        self.write_user_area(register.offset, value)
+    }
+
+    /// Writes a word of the process's `struct user` at a byte offset
     pub fn write_user_area(&self, offset: usize, pointer: u64) -> Result<()> {
         ptrace::write_user(self.pid, offset as isize as AddressType, pointer as c_long)?;
         Ok(())
     }
 
+    /// Writes the x87 and SSE registers to the process
     pub fn write_fprs(&self, f: user_fpregs_struct) -> Result<()> {
         ptrace::setregset::<regset::NT_PRFPREG>(self.pid, f)?;
         Ok(())
     }
 
+    /// Writes the general purpose registers to the process
     pub fn write_gprs(&self, f: user_regs_struct) -> Result<()> {
         ptrace::setregset::<regset::NT_PRSTATUS>(self.pid, f)?;
         Ok(())
     }
+
+    /// The registers as read at the last stop
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    /// Writes a register of the process from the bytes of a value
+    pub fn write_register(&mut self, info: &RegisterInfo, data: &[u8]) -> Result<()> {
+        let mut registers = self.registers.clone();
+        registers.write_bytes(info, data, self)?;
+        self.registers = registers;
+        Ok(())
+    }
+
+    fn open_memory(&self, write: bool) -> Result<File> {
+        let path = format!("/proc/{}/mem", self.pid);
+        Ok(OpenOptions::new().read(true).write(write).open(path)?)
+    }
+
+    /// Reads tracee memory through `/proc/<pid>/mem`, which unlike process_vm_readv is not
+    /// restricted by the page protections of the tracee.
+    pub fn read_memory(&self, address: u64, amount: usize) -> Result<Vec<u8>> {
+        let mut buf = vec![0u8; amount];
+        let read = self
+            .open_memory(false)?
+            .read_at(&mut buf, address)
+            .map_err(|err| anyhow!("cannot access memory at {address:#x}: {err}"))?;
+        if read != amount {
+            bail!("could only read {read} of {amount} bytes at {address:#x}");
+        }
+        Ok(buf)
+    }
+
+    /// Reads a value of type `T` from memory
+    pub fn read_memory_as<T: AnyBitPattern>(&self, address: u64) -> Result<T> {
+        let bytes = self.read_memory(address, mem::size_of::<T>())?;
+        Ok(pod_read_unaligned(&bytes))
+    }
+
+    /// Writes tracee memory through `/proc/<pid>/mem`, replacing the int3 of any site it covers
+    pub fn write_memory(&self, address: u64, data: &[u8]) -> Result<()> {
+        self.open_memory(true)?.write_all_at(data, address)?;
+        Ok(())
+    }
+
+    /// Reads memory as the program has it, with the original bytes in place of the int3s of
+    /// breakpoint sites
+    pub fn read_memory_without_sites(&self, address: u64, amount: usize) -> Result<Vec<u8>> {
+        let mut data = self.read_memory(address, amount)?;
+        for (&site, &saved) in &self.breakpoint_sites {
+            if let Some(offset) = site.checked_sub(address)
+                && (offset as usize) < amount
+            {
+                data[offset as usize] = saved;
+            }
+        }
+        Ok(data)
+    }
+
+    /// Writes memory without disturbing breakpoint sites: bytes written over a site become the
+    /// byte the site restores
+    pub fn write_memory_around_sites(&mut self, address: u64, data: &[u8]) -> Result<()> {
+        let mut data = data.to_vec();
+        for (&site, saved) in &mut self.breakpoint_sites {
+            if let Some(offset) = site.checked_sub(address)
+                && (offset as usize) < data.len()
+            {
+                *saved = data[offset as usize];
+                data[offset as usize] = INT3;
+            }
+        }
+        self.write_memory(address, &data)
+    }
+
+    /// The auxiliary vector the kernel passed to the program, keyed by AT_* type
+    pub fn read_auxv(&self) -> Result<HashMap<u64, u64>> {
+        let data = fs::read(format!("/proc/{}/auxv", self.pid))?;
+        Ok(data
+            .chunks_exact(16)
+            .map(|entry| {
+                (
+                    pod_read_unaligned(&entry[..8]),
+                    pod_read_unaligned(&entry[8..]),
+                )
+            })
+            .collect())
+    }
+
+    /// The path of the program's executable, from `/proc/<pid>/exe`
+    pub fn executable_path(&self) -> Result<PathBuf> {
+        Ok(fs::read_link(format!("/proc/{}/exe", self.pid))?)
+    }
+
+    /// The threads of the process, the main thread first and the others in the order they were
+    /// created
+    pub fn threads(&self) -> Result<Vec<Pid>> {
+        let mut threads = Vec::new();
+        for entry in fs::read_dir(format!("/proc/{}/task", self.pid))? {
+            if let Ok(tid) = entry?.file_name().to_string_lossy().parse() {
+                threads.push(Pid::from_raw(tid));
+            }
+        }
+        threads.sort_by_key(|&tid| (tid != self.pid, tid.as_raw()));
+        Ok(threads)
+    }
+
+    /// Stops every thread besides the traced one. Only the main thread is traced, so the
+    /// others are seized and interrupted, all of them before waiting for any, and are let go
+    /// when the result is dropped.
+    pub fn stop_threads(&self) -> Result<StoppedThreads> {
+        let mut stopped = StoppedThreads {
+            threads: Vec::new(),
+        };
+        for tid in self.threads()? {
+            if tid != self.pid {
+                ptrace::seize(tid, Options::empty())?;
+                stopped.threads.push((tid, None));
+                ptrace::interrupt(tid)?;
+            }
+        }
+        for (tid, signal) in &mut stopped.threads {
+            // An interrupt stops the thread without a signal, unless one arrived first
+            *signal = match wait::waitpid(*tid, Some(WaitPidFlag::__WALL))? {
+                WaitStatus::Stopped(_, signal) => Some(signal as i32),
+                _ => Some(0),
+            };
+        }
+        Ok(stopped)
+    }
+
+    /// The registers of every thread, the main thread first, the others being those held by
+    /// `stopped`
+    pub fn thread_registers(&self, stopped: &StoppedThreads) -> Result<Vec<ThreadRegisters>> {
+        let user = self.registers.user_data();
+        let mut states = vec![ThreadRegisters {
+            tid: self.pid,
+            regs: user.regs,
+            fp_regs: user.i387,
+            signal: ptrace::getsiginfo(self.pid).map_or(0, |info| info.si_signo),
+        }];
+        for &(tid, signal) in &stopped.threads {
+            states.push(ThreadRegisters {
+                tid,
+                regs: ptrace::getregs(tid)?,
+                fp_regs: ptrace::getregset::<regset::NT_PRFPREG>(tid)?,
+                signal: signal.unwrap_or(0),
+            });
+        }
+        Ok(states)
+    }
+
+    /// The stack pointer of a thread which is not running, as reported by
+    /// `/proc/<pid>/task/<tid>/syscall`. The traced thread is read from its registers instead.
+    pub fn thread_stack_pointer(&self, tid: Pid) -> Result<Option<u64>> {
+        if tid == self.pid {
+            let rsp = lookup_register_info_by_id(RegisterId::RSP)?;
+            return Ok(Some(self.registers.read_as_u64(rsp)?));
+        }
+        let syscall = fs::read_to_string(format!("/proc/{}/task/{tid}/syscall", self.pid))?;
+        // Either `running`, or the system call number and arguments followed by the stack
+        // pointer and the pc
+        let fields: Vec<&str> = syscall.split_ascii_whitespace().collect();
+        if fields.len() < 3 {
+            return Ok(None);
+        }
+        let sp = fields[fields.len() - 2].trim_start_matches("0x");
+        Ok(u64::from_str_radix(sp, 16).ok())
+    }
 }
 
 impl Drop for Process {
@@ -262,6 +1066,11 @@ impl Drop for Process {
                     .expect("failed while waiting for state change after SIGSTOP");
             }
 
+            // the tracee would trap on any int3 left behind once it is no longer traced
+            for (&address, &saved) in &self.breakpoint_sites {
+                _ = self.write_memory(address, &[saved]);
+            }
+
             // detach and continue tracee
             ptrace::detach(self.pid, None).expect("failed to detach from pid");
             signal::kill(self.pid, Signal::SIGCONT).expect("failed to continue pid");
@@ -278,7 +1087,7 @@ impl Drop for Process {
 mod tests {
     use crate::process::{DebugProcess, Process, ProcessState};
     use anyhow::Result;
-    use nix::sys::signal;
+    use nix::sys::signal::{self, Signal};
     use nix::unistd::Pid;
     use std::fs;
 
@@ -364,6 +1173,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn only_interrupts_of_the_debugger_are_kept_from_the_program() {
+        let mut p = Process::launch("target/debug/run-forever", DebugProcess::YES).unwrap();
+        p.resume().unwrap();
+        p.interrupter().interrupt().unwrap();
+        assert_eq!(p.wait_on_signal().unwrap().signal(), Some(Signal::SIGINT));
+        // Had the SIGINT been passed on, the program would end rather than stop again
+        p.resume().unwrap();
+        p.interrupter().interrupt().unwrap();
+        assert_eq!(p.wait_on_signal().unwrap().signal(), Some(Signal::SIGINT));
+        p.resume().unwrap();
+
+        // Anyone else's SIGINT is delivered, and ends the program
+        signal::kill(p.pid, Signal::SIGINT).unwrap();
+        assert_eq!(p.wait_on_signal().unwrap().signal(), Some(Signal::SIGINT));
+        p.resume().unwrap();
+        let reason = p.wait_on_signal().unwrap();
+        assert!(matches!(reason.process_state, ProcessState::Terminated));
+    }
+
     #[test]
     fn finished_program_cannot_resume() {
         let p = Process::launch("ls", DebugProcess::YES);
@@ -1,9 +1,13 @@
+//! The x86-64 registers: where each is kept in `struct user`, its size and its format.
+
 use crate::reg_macros::*;
 use anyhow::{anyhow, Result};
 use nix::libc::{user, user_fpregs_struct, user_regs_struct};
 use std::mem::offset_of;
 use std::sync::LazyLock;
 
+/// A register. Variants are named after the registers as they appear in the Intel manuals.
+#[allow(missing_docs, non_camel_case_types, clippy::upper_case_acronyms)]
 #[derive(Eq, PartialEq, Copy, Clone, Debug)]
 pub enum RegisterId {
     RAX,
@@ -134,6 +138,7 @@ pub enum RegisterId {
 }
 
 impl RegisterId {
+    /// The debug register DR`index`, which must be below 8
     pub fn debug_register(index: u8) -> RegisterId {
         match index {
             0 => RegisterId::DR0,
@@ -149,48 +154,89 @@ impl RegisterId {
     }
 }
 
+/// The group a register belongs to
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum RegisterKind {
+    /// A 64-bit general purpose register, RIP, EFLAGS or a segment register
     GeneralPurpose,
+    /// A 32, 16 or 8-bit part of a general purpose register
     SubGeneralPurpose,
+    /// An x87, MMX or SSE register
     FloatingPoint,
+    /// A debug register
     Debug,
 }
 
+/// How the bytes of a register are read
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum RegisterFormat {
+    /// An unsigned integer
     Uint,
+    /// A double
     DoubleFloat,
+    /// An x87 80-bit long double
     LongDouble,
+    /// Vector data, read as bytes
     Vector,
 }
 
+/// Where a register is kept and how to read it
 #[derive(Debug)]
 pub struct RegisterInfo {
+    /// The register
     pub id: RegisterId,
+    /// Its name, in upper case
     pub name: String,
+    /// Its number in DWARF, or -1 when DWARF has none
     pub dwarf_id: i32,
+    /// Its size in bytes
     pub size: usize,
+    /// Its byte offset in `struct user`
     pub offset: usize,
+    /// Its group
     pub kind: RegisterKind,
+    /// How its bytes are read
     pub format: RegisterFormat,
 }
 
 fn lookup_register_info(f: impl Fn(&RegisterInfo) -> bool) -> Result<&'static RegisterInfo> {
-    (&*REGISTER_INFO)
+    REGISTER_INFO
         .iter()
         .find(|r| f(r))
-        .ok_or(anyhow!("failed to find register info"))
+        .ok_or_else(|| anyhow!("failed to find register info"))
 }
 
+/// Looks up a register by its ID
 pub fn lookup_register_info_by_id(id: RegisterId) -> Result<&'static RegisterInfo> {
     lookup_register_info(|r| r.id == id)
 }
 
+/// The names gdb gives the pc, stack pointer and frame pointer, which name the same registers
+pub const REGISTER_ALIASES: &[(&str, RegisterId)] = &[
+    ("PC", RegisterId::RIP),
+    ("SP", RegisterId::RSP),
+    ("FP", RegisterId::RBP),
+];
+
+/// Looks up a register by its name in the table, or by one of its `REGISTER_ALIASES`
 pub fn lookup_register_info_by_name(name: &str) -> Result<&'static RegisterInfo> {
-    lookup_register_info(|r| r.name == name)
+    match REGISTER_ALIASES.iter().find(|(alias, _)| *alias == name) {
+        Some(&(_, id)) => lookup_register_info_by_id(id),
+        None => lookup_register_info(|r| r.name == name),
+    }
+}
+
+/// Every register, in the order of the register table
+pub fn registers() -> impl Iterator<Item = &'static RegisterInfo> {
+    REGISTER_INFO.iter()
+}
+
+/// The registers of a kind, in the order of the register table
+pub fn registers_of_kind(kind: RegisterKind) -> impl Iterator<Item = &'static RegisterInfo> {
+    REGISTER_INFO.iter().filter(move |r| r.kind == kind)
 }
 
+/// Looks up a register by its DWARF number
 pub fn lookup_register_by_dwarf(dwarf_id: i32) -> Result<&'static RegisterInfo> {
     lookup_register_info(|r| r.dwarf_id == dwarf_id)
 }
@@ -1,17 +1,20 @@
+//! The register file of a thread.
+
 use crate::process::Process;
 use crate::reginfo::{
     lookup_register_info_by_id, RegisterFormat, RegisterId, RegisterInfo, RegisterKind,
 };
-use crate::registers::values::Value;
 use anyhow::{bail, Result};
 use bytemuck::{
-    bytes_of, bytes_of_mut, from_bytes, AnyBitPattern, Pod, TransparentWrapper, Zeroable,
+    bytes_of, bytes_of_mut, pod_read_unaligned, AnyBitPattern, Pod, TransparentWrapper, Zeroable,
 };
 use nix::libc::user;
 use std::mem;
 
 mod values;
 
+pub use values::{Byte128, Byte64, Value};
+
 #[derive(Copy, Clone)]
 #[repr(transparent)]
 struct User(pub user);
@@ -20,7 +23,10 @@ unsafe impl TransparentWrapper<user> for User {}
 unsafe impl Zeroable for User {}
 unsafe impl Pod for User {}
 
-pub(crate) struct Registers {
+/// A copy of a thread's `struct user`, which holds its general purpose, floating point and debug
+/// registers at the offsets [`RegisterInfo`] gives
+#[derive(Clone)]
+pub struct Registers {
     data: User,
 }
 
@@ -35,10 +41,12 @@ impl Default for Registers {
 }
 
 impl Registers {
+    /// The registers as the kernel lays them out
     pub fn user_data(&self) -> user {
         self.data.0
     }
 
+    /// Replaces all the registers, without writing them to a process
     pub fn set_user_data(&mut self, u: user) {
         self.data = User::wrap(u);
     }
@@ -48,10 +56,11 @@ impl Registers {
         T: AnyBitPattern,
     {
         let slice = bytes_of(&self.data);
-        *from_bytes(&slice[offset..])
+        pod_read_unaligned(&slice[offset..offset + mem::size_of::<T>()])
     }
 
-    fn read(&self, info: &RegisterInfo) -> Result<Value> {
+    /// Reads a register as a value of its size and format
+    pub fn read(&self, info: &RegisterInfo) -> Result<Value> {
         use Value::*;
         let v = match info.format {
             RegisterFormat::Uint => match info.size {
@@ -69,11 +78,60 @@ impl Registers {
         Ok(v)
     }
 
-    fn read_by_id(&self, register_id: RegisterId) -> Result<Value> {
+    /// The bytes of a register as stored, in target byte order
+    pub fn read_bytes(&self, info: &RegisterInfo) -> &[u8] {
+        &bytes_of(&self.data)[info.offset..info.offset + info.size]
+    }
+
+    /// Reads a register by its ID
+    pub fn read_by_id(&self, register_id: RegisterId) -> Result<Value> {
         self.read(lookup_register_info_by_id(register_id)?)
     }
 
-    fn write(
+    /// Reads a register of at most 8 bytes, zero extended to 64 bits
+    pub fn read_as_u64(&self, info: &RegisterInfo) -> Result<u64> {
+        if info.size > 8 {
+            bail!("register {} is too wide to read as an integer", info.name);
+        }
+        let widened = self.read(info)?.widen();
+        Ok(pod_read_unaligned(&widened[..8]))
+    }
+
+    /// Renders a register for display: in hex, other than floating point ones which are shown
+    /// byte by byte, as there is no conversion for the 80-bit x87 format
+    pub fn format(&self, info: &RegisterInfo) -> Result<String> {
+        if info.format == RegisterFormat::Uint {
+            return Ok(format!("{:#x}", self.read_as_u64(info)?));
+        }
+        let bytes: Vec<String> = self
+            .read_bytes(info)
+            .iter()
+            .map(|b| format!("{b:#04x}"))
+            .collect();
+        Ok(format!("{{{}}}", bytes.join(" ")))
+    }
+
+    /// Updates the cached value of a register of at most 8 bytes, without writing it to the
+    /// process. Used for the registers of frames further up the stack, which are recovered by
+    /// unwinding rather than read.
+    pub fn set_cached_u64(&mut self, info: &RegisterInfo, value: u64) {
+        let user_bytes = bytes_of_mut(&mut self.data);
+        let size = info.size.min(8);
+        user_bytes[info.offset..info.offset + size].copy_from_slice(&value.to_le_bytes()[..size]);
+    }
+
+    /// Updates the cached bytes of a register, zero filling any not given, without writing them
+    /// to the process. Used for targets whose registers are read in bulk.
+    pub fn set_cached_bytes(&mut self, info: &RegisterInfo, data: &[u8]) {
+        let user_bytes = bytes_of_mut(&mut self.data);
+        let register = &mut user_bytes[info.offset..info.offset + info.size];
+        let size = data.len().min(info.size);
+        register.fill(0);
+        register[..size].copy_from_slice(&data[..size]);
+    }
+
+    /// Writes a register, both here and in the process
+    pub fn write(
         &mut self,
         register_info: &RegisterInfo,
         value: Value,
@@ -90,7 +148,7 @@ impl Registers {
         let end = start + register_info.size;
 
         let user_bytes_section = &mut user_bytes[start..end];
-        user_bytes_section.copy_from_slice(value_bytes);
+        user_bytes_section.copy_from_slice(&value_bytes[..register_info.size]);
 
         if register_info.kind == RegisterKind::FloatingPoint {
             process.write_fprs(self.user_data().i387)?;
@@ -102,7 +160,7 @@ impl Registers {
             // read 8 bytes starting from aligned address into word. note there are values which can
             // be well over 8 bytes, but all of those are floating point registers, which are
             // written en-masse in the other branch
-            let word = *from_bytes(&user_bytes[aligned_address..]);
+            let word = pod_read_unaligned(&user_bytes[aligned_address..aligned_address + 8]);
 
             // write into process user data. the assumption is that the value size is <= 8 bytes
             process.write_user_area(aligned_address, word)?;
@@ -110,7 +168,21 @@ impl Registers {
         }
     }
 
-    fn write_by_id(
+    /// Writes a register from the bytes of a value, which may be narrower than the register
+    pub fn write_bytes(
+        &mut self,
+        register_info: &RegisterInfo,
+        data: &[u8],
+        process: &Process,
+    ) -> Result<()> {
+        let mut bytes = [0u8; 16];
+        let size = data.len().min(bytes.len());
+        bytes[..size].copy_from_slice(&data[..size]);
+        self.write(register_info, Value::B128(bytes), process)
+    }
+
+    /// Writes a register by its ID, both here and in the process
+    pub fn write_by_id(
         &mut self,
         register_id: RegisterId,
         value: Value,
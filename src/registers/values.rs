@@ -1,38 +1,58 @@
-use bytemuck::{bytes_of, from_bytes};
+use bytemuck::bytes_of;
 
+/// The bytes of a 64-bit vector register, such as an MMX register
 pub type Byte64 = [u8; 8];
+/// The bytes of a 128-bit vector register, such as an XMM register
 pub type Byte128 = [u8; 16];
 
-pub(crate) enum Value {
+/// The value of a register
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Value {
+    /// An 8-bit unsigned integer
     U8(u8),
+    /// A 16-bit unsigned integer
     U16(u16),
+    /// A 32-bit unsigned integer
     U32(u32),
+    /// A 64-bit unsigned integer
     U64(u64),
+    /// An 8-bit signed integer
     I8(i8),
+    /// A 16-bit signed integer
     I16(i16),
+    /// A 32-bit signed integer
     I32(i32),
+    /// A 64-bit signed integer
     I64(i64),
+    /// A double
     F(f64),
-    LD(f64), // There is no f128 yet
+    /// An x87 long double, as a double since there is no f128 yet
+    LD(f64),
+    /// 64 bits of vector data
     B64(Byte64),
+    /// 128 bits of vector data
     B128(Byte128),
 }
 
 impl Value {
+    /// Zero extends the value to the widest register size
     pub fn widen(&self) -> Byte128 {
-        match self {
-            Value::I8(v) => *from_bytes(bytes_of(v)),
-            Value::I16(v) => *from_bytes(bytes_of(v)),
-            Value::I32(v) => *from_bytes(bytes_of(v)),
-            Value::I64(v) => *from_bytes(bytes_of(v)),
-            Value::F(v) => *from_bytes(bytes_of(v)),
-            Value::LD(v) => *from_bytes(bytes_of(v)),
-            Value::U8(v) => *from_bytes(bytes_of(v)),
-            Value::U16(v) => *from_bytes(bytes_of(v)),
-            Value::U32(v) => *from_bytes(bytes_of(v)),
-            Value::U64(v) => *from_bytes(bytes_of(v)),
-            Value::B64(v) => *from_bytes(bytes_of(v)),
-            Value::B128(v) => *from_bytes(bytes_of(v)),
-        }
+        let bytes = match self {
+            Value::I8(v) => bytes_of(v),
+            Value::I16(v) => bytes_of(v),
+            Value::I32(v) => bytes_of(v),
+            Value::I64(v) => bytes_of(v),
+            Value::F(v) => bytes_of(v),
+            Value::LD(v) => bytes_of(v),
+            Value::U8(v) => bytes_of(v),
+            Value::U16(v) => bytes_of(v),
+            Value::U32(v) => bytes_of(v),
+            Value::U64(v) => bytes_of(v),
+            Value::B64(v) => bytes_of(v),
+            Value::B128(v) => bytes_of(v),
+        };
+        let mut widened = Byte128::default();
+        widened[..bytes.len()].copy_from_slice(bytes);
+        widened
     }
 }
@@ -0,0 +1,610 @@
+use crate::dwarf::expr::MemoryReader;
+use crate::maps::{self, MemoryRegion};
+use crate::process::StopReason;
+use crate::reginfo::{lookup_register_info_by_id, RegisterId, RegisterInfo};
+use crate::registers::Registers;
+use crate::rsp::{
+    connect, frame, from_hex, gdb_signal_number, parse_hex, read_remote_register, receive,
+    remote_register_write, signal_from_gdb, to_hex, Connection, Received, RegisterSource,
+    RemoteRegister, REMOTE_REGISTERS,
+};
+use crate::target::Target;
+use anyhow::{anyhow, bail, Result};
+use nix::sys::signal::Signal;
+use nix::sys::wait::WaitStatus;
+use nix::unistd::Pid;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::PathBuf;
+
+// The client side of the remote protocol: a target served by gdbserver, `qemu -g`, `rr replay`
+// or `kitt-server`. Registers are read in bulk at each stop and laid out from the target
+// description of the stub, so that stubs which describe more registers than kitt knows, or
+// number them differently, still work.
+
+// The most data asked for in one packet, which stubs support at the least
+const CHUNK_SIZE: usize = 0x400;
+
+// A register of the stub, at its place in the `g` packet
+struct StubRegister {
+    number: usize,
+    bits: usize,
+    // kitt's counterpart, for registers it knows by name
+    register: Option<&'static RemoteRegister>,
+}
+
+/// A process served by a remote stub such as gdbserver, `qemu -g` or kitt-server
+pub struct RemoteTarget {
+    // Packets are exchanged behind shared references, to read memory
+    connection: RefCell<Box<dyn Connection>>,
+    acknowledge: bool,
+    pid: Pid,
+    // The local copy of the program, when the stub's path to it does not do
+    executable: Option<PathBuf>,
+    layout: Vec<StubRegister>,
+    registers: Registers,
+    breakpoint_sites: HashSet<u64>,
+    pending_signal: Option<Signal>,
+    // A stop while stepping over a breakpoint site, which the next wait reports
+    early_stop: Option<StopReason>,
+    running: bool,
+    finished: bool,
+    // Whether the stub attached to the process rather than launching it
+    attached: bool,
+}
+
+// The value of an attribute of an XML tag
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let start = tag.find(&format!(" {name}=\""))? + name.len() + 3;
+    let end = start + tag[start..].find('"')?;
+    Some(&tag[start..end])
+}
+
+fn is_error(reply: &[u8]) -> bool {
+    reply.len() == 3 && reply[0] == b'E'
+}
+
+impl RemoteTarget {
+    /// Connects to a stub at a TCP address or, with a unix: prefix, a socket path. The stub is
+    /// expected to have a stopped process.
+    pub fn connect(address: &str, executable: Option<PathBuf>) -> Result<Self> {
+        let mut target = Self {
+            connection: RefCell::new(connect(address)?),
+            acknowledge: true,
+            pid: Pid::from_raw(0),
+            executable,
+            layout: Vec::new(),
+            registers: Registers::default(),
+            breakpoint_sites: HashSet::new(),
+            pending_signal: None,
+            early_stop: None,
+            running: false,
+            finished: false,
+            attached: false,
+        };
+        let features = target.request("qSupported:swbreak+;hwbreak+;xmlRegisters=i386")?;
+        let features = String::from_utf8_lossy(&features).into_owned();
+        if features.contains("QStartNoAckMode+") && target.request("QStartNoAckMode")? == b"OK" {
+            target.acknowledge = false;
+        }
+        target.layout = if features.contains("qXfer:features:read+") {
+            let mut layout = Vec::new();
+            target.read_description("target.xml", &mut layout)?;
+            layout.sort_by_key(|register| register.number);
+            layout
+        } else {
+            // Stubs without descriptions use gdb's numbering, which kitt's own follows
+            (REMOTE_REGISTERS.iter().enumerate())
+                .map(|(number, register)| StubRegister {
+                    number,
+                    bits: register.bits,
+                    register: Some(register),
+                })
+                .collect()
+        };
+        target.attached = target.request("qAttached")? == b"1";
+
+        let stop = target.request("?")?;
+        let stop = String::from_utf8_lossy(&stop).into_owned();
+        let thread = stop
+            .split(';')
+            .find_map(|field| field.strip_prefix("thread:"))
+            .map(str::to_string);
+        let thread = match thread {
+            Some(thread) => thread,
+            None => String::from_utf8_lossy(&target.request("qC")?)
+                .strip_prefix("QC")
+                .ok_or_else(|| anyhow!("the stub did not say which process it debugs"))?
+                .to_string(),
+        };
+        target.pid = Pid::from_raw(parse_hex(&thread)? as i32);
+        target.stop_reason(stop.as_bytes())?;
+        Ok(target)
+    }
+
+    fn send(&self, data: &[u8]) -> Result<()> {
+        let packet = frame(data);
+        let mut connection = self.connection.borrow_mut();
+        loop {
+            connection.write_all(&packet)?;
+            if !self.acknowledge {
+                return Ok(());
+            }
+            match receive(&mut **connection, false)? {
+                Received::Nack => continue,
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn receive_packet(&self) -> Result<Vec<u8>> {
+        let mut connection = self.connection.borrow_mut();
+        loop {
+            if let Received::Packet(packet) = receive(&mut **connection, self.acknowledge)? {
+                return Ok(packet);
+            }
+        }
+    }
+
+    fn request(&self, packet: &str) -> Result<Vec<u8>> {
+        self.send(packet.as_bytes())?;
+        self.receive_packet()
+    }
+
+    fn request_ok(&self, packet: &str) -> Result<()> {
+        match &self.request(packet)?[..] {
+            b"OK" => Ok(()),
+            b"" => bail!("the stub does not support {packet}"),
+            reply => bail!(
+                "the stub refused {packet}: {}",
+                String::from_utf8_lossy(reply)
+            ),
+        }
+    }
+
+    // Reads a whole object through qXfer, part by part
+    fn xfer(&self, object: &str, annex: &str) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        loop {
+            let reply = self.request(&format!(
+                "qXfer:{object}:read:{annex}:{:x},{CHUNK_SIZE:x}",
+                data.len()
+            ))?;
+            match reply.split_first() {
+                Some((b'm', part)) => data.extend(part),
+                Some((b'l', part)) => {
+                    data.extend(part);
+                    return Ok(data);
+                }
+                _ => bail!("the stub cannot read {object} {annex}"),
+            }
+        }
+    }
+
+    // Adds the registers of a target description, and of those it includes, in the order
+    // they are described. Registers without a number follow the one before.
+    fn read_description(&self, annex: &str, layout: &mut Vec<StubRegister>) -> Result<()> {
+        let xml = String::from_utf8(self.xfer("features", annex)?)?;
+        for tag in xml.split('<').skip(1) {
+            if tag.starts_with("xi:include ") {
+                let href = attribute(tag, "href")
+                    .ok_or_else(|| anyhow!("include without href in {annex}"))?;
+                self.read_description(href, layout)?;
+            } else if tag.starts_with("reg ") {
+                let name = attribute(tag, "name").unwrap_or_default();
+                let number = match attribute(tag, "regnum") {
+                    Some(number) => number.parse()?,
+                    None => layout.last().map_or(0, |last| last.number + 1),
+                };
+                let bits = attribute(tag, "bitsize")
+                    .ok_or_else(|| anyhow!("register {name} without bitsize"))?
+                    .parse()?;
+                let register = REMOTE_REGISTERS
+                    .iter()
+                    .find(|register| register.name == name && register.bits == bits);
+                layout.push(StubRegister {
+                    number,
+                    bits,
+                    register,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // Reads a file of the stub's system through its host I/O packets
+    fn read_file(&self, path: &str) -> Result<Vec<u8>> {
+        // Replies are F<result> or F-1,<errno>, with any data after a semicolon
+        let result = |reply: &[u8]| -> Result<(u64, usize)> {
+            let text_end = reply.iter().position(|&b| b == b';').unwrap_or(reply.len());
+            let text = std::str::from_utf8(&reply[..text_end])?;
+            match text.strip_prefix('F') {
+                Some(error) if error.starts_with('-') => bail!("cannot read {path} on the stub"),
+                Some(result) => Ok((parse_hex(result)?, text_end + 1)),
+                None => bail!("the stub cannot read files"),
+            }
+        };
+        let open = self.request(&format!("vFile:open:{},0,0", to_hex(path.as_bytes())))?;
+        let (fd, _) = result(&open)?;
+        let mut data = Vec::new();
+        let read = loop {
+            let reply = self.request(&format!(
+                "vFile:pread:{fd:x},{CHUNK_SIZE:x},{:x}",
+                data.len()
+            ))?;
+            match result(&reply) {
+                Ok((0, _)) => break Ok(()),
+                Ok((_, start)) => data.extend(&reply[start.min(reply.len())..]),
+                Err(err) => break Err(err),
+            }
+        };
+        self.request(&format!("vFile:close:{fd:x}"))?;
+        read.map(|_| data)
+    }
+
+    // Reads the registers of the current thread, leaving those the stub does not have as they
+    // were
+    fn fetch_registers(&mut self) -> Result<()> {
+        let reply = self.request("g")?;
+        if is_error(&reply) {
+            bail!("the stub cannot read registers");
+        }
+        let mut offset = 0;
+        for slot in &self.layout {
+            let size = slot.bits / 8 * 2;
+            let Some(hex) = reply.get(offset..offset + size) else {
+                break;
+            };
+            offset += size;
+            let (Some(register), false) = (slot.register, hex.starts_with(b"x")) else {
+                continue;
+            };
+            let value = from_hex(std::str::from_utf8(hex)?)?;
+            let (info, bytes) = remote_register_write(&self.registers, register, &value)?;
+            self.registers.set_cached_bytes(info, &bytes);
+        }
+        Ok(())
+    }
+
+    // Turns a stop reply into a stop reason, reading the registers of the stop
+    fn stop_reason(&mut self, reply: &[u8]) -> Result<StopReason> {
+        let text = std::str::from_utf8(reply)?;
+        let number = || -> Result<u8> { Ok(parse_hex(text.get(1..3).unwrap_or_default())? as u8) };
+        // Stops without a signal, as at the start of a session, are treated as traps
+        let signal =
+            || -> Result<Signal> { Ok(signal_from_gdb(number()?).unwrap_or(Signal::SIGTRAP)) };
+        let status = match reply.first() {
+            Some(b'T' | b'S') => WaitStatus::Stopped(self.pid, signal()?),
+            Some(b'W') => WaitStatus::Exited(self.pid, number()? as i32),
+            Some(b'X') => WaitStatus::Signaled(self.pid, signal()?, false),
+            _ => bail!("unexpected stop reply {text}"),
+        };
+        let reason = StopReason::new(status);
+        if reason.is_stopped() {
+            self.fetch_registers()?;
+        } else {
+            self.finished = true;
+        }
+        // As with local processes, signals the debugger uses itself are not passed on. kitt
+        // never interrupts the stub, so a SIGINT came from elsewhere and is passed on.
+        self.pending_signal = reason
+            .signal()
+            .filter(|_| reason.is_stopped())
+            .filter(|signal| ![Signal::SIGTRAP, Signal::SIGSTOP].contains(signal));
+        Ok(reason)
+    }
+
+    // The packet which resumes or steps with the pending signal
+    fn resume_packet(&mut self, step: bool) -> String {
+        let command = if step { 's' } else { 'c' };
+        match self.pending_signal.take() {
+            Some(signal) => format!(
+                "{}{:02x}",
+                command.to_ascii_uppercase(),
+                gdb_signal_number(signal)
+            ),
+            None => command.to_string(),
+        }
+    }
+
+    // Steps one instruction, lifting a breakpoint site at the pc for the step
+    fn step(&mut self) -> Result<StopReason> {
+        if self.finished {
+            bail!("the program is not being run");
+        }
+        let pc = self.pc()?;
+        let site = self.breakpoint_sites.contains(&pc);
+        if site {
+            self.request_ok(&format!("z0,{pc:x},1"))?;
+        }
+        let packet = self.resume_packet(true);
+        self.send(packet.as_bytes())?;
+        self.running = true;
+        let reason = self.wait_on_signal()?;
+        if site && reason.is_stopped() {
+            self.request_ok(&format!("Z0,{pc:x},1"))?;
+        }
+        Ok(reason)
+    }
+
+    fn pc(&self) -> Result<u64> {
+        let info = lookup_register_info_by_id(RegisterId::RIP)?;
+        self.registers.read_as_u64(info)
+    }
+
+    // The stub's number for the register and the slot it fills, for each slot kitt's register
+    // contributes to
+    fn slots_of(&self, id: RegisterId) -> impl Iterator<Item = &StubRegister> {
+        self.layout
+            .iter()
+            .filter(move |slot| match slot.register.map(|r| r.source) {
+                Some(RegisterSource::Register(source))
+                | Some(RegisterSource::LowHalf(source))
+                | Some(RegisterSource::HighHalf(source)) => source == id,
+                Some(RegisterSource::FullTag) => id == RegisterId::FTW,
+                None => false,
+            })
+    }
+}
+
+impl Drop for RemoteTarget {
+    // Like a local process, a process the stub launched is killed and one it attached to is
+    // left running
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        if self.attached {
+            _ = self.request("D");
+        } else {
+            _ = self.send(b"k");
+        }
+    }
+}
+
+impl MemoryReader for RemoteTarget {
+    fn read_memory(&self, address: u64, amount: usize) -> Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(amount);
+        while data.len() < amount {
+            let start = address + data.len() as u64;
+            let size = (amount - data.len()).min(CHUNK_SIZE);
+            let reply = self.request(&format!("m{start:x},{size:x}"))?;
+            if reply.is_empty() || is_error(&reply) {
+                bail!("cannot access memory at {start:#x}");
+            }
+            data.extend(from_hex(std::str::from_utf8(&reply)?)?);
+        }
+        data.truncate(amount);
+        Ok(data)
+    }
+}
+
+impl Target for RemoteTarget {
+    fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    fn executable_path(&self) -> Result<PathBuf> {
+        if let Some(executable) = &self.executable {
+            return Ok(executable.clone());
+        }
+        let path = self.xfer("exec-file", &format!("{:x}", self.pid.as_raw()))?;
+        Ok(PathBuf::from(String::from_utf8(path)?))
+    }
+
+    fn read_auxv(&self) -> Result<HashMap<u64, u64>> {
+        let data = self.xfer("auxv", "")?;
+        Ok(data
+            .chunks_exact(16)
+            .map(|pair| {
+                let (key, value) = pair.split_at(8);
+                (
+                    u64::from_le_bytes(key.try_into().unwrap()),
+                    u64::from_le_bytes(value.try_into().unwrap()),
+                )
+            })
+            .collect())
+    }
+
+    fn threads(&self) -> Result<Vec<Pid>> {
+        let mut threads = Vec::new();
+        let mut reply = self.request("qfThreadInfo")?;
+        while let Some((b'm', list)) = reply.split_first() {
+            for tid in std::str::from_utf8(list)?.split(',') {
+                threads.push(Pid::from_raw(parse_hex(tid)? as i32));
+            }
+            reply = self.request("qsThreadInfo")?;
+        }
+        if threads.is_empty() {
+            threads.push(self.pid);
+        }
+        // The main thread first
+        if let Some(index) = threads.iter().position(|&tid| tid == self.pid) {
+            threads[..=index].rotate_right(1);
+        }
+        Ok(threads)
+    }
+
+    fn thread_stack_pointer(&self, tid: Pid) -> Result<Option<u64>> {
+        let Some(slot) = self.slots_of(RegisterId::RSP).next() else {
+            return Ok(None);
+        };
+        if tid == self.pid {
+            let info = lookup_register_info_by_id(RegisterId::RSP)?;
+            return Ok(Some(self.registers.read_as_u64(info)?));
+        }
+        self.request_ok(&format!("Hg{:x}", tid.as_raw()))?;
+        let reply = self.request(&format!("p{:x}", slot.number));
+        self.request_ok(&format!("Hg{:x}", self.pid.as_raw()))?;
+        let reply = reply?;
+        if reply.is_empty() || is_error(&reply) || reply.starts_with(b"x") {
+            return Ok(None);
+        }
+        let bytes = from_hex(std::str::from_utf8(&reply)?)?;
+        let mut value = [0; 8];
+        let size = bytes.len().min(8);
+        value[..size].copy_from_slice(&bytes[..size]);
+        Ok(Some(u64::from_le_bytes(value)))
+    }
+
+    fn memory_regions(&self) -> Result<Vec<MemoryRegion>> {
+        let maps = self.read_file(&format!("/proc/{}/maps", self.pid))?;
+        maps::parse_maps(&String::from_utf8_lossy(&maps))
+    }
+
+    fn can_resume(&self) -> bool {
+        !self.finished
+    }
+
+    // Registers are written one at a time with P packets, each remote register kitt's one
+    // makes up
+    fn write_register(&mut self, info: &RegisterInfo, data: &[u8]) -> Result<()> {
+        let mut registers = self.registers.clone();
+        registers.set_cached_bytes(info, data);
+        let mut written = false;
+        for slot in self.slots_of(info.id) {
+            let register = slot.register.expect("slots of a register are known");
+            let value = read_remote_register(&registers, register)?;
+            self.request_ok(&format!("P{:x}={}", slot.number, to_hex(&value)))?;
+            written = true;
+        }
+        if !written {
+            bail!("the stub has no register {}", info.name);
+        }
+        self.registers = registers;
+        Ok(())
+    }
+
+    fn write_memory(&mut self, address: u64, data: &[u8]) -> Result<()> {
+        for (index, chunk) in data.chunks(CHUNK_SIZE).enumerate() {
+            let start = address + (index * CHUNK_SIZE) as u64;
+            self.request_ok(&format!("M{start:x},{:x}:{}", chunk.len(), to_hex(chunk)))?;
+        }
+        Ok(())
+    }
+
+    // A breakpoint site at the pc is stepped over first, as the stub would stop on it straight
+    // away
+    fn resume(&mut self) -> Result<()> {
+        if self.finished {
+            bail!("the program is not being run");
+        }
+        if self.breakpoint_sites.contains(&self.pc()?) {
+            let reason = self.step()?;
+            if !reason.is_stopped() || reason.signal() != Some(Signal::SIGTRAP) {
+                self.early_stop = Some(reason);
+                return Ok(());
+            }
+        }
+        let packet = self.resume_packet(false);
+        self.send(packet.as_bytes())?;
+        self.running = true;
+        Ok(())
+    }
+
+    fn step_instruction(&mut self) -> Result<StopReason> {
+        self.step()
+    }
+
+    // Waits for the stop reply, passing on any output of the program the stub forwards
+    fn wait_on_signal(&mut self) -> Result<StopReason> {
+        if let Some(reason) = self.early_stop.take() {
+            return Ok(reason);
+        }
+        if !self.running {
+            bail!("the program is not being run");
+        }
+        loop {
+            let reply = self.receive_packet()?;
+            if let Some(output) = reply.strip_prefix(b"O")
+                && reply != b"OK"
+            {
+                std::io::stdout().write_all(&from_hex(std::str::from_utf8(output)?)?)?;
+                continue;
+            }
+            self.running = false;
+            return self.stop_reason(&reply);
+        }
+    }
+
+    fn add_breakpoint_site(&mut self, address: u64) -> Result<()> {
+        if self.breakpoint_sites.insert(address)
+            && let Err(err) = self.request_ok(&format!("Z0,{address:x},1"))
+        {
+            self.breakpoint_sites.remove(&address);
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    fn remove_breakpoint_site(&mut self, address: u64) -> Result<()> {
+        if self.breakpoint_sites.remove(&address) {
+            self.request_ok(&format!("z0,{address:x},1"))?;
+        }
+        Ok(())
+    }
+
+    fn forget_breakpoint_site(&mut self, address: u64) {
+        self.breakpoint_sites.remove(&address);
+    }
+
+    fn has_breakpoint_site(&self, address: u64) -> bool {
+        self.breakpoint_sites.contains(&address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::debugger::{Debugger, VariableKind};
+    use crate::process::{DebugProcess, Process};
+    use crate::rsp::client::RemoteTarget;
+    use crate::rsp::server::Server;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn debugging_through_kitts_own_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || {
+            let (connection, _) = listener.accept().unwrap();
+            let process = Process::launch("target/debug/variables", DebugProcess::YES).unwrap();
+            let mut server = Server::new(process, false, Box::new(connection)).unwrap();
+            server.serve().unwrap();
+        });
+
+        let target = RemoteTarget::connect(&address, None).unwrap();
+        let mut debugger = Debugger::new(target).unwrap();
+        let id = debugger.set_breakpoint("variables::inspect").unwrap().id;
+        assert!(debugger.continue_execution().unwrap().is_stopped());
+        let pc = debugger.pc().unwrap();
+        assert!(debugger.describe_address(pc).contains("inspect"));
+        let args = debugger.frame_variables(VariableKind::Arguments).unwrap();
+        assert_eq!(args.len(), 2);
+        assert!(debugger
+            .shared_libraries()
+            .iter()
+            .any(|library| library.path.to_string_lossy().contains("libc.so")));
+        assert!(debugger
+            .memory_regions()
+            .unwrap()
+            .iter()
+            .any(|region| region.path == "[stack]"));
+
+        // The step lifts the breakpoint at the pc
+        assert!(debugger.step_instruction().unwrap().is_stopped());
+        assert_ne!(debugger.pc().unwrap(), pc);
+        debugger.delete_breakpoint(id).unwrap();
+        let exit_code = (0..5)
+            .find_map(|_| debugger.continue_execution().unwrap().exit_code())
+            .unwrap();
+        assert_eq!(exit_code, 0);
+        drop(debugger);
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,497 @@
+//! The GDB Remote Serial Protocol: [`Server`] serves a process to gdb, lldb or kitt, and
+//! [`RemoteTarget`] debugs one served by a stub.
+
+use crate::reginfo::{lookup_register_info_by_id, RegisterId, RegisterInfo};
+use crate::registers::Registers;
+use anyhow::{anyhow, bail, Result};
+use nix::sys::signal::Signal;
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+mod client;
+mod server;
+
+pub use client::RemoteTarget;
+pub use server::Server;
+
+// The GDB Remote Serial Protocol. Packets are framed as `$data#cc`, where cc is the sum of the
+// data bytes modulo 256 in hex, and are acknowledged with `+` or `-` until both sides agree to
+// drop acknowledgements. The bytes `$`, `#`, `}` and `*` are escaped within data as `}` followed
+// by the byte xored with 0x20.
+
+const ESCAPE: u8 = b'}';
+/// Sent outside of any packet to stop a running target
+pub(crate) const INTERRUPT: u8 = 0x03;
+
+/// The connections packets travel over
+pub trait Connection: Read + Write + Send {
+    /// Another handle to the same connection
+    fn try_clone_connection(&self) -> io::Result<Box<dyn Connection>>;
+    /// Limits how long reads wait, or lets them wait forever with None
+    fn set_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+impl Connection for TcpStream {
+    fn try_clone_connection(&self) -> io::Result<Box<dyn Connection>> {
+        Ok(Box::new(self.try_clone()?))
+    }
+
+    fn set_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.set_read_timeout(timeout)
+    }
+}
+
+impl Connection for UnixStream {
+    fn try_clone_connection(&self) -> io::Result<Box<dyn Connection>> {
+        Ok(Box::new(self.try_clone()?))
+    }
+
+    fn set_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.set_read_timeout(timeout)
+    }
+}
+
+/// Connects to a stub listening on a TCP address or, with a unix: prefix, a socket path
+pub(crate) fn connect(address: &str) -> Result<Box<dyn Connection>> {
+    Ok(match address.strip_prefix("unix:") {
+        Some(path) => Box::new(UnixStream::connect(path)?),
+        None => {
+            let stream = TcpStream::connect(address)?;
+            // Packets are small and each waits on the reply to the last
+            stream.set_nodelay(true)?;
+            Box::new(stream)
+        }
+    })
+}
+
+/// What arrives on a connection: a packet, or one of the single bytes sent between them
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) enum Received {
+    Packet(Vec<u8>),
+    Ack,
+    Nack,
+    Interrupt,
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+pub(crate) fn escape(data: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(data.len());
+    for &b in data {
+        if matches!(b, b'$' | b'#' | b'}' | b'*') {
+            escaped.extend([ESCAPE, b ^ 0x20]);
+        } else {
+            escaped.push(b);
+        }
+    }
+    escaped
+}
+
+pub(crate) fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut unescaped = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+    while let Some(&b) = bytes.next() {
+        match b {
+            ESCAPE => unescaped.extend(bytes.next().map(|b| b ^ 0x20)),
+            _ => unescaped.push(b),
+        }
+    }
+    unescaped
+}
+
+// Expands the run length encoding of replies, where `*` followed by a byte n repeats the byte
+// before it n - 29 times. Counts below 29 cannot be sent, so a packet with one is malformed.
+fn expand_runs(data: &[u8]) -> Result<Vec<u8>> {
+    let mut expanded: Vec<u8> = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+    while let Some(&b) = bytes.next() {
+        match (b, expanded.last().copied(), bytes.clone().next()) {
+            (b'*', Some(previous), Some(&count)) => {
+                bytes.next();
+                let count = count
+                    .checked_sub(29)
+                    .ok_or_else(|| anyhow!("bad run length {count:#x} in a packet"))?;
+                expanded.extend(std::iter::repeat_n(previous, count as usize));
+            }
+            _ => expanded.push(b),
+        }
+    }
+    Ok(expanded)
+}
+
+/// Frames data, which is escaped here, as a packet
+pub(crate) fn frame(data: &[u8]) -> Vec<u8> {
+    let escaped = escape(data);
+    let mut packet = Vec::with_capacity(escaped.len() + 4);
+    packet.push(b'$');
+    packet.extend(&escaped);
+    packet.extend(format!("#{:02x}", checksum(&escaped)).as_bytes());
+    packet
+}
+
+fn read_byte(connection: &mut dyn Connection) -> Result<u8> {
+    let mut byte = [0];
+    connection.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+/// Reads the next packet or single byte message. Packets are returned unescaped, and ones with a
+/// bad checksum are refused with `-` so that they are sent again.
+pub(crate) fn receive(connection: &mut dyn Connection, acknowledge: bool) -> Result<Received> {
+    loop {
+        match read_byte(connection)? {
+            b'+' => return Ok(Received::Ack),
+            b'-' => return Ok(Received::Nack),
+            INTERRUPT => return Ok(Received::Interrupt),
+            b'$' => {}
+            // Noise between packets
+            _ => continue,
+        }
+        let mut data = Vec::new();
+        loop {
+            match read_byte(connection)? {
+                b'#' => break,
+                b => data.push(b),
+            }
+        }
+        let mut sum = [0; 2];
+        connection.read_exact(&mut sum)?;
+        let expected = u8::from_str_radix(std::str::from_utf8(&sum)?, 16)?;
+        if checksum(&data) != expected {
+            if acknowledge {
+                connection.write_all(b"-")?;
+            }
+            continue;
+        }
+        if acknowledge {
+            connection.write_all(b"+")?;
+        }
+        // Runs are expanded first, as an escaped `*` is data rather than a run
+        return Ok(Received::Packet(unescape(&expand_runs(&data)?)));
+    }
+}
+
+pub(crate) fn to_hex(data: &[u8]) -> String {
+    let mut hex = String::with_capacity(data.len() * 2);
+    for b in data {
+        _ = write!(hex, "{b:02x}");
+    }
+    hex
+}
+
+pub(crate) fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        bail!("odd number of hex digits in {hex}");
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| anyhow!("invalid hex {hex}")))
+        .collect()
+}
+
+pub(crate) fn parse_hex(hex: &str) -> Result<u64> {
+    u64::from_str_radix(hex, 16).map_err(|_| anyhow!("invalid hex number {hex}"))
+}
+
+// The protocol numbers signals the way gdb does, which matches Linux only for the traditional
+// signals
+const GDB_SIGNALS: [(Signal, u8); 30] = [
+    (Signal::SIGHUP, 1),
+    (Signal::SIGINT, 2),
+    (Signal::SIGQUIT, 3),
+    (Signal::SIGILL, 4),
+    (Signal::SIGTRAP, 5),
+    (Signal::SIGABRT, 6),
+    (Signal::SIGFPE, 8),
+    (Signal::SIGKILL, 9),
+    (Signal::SIGBUS, 10),
+    (Signal::SIGSEGV, 11),
+    (Signal::SIGSYS, 12),
+    (Signal::SIGPIPE, 13),
+    (Signal::SIGALRM, 14),
+    (Signal::SIGTERM, 15),
+    (Signal::SIGURG, 16),
+    (Signal::SIGSTOP, 17),
+    (Signal::SIGTSTP, 18),
+    (Signal::SIGCONT, 19),
+    (Signal::SIGCHLD, 20),
+    (Signal::SIGTTIN, 21),
+    (Signal::SIGTTOU, 22),
+    (Signal::SIGIO, 23),
+    (Signal::SIGXCPU, 24),
+    (Signal::SIGXFSZ, 25),
+    (Signal::SIGVTALRM, 26),
+    (Signal::SIGPROF, 27),
+    (Signal::SIGWINCH, 28),
+    (Signal::SIGUSR1, 30),
+    (Signal::SIGUSR2, 31),
+    (Signal::SIGPWR, 32),
+];
+
+/// Signals gdb has no number for are sent as its unknown signal
+pub(crate) fn gdb_signal_number(signal: Signal) -> u8 {
+    GDB_SIGNALS
+        .iter()
+        .find(|(s, _)| *s == signal)
+        .map_or(143, |&(_, number)| number)
+}
+
+pub(crate) fn signal_from_gdb(number: u8) -> Option<Signal> {
+    GDB_SIGNALS
+        .iter()
+        .find(|&&(_, n)| n == number)
+        .map(|&(signal, _)| signal)
+}
+
+/// A register as the remote protocol numbers it, in the order of the `g` packet
+pub(crate) struct RemoteRegister {
+    pub name: &'static str,
+    pub bits: usize,
+    pub ty: &'static str,
+    pub group: &'static str,
+    pub source: RegisterSource,
+}
+
+/// Where the value of a remote register lives in kitt's registers. The x87 instruction and
+/// operand pointers are split into offset and segment halves, and the tag word is the full two
+/// bits per register form rather than the abridged one of fxsave.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum RegisterSource {
+    Register(RegisterId),
+    LowHalf(RegisterId),
+    HighHalf(RegisterId),
+    FullTag,
+}
+
+const fn remote(
+    name: &'static str,
+    bits: usize,
+    ty: &'static str,
+    group: &'static str,
+    source: RegisterSource,
+) -> RemoteRegister {
+    RemoteRegister {
+        name,
+        bits,
+        ty,
+        group,
+        source,
+    }
+}
+
+macro_rules! remote_registers {
+    ($($name:literal $bits:literal $ty:literal $group:literal $source:expr),* $(,)?) => {
+        [$(remote($name, $bits, $ty, $group, $source)),*]
+    };
+}
+
+use RegisterSource::{FullTag, HighHalf, LowHalf, Register};
+
+/// The registers of the x86-64 target description: the core, sse and linux features of gdb
+pub(crate) static REMOTE_REGISTERS: [RemoteRegister; 58] = remote_registers![
+    "rax" 64 "int64" "general" Register(RegisterId::RAX),
+    "rbx" 64 "int64" "general" Register(RegisterId::RBX),
+    "rcx" 64 "int64" "general" Register(RegisterId::RCX),
+    "rdx" 64 "int64" "general" Register(RegisterId::RDX),
+    "rsi" 64 "int64" "general" Register(RegisterId::RSI),
+    "rdi" 64 "int64" "general" Register(RegisterId::RDI),
+    "rbp" 64 "data_ptr" "general" Register(RegisterId::RBP),
+    "rsp" 64 "data_ptr" "general" Register(RegisterId::RSP),
+    "r8" 64 "int64" "general" Register(RegisterId::R8),
+    "r9" 64 "int64" "general" Register(RegisterId::R9),
+    "r10" 64 "int64" "general" Register(RegisterId::R10),
+    "r11" 64 "int64" "general" Register(RegisterId::R11),
+    "r12" 64 "int64" "general" Register(RegisterId::R12),
+    "r13" 64 "int64" "general" Register(RegisterId::R13),
+    "r14" 64 "int64" "general" Register(RegisterId::R14),
+    "r15" 64 "int64" "general" Register(RegisterId::R15),
+    "rip" 64 "code_ptr" "general" Register(RegisterId::RIP),
+    "eflags" 32 "int32" "general" Register(RegisterId::EFLAGS),
+    "cs" 32 "int32" "general" Register(RegisterId::CS),
+    "ss" 32 "int32" "general" Register(RegisterId::SS),
+    "ds" 32 "int32" "general" Register(RegisterId::DS),
+    "es" 32 "int32" "general" Register(RegisterId::ES),
+    "fs" 32 "int32" "general" Register(RegisterId::FS),
+    "gs" 32 "int32" "general" Register(RegisterId::GS),
+    "st0" 80 "i387_ext" "float" Register(RegisterId::ST0),
+    "st1" 80 "i387_ext" "float" Register(RegisterId::ST1),
+    "st2" 80 "i387_ext" "float" Register(RegisterId::ST2),
+    "st3" 80 "i387_ext" "float" Register(RegisterId::ST3),
+    "st4" 80 "i387_ext" "float" Register(RegisterId::ST4),
+    "st5" 80 "i387_ext" "float" Register(RegisterId::ST5),
+    "st6" 80 "i387_ext" "float" Register(RegisterId::ST6),
+    "st7" 80 "i387_ext" "float" Register(RegisterId::ST7),
+    "fctrl" 32 "int" "float" Register(RegisterId::FCW),
+    "fstat" 32 "int" "float" Register(RegisterId::FSW),
+    "ftag" 32 "int" "float" FullTag,
+    "fiseg" 32 "int" "float" HighHalf(RegisterId::FRIP),
+    "fioff" 32 "int" "float" LowHalf(RegisterId::FRIP),
+    "foseg" 32 "int" "float" HighHalf(RegisterId::FRDP),
+    "fooff" 32 "int" "float" LowHalf(RegisterId::FRDP),
+    "fop" 32 "int" "float" Register(RegisterId::FOP),
+    "xmm0" 128 "vec128" "vector" Register(RegisterId::XMM0),
+    "xmm1" 128 "vec128" "vector" Register(RegisterId::XMM1),
+    "xmm2" 128 "vec128" "vector" Register(RegisterId::XMM2),
+    "xmm3" 128 "vec128" "vector" Register(RegisterId::XMM3),
+    "xmm4" 128 "vec128" "vector" Register(RegisterId::XMM4),
+    "xmm5" 128 "vec128" "vector" Register(RegisterId::XMM5),
+    "xmm6" 128 "vec128" "vector" Register(RegisterId::XMM6),
+    "xmm7" 128 "vec128" "vector" Register(RegisterId::XMM7),
+    "xmm8" 128 "vec128" "vector" Register(RegisterId::XMM8),
+    "xmm9" 128 "vec128" "vector" Register(RegisterId::XMM9),
+    "xmm10" 128 "vec128" "vector" Register(RegisterId::XMM10),
+    "xmm11" 128 "vec128" "vector" Register(RegisterId::XMM11),
+    "xmm12" 128 "vec128" "vector" Register(RegisterId::XMM12),
+    "xmm13" 128 "vec128" "vector" Register(RegisterId::XMM13),
+    "xmm14" 128 "vec128" "vector" Register(RegisterId::XMM14),
+    "xmm15" 128 "vec128" "vector" Register(RegisterId::XMM15),
+    "mxcsr" 32 "int" "vector" Register(RegisterId::MXCSR),
+    "orig_rax" 64 "int" "system" Register(RegisterId::ORIG_RAX),
+];
+
+// The features the registers are grouped into, by the index of their first register
+const FEATURES: [(&str, usize); 3] = [
+    ("org.gnu.gdb.i386.core", 0),
+    ("org.gnu.gdb.i386.sse", 40),
+    ("org.gnu.gdb.i386.linux", 57),
+];
+
+const VEC128_TYPE: &str = r#"    <vector id="v4f" type="ieee_single" count="4"/>
+    <vector id="v2d" type="ieee_double" count="2"/>
+    <vector id="v16i8" type="int8" count="16"/>
+    <vector id="v8i16" type="int16" count="8"/>
+    <vector id="v4i32" type="int32" count="4"/>
+    <vector id="v2i64" type="int64" count="2"/>
+    <union id="vec128">
+      <field name="v4_float" type="v4f"/>
+      <field name="v2_double" type="v2d"/>
+      <field name="v16_int8" type="v16i8"/>
+      <field name="v8_int16" type="v8i16"/>
+      <field name="v4_int32" type="v4i32"/>
+      <field name="v2_int64" type="v2i64"/>
+      <field name="uint128" type="uint128"/>
+    </union>
+"#;
+
+/// The target description served by qXfer:features:read:target.xml
+pub(crate) fn target_description() -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\"?>\n<!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n<target>\n  \
+         <architecture>i386:x86-64</architecture>\n  <osabi>GNU/Linux</osabi>\n",
+    );
+    for (index, &(feature, start)) in FEATURES.iter().enumerate() {
+        let end = FEATURES
+            .get(index + 1)
+            .map_or(REMOTE_REGISTERS.len(), |next| next.1);
+        _ = writeln!(xml, "  <feature name=\"{feature}\">");
+        if feature.ends_with("sse") {
+            xml.push_str(VEC128_TYPE);
+        }
+        for (number, register) in REMOTE_REGISTERS.iter().enumerate().take(end).skip(start) {
+            _ = writeln!(
+                xml,
+                "    <reg name=\"{}\" bitsize=\"{}\" type=\"{}\" group=\"{}\" regnum=\"{number}\"/>",
+                register.name, register.bits, register.ty, register.group
+            );
+        }
+        xml.push_str("  </feature>\n");
+    }
+    xml.push_str("</target>\n");
+    xml
+}
+
+/// The value of a remote register in target byte order
+pub(crate) fn read_remote_register(
+    registers: &Registers,
+    register: &RemoteRegister,
+) -> Result<Vec<u8>> {
+    let read_u64 = |id| registers.read_as_u64(lookup_register_info_by_id(id)?);
+    let mut bytes = match register.source {
+        Register(id) => registers
+            .read_bytes(lookup_register_info_by_id(id)?)
+            .to_vec(),
+        LowHalf(id) => (read_u64(id)? as u32).to_le_bytes().to_vec(),
+        HighHalf(id) => ((read_u64(id)? >> 32) as u32).to_le_bytes().to_vec(),
+        // A set bit of the abridged tag marks a register in use, shown as valid, and a clear
+        // one an empty register
+        FullTag => {
+            let abridged = read_u64(RegisterId::FTW)?;
+            let tag = (0..8)
+                .filter(|i| abridged & (1 << i) == 0)
+                .fold(0u32, |tag, i| tag | (0b11 << (i * 2)));
+            tag.to_le_bytes().to_vec()
+        }
+    };
+    bytes.resize(register.bits / 8, 0);
+    Ok(bytes)
+}
+
+/// The kitt register to write, and its new bytes, for a new value of a remote register
+pub(crate) fn remote_register_write(
+    registers: &Registers,
+    register: &RemoteRegister,
+    data: &[u8],
+) -> Result<(&'static RegisterInfo, Vec<u8>)> {
+    if data.len() != register.bits / 8 {
+        bail!("{} is {} bits wide", register.name, register.bits);
+    }
+    let half = |data: &[u8]| u32::from_le_bytes(data[..4].try_into().unwrap()) as u64;
+    let (id, bytes) = match register.source {
+        Register(id) => (id, data.to_vec()),
+        LowHalf(id) | HighHalf(id) => {
+            let old = registers.read_as_u64(lookup_register_info_by_id(id)?)?;
+            let new = match register.source {
+                LowHalf(_) => (old & !0xffff_ffff) | half(data),
+                _ => (old & 0xffff_ffff) | (half(data) << 32),
+            };
+            (id, new.to_le_bytes().to_vec())
+        }
+        FullTag => {
+            let tag = half(data);
+            let abridged = (0..8)
+                .filter(|i| (tag >> (i * 2)) & 0b11 != 0b11)
+                .fold(0u16, |abridged, i| abridged | (1 << i));
+            (RegisterId::FTW, abridged.to_le_bytes().to_vec())
+        }
+    };
+    Ok((lookup_register_info_by_id(id)?, bytes))
+}
+
+/// The part of an object at `offset` of at most `length` bytes, as the reply to a qXfer read:
+/// `m` when there is more to come and `l` for the last part
+pub(crate) fn xfer_reply(object: &[u8], offset: usize, length: usize) -> Vec<u8> {
+    let start = offset.min(object.len());
+    let end = start.saturating_add(length).min(object.len());
+    let mut reply = vec![if end < object.len() { b'm' } else { b'l' }];
+    reply.extend(&object[start..end]);
+    reply
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rsp::{escape, expand_runs, frame, from_hex, target_description, to_hex, unescape};
+
+    #[test]
+    fn packets_are_framed_and_escaped() {
+        assert_eq!(frame(b"OK"), b"$OK#9a");
+        assert_eq!(frame(b""), b"$#00");
+        assert_eq!(escape(b"a$b}"), b"a}\x04b}]");
+        assert_eq!(unescape(&escape(b"#*}$x")), b"#*}$x");
+        assert_eq!(expand_runs(b"0* ").unwrap(), b"0000");
+        assert!(expand_runs(b"0*\x01").is_err());
+        assert_eq!(to_hex(&[0, 0xab, 0x10]), "00ab10");
+        assert_eq!(from_hex("00ab10").unwrap(), [0, 0xab, 0x10]);
+        assert!(from_hex("abc").is_err());
+
+        let description = target_description();
+        assert!(description.contains("<architecture>i386:x86-64</architecture>"));
+        assert!(description.contains(r#"<reg name="rip" bitsize="64" type="code_ptr""#));
+        assert!(description
+            .contains(r#"name="orig_rax" bitsize="64" type="int" group="system" regnum="57""#));
+    }
+}
@@ -0,0 +1,732 @@
+use crate::debugger::Debugger;
+use crate::module::Module;
+use crate::process::{Process, StopReason, StoppointMode};
+use crate::registers::Registers;
+use crate::rsp::{
+    from_hex, gdb_signal_number, parse_hex, read_remote_register, receive, remote_register_write,
+    signal_from_gdb, target_description, to_hex, xfer_reply, Connection, Received, INTERRUPT,
+    REMOTE_REGISTERS,
+};
+use crate::{maps, solib};
+use anyhow::{anyhow, bail, Result};
+use nix::libc::{EACCES, EBADF, EIO, EMFILE};
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read};
+use std::os::unix::fs::FileExt;
+use std::path::{Component, Path};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+// The packets of the remote protocol which a traced process is served with, so that gdb, lldb
+// and other clients can debug through kitt. Only all-stop mode is supported: while the process
+// runs, the only packet expected is an interrupt.
+
+const FEATURES: &str = "PacketSize=4000;QStartNoAckMode+;qXfer:features:read+;qXfer:auxv:read+;\
+    qXfer:exec-file:read+;qXfer:libraries-svr4:read+;swbreak+;hwbreak+;vContSupported+";
+const PAGE_SIZE: u64 = 4096;
+// The most bytes a reply carries, whatever the client asks for. Data is hex encoded or escaped,
+// which can double it, within the PacketSize of 0x4000 in FEATURES.
+const MAX_DATA: usize = 0x4000 / 2;
+// How many files a client may hold open through host I/O at once
+const MAX_FILES: usize = 16;
+
+/// Serves a process to one client over the remote protocol
+pub struct Server {
+    process: Process,
+    connection: Box<dyn Connection>,
+    // Whether the process was attached to rather than launched
+    attached: bool,
+    acknowledge: bool,
+    // The executable, whose dynamic section leads to the link map of the dynamic linker
+    executable: Module,
+    // The thread whose registers `g` and `p` read, chosen with `Hg`
+    thread: Pid,
+    // Debug registers in use, by the type of the Z packet which set them and the address
+    hardware_stoppoints: HashMap<(u8, u64), usize>,
+    last_stop: Vec<u8>,
+    // Files opened by clients through host I/O, by descriptor
+    files: HashMap<u64, File>,
+    done: bool,
+}
+
+// Splits `addr,length` as used by the memory and breakpoint packets
+fn address_and_length(text: &str) -> Result<(u64, usize)> {
+    let (address, length) = text
+        .split_once(',')
+        .ok_or_else(|| anyhow!("expected <address>,<length> in {text}"))?;
+    Ok((parse_hex(address)?, parse_hex(length)? as usize))
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Server {
+    /// A server for a stopped process, which was attached to rather than launched when
+    /// `attached` is set
+    pub fn new(process: Process, attached: bool, connection: Box<dyn Connection>) -> Result<Self> {
+        let executable = Debugger::load_modules(&process)?.remove(0);
+        let thread = process.pid;
+        Ok(Self {
+            process,
+            connection,
+            attached,
+            acknowledge: true,
+            executable,
+            thread,
+            hardware_stoppoints: HashMap::new(),
+            last_stop: format!("T05thread:{:x};", thread.as_raw()).into_bytes(),
+            files: HashMap::new(),
+            done: false,
+        })
+    }
+
+    /// Answers packets until the client detaches or kills the process, or hangs up
+    pub fn serve(&mut self) -> Result<()> {
+        while !self.done {
+            let packet = match receive(&mut *self.connection, self.acknowledge) {
+                Ok(Received::Packet(packet)) => packet,
+                Ok(_) => continue,
+                Err(err)
+                    if err
+                        .downcast_ref::<std::io::Error>()
+                        .is_some_and(|err| err.kind() == ErrorKind::UnexpectedEof) =>
+                {
+                    return Ok(());
+                }
+                Err(err) => return Err(err),
+            };
+            let reply = match self.handle(&packet) {
+                Ok(Some(reply)) => reply,
+                Ok(None) => continue,
+                Err(_) => b"E01".to_vec(),
+            };
+            self.send(&reply)?;
+            if packet == b"QStartNoAckMode" {
+                self.acknowledge = false;
+            }
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, data: &[u8]) -> Result<()> {
+        let packet = super::frame(data);
+        loop {
+            self.connection.write_all(&packet)?;
+            if !self.acknowledge {
+                return Ok(());
+            }
+            match receive(&mut *self.connection, false)? {
+                Received::Nack => continue,
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    // The reply to a packet, or None for packets which have none
+    fn handle(&mut self, packet: &[u8]) -> Result<Option<Vec<u8>>> {
+        // Only the data of X packets is binary
+        if let Some(rest) = packet.strip_prefix(b"X") {
+            let colon = rest
+                .iter()
+                .position(|&b| b == b':')
+                .ok_or_else(|| anyhow!("X packet without data"))?;
+            let (address, _) = address_and_length(std::str::from_utf8(&rest[..colon])?)?;
+            self.process
+                .write_memory_around_sites(address, &rest[colon + 1..])?;
+            return Ok(Some(b"OK".to_vec()));
+        }
+        let packet = std::str::from_utf8(packet)?;
+        let reply = match packet.split_at(packet.len().min(1)) {
+            ("?", _) => self.last_stop.clone(),
+            ("g", _) => self.read_registers()?.into_bytes(),
+            ("G", hex) => {
+                self.write_registers(&from_hex(hex)?)?;
+                b"OK".to_vec()
+            }
+            ("p", number) => {
+                let register = REMOTE_REGISTERS
+                    .get(parse_hex(number)? as usize)
+                    .ok_or_else(|| anyhow!("no register {number}"))?;
+                let registers = self.thread_registers()?;
+                to_hex(&read_remote_register(&registers, register)?).into_bytes()
+            }
+            ("P", assignment) => {
+                let (number, hex) = assignment
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("expected <register>=<value>"))?;
+                self.write_register(parse_hex(number)? as usize, &from_hex(hex)?)?;
+                b"OK".to_vec()
+            }
+            ("m", range) => {
+                let (address, length) = address_and_length(range)?;
+                to_hex(&self.read_memory(address, length)?).into_bytes()
+            }
+            ("M", rest) => {
+                let (range, hex) = rest
+                    .split_once(':')
+                    .ok_or_else(|| anyhow!("M packet without data"))?;
+                let (address, _) = address_and_length(range)?;
+                self.process
+                    .write_memory_around_sites(address, &from_hex(hex)?)?;
+                b"OK".to_vec()
+            }
+            ("Z", rest) => self.set_stoppoint(rest, true)?,
+            ("z", rest) => self.set_stoppoint(rest, false)?,
+            ("c", address) => self.resume(false, None, address)?,
+            ("s", address) => self.resume(true, None, address)?,
+            ("C", rest) | ("S", rest) => {
+                let (signal, address) = rest.split_once(';').unwrap_or((rest, ""));
+                let signal = signal_from_gdb(parse_hex(signal)? as u8);
+                self.resume(packet.starts_with('S'), signal, address)?
+            }
+            ("H", rest) => {
+                // Hc picks the thread to resume, but only the traced thread ever runs alone
+                let tid = rest.get(1..).unwrap_or_default();
+                if rest.starts_with('g') {
+                    self.thread = match tid {
+                        "0" | "-1" | "" => self.process.pid,
+                        tid => Pid::from_raw(parse_hex(tid)? as i32),
+                    };
+                }
+                b"OK".to_vec()
+            }
+            ("T", tid) => {
+                let tid = Pid::from_raw(parse_hex(tid)? as i32);
+                match self.process.threads()?.contains(&tid) {
+                    true => b"OK".to_vec(),
+                    false => b"E01".to_vec(),
+                }
+            }
+            ("k", _) => {
+                // The process may already have exited
+                _ = self.process.kill();
+                self.done = true;
+                return Ok(None);
+            }
+            ("D", _) => {
+                self.process.leave_running();
+                self.done = true;
+                b"OK".to_vec()
+            }
+            ("v", _) => self.handle_v_packet(packet)?,
+            ("q", _) | ("Q", _) => self.handle_query(packet)?,
+            _ => Vec::new(),
+        };
+        Ok(Some(reply))
+    }
+
+    fn handle_v_packet(&mut self, packet: &str) -> Result<Vec<u8>> {
+        if packet == "vCont?" {
+            return Ok(b"vCont;c;C;s;S".to_vec());
+        }
+        if let Some(request) = packet.strip_prefix("vFile:") {
+            return self.host_io(request);
+        }
+        let Some(actions) = packet.strip_prefix("vCont;") else {
+            return Ok(Vec::new());
+        };
+        // The action for the traced thread, which is either named or the default
+        let pid = self.process.pid;
+        let action = actions
+            .split(';')
+            .find(|action| match action.split_once(':') {
+                Some((_, tid)) => tid == "-1" || parse_hex(tid).ok() == Some(pid.as_raw() as u64),
+                None => true,
+            })
+            .ok_or_else(|| anyhow!("no action for thread {pid}"))?;
+        let action = action.split(':').next().unwrap_or_default();
+        let (kind, signal) = action.split_at(1);
+        let signal = match signal {
+            "" => None,
+            signal => signal_from_gdb(parse_hex(signal)? as u8),
+        };
+        match kind {
+            "c" | "C" => self.resume(false, signal, ""),
+            "s" | "S" => self.resume(true, signal, ""),
+            _ => bail!("unsupported vCont action {action}"),
+        }
+    }
+
+    fn handle_query(&mut self, packet: &str) -> Result<Vec<u8>> {
+        let reply = match packet {
+            _ if packet.starts_with("qSupported") => FEATURES.to_string(),
+            "QStartNoAckMode" => "OK".to_string(),
+            "qAttached" => if self.attached { "1" } else { "0" }.to_string(),
+            "qC" => format!("QC{:x}", self.process.pid.as_raw()),
+            "qfThreadInfo" => {
+                let threads: Vec<String> = self
+                    .process
+                    .threads()?
+                    .iter()
+                    .map(|tid| format!("{:x}", tid.as_raw()))
+                    .collect();
+                format!("m{}", threads.join(","))
+            }
+            "qsThreadInfo" => "l".to_string(),
+            "qSymbol::" => "OK".to_string(),
+            _ if packet.starts_with("qXfer:") => return self.handle_xfer(packet),
+            _ => String::new(),
+        };
+        Ok(reply.into_bytes())
+    }
+
+    // Whether host I/O may open a path: only the files of the process itself, which are those
+    // under its /proc directory and the objects it maps. Anything else on the system stays out
+    // of reach of clients.
+    fn may_open(&self, path: &str) -> bool {
+        let path = Path::new(path);
+        if path
+            .components()
+            .any(|component| component == Component::ParentDir)
+        {
+            return false;
+        }
+        if path.starts_with(format!("/proc/{}", self.process.pid)) {
+            return true;
+        }
+        maps::read_maps(self.process.pid).is_ok_and(|regions| {
+            regions
+                .iter()
+                .any(|region| region.path.starts_with('/') && Path::new(&region.path) == path)
+        })
+    }
+
+    // Host I/O, which clients use to read files such as the memory map of the process. Files
+    // are only opened for reading, only the process's own files can be, and no more than
+    // MAX_FILES at once. Results are F<result>, or F-1,<errno> on failure.
+    fn host_io(&mut self, request: &str) -> Result<Vec<u8>> {
+        let (operation, arguments) = request.split_once(':').unwrap_or((request, ""));
+        let arguments: Vec<&str> = arguments.split(',').collect();
+        let failure = |err: std::io::Error| format!("F-1,{:x}", err.raw_os_error().unwrap_or(EIO));
+        let reply = match (operation, &arguments[..]) {
+            ("open", [path, ..]) => {
+                let path = String::from_utf8(from_hex(path)?)?;
+                if !self.may_open(&path) {
+                    return Ok(format!("F-1,{EACCES:x}").into_bytes());
+                }
+                if self.files.len() >= MAX_FILES {
+                    return Ok(format!("F-1,{EMFILE:x}").into_bytes());
+                }
+                match File::open(path) {
+                    Ok(file) => {
+                        let fd = (0..).find(|fd| !self.files.contains_key(fd)).unwrap();
+                        self.files.insert(fd, file);
+                        format!("F{fd:x}")
+                    }
+                    Err(err) => failure(err),
+                }
+            }
+            ("pread", [fd, count, offset]) => {
+                let file = self
+                    .files
+                    .get(&parse_hex(fd)?)
+                    .ok_or_else(|| anyhow!("no file {fd}"))?;
+                let count = (parse_hex(count)? as usize).min(MAX_DATA);
+                let mut data = vec![0; count];
+                match file.read_at(&mut data, parse_hex(offset)?) {
+                    Ok(read) => {
+                        let mut reply = format!("F{read:x};").into_bytes();
+                        reply.extend(&data[..read]);
+                        return Ok(reply);
+                    }
+                    Err(err) => failure(err),
+                }
+            }
+            ("close", [fd]) => match self.files.remove(&parse_hex(fd)?) {
+                Some(_) => "F0".to_string(),
+                None => format!("F-1,{EBADF:x}"),
+            },
+            _ => String::new(),
+        };
+        Ok(reply.into_bytes())
+    }
+
+    // qXfer:<object>:read:<annex>:<offset>,<length>
+    fn handle_xfer(&mut self, packet: &str) -> Result<Vec<u8>> {
+        let fields: Vec<&str> = packet.splitn(5, ':').collect();
+        let [_, object, "read", annex, range] = fields[..] else {
+            return Ok(Vec::new());
+        };
+        let (offset, length) = address_and_length(range)?;
+        let data = match (object, annex) {
+            ("features", "target.xml") => target_description().into_bytes(),
+            ("auxv", "") => fs::read(format!("/proc/{}/auxv", self.process.pid))?,
+            ("exec-file", _) => self
+                .process
+                .executable_path()?
+                .to_string_lossy()
+                .into_owned()
+                .into_bytes(),
+            ("libraries-svr4", "") => self.libraries_svr4()?.into_bytes(),
+            _ => return Ok(b"E00".to_vec()),
+        };
+        Ok(xfer_reply(&data, offset as usize, length))
+    }
+
+    // The loaded objects as listed by the link map of the dynamic linker, which is empty until
+    // the linker has set it up
+    fn libraries_svr4(&self) -> Result<String> {
+        let empty = "<library-list-svr4 version=\"1.0\"/>".to_string();
+        let Some(address) = solib::find_r_debug(&self.process, &self.executable)? else {
+            return Ok(empty);
+        };
+        let r_debug = solib::read_r_debug(&self.process, address)?;
+        let entries = solib::read_link_map(&self.process, &r_debug)?;
+        let Some((main, libraries)) = entries.split_first() else {
+            return Ok(empty);
+        };
+        let mut xml = format!(
+            "<library-list-svr4 version=\"1.0\" main-lm=\"{:#x}\">",
+            main.address
+        );
+        for library in libraries {
+            _ = write!(
+                xml,
+                "<library name=\"{}\" lm=\"{:#x}\" l_addr=\"{:#x}\" l_ld=\"{:#x}\"/>",
+                xml_escape(&library.path),
+                library.address,
+                library.load_bias,
+                library.dynamic
+            );
+        }
+        xml.push_str("</library-list-svr4>");
+        Ok(xml)
+    }
+
+    // The registers of the thread chosen with Hg. Threads other than the traced one are read
+    // through a brief attach.
+    fn thread_registers(&self) -> Result<Registers> {
+        if self.thread == self.process.pid {
+            return Ok(self.process.registers().clone());
+        }
+        let stopped = self.process.stop_threads()?;
+        let thread = self
+            .process
+            .thread_registers(&stopped)?
+            .into_iter()
+            .find(|thread| thread.tid == self.thread)
+            .ok_or_else(|| anyhow!("no thread {}", self.thread))?;
+        let mut registers = Registers::default();
+        let mut user = registers.user_data();
+        user.regs = thread.regs;
+        user.i387 = thread.fp_regs;
+        registers.set_user_data(user);
+        Ok(registers)
+    }
+
+    fn read_registers(&self) -> Result<String> {
+        let registers = self.thread_registers()?;
+        let mut hex = String::new();
+        for register in &REMOTE_REGISTERS {
+            hex.push_str(&to_hex(&read_remote_register(&registers, register)?));
+        }
+        Ok(hex)
+    }
+
+    fn write_register(&mut self, number: usize, data: &[u8]) -> Result<()> {
+        if self.thread != self.process.pid {
+            bail!("only the registers of the traced thread can be written");
+        }
+        let register = REMOTE_REGISTERS
+            .get(number)
+            .ok_or_else(|| anyhow!("no register {number}"))?;
+        let (info, bytes) = remote_register_write(self.process.registers(), register, data)?;
+        self.process.write_register(info, &bytes)
+    }
+
+    // Writes the registers whose values differ from the current ones
+    fn write_registers(&mut self, data: &[u8]) -> Result<()> {
+        let mut offset = 0;
+        for (number, register) in REMOTE_REGISTERS.iter().enumerate() {
+            let size = register.bits / 8;
+            let Some(value) = data.get(offset..offset + size) else {
+                break;
+            };
+            offset += size;
+            if read_remote_register(self.process.registers(), register)? != value {
+                self.write_register(number, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Reads as much of the range as is mapped, stopping at the first page which is not and at
+    // the end of the address space
+    fn read_memory(&self, address: u64, length: usize) -> Result<Vec<u8>> {
+        let length = length.min(MAX_DATA);
+        let mut data = Vec::with_capacity(length);
+        while data.len() < length {
+            let Some(start) = address.checked_add(data.len() as u64) else {
+                break;
+            };
+            let amount = ((PAGE_SIZE - start % PAGE_SIZE) as usize).min(length - data.len());
+            match self.process.read_memory_without_sites(start, amount) {
+                Ok(chunk) => data.extend(chunk),
+                Err(_) if !data.is_empty() => break,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(data)
+    }
+
+    // Z<type>,<address>,<kind> inserts and z removes: 0 is a software breakpoint, 1 a hardware
+    // one, and 2 and 4 write and access watchpoints. x86 cannot watch reads alone, so 3 is not
+    // supported.
+    fn set_stoppoint(&mut self, text: &str, insert: bool) -> Result<Vec<u8>> {
+        let mut fields = text.split(',');
+        let (Some(kind), Some(address), Some(size)) = (fields.next(), fields.next(), fields.next())
+        else {
+            bail!("expected <type>,<address>,<kind>");
+        };
+        let (address, size) = (parse_hex(address)?, parse_hex(size)? as usize);
+        let kind: u8 = kind.parse()?;
+        let mode = match kind {
+            0 => {
+                match insert {
+                    true => self.process.add_breakpoint_site(address)?,
+                    false => self.process.remove_breakpoint_site(address)?,
+                }
+                return Ok(b"OK".to_vec());
+            }
+            1 => StoppointMode::Execute,
+            2 => StoppointMode::Write,
+            4 => StoppointMode::ReadWrite,
+            _ => return Ok(Vec::new()),
+        };
+        if insert {
+            let index = self.process.set_hardware_stoppoint(address, mode, size)?;
+            self.hardware_stoppoints.insert((kind, address), index);
+        } else if let Some(index) = self.hardware_stoppoints.remove(&(kind, address)) {
+            self.process.clear_hardware_stoppoint(index)?;
+        }
+        Ok(b"OK".to_vec())
+    }
+
+    // Resumes or steps the process, from a new pc when one is given, and waits for it to stop
+    fn resume(&mut self, step: bool, signal: Option<Signal>, address: &str) -> Result<Vec<u8>> {
+        if !address.is_empty() {
+            self.process.set_pc(parse_hex(address)?)?;
+        }
+        self.process.set_pending_signal(signal);
+        self.thread = self.process.pid;
+        let reason = if step {
+            self.process.step_instruction()?
+        } else {
+            self.process.resume()?;
+            self.wait_interruptibly()?
+        };
+        self.last_stop = self.stop_reply(&reason)?;
+        Ok(self.last_stop.clone())
+    }
+
+    // Waits for the process to stop, meanwhile watching the connection for an interrupt, which
+    // stops the process with SIGINT
+    fn wait_interruptibly(&mut self) -> Result<StopReason> {
+        let running = Arc::new(AtomicBool::new(true));
+        let mut watcher = self.connection.try_clone_connection()?;
+        watcher.set_timeout(Some(Duration::from_millis(50)))?;
+        let interrupter = self.process.interrupter();
+        let watching = {
+            let running = running.clone();
+            thread::spawn(move || {
+                let mut byte = [0];
+                while running.load(Ordering::Relaxed) {
+                    match watcher.read(&mut byte) {
+                        Ok(1) if byte[0] == INTERRUPT => _ = interrupter.interrupt(),
+                        Ok(0) => break,
+                        _ => {}
+                    }
+                }
+            })
+        };
+        let reason = self.process.wait_on_signal();
+        running.store(false, Ordering::Relaxed);
+        _ = watching.join();
+        // The timeout belongs to the socket rather than to the clone
+        self.connection.set_timeout(None)?;
+        reason
+    }
+
+    fn stop_reply(&mut self, reason: &StopReason) -> Result<Vec<u8>> {
+        if let Some(code) = reason.exit_code() {
+            return Ok(format!("W{:02x}", code as u8).into_bytes());
+        }
+        let signal = reason.signal().unwrap_or(Signal::SIGTRAP);
+        if !reason.is_stopped() {
+            return Ok(format!("X{:02x}", gdb_signal_number(signal)).into_bytes());
+        }
+        let mut reply = format!(
+            "T{:02x}thread:{:x};",
+            gdb_signal_number(signal),
+            self.process.pid.as_raw()
+        );
+        if signal == Signal::SIGTRAP {
+            for index in self.process.triggered_hardware_stoppoints()? {
+                let Some((&(kind, address), _)) = self
+                    .hardware_stoppoints
+                    .iter()
+                    .find(|&(_, &used)| used == index)
+                else {
+                    continue;
+                };
+                match kind {
+                    1 => reply.push_str("hwbreak:;"),
+                    2 => _ = write!(reply, "watch:{address:x};"),
+                    _ => _ = write!(reply, "awatch:{address:x};"),
+                }
+            }
+            if self.process.has_breakpoint_site(self.process.pc()?) {
+                reply.push_str("swbreak:;");
+            }
+        }
+        Ok(reply.into_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::elf::Elf;
+    use crate::module::Module;
+    use crate::process::{DebugProcess, Process};
+    use crate::rsp::server::Server;
+    use crate::rsp::{frame, receive, to_hex, Connection, Received};
+    use nix::libc::AT_ENTRY;
+    use std::fs;
+    use std::io::Write;
+    use std::net::{TcpListener, TcpStream};
+    use std::os::unix::ffi::OsStrExt;
+    use std::thread;
+
+    // Sends a packet and returns the reply, acknowledging it unless in no-ack mode. The
+    // acknowledgement of the packet sent is skipped.
+    fn exchange(connection: &mut TcpStream, packet: &str, acknowledge: bool) -> Vec<u8> {
+        connection.write_all(&frame(packet.as_bytes())).unwrap();
+        loop {
+            if let Received::Packet(reply) = receive(connection, acknowledge).unwrap() {
+                return reply;
+            }
+        }
+    }
+
+    fn request(connection: &mut TcpStream, packet: &str) -> Vec<u8> {
+        exchange(connection, packet, false)
+    }
+
+    fn request_text(connection: &mut TcpStream, packet: &str) -> String {
+        String::from_utf8(request(connection, packet)).unwrap()
+    }
+
+    #[test]
+    fn gdb_packets_over_a_loopback_socket() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        // The process must be traced by the thread which serves it
+        let server = thread::spawn(move || {
+            let (connection, _) = listener.accept().unwrap();
+            let process = Process::launch("target/debug/variables", DebugProcess::YES).unwrap();
+            let mut server = Server::new(process, false, Box::new(connection)).unwrap();
+            server.serve().unwrap();
+        });
+        let mut client = TcpStream::connect(address).unwrap();
+        client.set_timeout(None).unwrap();
+
+        // Acknowledgements are exchanged until no-ack mode starts
+        let features = exchange(&mut client, "qSupported:multiprocess+", true);
+        assert!(String::from_utf8(features).unwrap().contains("swbreak+"));
+        assert_eq!(exchange(&mut client, "QStartNoAckMode", true), b"OK");
+
+        let stop = request_text(&mut client, "?");
+        let pid = stop
+            .strip_prefix("T05thread:")
+            .and_then(|thread| thread.strip_suffix(';'))
+            .map(|thread| i32::from_str_radix(thread, 16).unwrap())
+            .unwrap();
+        let registers = request_text(&mut client, "g");
+        assert_eq!(
+            registers.len(),
+            2 * (17 * 8 + 7 * 4 + 8 * 10 + 8 * 4 + 16 * 16 + 4 + 8)
+        );
+        let description = request_text(&mut client, "qXfer:features:read:target.xml:0,fff");
+        assert!(description[1..].starts_with("<?xml"), "{description}");
+
+        // Find where inspect was loaded from the entry point in the auxiliary vector
+        let auxv = request(&mut client, "qXfer:auxv:read::0,1000");
+        let entry = auxv[1..]
+            .chunks_exact(16)
+            .find(|pair| u64::from_le_bytes(pair[..8].try_into().unwrap()) == AT_ENTRY)
+            .map(|pair| u64::from_le_bytes(pair[8..].try_into().unwrap()))
+            .unwrap();
+        let bias = entry - Elf::open("target/debug/variables").unwrap().entry();
+        let module = Module::load("target/debug/variables", bias).unwrap();
+        let inspect = module.symbols.lookup("variables::inspect")[0].address + bias;
+
+        let original = request_text(&mut client, &format!("m{inspect:x},1"));
+        assert_eq!(
+            request_text(&mut client, &format!("Z0,{inspect:x},1")),
+            "OK"
+        );
+        // The int3 is hidden from reads
+        assert_eq!(
+            request_text(&mut client, &format!("m{inspect:x},1")),
+            original
+        );
+        // Replies hold no more than fits in a packet, however much is asked for
+        let code = request_text(&mut client, &format!("m{inspect:x},ffffffffffffffff"));
+        assert!(!code.is_empty() && code.len() <= 0x4000, "{}", code.len());
+        assert!(request_text(&mut client, "mfffffffffffffff0,20").starts_with('E'));
+        let path = fs::canonicalize("target/debug/variables").unwrap();
+        let open = format!("vFile:open:{},0,0", to_hex(path.as_os_str().as_bytes()));
+        let fd = request_text(&mut client, &open)[1..].to_string();
+        let read = request(&mut client, &format!("vFile:pread:{fd},ffffffffffffffff,0"));
+        assert!(read.starts_with(b"F2000;"), "{:?}", &read[..8]);
+        assert_eq!(
+            request_text(&mut client, &format!("vFile:close:{fd}")),
+            "F0"
+        );
+        // Only the files of the process open, and only so many at once
+        let open = |path: &str| format!("vFile:open:{},0,0", to_hex(path.as_bytes()));
+        assert_eq!(request_text(&mut client, &open("/etc/passwd")), "F-1,d");
+        let escape = format!("/proc/{pid}/../1/environ");
+        assert_eq!(request_text(&mut client, &open(&escape)), "F-1,d");
+        let maps = format!("/proc/{pid}/maps");
+        for fd in 0..16 {
+            assert_eq!(request_text(&mut client, &open(&maps)), format!("F{fd:x}"));
+        }
+        assert_eq!(request_text(&mut client, &open(&maps)), "F-1,18");
+        for fd in 0..16 {
+            request(&mut client, &format!("vFile:close:{fd:x}"));
+        }
+        let stop = request_text(&mut client, "vCont;c");
+        assert!(
+            stop.starts_with("T05") && stop.ends_with("swbreak:;"),
+            "{stop}"
+        );
+        let rip = request_text(&mut client, "p10");
+        assert_eq!(rip, to_hex(&inspect.to_le_bytes()));
+
+        let libraries = request_text(&mut client, "qXfer:libraries-svr4:read::0,fff");
+        assert!(libraries.contains("libc.so"), "{libraries}");
+
+        // Past the breakpoint to the int3 of the program, and on to the exit
+        assert_eq!(
+            request_text(&mut client, &format!("z0,{inspect:x},1")),
+            "OK"
+        );
+        assert!(request_text(&mut client, "c").starts_with("T05"));
+        assert_eq!(request_text(&mut client, "c"), "W00");
+        // Kill has no reply, and ends the session
+        client.write_all(&frame(b"k")).unwrap();
+        drop(client);
+        server.join().unwrap();
+    }
+}
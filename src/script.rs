@@ -0,0 +1,349 @@
+use crate::console::{self, Console};
+use crate::handle_command;
+use anyhow::{anyhow, bail, Result};
+use kitt::debugger::Debugger;
+use kitt::process::StopReason;
+use kitt::reginfo::{lookup_register_info_by_name, RegisterInfo};
+use rhai::{Array, Blob, Dynamic, Engine, EvalAltResult, FnPtr, Map, NativeCallContext};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+// Rhai scripts which drive the debugger. The engine only takes functions which own what they
+// use, so a script cannot borrow the debugger. It runs on a thread of its own instead and sends
+// what it asks of the debugger to the thread which owns it, which is also the one tracing the
+// process as ptrace requires.
+//
+// Scripts get these functions besides Rhai's own:
+//   command(line)                    runs a kitt command
+//   reg(name), set_reg(name, value)  read and write registers of up to 64 bits
+//   read_memory(address, length)     reads a blob of memory
+//   read_u64(address)                reads a 64 bit word
+//   write_memory(address, blob)
+//   break_at(location [, callback])  sets a breakpoint, returning its id
+//   delete_break(id)
+//   cont(), stepi(), nexti()         run the process, returning how it stopped
+//   evaluate(expression)             the value of an expression, as `print` shows it
+//   describe(address)                the symbol and object of an address
+//
+// Stops are maps with the `event`, `pid`, `pc`, `location` and `reason` of the events of the
+// JSON interpreter. `cont` calls the callback of a breakpoint it stops at with the stop, and
+// carries on unless the callback returns false.
+
+// What a script asks of the debugger
+enum Request {
+    Print(String),
+    Command(String),
+    ReadRegister(String),
+    WriteRegister(String, u64),
+    ReadMemory(u64, usize),
+    WriteMemory(u64, Vec<u8>),
+    SetBreakpoint(String),
+    DeleteBreakpoint(usize),
+    Continue,
+    StepInstruction,
+    StepOverInstruction,
+    Evaluate(String),
+    Describe(u64),
+}
+
+enum Reply {
+    Done,
+    Integer(u64),
+    Text(String),
+    Bytes(Vec<u8>),
+    Stop(Value),
+}
+
+// The script's end of the channels. Every request gets a reply, with errors as their messages.
+struct Client {
+    requests: Sender<Request>,
+    replies: Receiver<Result<Reply, String>>,
+}
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+impl Client {
+    fn call(&self, request: Request) -> ScriptResult<Reply> {
+        let gone = || Box::<EvalAltResult>::from("the debugger has gone");
+        self.requests.send(request).map_err(|_| gone())?;
+        self.replies.recv().map_err(|_| gone())?.map_err(Into::into)
+    }
+
+    fn integer(&self, request: Request) -> ScriptResult<i64> {
+        match self.call(request)? {
+            Reply::Integer(value) => Ok(value as i64),
+            _ => Err("expected an integer from the debugger".into()),
+        }
+    }
+
+    fn text(&self, request: Request) -> ScriptResult<String> {
+        match self.call(request)? {
+            Reply::Text(text) => Ok(text),
+            _ => Err("expected text from the debugger".into()),
+        }
+    }
+
+    fn stop(&self, request: Request) -> ScriptResult<Map> {
+        match self.call(request)? {
+            Reply::Stop(stop) => Ok(to_dynamic(stop).cast()),
+            _ => Err("expected a stop from the debugger".into()),
+        }
+    }
+}
+
+// Runs the script at `path` until it ends, serving its requests
+pub fn run(console: &mut Console, debugger: &mut Debugger, path: &Path) -> Result<()> {
+    let source =
+        fs::read_to_string(path).map_err(|err| anyhow!("cannot read {}: {err}", path.display()))?;
+    let (requests, incoming) = mpsc::channel();
+    let (outgoing, replies) = mpsc::channel();
+    let script = thread::spawn(move || {
+        let engine = engine(Client { requests, replies });
+        engine.run(&source).map_err(|err| err.to_string())
+    });
+    for request in incoming {
+        let reply = serve(console, debugger, request).map_err(|err| err.to_string());
+        if outgoing.send(reply).is_err() {
+            break;
+        }
+    }
+    match script.join() {
+        Ok(result) => result.map_err(|err| anyhow!("{}: {err}", path.display())),
+        Err(_) => bail!("{}: the script panicked", path.display()),
+    }
+}
+
+fn serve(console: &mut Console, debugger: &mut Debugger, request: Request) -> Result<Reply> {
+    Ok(match request {
+        Request::Print(text) => {
+            writeln!(console, "{text}")?;
+            Reply::Done
+        }
+        Request::Command(line) => {
+            handle_command(console, debugger, &line)?;
+            Reply::Done
+        }
+        Request::ReadRegister(name) => Reply::Integer(
+            debugger
+                .target()
+                .registers()
+                .read_as_u64(register(&name)?)?,
+        ),
+        Request::WriteRegister(name, value) => {
+            let info = register(&name)?;
+            if info.size > 8 {
+                bail!("register {} is too wide to write as an integer", info.name);
+            }
+            debugger
+                .target_mut()
+                .write_register(info, &value.to_le_bytes()[..info.size])?;
+            Reply::Done
+        }
+        Request::ReadMemory(address, length) => {
+            Reply::Bytes(debugger.target().read_memory(address, length)?)
+        }
+        Request::WriteMemory(address, data) => {
+            debugger.target_mut().write_memory(address, &data)?;
+            Reply::Done
+        }
+        Request::SetBreakpoint(location) => {
+            Reply::Integer(debugger.set_breakpoint(&location)?.id as u64)
+        }
+        Request::DeleteBreakpoint(id) => {
+            debugger.delete_breakpoint(id)?;
+            Reply::Done
+        }
+        Request::Continue => {
+            let reason = debugger.continue_execution()?;
+            stop(debugger, &reason)?
+        }
+        Request::StepInstruction => {
+            let reason = debugger.step_instruction()?;
+            stop(debugger, &reason)?
+        }
+        Request::StepOverInstruction => {
+            let reason = debugger.step_over_instruction()?;
+            stop(debugger, &reason)?
+        }
+        Request::Evaluate(text) => {
+            let value = debugger.evaluate(&text)?;
+            Reply::Text(debugger.format_value(&value)?)
+        }
+        Request::Describe(address) => Reply::Text(debugger.describe_address(address)),
+    })
+}
+
+fn register(name: &str) -> Result<&'static RegisterInfo> {
+    lookup_register_info_by_name(&name.trim_start_matches('$').to_ascii_uppercase())
+        .map_err(|_| anyhow!("no register named {name}"))
+}
+
+// The stop as the JSON interpreter reports it, but with the pc as a number for arithmetic
+fn stop(debugger: &Debugger, reason: &StopReason) -> Result<Reply> {
+    let mut stop = console::stop_event(debugger, reason)?;
+    if let Some(object) = stop.as_object_mut() {
+        object.remove("type");
+        if reason.is_stopped() {
+            object.insert("pc".to_string(), debugger.pc()?.into());
+        }
+    }
+    Ok(Reply::Stop(stop))
+}
+
+fn to_dynamic(value: Value) -> Dynamic {
+    match value {
+        Value::Null => Dynamic::UNIT,
+        Value::Bool(value) => value.into(),
+        Value::Number(number) => match number.as_i64() {
+            Some(value) => value.into(),
+            None => (number.as_u64().unwrap_or_default() as i64).into(),
+        },
+        Value::String(text) => text.into(),
+        Value::Array(values) => values.into_iter().map(to_dynamic).collect::<Array>().into(),
+        Value::Object(fields) => fields
+            .into_iter()
+            .map(|(name, value)| (name.into(), to_dynamic(value)))
+            .collect::<Map>()
+            .into(),
+    }
+}
+
+fn engine(client: Client) -> Engine {
+    let client = Rc::new(client);
+    let callbacks: Rc<RefCell<HashMap<i64, FnPtr>>> = Rc::default();
+    let mut engine = Engine::new();
+
+    let c = client.clone();
+    engine.on_print(move |text| _ = c.call(Request::Print(text.to_string())));
+    let c = client.clone();
+    engine.register_fn("command", move |line: &str| {
+        c.call(Request::Command(line.to_string())).map(|_| ())
+    });
+
+    let c = client.clone();
+    engine.register_fn("reg", move |name: &str| {
+        c.integer(Request::ReadRegister(name.to_string()))
+    });
+    let c = client.clone();
+    engine.register_fn("set_reg", move |name: &str, value: i64| {
+        c.call(Request::WriteRegister(name.to_string(), value as u64))
+            .map(|_| ())
+    });
+
+    let c = client.clone();
+    engine.register_fn("read_memory", move |address: i64, length: i64| {
+        match c.call(Request::ReadMemory(address as u64, length.max(0) as usize))? {
+            Reply::Bytes(data) => Ok(data as Blob),
+            _ => Err::<_, Box<EvalAltResult>>("expected memory from the debugger".into()),
+        }
+    });
+    let c = client.clone();
+    engine.register_fn("read_u64", move |address: i64| {
+        match c.call(Request::ReadMemory(address as u64, 8))? {
+            Reply::Bytes(data) if data.len() == 8 => {
+                Ok(u64::from_le_bytes(data.try_into().unwrap()) as i64)
+            }
+            _ => Err::<_, Box<EvalAltResult>>("expected memory from the debugger".into()),
+        }
+    });
+    let c = client.clone();
+    engine.register_fn("write_memory", move |address: i64, data: Blob| {
+        c.call(Request::WriteMemory(address as u64, data))
+            .map(|_| ())
+    });
+
+    let c = client.clone();
+    engine.register_fn("break_at", move |location: &str| {
+        c.integer(Request::SetBreakpoint(location.to_string()))
+    });
+    let (c, saved) = (client.clone(), callbacks.clone());
+    engine.register_fn("break_at", move |location: &str, callback: FnPtr| {
+        let id = c.integer(Request::SetBreakpoint(location.to_string()))?;
+        saved.borrow_mut().insert(id, callback);
+        Ok::<_, Box<EvalAltResult>>(id)
+    });
+    let (c, saved) = (client.clone(), callbacks.clone());
+    engine.register_fn("delete_break", move |id: i64| {
+        saved.borrow_mut().remove(&id);
+        c.call(Request::DeleteBreakpoint(id as usize)).map(|_| ())
+    });
+
+    let c = client.clone();
+    engine.register_fn("cont", move |context: NativeCallContext| loop {
+        let stop = c.stop(Request::Continue)?;
+        let id = stop.get("breakpoint").and_then(|id| id.as_int().ok());
+        let callback = id.and_then(|id| callbacks.borrow().get(&id).cloned());
+        let Some(callback) = callback else {
+            return Ok::<_, Box<EvalAltResult>>(stop);
+        };
+        let carry_on: Dynamic = callback.call_within_context(&context, (stop.clone(),))?;
+        if carry_on.as_bool() == Ok(false) {
+            return Ok(stop);
+        }
+    });
+    let c = client.clone();
+    engine.register_fn("stepi", move || c.stop(Request::StepInstruction));
+    let c = client.clone();
+    engine.register_fn("nexti", move || c.stop(Request::StepOverInstruction));
+
+    let c = client.clone();
+    engine.register_fn("evaluate", move |text: &str| {
+        c.text(Request::Evaluate(text.to_string()))
+    });
+    engine.register_fn("describe", move |address: i64| {
+        client.text(Request::Describe(address as u64))
+    });
+    engine
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::console::Console;
+    use crate::script;
+    use kitt::debugger::Debugger;
+    use kitt::process::{DebugProcess, Process};
+    use std::fs;
+
+    #[test]
+    fn scripts_drive_the_debugger() {
+        let process = Process::launch("target/debug/variables", DebugProcess::YES).unwrap();
+        let mut debugger = Debugger::new(process).unwrap();
+        let path = std::env::temp_dir().join(format!("kitt-{}.rhai", std::process::id()));
+        fs::write(
+            &path,
+            r#"
+            let hits = 0;
+            break_at("variables::inspect", |stop| {
+                hits += 1;
+                print(`hit ${stop.breakpoint} at ${describe(stop.pc)}`);
+                false
+            });
+            let stop = cont();
+            if stop.pc != reg("rip") { throw "the pc is not rip"; }
+            set_reg("rax", 42);
+            print(`${hits} ${reg("rax")} ${read_memory(reg("rsp"), 8).len()}`);
+            stepi();
+            command("x/1xg $rsp");
+            "#,
+        )
+        .unwrap();
+        let mut console = Console::json();
+        let result = script::run(&mut console, &mut debugger, &path);
+        fs::remove_file(&path).unwrap();
+        result.unwrap();
+
+        let output = console.take_output();
+        let lines: Vec<_> = output.lines().collect();
+        assert!(lines[0].starts_with("hit 1 at "));
+        assert!(lines[0].ends_with("in variables::inspect"));
+        assert_eq!(lines[1], "1 42 8");
+        assert!(lines[2].starts_with("0x"));
+    }
+}
@@ -0,0 +1,107 @@
+use crate::dwarf::expr::MemoryReader;
+use crate::module::Module;
+use anyhow::Result;
+use bytemuck::pod_read_unaligned;
+
+// Shared library tracking through the rendezvous structure of the dynamic linker. The linker
+// keeps the loaded objects in a list reachable from its `r_debug`, and calls the function at
+// `r_brk` before and after it changes the list, which is where kitt keeps a breakpoint.
+
+const DT_NULL: u64 = 0;
+const DT_DEBUG: u64 = 21;
+
+/// The value of r_debug.r_state once the linker has finished changing the list
+pub const RT_CONSISTENT: u32 = 0;
+
+/// The symbol of the dynamic linker which r_brk points at, for when r_debug is not yet set up
+pub const DEBUG_STATE_SYMBOL: &str = "_dl_debug_state";
+pub const R_DEBUG_SYMBOL: &str = "_r_debug";
+
+/// The fields of struct r_debug from <link.h> which kitt uses
+pub struct RDebug {
+    pub map: u64,
+    pub brk: u64,
+    pub state: u32,
+}
+
+/// An entry of the link map: an object and the bias it was loaded at
+pub struct LinkMapEntry {
+    /// The address of the struct link_map itself
+    pub address: u64,
+    pub path: String,
+    pub load_bias: u64,
+    /// The address of the dynamic section of the object
+    pub dynamic: u64,
+}
+
+fn read_u64(memory: &dyn MemoryReader, address: u64) -> Result<u64> {
+    Ok(pod_read_unaligned(&memory.read_memory(address, 8)?))
+}
+
+pub fn read_r_debug(memory: &dyn MemoryReader, address: u64) -> Result<RDebug> {
+    let data = memory.read_memory(address, 32)?;
+    Ok(RDebug {
+        map: pod_read_unaligned(&data[8..16]),
+        brk: pod_read_unaligned(&data[16..24]),
+        state: pod_read_unaligned(&data[24..28]),
+    })
+}
+
+/// Reads a NUL terminated string from memory
+pub fn read_c_string(memory: &dyn MemoryReader, address: u64) -> Result<String> {
+    let mut bytes = Vec::new();
+    // Read in small chunks, since a larger read could run off the end of the mapping
+    loop {
+        let chunk = memory.read_memory(address + bytes.len() as u64, 16)?;
+        match chunk.iter().position(|&b| b == 0) {
+            Some(end) => {
+                bytes.extend(&chunk[..end]);
+                return Ok(String::from_utf8_lossy(&bytes).into_owned());
+            }
+            None => bytes.extend(chunk),
+        }
+    }
+}
+
+/// Walks the struct link_map list starting at r_debug.r_map
+pub fn read_link_map(memory: &dyn MemoryReader, r_debug: &RDebug) -> Result<Vec<LinkMapEntry>> {
+    let mut entries = Vec::new();
+    let mut node = r_debug.map;
+    while node != 0 {
+        let load_bias = read_u64(memory, node)?;
+        let name = read_u64(memory, node + 8)?;
+        let path = if name == 0 {
+            String::new()
+        } else {
+            read_c_string(memory, name)?
+        };
+        entries.push(LinkMapEntry {
+            address: node,
+            path,
+            load_bias,
+            dynamic: read_u64(memory, node + 16)?,
+        });
+        node = read_u64(memory, node + 24)?;
+    }
+    Ok(entries)
+}
+
+/// The address of r_debug as published in the DT_DEBUG entry of the dynamic section of the
+/// executable. The dynamic linker fills the entry in, so it is zero until the linker has run.
+pub fn find_r_debug(memory: &dyn MemoryReader, executable: &Module) -> Result<Option<u64>> {
+    let Some(dynamic) = executable.elf.section(".dynamic") else {
+        return Ok(None);
+    };
+    let start = dynamic.sh_addr + executable.load_bias;
+    for address in (start..start + dynamic.sh_size).step_by(16) {
+        match read_u64(memory, address)? {
+            DT_NULL => break,
+            DT_DEBUG => {
+                let value = read_u64(memory, address + 8)?;
+                return Ok((value != 0).then_some(value));
+            }
+            _ => {}
+        }
+    }
+    Ok(None)
+}
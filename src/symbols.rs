@@ -0,0 +1,205 @@
+use crate::elf::Elf;
+use anyhow::Result;
+use cpp_demangle::DemangleOptions;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+const STT_GNU_IFUNC: u8 = 10;
+const STB_GLOBAL: u8 = 1;
+const SHN_UNDEF: u16 = 0;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SymbolKind {
+    Function,
+    Object,
+}
+
+#[derive(Clone, Debug)]
+pub struct Symbol {
+    /// The name as it appears in the symbol table, possibly mangled
+    pub name: String,
+    pub demangled: Option<String>,
+    /// Link time address
+    pub address: u64,
+    pub size: u64,
+    pub kind: SymbolKind,
+    pub is_global: bool,
+}
+
+impl Symbol {
+    /// The name shown to users
+    pub fn display_name(&self) -> &str {
+        self.demangled.as_deref().unwrap_or(&self.name)
+    }
+}
+
+// The readable forms of a mangled name, the first of which is the one to show. Rust names are
+// also given with their hash, and C++ names without their parameter list, so that users can
+// refer to a symbol by either. Names which are not mangled have no forms.
+fn demangled_forms(name: &str) -> Vec<String> {
+    // Legacy Rust names are valid Itanium names too, so Rust is tried first. The alternate format
+    // leaves out the hash of legacy names and the crate disambiguators of v0 names.
+    if let Ok(demangled) = rustc_demangle::try_demangle(name) {
+        return vec![format!("{demangled:#}"), format!("{demangled}")];
+    }
+    if name.starts_with("_Z")
+        && let Ok(symbol) = cpp_demangle::Symbol::new(name)
+        && let Ok(demangled) = symbol.demangle()
+    {
+        let mut forms = vec![demangled];
+        if let Ok(short) = symbol.demangle_with_options(&DemangleOptions::new().no_params()) {
+            forms.push(short);
+        }
+        return forms;
+    }
+    Vec::new()
+}
+
+// Orders aliases such as `cbrt` and `cbrtf32x` so that global names, names without leading
+// underscores and shorter names are preferred
+fn alias_preference(symbol: &Symbol) -> (bool, bool, Reverse<usize>) {
+    (
+        symbol.is_global,
+        !symbol.name.starts_with('_'),
+        Reverse(symbol.name.len()),
+    )
+}
+
+/// Demangles legacy and v0 Rust names and Itanium C++ names
+pub fn demangle(name: &str) -> Option<String> {
+    demangled_forms(name).into_iter().next()
+}
+
+/// The function and object symbols of an object file, indexed by address and by every form of
+/// their names
+pub struct SymbolTable {
+    // Sorted by address
+    symbols: Vec<Symbol>,
+    by_name: HashMap<String, Vec<usize>>,
+}
+
+impl SymbolTable {
+    pub fn new(elf: &Elf) -> Result<Self> {
+        let mut symbols: Vec<Symbol> = elf
+            .symbols()?
+            .into_iter()
+            .filter(|(name, symbol)| !name.is_empty() && symbol.st_shndx != SHN_UNDEF)
+            .filter_map(|(name, symbol)| {
+                let kind = match symbol.st_info & 0xf {
+                    STT_FUNC | STT_GNU_IFUNC => SymbolKind::Function,
+                    STT_OBJECT => SymbolKind::Object,
+                    _ => return None,
+                };
+                Some(Symbol {
+                    demangled: demangle(&name),
+                    is_global: symbol.st_info >> 4 == STB_GLOBAL,
+                    name,
+                    address: symbol.st_value,
+                    size: symbol.st_size,
+                    kind,
+                })
+            })
+            .collect();
+        // Of the aliases at an address, the one to show sorts last, which is where a backwards
+        // search from a later address finds it first
+        symbols.sort_by_key(|symbol| (symbol.address, alias_preference(symbol)));
+
+        let mut by_name: HashMap<String, Vec<usize>> = HashMap::new();
+        for (index, symbol) in symbols.iter().enumerate() {
+            let mut names = demangled_forms(&symbol.name);
+            names.push(symbol.name.clone());
+            names.dedup();
+            for name in names {
+                by_name.entry(name).or_default().push(index);
+            }
+        }
+
+        Ok(Self { symbols, by_name })
+    }
+
+    /// Symbols with the given name, which may be mangled or demangled, and for Rust may leave out
+    /// the hash. Aliases at the same address are returned once.
+    pub fn lookup(&self, name: &str) -> Vec<&Symbol> {
+        let mut found: Vec<&Symbol> = Vec::new();
+        for &index in self.by_name.get(name).into_iter().flatten() {
+            let symbol = &self.symbols[index];
+            if !found.iter().any(|other| other.address == symbol.address) {
+                found.push(symbol);
+            }
+        }
+        found
+    }
+
+    /// The names the symbols are shown by, each of which `lookup` takes
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.symbols.iter().map(Symbol::display_name)
+    }
+
+    /// The symbol whose extent covers a link time address
+    pub fn symbol_containing(&self, address: u64) -> Option<&Symbol> {
+        let end = self.symbols.partition_point(|s| s.address <= address);
+        // Searching backwards finds a function even when smaller symbols, such as local labels,
+        // start inside it. Symbols without a size cover only their own address.
+        self.symbols[..end]
+            .iter()
+            .rev()
+            .find(|s| address < s.address + s.size.max(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::elf::Elf;
+    use crate::symbols::{demangle, SymbolKind, SymbolTable};
+
+    #[test]
+    fn names_are_demangled() {
+        assert_eq!(
+            demangle("_ZN9variables7inspect17hdc33eaa84cd76f29E").as_deref(),
+            Some("variables::inspect")
+        );
+        assert_eq!(
+            demangle("_RNvNtCs1234_7mycrate3foo3bar").as_deref(),
+            Some("mycrate::foo::bar")
+        );
+        assert_eq!(
+            demangle("_ZN2ns5Shape4areaEv").as_deref(),
+            Some("ns::Shape::area()")
+        );
+        assert_eq!(
+            demangle("_Z3addIiET_S0_S0_").as_deref(),
+            Some("int add<int>(int, int)")
+        );
+        assert_eq!(demangle("main"), None);
+        assert_eq!(demangle("_Zgarbage"), None);
+    }
+
+    #[test]
+    fn symbols_are_found_by_any_form_of_their_name() {
+        let elf = Elf::open("target/debug/variables").unwrap();
+        let symbols = SymbolTable::new(&elf).unwrap();
+
+        let by_demangled = symbols.lookup("variables::inspect");
+        assert_eq!(by_demangled.len(), 1);
+        let inspect = by_demangled[0];
+        assert_eq!(inspect.kind, SymbolKind::Function);
+        assert_eq!(inspect.display_name(), "variables::inspect");
+
+        let mangled = inspect.name.clone();
+        assert!(mangled.starts_with("_ZN9variables7inspect17h"));
+        assert_eq!(symbols.lookup(&mangled)[0].address, inspect.address);
+        // With the hash, as printed by the non alternate format of rustc-demangle
+        let hash = &mangled[mangled.len() - 18..mangled.len() - 1];
+        let with_hash = format!("variables::inspect::{hash}");
+        assert_eq!(symbols.lookup(&with_hash)[0].address, inspect.address);
+
+        let counter = symbols.lookup("variables::COUNTER");
+        assert!(counter.len() == 1 && counter[0].kind == SymbolKind::Object);
+        assert!(symbols.lookup("variables::no_such_function").is_empty());
+
+        let inside = symbols.symbol_containing(inspect.address + 4).unwrap();
+        assert_eq!(inside.display_name(), "variables::inspect");
+    }
+}
@@ -0,0 +1,1210 @@
+//! The x86-64 system calls, by name and number, and how strace shows their arguments.
+
+use crate::dwarf::expr::MemoryReader;
+use anyhow::{anyhow, Result};
+use nix::errno::Errno;
+use nix::sys::signal::Signal;
+use ArgKind::*;
+
+/// How an argument of a system call is shown
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum ArgKind {
+    /// A C int, of which only the low 32 bits are passed
+    Int,
+    Long,
+    Uint,
+    Hex,
+    /// File modes
+    Octal,
+    Fd,
+    /// A directory file descriptor, which may be AT_FDCWD
+    AtFd,
+    Path,
+    /// A string other than a path, such as the type of a file system
+    Str,
+    /// A buffer read by the kernel, whose size is the argument at index `length`
+    Buffer {
+        length: usize,
+    },
+    /// A buffer the kernel fills in, which holds nothing of interest at entry
+    OutBuffer,
+    Pointer,
+    /// A pointer to a structure of the named type
+    Struct(&'static str),
+    SignalNumber,
+    /// The flags of open, whose lowest two bits are the access mode
+    OpenFlags,
+    Flags(&'static [(u64, &'static str)]),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum ReturnKind {
+    Int,
+    Address,
+}
+
+pub(crate) struct Syscall {
+    pub number: u64,
+    pub name: &'static str,
+    pub args: &'static [ArgKind],
+    pub returns: ReturnKind,
+}
+
+const fn sys(number: u64, name: &'static str, args: &'static [ArgKind]) -> Syscall {
+    Syscall {
+        number,
+        name,
+        args,
+        returns: ReturnKind::Int,
+    }
+}
+
+impl Syscall {
+    const fn returning_address(self) -> Self {
+        Syscall {
+            returns: ReturnKind::Address,
+            ..self
+        }
+    }
+}
+
+// Flags are matched in order, so flags which include others come first. An entry for zero
+// names the value with no flags set.
+const OPEN_FLAGS: &[(u64, &str)] = &[
+    (0o20200000, "O_TMPFILE"),
+    (0o4010000, "O_SYNC"),
+    (0o100, "O_CREAT"),
+    (0o200, "O_EXCL"),
+    (0o400, "O_NOCTTY"),
+    (0o1000, "O_TRUNC"),
+    (0o2000, "O_APPEND"),
+    (0o4000, "O_NONBLOCK"),
+    (0o10000, "O_DSYNC"),
+    (0o20000, "O_ASYNC"),
+    (0o40000, "O_DIRECT"),
+    (0o100000, "O_LARGEFILE"),
+    (0o200000, "O_DIRECTORY"),
+    (0o400000, "O_NOFOLLOW"),
+    (0o1000000, "O_NOATIME"),
+    (0o2000000, "O_CLOEXEC"),
+    (0o10000000, "O_PATH"),
+];
+
+// The flags of calls such as pipe2 and dup3 which create file descriptors
+const FD_FLAGS: &[(u64, &str)] = &[
+    (0o4000, "O_NONBLOCK"),
+    (0o40000, "O_DIRECT"),
+    (0o2000000, "O_CLOEXEC"),
+];
+
+const PROT_FLAGS: &[(u64, &str)] = &[
+    (0, "PROT_NONE"),
+    (0x1, "PROT_READ"),
+    (0x2, "PROT_WRITE"),
+    (0x4, "PROT_EXEC"),
+    (0x8, "PROT_SEM"),
+    (0x1000000, "PROT_GROWSDOWN"),
+    (0x2000000, "PROT_GROWSUP"),
+];
+
+const MAP_FLAGS: &[(u64, &str)] = &[
+    (0x3, "MAP_SHARED_VALIDATE"),
+    (0x1, "MAP_SHARED"),
+    (0x2, "MAP_PRIVATE"),
+    (0x10, "MAP_FIXED"),
+    (0x20, "MAP_ANONYMOUS"),
+    (0x40, "MAP_32BIT"),
+    (0x100, "MAP_GROWSDOWN"),
+    (0x800, "MAP_DENYWRITE"),
+    (0x1000, "MAP_EXECUTABLE"),
+    (0x2000, "MAP_LOCKED"),
+    (0x4000, "MAP_NORESERVE"),
+    (0x8000, "MAP_POPULATE"),
+    (0x10000, "MAP_NONBLOCK"),
+    (0x20000, "MAP_STACK"),
+    (0x40000, "MAP_HUGETLB"),
+    (0x80000, "MAP_SYNC"),
+    (0x100000, "MAP_FIXED_NOREPLACE"),
+];
+
+// The system calls of x86-64 Linux, as listed in the kernel's syscall_64.tbl, with the types
+// of their arguments
+const SYSCALLS: &[Syscall] = &[
+    sys(0, "read", &[Fd, OutBuffer, Uint]),
+    sys(1, "write", &[Fd, Buffer { length: 2 }, Uint]),
+    sys(2, "open", &[Path, OpenFlags, Octal]),
+    sys(3, "close", &[Fd]),
+    sys(4, "stat", &[Path, Struct("stat")]),
+    sys(5, "fstat", &[Fd, Struct("stat")]),
+    sys(6, "lstat", &[Path, Struct("stat")]),
+    sys(7, "poll", &[Struct("pollfd"), Uint, Int]),
+    sys(8, "lseek", &[Fd, Long, Int]),
+    sys(
+        9,
+        "mmap",
+        &[Pointer, Uint, Flags(PROT_FLAGS), Flags(MAP_FLAGS), Fd, Hex],
+    )
+    .returning_address(),
+    sys(10, "mprotect", &[Pointer, Uint, Flags(PROT_FLAGS)]),
+    sys(11, "munmap", &[Pointer, Uint]),
+    sys(12, "brk", &[Pointer]).returning_address(),
+    sys(
+        13,
+        "rt_sigaction",
+        &[SignalNumber, Struct("sigaction"), Struct("sigaction"), Uint],
+    ),
+    sys(
+        14,
+        "rt_sigprocmask",
+        &[Int, Struct("sigset_t"), Struct("sigset_t"), Uint],
+    ),
+    sys(15, "rt_sigreturn", &[]),
+    sys(16, "ioctl", &[Fd, Hex, Hex]),
+    sys(17, "pread64", &[Fd, OutBuffer, Uint, Long]),
+    sys(18, "pwrite64", &[Fd, Buffer { length: 2 }, Uint, Long]),
+    sys(19, "readv", &[Fd, Struct("iovec"), Int]),
+    sys(20, "writev", &[Fd, Struct("iovec"), Int]),
+    sys(21, "access", &[Path, Int]),
+    sys(22, "pipe", &[Pointer]),
+    sys(
+        23,
+        "select",
+        &[Int, Pointer, Pointer, Pointer, Struct("timeval")],
+    ),
+    sys(24, "sched_yield", &[]),
+    sys(25, "mremap", &[Pointer, Uint, Uint, Hex, Pointer]).returning_address(),
+    sys(26, "msync", &[Pointer, Uint, Hex]),
+    sys(27, "mincore", &[Pointer, Uint, Pointer]),
+    sys(28, "madvise", &[Pointer, Uint, Int]),
+    sys(29, "shmget", &[Int, Uint, Hex]),
+    sys(30, "shmat", &[Int, Pointer, Hex]).returning_address(),
+    sys(31, "shmctl", &[Int, Int, Struct("shmid_ds")]),
+    sys(32, "dup", &[Fd]),
+    sys(33, "dup2", &[Fd, Fd]),
+    sys(34, "pause", &[]),
+    sys(35, "nanosleep", &[Struct("timespec"), Struct("timespec")]),
+    sys(36, "getitimer", &[Int, Struct("itimerval")]),
+    sys(37, "alarm", &[Uint]),
+    sys(
+        38,
+        "setitimer",
+        &[Int, Struct("itimerval"), Struct("itimerval")],
+    ),
+    sys(39, "getpid", &[]),
+    sys(40, "sendfile", &[Fd, Fd, Pointer, Uint]),
+    sys(41, "socket", &[Int, Int, Int]),
+    sys(42, "connect", &[Fd, Struct("sockaddr"), Uint]),
+    sys(43, "accept", &[Fd, Struct("sockaddr"), Pointer]),
+    sys(
+        44,
+        "sendto",
+        &[
+            Fd,
+            Buffer { length: 2 },
+            Uint,
+            Hex,
+            Struct("sockaddr"),
+            Uint,
+        ],
+    ),
+    sys(
+        45,
+        "recvfrom",
+        &[Fd, OutBuffer, Uint, Hex, Struct("sockaddr"), Pointer],
+    ),
+    sys(46, "sendmsg", &[Fd, Struct("msghdr"), Hex]),
+    sys(47, "recvmsg", &[Fd, Struct("msghdr"), Hex]),
+    sys(48, "shutdown", &[Fd, Int]),
+    sys(49, "bind", &[Fd, Struct("sockaddr"), Uint]),
+    sys(50, "listen", &[Fd, Int]),
+    sys(51, "getsockname", &[Fd, Struct("sockaddr"), Pointer]),
+    sys(52, "getpeername", &[Fd, Struct("sockaddr"), Pointer]),
+    sys(53, "socketpair", &[Int, Int, Int, Pointer]),
+    sys(54, "setsockopt", &[Fd, Int, Int, Pointer, Uint]),
+    sys(55, "getsockopt", &[Fd, Int, Int, Pointer, Pointer]),
+    sys(56, "clone", &[Hex, Pointer, Pointer, Pointer, Hex]),
+    sys(57, "fork", &[]),
+    sys(58, "vfork", &[]),
+    sys(59, "execve", &[Path, Pointer, Pointer]),
+    sys(60, "exit", &[Int]),
+    sys(61, "wait4", &[Int, Pointer, Hex, Struct("rusage")]),
+    sys(62, "kill", &[Int, SignalNumber]),
+    sys(63, "uname", &[Struct("utsname")]),
+    sys(64, "semget", &[Int, Int, Hex]),
+    sys(65, "semop", &[Int, Struct("sembuf"), Uint]),
+    sys(66, "semctl", &[Int, Int, Int, Hex]),
+    sys(67, "shmdt", &[Pointer]),
+    sys(68, "msgget", &[Int, Hex]),
+    sys(69, "msgsnd", &[Int, Pointer, Uint, Hex]),
+    sys(70, "msgrcv", &[Int, Pointer, Uint, Long, Hex]),
+    sys(71, "msgctl", &[Int, Int, Struct("msqid_ds")]),
+    sys(72, "fcntl", &[Fd, Int, Hex]),
+    sys(73, "flock", &[Fd, Int]),
+    sys(74, "fsync", &[Fd]),
+    sys(75, "fdatasync", &[Fd]),
+    sys(76, "truncate", &[Path, Long]),
+    sys(77, "ftruncate", &[Fd, Long]),
+    sys(78, "getdents", &[Fd, OutBuffer, Uint]),
+    sys(79, "getcwd", &[OutBuffer, Uint]),
+    sys(80, "chdir", &[Path]),
+    sys(81, "fchdir", &[Fd]),
+    sys(82, "rename", &[Path, Path]),
+    sys(83, "mkdir", &[Path, Octal]),
+    sys(84, "rmdir", &[Path]),
+    sys(85, "creat", &[Path, Octal]),
+    sys(86, "link", &[Path, Path]),
+    sys(87, "unlink", &[Path]),
+    sys(88, "symlink", &[Path, Path]),
+    sys(89, "readlink", &[Path, OutBuffer, Uint]),
+    sys(90, "chmod", &[Path, Octal]),
+    sys(91, "fchmod", &[Fd, Octal]),
+    sys(92, "chown", &[Path, Int, Int]),
+    sys(93, "fchown", &[Fd, Int, Int]),
+    sys(94, "lchown", &[Path, Int, Int]),
+    sys(95, "umask", &[Octal]),
+    sys(96, "gettimeofday", &[Struct("timeval"), Struct("timezone")]),
+    sys(97, "getrlimit", &[Int, Struct("rlimit")]),
+    sys(98, "getrusage", &[Int, Struct("rusage")]),
+    sys(99, "sysinfo", &[Struct("sysinfo")]),
+    sys(100, "times", &[Struct("tms")]),
+    sys(101, "ptrace", &[Int, Int, Pointer, Pointer]),
+    sys(102, "getuid", &[]),
+    sys(103, "syslog", &[Int, OutBuffer, Int]),
+    sys(104, "getgid", &[]),
+    sys(105, "setuid", &[Int]),
+    sys(106, "setgid", &[Int]),
+    sys(107, "geteuid", &[]),
+    sys(108, "getegid", &[]),
+    sys(109, "setpgid", &[Int, Int]),
+    sys(110, "getppid", &[]),
+    sys(111, "getpgrp", &[]),
+    sys(112, "setsid", &[]),
+    sys(113, "setreuid", &[Int, Int]),
+    sys(114, "setregid", &[Int, Int]),
+    sys(115, "getgroups", &[Int, Pointer]),
+    sys(116, "setgroups", &[Int, Pointer]),
+    sys(117, "setresuid", &[Int, Int, Int]),
+    sys(118, "getresuid", &[Pointer, Pointer, Pointer]),
+    sys(119, "setresgid", &[Int, Int, Int]),
+    sys(120, "getresgid", &[Pointer, Pointer, Pointer]),
+    sys(121, "getpgid", &[Int]),
+    sys(122, "setfsuid", &[Int]),
+    sys(123, "setfsgid", &[Int]),
+    sys(124, "getsid", &[Int]),
+    sys(125, "capget", &[Pointer, Pointer]),
+    sys(126, "capset", &[Pointer, Pointer]),
+    sys(127, "rt_sigpending", &[Struct("sigset_t"), Uint]),
+    sys(
+        128,
+        "rt_sigtimedwait",
+        &[
+            Struct("sigset_t"),
+            Struct("siginfo_t"),
+            Struct("timespec"),
+            Uint,
+        ],
+    ),
+    sys(
+        129,
+        "rt_sigqueueinfo",
+        &[Int, SignalNumber, Struct("siginfo_t")],
+    ),
+    sys(130, "rt_sigsuspend", &[Struct("sigset_t"), Uint]),
+    sys(131, "sigaltstack", &[Struct("stack_t"), Struct("stack_t")]),
+    sys(132, "utime", &[Path, Struct("utimbuf")]),
+    sys(133, "mknod", &[Path, Octal, Uint]),
+    sys(134, "uselib", &[Path]),
+    sys(135, "personality", &[Hex]),
+    sys(136, "ustat", &[Uint, Struct("ustat")]),
+    sys(137, "statfs", &[Path, Struct("statfs")]),
+    sys(138, "fstatfs", &[Fd, Struct("statfs")]),
+    sys(139, "sysfs", &[Int, Hex, Hex]),
+    sys(140, "getpriority", &[Int, Int]),
+    sys(141, "setpriority", &[Int, Int, Int]),
+    sys(142, "sched_setparam", &[Int, Struct("sched_param")]),
+    sys(143, "sched_getparam", &[Int, Struct("sched_param")]),
+    sys(
+        144,
+        "sched_setscheduler",
+        &[Int, Int, Struct("sched_param")],
+    ),
+    sys(145, "sched_getscheduler", &[Int]),
+    sys(146, "sched_get_priority_max", &[Int]),
+    sys(147, "sched_get_priority_min", &[Int]),
+    sys(148, "sched_rr_get_interval", &[Int, Struct("timespec")]),
+    sys(149, "mlock", &[Pointer, Uint]),
+    sys(150, "munlock", &[Pointer, Uint]),
+    sys(151, "mlockall", &[Hex]),
+    sys(152, "munlockall", &[]),
+    sys(153, "vhangup", &[]),
+    sys(154, "modify_ldt", &[Int, Pointer, Uint]),
+    sys(155, "pivot_root", &[Path, Path]),
+    sys(156, "_sysctl", &[Pointer]),
+    sys(157, "prctl", &[Int, Hex, Hex, Hex, Hex]),
+    sys(158, "arch_prctl", &[Int, Hex]),
+    sys(159, "adjtimex", &[Struct("timex")]),
+    sys(160, "setrlimit", &[Int, Struct("rlimit")]),
+    sys(161, "chroot", &[Path]),
+    sys(162, "sync", &[]),
+    sys(163, "acct", &[Path]),
+    sys(
+        164,
+        "settimeofday",
+        &[Struct("timeval"), Struct("timezone")],
+    ),
+    sys(165, "mount", &[Str, Path, Str, Hex, Pointer]),
+    sys(166, "umount2", &[Path, Hex]),
+    sys(167, "swapon", &[Path, Hex]),
+    sys(168, "swapoff", &[Path]),
+    sys(169, "reboot", &[Hex, Hex, Uint, Pointer]),
+    sys(170, "sethostname", &[Buffer { length: 1 }, Uint]),
+    sys(171, "setdomainname", &[Buffer { length: 1 }, Uint]),
+    sys(172, "iopl", &[Int]),
+    sys(173, "ioperm", &[Uint, Uint, Int]),
+    sys(174, "create_module", &[Str, Uint]),
+    sys(175, "init_module", &[Pointer, Uint, Str]),
+    sys(176, "delete_module", &[Str, Hex]),
+    sys(177, "get_kernel_syms", &[Pointer]),
+    sys(178, "query_module", &[Str, Int, Pointer, Uint, Pointer]),
+    sys(179, "quotactl", &[Int, Str, Int, Pointer]),
+    sys(180, "nfsservctl", &[Int, Pointer, Pointer]),
+    sys(181, "getpmsg", &[]),
+    sys(182, "putpmsg", &[]),
+    sys(183, "afs_syscall", &[]),
+    sys(184, "tuxcall", &[]),
+    sys(185, "security", &[]),
+    sys(186, "gettid", &[]),
+    sys(187, "readahead", &[Fd, Long, Uint]),
+    sys(188, "setxattr", &[Path, Str, Pointer, Uint, Hex]),
+    sys(189, "lsetxattr", &[Path, Str, Pointer, Uint, Hex]),
+    sys(190, "fsetxattr", &[Fd, Str, Pointer, Uint, Hex]),
+    sys(191, "getxattr", &[Path, Str, Pointer, Uint]),
+    sys(192, "lgetxattr", &[Path, Str, Pointer, Uint]),
+    sys(193, "fgetxattr", &[Fd, Str, Pointer, Uint]),
+    sys(194, "listxattr", &[Path, Pointer, Uint]),
+    sys(195, "llistxattr", &[Path, Pointer, Uint]),
+    sys(196, "flistxattr", &[Fd, Pointer, Uint]),
+    sys(197, "removexattr", &[Path, Str]),
+    sys(198, "lremovexattr", &[Path, Str]),
+    sys(199, "fremovexattr", &[Fd, Str]),
+    sys(200, "tkill", &[Int, SignalNumber]),
+    sys(201, "time", &[Pointer]),
+    sys(
+        202,
+        "futex",
+        &[Pointer, Int, Uint, Struct("timespec"), Pointer, Uint],
+    ),
+    sys(203, "sched_setaffinity", &[Int, Uint, Pointer]),
+    sys(204, "sched_getaffinity", &[Int, Uint, Pointer]),
+    sys(205, "set_thread_area", &[Pointer]),
+    sys(206, "io_setup", &[Uint, Pointer]),
+    sys(207, "io_destroy", &[Hex]),
+    sys(
+        208,
+        "io_getevents",
+        &[Hex, Long, Long, Pointer, Struct("timespec")],
+    ),
+    sys(209, "io_submit", &[Hex, Long, Pointer]),
+    sys(210, "io_cancel", &[Hex, Pointer, Pointer]),
+    sys(211, "get_thread_area", &[Pointer]),
+    sys(212, "lookup_dcookie", &[Uint, OutBuffer, Uint]),
+    sys(213, "epoll_create", &[Int]),
+    sys(214, "epoll_ctl_old", &[]),
+    sys(215, "epoll_wait_old", &[]),
+    sys(216, "remap_file_pages", &[Pointer, Uint, Hex, Uint, Hex]),
+    sys(217, "getdents64", &[Fd, OutBuffer, Uint]),
+    sys(218, "set_tid_address", &[Pointer]),
+    sys(219, "restart_syscall", &[]),
+    sys(
+        220,
+        "semtimedop",
+        &[Int, Struct("sembuf"), Uint, Struct("timespec")],
+    ),
+    sys(221, "fadvise64", &[Fd, Long, Long, Int]),
+    sys(222, "timer_create", &[Int, Struct("sigevent"), Pointer]),
+    sys(
+        223,
+        "timer_settime",
+        &[Int, Hex, Struct("itimerspec"), Struct("itimerspec")],
+    ),
+    sys(224, "timer_gettime", &[Int, Struct("itimerspec")]),
+    sys(225, "timer_getoverrun", &[Int]),
+    sys(226, "timer_delete", &[Int]),
+    sys(227, "clock_settime", &[Int, Struct("timespec")]),
+    sys(228, "clock_gettime", &[Int, Struct("timespec")]),
+    sys(229, "clock_getres", &[Int, Struct("timespec")]),
+    sys(
+        230,
+        "clock_nanosleep",
+        &[Int, Hex, Struct("timespec"), Struct("timespec")],
+    ),
+    sys(231, "exit_group", &[Int]),
+    sys(232, "epoll_wait", &[Fd, Struct("epoll_event"), Int, Int]),
+    sys(233, "epoll_ctl", &[Fd, Int, Fd, Struct("epoll_event")]),
+    sys(234, "tgkill", &[Int, Int, SignalNumber]),
+    sys(235, "utimes", &[Path, Struct("timeval")]),
+    sys(236, "vserver", &[]),
+    sys(237, "mbind", &[Pointer, Uint, Int, Pointer, Uint, Hex]),
+    sys(238, "set_mempolicy", &[Int, Pointer, Uint]),
+    sys(
+        239,
+        "get_mempolicy",
+        &[Pointer, Pointer, Uint, Pointer, Hex],
+    ),
+    sys(240, "mq_open", &[Str, OpenFlags, Octal, Struct("mq_attr")]),
+    sys(241, "mq_unlink", &[Str]),
+    sys(
+        242,
+        "mq_timedsend",
+        &[Fd, Buffer { length: 2 }, Uint, Uint, Struct("timespec")],
+    ),
+    sys(
+        243,
+        "mq_timedreceive",
+        &[Fd, OutBuffer, Uint, Pointer, Struct("timespec")],
+    ),
+    sys(244, "mq_notify", &[Fd, Struct("sigevent")]),
+    sys(
+        245,
+        "mq_getsetattr",
+        &[Fd, Struct("mq_attr"), Struct("mq_attr")],
+    ),
+    sys(246, "kexec_load", &[Hex, Uint, Pointer, Hex]),
+    sys(
+        247,
+        "waitid",
+        &[Int, Int, Struct("siginfo_t"), Hex, Struct("rusage")],
+    ),
+    sys(248, "add_key", &[Str, Str, Pointer, Uint, Int]),
+    sys(249, "request_key", &[Str, Str, Str, Int]),
+    sys(250, "keyctl", &[Int, Hex, Hex, Hex, Hex]),
+    sys(251, "ioprio_set", &[Int, Int, Int]),
+    sys(252, "ioprio_get", &[Int, Int]),
+    sys(253, "inotify_init", &[]),
+    sys(254, "inotify_add_watch", &[Fd, Path, Hex]),
+    sys(255, "inotify_rm_watch", &[Fd, Int]),
+    sys(256, "migrate_pages", &[Int, Uint, Pointer, Pointer]),
+    sys(257, "openat", &[AtFd, Path, OpenFlags, Octal]),
+    sys(258, "mkdirat", &[AtFd, Path, Octal]),
+    sys(259, "mknodat", &[AtFd, Path, Octal, Uint]),
+    sys(260, "fchownat", &[AtFd, Path, Int, Int, Hex]),
+    sys(261, "futimesat", &[AtFd, Path, Struct("timeval")]),
+    sys(262, "newfstatat", &[AtFd, Path, Struct("stat"), Hex]),
+    sys(263, "unlinkat", &[AtFd, Path, Hex]),
+    sys(264, "renameat", &[AtFd, Path, AtFd, Path]),
+    sys(265, "linkat", &[AtFd, Path, AtFd, Path, Hex]),
+    sys(266, "symlinkat", &[Path, AtFd, Path]),
+    sys(267, "readlinkat", &[AtFd, Path, OutBuffer, Uint]),
+    sys(268, "fchmodat", &[AtFd, Path, Octal]),
+    sys(269, "faccessat", &[AtFd, Path, Int]),
+    sys(
+        270,
+        "pselect6",
+        &[Int, Pointer, Pointer, Pointer, Struct("timespec"), Pointer],
+    ),
+    sys(
+        271,
+        "ppoll",
+        &[
+            Struct("pollfd"),
+            Uint,
+            Struct("timespec"),
+            Struct("sigset_t"),
+            Uint,
+        ],
+    ),
+    sys(272, "unshare", &[Hex]),
+    sys(273, "set_robust_list", &[Pointer, Uint]),
+    sys(274, "get_robust_list", &[Int, Pointer, Pointer]),
+    sys(275, "splice", &[Fd, Pointer, Fd, Pointer, Uint, Hex]),
+    sys(276, "tee", &[Fd, Fd, Uint, Hex]),
+    sys(277, "sync_file_range", &[Fd, Long, Long, Hex]),
+    sys(278, "vmsplice", &[Fd, Struct("iovec"), Uint, Hex]),
+    sys(
+        279,
+        "move_pages",
+        &[Int, Uint, Pointer, Pointer, Pointer, Hex],
+    ),
+    sys(280, "utimensat", &[AtFd, Path, Struct("timespec"), Hex]),
+    sys(
+        281,
+        "epoll_pwait",
+        &[
+            Fd,
+            Struct("epoll_event"),
+            Int,
+            Int,
+            Struct("sigset_t"),
+            Uint,
+        ],
+    ),
+    sys(282, "signalfd", &[Fd, Struct("sigset_t"), Uint]),
+    sys(283, "timerfd_create", &[Int, Hex]),
+    sys(284, "eventfd", &[Uint]),
+    sys(285, "fallocate", &[Fd, Int, Long, Long]),
+    sys(
+        286,
+        "timerfd_settime",
+        &[Fd, Hex, Struct("itimerspec"), Struct("itimerspec")],
+    ),
+    sys(287, "timerfd_gettime", &[Fd, Struct("itimerspec")]),
+    sys(288, "accept4", &[Fd, Struct("sockaddr"), Pointer, Hex]),
+    sys(
+        289,
+        "signalfd4",
+        &[Fd, Struct("sigset_t"), Uint, Flags(FD_FLAGS)],
+    ),
+    sys(290, "eventfd2", &[Uint, Hex]),
+    sys(291, "epoll_create1", &[Flags(FD_FLAGS)]),
+    sys(292, "dup3", &[Fd, Fd, Flags(FD_FLAGS)]),
+    sys(293, "pipe2", &[Pointer, Flags(FD_FLAGS)]),
+    sys(294, "inotify_init1", &[Flags(FD_FLAGS)]),
+    sys(295, "preadv", &[Fd, Struct("iovec"), Int, Long, Long]),
+    sys(296, "pwritev", &[Fd, Struct("iovec"), Int, Long, Long]),
+    sys(
+        297,
+        "rt_tgsigqueueinfo",
+        &[Int, Int, SignalNumber, Struct("siginfo_t")],
+    ),
+    sys(
+        298,
+        "perf_event_open",
+        &[Struct("perf_event_attr"), Int, Int, Fd, Hex],
+    ),
+    sys(
+        299,
+        "recvmmsg",
+        &[Fd, Struct("mmsghdr"), Uint, Hex, Struct("timespec")],
+    ),
+    sys(300, "fanotify_init", &[Hex, Hex]),
+    sys(301, "fanotify_mark", &[Fd, Hex, Hex, AtFd, Path]),
+    sys(
+        302,
+        "prlimit64",
+        &[Int, Int, Struct("rlimit"), Struct("rlimit")],
+    ),
+    sys(
+        303,
+        "name_to_handle_at",
+        &[AtFd, Path, Struct("file_handle"), Pointer, Hex],
+    ),
+    sys(
+        304,
+        "open_by_handle_at",
+        &[Fd, Struct("file_handle"), OpenFlags],
+    ),
+    sys(305, "clock_adjtime", &[Int, Struct("timex")]),
+    sys(306, "syncfs", &[Fd]),
+    sys(307, "sendmmsg", &[Fd, Struct("mmsghdr"), Uint, Hex]),
+    sys(308, "setns", &[Fd, Hex]),
+    sys(309, "getcpu", &[Pointer, Pointer, Pointer]),
+    sys(
+        310,
+        "process_vm_readv",
+        &[Int, Struct("iovec"), Uint, Struct("iovec"), Uint, Hex],
+    ),
+    sys(
+        311,
+        "process_vm_writev",
+        &[Int, Struct("iovec"), Uint, Struct("iovec"), Uint, Hex],
+    ),
+    sys(312, "kcmp", &[Int, Int, Int, Uint, Uint]),
+    sys(313, "finit_module", &[Fd, Str, Hex]),
+    sys(314, "sched_setattr", &[Int, Struct("sched_attr"), Hex]),
+    sys(
+        315,
+        "sched_getattr",
+        &[Int, Struct("sched_attr"), Uint, Hex],
+    ),
+    sys(316, "renameat2", &[AtFd, Path, AtFd, Path, Hex]),
+    sys(317, "seccomp", &[Uint, Hex, Pointer]),
+    sys(318, "getrandom", &[OutBuffer, Uint, Hex]),
+    sys(319, "memfd_create", &[Str, Hex]),
+    sys(320, "kexec_file_load", &[Fd, Fd, Uint, Str, Hex]),
+    sys(321, "bpf", &[Int, Pointer, Uint]),
+    sys(322, "execveat", &[AtFd, Path, Pointer, Pointer, Hex]),
+    sys(323, "userfaultfd", &[Hex]),
+    sys(324, "membarrier", &[Int, Hex, Int]),
+    sys(325, "mlock2", &[Pointer, Uint, Hex]),
+    sys(
+        326,
+        "copy_file_range",
+        &[Fd, Pointer, Fd, Pointer, Uint, Hex],
+    ),
+    sys(327, "preadv2", &[Fd, Struct("iovec"), Int, Long, Long, Hex]),
+    sys(
+        328,
+        "pwritev2",
+        &[Fd, Struct("iovec"), Int, Long, Long, Hex],
+    ),
+    sys(
+        329,
+        "pkey_mprotect",
+        &[Pointer, Uint, Flags(PROT_FLAGS), Int],
+    ),
+    sys(330, "pkey_alloc", &[Hex, Hex]),
+    sys(331, "pkey_free", &[Int]),
+    sys(332, "statx", &[AtFd, Path, Hex, Hex, Struct("statx")]),
+    sys(
+        333,
+        "io_pgetevents",
+        &[Hex, Long, Long, Pointer, Struct("timespec"), Pointer],
+    ),
+    sys(334, "rseq", &[Pointer, Uint, Hex, Uint]),
+    sys(
+        424,
+        "pidfd_send_signal",
+        &[Fd, SignalNumber, Struct("siginfo_t"), Hex],
+    ),
+    sys(425, "io_uring_setup", &[Uint, Struct("io_uring_params")]),
+    sys(426, "io_uring_enter", &[Fd, Uint, Uint, Hex, Pointer, Uint]),
+    sys(427, "io_uring_register", &[Fd, Uint, Pointer, Uint]),
+    sys(428, "open_tree", &[AtFd, Path, Hex]),
+    sys(429, "move_mount", &[AtFd, Path, AtFd, Path, Hex]),
+    sys(430, "fsopen", &[Str, Hex]),
+    sys(431, "fsconfig", &[Fd, Uint, Str, Pointer, Int]),
+    sys(432, "fsmount", &[Fd, Hex, Hex]),
+    sys(433, "fspick", &[AtFd, Path, Hex]),
+    sys(434, "pidfd_open", &[Int, Hex]),
+    sys(435, "clone3", &[Struct("clone_args"), Uint]),
+    sys(436, "close_range", &[Uint, Uint, Hex]),
+    sys(437, "openat2", &[AtFd, Path, Struct("open_how"), Uint]),
+    sys(438, "pidfd_getfd", &[Fd, Int, Hex]),
+    sys(439, "faccessat2", &[AtFd, Path, Int, Hex]),
+    sys(
+        440,
+        "process_madvise",
+        &[Fd, Struct("iovec"), Uint, Int, Hex],
+    ),
+    sys(
+        441,
+        "epoll_pwait2",
+        &[
+            Fd,
+            Struct("epoll_event"),
+            Int,
+            Struct("timespec"),
+            Struct("sigset_t"),
+            Uint,
+        ],
+    ),
+    sys(
+        442,
+        "mount_setattr",
+        &[AtFd, Path, Hex, Struct("mount_attr"), Uint],
+    ),
+    sys(443, "quotactl_fd", &[Fd, Uint, Int, Pointer]),
+    sys(444, "landlock_create_ruleset", &[Pointer, Uint, Hex]),
+    sys(445, "landlock_add_rule", &[Fd, Int, Pointer, Hex]),
+    sys(446, "landlock_restrict_self", &[Fd, Hex]),
+    sys(447, "memfd_secret", &[Hex]),
+    sys(448, "process_mrelease", &[Fd, Hex]),
+    sys(
+        449,
+        "futex_waitv",
+        &[Pointer, Uint, Hex, Struct("timespec"), Int],
+    ),
+    sys(450, "set_mempolicy_home_node", &[Pointer, Uint, Uint, Hex]),
+    sys(451, "cachestat", &[Fd, Pointer, Pointer, Hex]),
+    sys(452, "fchmodat2", &[AtFd, Path, Octal, Hex]),
+    sys(453, "map_shadow_stack", &[Pointer, Uint, Hex]).returning_address(),
+    sys(454, "futex_wake", &[Pointer, Uint, Int, Hex]),
+    sys(
+        455,
+        "futex_wait",
+        &[Pointer, Uint, Uint, Hex, Struct("timespec"), Int],
+    ),
+    sys(456, "futex_requeue", &[Pointer, Hex, Int, Int]),
+    sys(457, "statmount", &[Pointer, Pointer, Uint, Hex]),
+    sys(458, "listmount", &[Pointer, Pointer, Uint, Hex]),
+    sys(459, "lsm_get_self_attr", &[Uint, Pointer, Pointer, Hex]),
+    sys(460, "lsm_set_self_attr", &[Uint, Pointer, Uint, Hex]),
+    sys(461, "lsm_list_modules", &[Pointer, Pointer, Hex]),
+    sys(462, "mseal", &[Pointer, Uint, Hex]),
+];
+
+// Sets of related system calls which can be caught together, after the classes of strace
+const GROUPS: &[(&str, &[&str])] = &[
+    (
+        "file",
+        &[
+            "open",
+            "stat",
+            "lstat",
+            "access",
+            "execve",
+            "truncate",
+            "getcwd",
+            "chdir",
+            "rename",
+            "mkdir",
+            "rmdir",
+            "creat",
+            "link",
+            "unlink",
+            "symlink",
+            "readlink",
+            "chmod",
+            "chown",
+            "lchown",
+            "utime",
+            "mknod",
+            "uselib",
+            "statfs",
+            "pivot_root",
+            "chroot",
+            "acct",
+            "mount",
+            "umount2",
+            "swapon",
+            "swapoff",
+            "quotactl",
+            "setxattr",
+            "lsetxattr",
+            "getxattr",
+            "lgetxattr",
+            "listxattr",
+            "llistxattr",
+            "removexattr",
+            "lremovexattr",
+            "utimes",
+            "inotify_add_watch",
+            "openat",
+            "mkdirat",
+            "mknodat",
+            "fchownat",
+            "futimesat",
+            "newfstatat",
+            "unlinkat",
+            "renameat",
+            "linkat",
+            "symlinkat",
+            "readlinkat",
+            "fchmodat",
+            "faccessat",
+            "utimensat",
+            "fanotify_mark",
+            "name_to_handle_at",
+            "renameat2",
+            "execveat",
+            "statx",
+            "open_tree",
+            "move_mount",
+            "fsconfig",
+            "fspick",
+            "openat2",
+            "faccessat2",
+            "mount_setattr",
+            "fchmodat2",
+        ],
+    ),
+    (
+        "process",
+        &[
+            "clone",
+            "fork",
+            "vfork",
+            "execve",
+            "exit",
+            "wait4",
+            "kill",
+            "tkill",
+            "exit_group",
+            "tgkill",
+            "waitid",
+            "rt_tgsigqueueinfo",
+            "execveat",
+            "pidfd_send_signal",
+            "pidfd_open",
+            "clone3",
+            "pidfd_getfd",
+            "process_mrelease",
+        ],
+    ),
+    (
+        "network",
+        &[
+            "sendfile",
+            "socket",
+            "connect",
+            "accept",
+            "sendto",
+            "recvfrom",
+            "sendmsg",
+            "recvmsg",
+            "shutdown",
+            "bind",
+            "listen",
+            "getsockname",
+            "getpeername",
+            "socketpair",
+            "setsockopt",
+            "getsockopt",
+            "accept4",
+            "recvmmsg",
+            "sendmmsg",
+        ],
+    ),
+    (
+        "signal",
+        &[
+            "rt_sigaction",
+            "rt_sigprocmask",
+            "rt_sigreturn",
+            "pause",
+            "kill",
+            "rt_sigpending",
+            "rt_sigtimedwait",
+            "rt_sigqueueinfo",
+            "rt_sigsuspend",
+            "sigaltstack",
+            "tkill",
+            "tgkill",
+            "signalfd",
+            "signalfd4",
+            "rt_tgsigqueueinfo",
+            "pidfd_send_signal",
+        ],
+    ),
+    (
+        "ipc",
+        &[
+            "shmget",
+            "shmat",
+            "shmctl",
+            "semget",
+            "semop",
+            "semctl",
+            "shmdt",
+            "msgget",
+            "msgsnd",
+            "msgrcv",
+            "msgctl",
+            "semtimedop",
+        ],
+    ),
+    (
+        "memory",
+        &[
+            "mmap",
+            "mprotect",
+            "munmap",
+            "brk",
+            "mremap",
+            "msync",
+            "mincore",
+            "madvise",
+            "mlock",
+            "munlock",
+            "mlockall",
+            "munlockall",
+            "remap_file_pages",
+            "mbind",
+            "set_mempolicy",
+            "get_mempolicy",
+            "migrate_pages",
+            "move_pages",
+            "mlock2",
+            "pkey_mprotect",
+            "process_madvise",
+            "set_mempolicy_home_node",
+            "map_shadow_stack",
+            "mseal",
+        ],
+    ),
+];
+
+const AT_FDCWD: i32 = -100;
+// Longest prefix of strings and buffers shown, as with strace -s
+const STRING_LIMIT: usize = 32;
+const PATH_MAX: usize = 4096;
+
+pub(crate) fn syscall(number: u64) -> Option<&'static Syscall> {
+    SYSCALLS.iter().find(|syscall| syscall.number == number)
+}
+
+/// The name of the system call of a number
+pub fn syscall_name(number: u64) -> Option<&'static str> {
+    syscall(number).map(|syscall| syscall.name)
+}
+
+pub(crate) fn syscall_number(name: &str) -> Option<u64> {
+    SYSCALLS
+        .iter()
+        .find(|syscall| syscall.name == name)
+        .map(|syscall| syscall.number)
+}
+
+/// The names `parse_syscalls` takes: those of the calls and then of their groups
+pub fn syscall_names() -> impl Iterator<Item = String> {
+    let calls = SYSCALLS.iter().map(|syscall| syscall.name.to_string());
+    calls.chain(GROUPS.iter().map(|(group, _)| format!("group:{group}")))
+}
+
+/// Resolves a system call given as a name, a number, or a group written as `group:<name>` or
+/// `g:<name>` to the numbers of the calls it covers
+pub fn parse_syscalls(text: &str) -> Result<Vec<u64>> {
+    if let Some(group) = text
+        .strip_prefix("group:")
+        .or_else(|| text.strip_prefix("g:"))
+    {
+        let (_, names) = GROUPS
+            .iter()
+            .find(|(name, _)| *name == group)
+            .ok_or_else(|| anyhow!("unknown syscall group {group}"))?;
+        return Ok(names
+            .iter()
+            .filter_map(|name| syscall_number(name))
+            .collect());
+    }
+    if let Ok(number) = text.parse::<u64>() {
+        return match syscall_name(number) {
+            Some(_) => Ok(vec![number]),
+            None => Err(anyhow!("unknown syscall number {number}")),
+        };
+    }
+    syscall_number(text)
+        .map(|number| vec![number])
+        .ok_or_else(|| anyhow!("unknown syscall {text}"))
+}
+
+// Names the set bits of a value, with any bits left over in hex
+fn format_flags(value: u64, flags: &[(u64, &str)]) -> String {
+    if value == 0 {
+        return match flags.iter().find(|(flag, _)| *flag == 0) {
+            Some((_, name)) => name.to_string(),
+            None => "0".to_string(),
+        };
+    }
+    let mut names = Vec::new();
+    let mut rest = value;
+    for &(flag, name) in flags {
+        if flag != 0 && rest & flag == flag {
+            names.push(name.to_string());
+            rest &= !flag;
+        }
+    }
+    if rest != 0 {
+        names.push(format!("{rest:#x}"));
+    }
+    names.join("|")
+}
+
+fn format_open_flags(value: u64) -> String {
+    let access = match value & 0o3 {
+        0 => "O_RDONLY",
+        1 => "O_WRONLY",
+        2 => "O_RDWR",
+        _ => "O_ACCMODE",
+    };
+    match value & !0o3 {
+        0 => access.to_string(),
+        rest => format!("{access}|{}", format_flags(rest, OPEN_FLAGS)),
+    }
+}
+
+pub(crate) fn signal_name(number: i32) -> String {
+    match Signal::try_from(number) {
+        Ok(signal) => signal.as_str().to_string(),
+        Err(_) => number.to_string(),
+    }
+}
+
+// Reads up to `limit` bytes of a NUL terminated string, returning whether it was cut short.
+// Reads are aligned chunks, which never cross into a page past the end of the string.
+fn read_string(memory: &dyn MemoryReader, address: u64, limit: usize) -> Result<(Vec<u8>, bool)> {
+    let mut bytes = Vec::new();
+    while bytes.len() < limit {
+        let next = address + bytes.len() as u64;
+        let amount = (16 - next as usize % 16).min(limit - bytes.len());
+        let chunk = memory.read_memory(next, amount)?;
+        if let Some(end) = chunk.iter().position(|&b| b == 0) {
+            bytes.extend(&chunk[..end]);
+            return Ok((bytes, false));
+        }
+        bytes.extend(chunk);
+    }
+    Ok((bytes, true))
+}
+
+fn quote(bytes: &[u8], truncated: bool) -> String {
+    let text = format!("{:?}", String::from_utf8_lossy(bytes));
+    if truncated {
+        text + "..."
+    } else {
+        text
+    }
+}
+
+fn format_arg(memory: &dyn MemoryReader, kind: ArgKind, value: u64, args: &[u64; 6]) -> String {
+    let pointer = |value: u64| match value {
+        0 => "NULL".to_string(),
+        _ => format!("{value:#x}"),
+    };
+    let string = |limit: usize| match read_string(memory, value, limit) {
+        Ok((bytes, truncated)) => quote(&bytes, truncated),
+        Err(_) => pointer(value),
+    };
+    match kind {
+        _ if value == 0 && matches!(kind, Path | Str | Buffer { .. } | Struct(_)) => {
+            "NULL".to_string()
+        }
+        Int | Fd => (value as i32).to_string(),
+        Long => (value as i64).to_string(),
+        Uint => value.to_string(),
+        Hex => format!("{value:#x}"),
+        // Written the C way, as in 0644
+        Octal => match value {
+            0 => "0".to_string(),
+            _ => format!("0{value:o}"),
+        },
+        AtFd if value as i32 == AT_FDCWD => "AT_FDCWD".to_string(),
+        AtFd => (value as i32).to_string(),
+        Path => string(PATH_MAX),
+        Str => string(STRING_LIMIT),
+        Buffer { length } => {
+            let size = args[length] as usize;
+            match memory.read_memory(value, size.min(STRING_LIMIT)) {
+                Ok(bytes) => quote(&bytes, size > STRING_LIMIT),
+                Err(_) => pointer(value),
+            }
+        }
+        OutBuffer | Pointer => pointer(value),
+        Struct(name) => format!("(struct {name} *) {value:#x}"),
+        SignalNumber => signal_name(value as i32),
+        OpenFlags => format_open_flags(value),
+        Flags(flags) => format_flags(value, flags),
+    }
+}
+
+/// A call as `name(arg, ...)`, reading strings and buffers from the memory of the process.
+/// Calls missing from the table are shown with all six argument registers in hex.
+pub fn format_call(number: u64, args: &[u64; 6], memory: &dyn MemoryReader) -> String {
+    let Some(syscall) = syscall(number) else {
+        let args: Vec<String> = args.iter().map(|arg| format!("{arg:#x}")).collect();
+        return format!("syscall_{number}({})", args.join(", "));
+    };
+    let formatted: Vec<String> = syscall
+        .args
+        .iter()
+        .zip(args)
+        .map(|(&kind, &value)| format_arg(memory, kind, value, args))
+        .collect();
+    format!("{}({})", syscall.name, formatted.join(", "))
+}
+
+/// A return value, with errors shown as -1 and the name of the errno like strace does
+pub fn format_return(number: u64, value: i64) -> String {
+    if (-4095..0).contains(&value) {
+        let errno = Errno::from_raw(-value as i32);
+        return format!("-1 {errno:?} ({})", errno.desc());
+    }
+    match syscall(number).map(|syscall| syscall.returns) {
+        Some(ReturnKind::Address) => format!("{value:#x}"),
+        _ => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dwarf::expr::MemoryReader;
+    use crate::syscalls::{
+        format_call, format_return, parse_syscalls, syscall_name, syscall_number,
+    };
+    use anyhow::{bail, Result};
+
+    // A page of memory at 0x1000 holding a path at its start and a buffer at 0x1ff0, which
+    // runs up to the end of the page
+    struct FakeMemory {
+        data: Vec<u8>,
+    }
+
+    const BASE: u64 = 0x1000;
+
+    impl FakeMemory {
+        fn new() -> Self {
+            let mut data = vec![0u8; 0x1000];
+            data[..11].copy_from_slice(b"/etc/hosts\0");
+            data[0xff0..].copy_from_slice(b"hello, world!\n\0\0");
+            Self { data }
+        }
+    }
+
+    impl MemoryReader for FakeMemory {
+        fn read_memory(&self, address: u64, amount: usize) -> Result<Vec<u8>> {
+            let start = address.wrapping_sub(BASE) as usize;
+            match self.data.get(start..start + amount) {
+                Some(bytes) => Ok(bytes.to_vec()),
+                None => bail!("address {address:#x} is not mapped"),
+            }
+        }
+    }
+
+    #[test]
+    fn syscalls_by_name_number_and_group() {
+        assert_eq!(syscall_name(0), Some("read"));
+        assert_eq!(syscall_name(231), Some("exit_group"));
+        assert_eq!(syscall_name(435), Some("clone3"));
+        assert_eq!(syscall_name(400), None);
+        assert_eq!(syscall_number("openat"), Some(257));
+
+        assert_eq!(parse_syscalls("write").unwrap(), vec![1]);
+        assert_eq!(parse_syscalls("59").unwrap(), vec![59]);
+        assert!(parse_syscalls("1000").is_err());
+        assert!(parse_syscalls("no_such_call").is_err());
+        let network = parse_syscalls("group:network").unwrap();
+        assert!(network.contains(&41) && network.contains(&42) && !network.contains(&1));
+        assert_eq!(parse_syscalls("g:ipc").unwrap().len(), 12);
+        assert!(parse_syscalls("g:nothing").is_err());
+    }
+
+    #[test]
+    fn arguments_are_decoded() {
+        let memory = FakeMemory::new();
+        let openat = [(-100i64) as u64, BASE, 0o2000101, 0o644, 0, 0];
+        assert_eq!(
+            format_call(257, &openat, &memory),
+            r#"openat(AT_FDCWD, "/etc/hosts", O_WRONLY|O_CREAT|O_CLOEXEC, 0644)"#
+        );
+        let write = [1, BASE + 0xff0, 14, 0, 0, 0];
+        assert_eq!(
+            format_call(1, &write, &memory),
+            r#"write(1, "hello, world!\n", 14)"#
+        );
+        let long_write = [1, BASE, 100, 0, 0, 0];
+        assert!(format_call(1, &long_write, &memory).ends_with(r#"\0\0"..., 100)"#));
+
+        let mmap = [0, 0x2000, 0x3, 0x22, (-1i64) as u64, 0];
+        assert_eq!(
+            format_call(9, &mmap, &memory),
+            "mmap(NULL, 8192, PROT_READ|PROT_WRITE, MAP_PRIVATE|MAP_ANONYMOUS, -1, 0x0)"
+        );
+        assert_eq!(
+            format_call(10, &[0x7000, 0x1000, 0, 0, 0, 0], &memory),
+            "mprotect(0x7000, 4096, PROT_NONE)"
+        );
+        assert_eq!(
+            format_call(62, &[42, 15, 0, 0, 0, 0], &memory),
+            "kill(42, SIGTERM)"
+        );
+        assert_eq!(
+            format_call(5, &[3, 0x7ffc0000, 0, 0, 0, 0], &memory),
+            "fstat(3, (struct stat *) 0x7ffc0000)"
+        );
+        assert_eq!(
+            format_call(999, &[1, 2, 3, 4, 5, 6], &memory),
+            "syscall_999(0x1, 0x2, 0x3, 0x4, 0x5, 0x6)"
+        );
+    }
+
+    #[test]
+    fn return_values_are_decoded() {
+        assert_eq!(format_return(257, 3), "3");
+        assert_eq!(
+            format_return(257, -2),
+            "-1 ENOENT (No such file or directory)"
+        );
+        assert_eq!(format_return(9, 0x7f0000001000), "0x7f0000001000");
+        assert_eq!(format_return(12, -12), "-1 ENOMEM (Out of memory)");
+    }
+}
@@ -0,0 +1,239 @@
+use crate::dwarf::expr::MemoryReader;
+use crate::elf::Elf;
+use crate::maps::{MemoryRegion, Permissions};
+use crate::process::StopReason;
+use crate::reginfo::{lookup_register_info_by_id, RegisterId, RegisterInfo};
+use crate::registers::Registers;
+use crate::target::Target;
+use anyhow::{anyhow, bail, Result};
+use nix::libc::AT_ENTRY;
+use nix::sys::signal::Signal;
+use nix::sys::wait::WaitStatus;
+use nix::unistd::Pid;
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+
+const INT3: u8 = 0xcc;
+pub const MOCK_PID: i32 = 4242;
+
+/// A target held entirely in memory, for testing the debugger without a process. The segments
+/// of a real executable are copied into its memory at a load bias of the test's choosing, and
+/// each resume or step stops at the next of the pcs queued, exiting once they run out.
+pub struct MockTarget {
+    executable: PathBuf,
+    registers: Registers,
+    auxv: HashMap<u64, u64>,
+    // Blocks of memory by start address
+    memory: Vec<(u64, Vec<u8>)>,
+    breakpoint_sites: HashMap<u64, u8>,
+    stops: VecDeque<u64>,
+    // What the last resume or step did, reported by the next wait
+    status: Option<WaitStatus>,
+}
+
+impl MockTarget {
+    pub fn new(executable: &str, load_bias: u64) -> Result<Self> {
+        let elf = Elf::open(executable)?;
+        let mut target = Self {
+            executable: PathBuf::from(executable),
+            registers: Registers::default(),
+            auxv: HashMap::from([(AT_ENTRY, elf.entry() + load_bias)]),
+            memory: Vec::new(),
+            breakpoint_sites: HashMap::new(),
+            stops: VecDeque::new(),
+            status: None,
+        };
+        for (address, data, size) in elf.load_segments() {
+            let mut contents = data.to_vec();
+            contents.resize(size as usize, 0);
+            target.map(address + load_bias, contents);
+        }
+        target.set_register(RegisterId::RIP, elf.entry() + load_bias)?;
+        Ok(target)
+    }
+
+    pub fn map(&mut self, address: u64, data: Vec<u8>) {
+        self.memory.push((address, data));
+    }
+
+    pub fn set_register(&mut self, id: RegisterId, value: u64) -> Result<()> {
+        self.registers
+            .set_cached_u64(lookup_register_info_by_id(id)?, value);
+        Ok(())
+    }
+
+    pub fn queue_stop(&mut self, pc: u64) {
+        self.stops.push_back(pc);
+    }
+
+    // The block holding the address and the offset of the address into it
+    fn block_index(&self, address: u64) -> Result<(usize, usize)> {
+        self.memory
+            .iter()
+            .position(|(start, data)| (*start..*start + data.len() as u64).contains(&address))
+            .map(|index| (index, (address - self.memory[index].0) as usize))
+            .ok_or_else(|| anyhow!("cannot access memory at {address:#x}"))
+    }
+
+    // Memory as stored, with the int3s of breakpoint sites
+    fn read_raw(&self, address: u64, amount: usize) -> Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(amount);
+        while data.len() < amount {
+            let (index, offset) = self.block_index(address + data.len() as u64)?;
+            let block = &self.memory[index].1[offset..];
+            data.extend(&block[..block.len().min(amount - data.len())]);
+        }
+        Ok(data)
+    }
+
+    fn write_raw(&mut self, address: u64, data: &[u8]) -> Result<()> {
+        for (i, &byte) in data.iter().enumerate() {
+            let (index, offset) = self.block_index(address + i as u64)?;
+            self.memory[index].1[offset] = byte;
+        }
+        Ok(())
+    }
+
+    fn run(&mut self) -> Result<()> {
+        let pid = Pid::from_raw(MOCK_PID);
+        self.status = Some(match self.stops.pop_front() {
+            Some(pc) => {
+                self.set_register(RegisterId::RIP, pc)?;
+                WaitStatus::Stopped(pid, Signal::SIGTRAP)
+            }
+            None => WaitStatus::Exited(pid, 0),
+        });
+        Ok(())
+    }
+}
+
+impl MemoryReader for MockTarget {
+    fn read_memory(&self, address: u64, amount: usize) -> Result<Vec<u8>> {
+        let mut data = self.read_raw(address, amount)?;
+        for (&site, &saved) in &self.breakpoint_sites {
+            if let Some(offset) = site.checked_sub(address)
+                && (offset as usize) < amount
+            {
+                data[offset as usize] = saved;
+            }
+        }
+        Ok(data)
+    }
+}
+
+impl Target for MockTarget {
+    fn pid(&self) -> Pid {
+        Pid::from_raw(MOCK_PID)
+    }
+
+    fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    fn executable_path(&self) -> Result<PathBuf> {
+        Ok(self.executable.clone())
+    }
+
+    fn read_auxv(&self) -> Result<HashMap<u64, u64>> {
+        Ok(self.auxv.clone())
+    }
+
+    fn threads(&self) -> Result<Vec<Pid>> {
+        Ok(vec![self.pid()])
+    }
+
+    fn thread_stack_pointer(&self, _tid: Pid) -> Result<Option<u64>> {
+        let rsp = lookup_register_info_by_id(RegisterId::RSP)?;
+        Ok(Some(self.registers.read_as_u64(rsp)?))
+    }
+
+    fn memory_regions(&self) -> Result<Vec<MemoryRegion>> {
+        Ok(self
+            .memory
+            .iter()
+            .map(|(start, data)| MemoryRegion {
+                range: *start..*start + data.len() as u64,
+                permissions: Permissions {
+                    read: true,
+                    write: true,
+                    execute: true,
+                    shared: false,
+                },
+                offset: 0,
+                device: "00:00".to_string(),
+                inode: 0,
+                path: String::new(),
+                rss: None,
+            })
+            .collect())
+    }
+
+    fn can_resume(&self) -> bool {
+        true
+    }
+
+    // Only registers of up to eight bytes are supported
+    fn write_register(&mut self, info: &RegisterInfo, data: &[u8]) -> Result<()> {
+        if data.len() > 8 {
+            bail!("cannot write {} bytes to {}", data.len(), info.name);
+        }
+        let mut bytes = [0; 8];
+        bytes[..data.len()].copy_from_slice(data);
+        self.registers
+            .set_cached_u64(info, u64::from_le_bytes(bytes));
+        Ok(())
+    }
+
+    fn write_memory(&mut self, address: u64, data: &[u8]) -> Result<()> {
+        let mut data = data.to_vec();
+        for (&site, saved) in &mut self.breakpoint_sites {
+            if let Some(offset) = site.checked_sub(address)
+                && (offset as usize) < data.len()
+            {
+                *saved = data[offset as usize];
+                data[offset as usize] = INT3;
+            }
+        }
+        self.write_raw(address, &data)
+    }
+
+    fn resume(&mut self) -> Result<()> {
+        self.run()
+    }
+
+    fn step_instruction(&mut self) -> Result<StopReason> {
+        self.run()?;
+        self.wait_on_signal()
+    }
+
+    fn wait_on_signal(&mut self) -> Result<StopReason> {
+        self.status
+            .take()
+            .map(StopReason::new)
+            .ok_or_else(|| anyhow!("the target is not running"))
+    }
+
+    fn add_breakpoint_site(&mut self, address: u64) -> Result<()> {
+        if !self.breakpoint_sites.contains_key(&address) {
+            let saved = self.read_raw(address, 1)?[0];
+            self.write_raw(address, &[INT3])?;
+            self.breakpoint_sites.insert(address, saved);
+        }
+        Ok(())
+    }
+
+    fn remove_breakpoint_site(&mut self, address: u64) -> Result<()> {
+        if let Some(saved) = self.breakpoint_sites.remove(&address) {
+            self.write_raw(address, &[saved])?;
+        }
+        Ok(())
+    }
+
+    fn forget_breakpoint_site(&mut self, address: u64) {
+        self.breakpoint_sites.remove(&address);
+    }
+
+    fn has_breakpoint_site(&self, address: u64) -> bool {
+        self.breakpoint_sites.contains_key(&address)
+    }
+}
@@ -0,0 +1,219 @@
+//! The targets the debugger works on.
+
+use crate::maps;
+use crate::process::{EventCatch, Process, StopReason, SyscallCatchPolicy};
+use crate::reginfo::RegisterInfo;
+use crate::registers::Registers;
+use anyhow::{bail, Result};
+use nix::unistd::Pid;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[cfg(test)]
+pub(crate) mod mock;
+
+pub use crate::dwarf::expr::MemoryReader;
+pub use crate::maps::{MemoryRegion, Permissions};
+pub use crate::solib::read_c_string;
+
+/// What the debugger inspects and controls: a live process, the snapshot of one in a core file,
+/// or a stand-in for tests. The debugger only goes through this trait, so other kinds of target
+/// such as remote stubs plug in without changes to it. Targets which cannot run leave the
+/// execution control methods to their defaults, which refuse.
+pub trait Target: MemoryReader {
+    /// The process ID of the program, live or as recorded
+    fn pid(&self) -> Pid;
+    /// The registers of the thread the target stopped in
+    fn registers(&self) -> &Registers;
+    /// The path of the program's executable
+    fn executable_path(&self) -> Result<PathBuf>;
+    /// The auxiliary vector the kernel passed to the program, keyed by AT_* type
+    fn read_auxv(&self) -> Result<HashMap<u64, u64>>;
+    /// The threads, the main thread first
+    fn threads(&self) -> Result<Vec<Pid>>;
+    /// The stack pointer of a thread, if the target knows it
+    fn thread_stack_pointer(&self, tid: Pid) -> Result<Option<u64>>;
+    /// The mapped regions of the program's address space
+    fn memory_regions(&self) -> Result<Vec<MemoryRegion>>;
+
+    /// The live process behind the target, for commands which only make sense on one
+    fn process(&self) -> Option<&Process> {
+        None
+    }
+
+    /// The live process behind the target, to change
+    fn process_mut(&mut self) -> Option<&mut Process> {
+        None
+    }
+
+    /// Whether the target can run, which a core file cannot
+    fn can_resume(&self) -> bool {
+        false
+    }
+
+    /// Writes a register of the stopped thread
+    fn write_register(&mut self, _info: &RegisterInfo, _data: &[u8]) -> Result<()> {
+        bail!("the registers of the target cannot be written")
+    }
+
+    /// Writes memory. Writes which cover a breakpoint site change the byte the site restores, so
+    /// that the int3 stays in place until the site is removed.
+    fn write_memory(&mut self, _address: u64, _data: &[u8]) -> Result<()> {
+        bail!("the memory of the target cannot be written")
+    }
+
+    /// Continues the program, stepping over any breakpoint site it stopped on
+    fn resume(&mut self) -> Result<()> {
+        bail!("the program is not being run")
+    }
+
+    /// Executes a single instruction and waits for the target to stop again
+    fn step_instruction(&mut self) -> Result<StopReason> {
+        bail!("the program is not being run")
+    }
+
+    /// Waits for the program to stop and says why it did
+    fn wait_on_signal(&mut self) -> Result<StopReason> {
+        bail!("the program is not being run")
+    }
+
+    /// Puts an int3 at an address, saving the byte it replaces
+    fn add_breakpoint_site(&mut self, _address: u64) -> Result<()> {
+        bail!("the program is not being run")
+    }
+
+    /// Takes the int3 at an address out, restoring the saved byte
+    fn remove_breakpoint_site(&mut self, _address: u64) -> Result<()> {
+        Ok(())
+    }
+
+    /// Drops a site without touching memory, for code which is no longer mapped
+    fn forget_breakpoint_site(&mut self, _address: u64) {}
+
+    /// Whether there is a breakpoint site at an address
+    fn has_breakpoint_site(&self, _address: u64) -> bool {
+        false
+    }
+
+    /// Which system calls stop the program
+    fn syscall_catch_policy(&self) -> &SyscallCatchPolicy {
+        &SyscallCatchPolicy::None
+    }
+
+    /// Sets which system calls stop the program
+    fn set_syscall_catch_policy(&mut self, _policy: SyscallCatchPolicy) -> Result<()> {
+        bail!("the program is not being run")
+    }
+
+    /// Which process events stop the program
+    fn event_catch(&self) -> &EventCatch {
+        const NO_EVENTS: &EventCatch = &EventCatch {
+            fork: false,
+            vfork: false,
+            clone: false,
+            exec: false,
+            exit: false,
+        };
+        NO_EVENTS
+    }
+
+    /// Sets which process events stop the program
+    fn set_event_catch(&mut self, _events: EventCatch) -> Result<()> {
+        bail!("the program is not being run")
+    }
+}
+
+impl Target for Process {
+    fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    fn registers(&self) -> &Registers {
+        Process::registers(self)
+    }
+
+    fn executable_path(&self) -> Result<PathBuf> {
+        Process::executable_path(self)
+    }
+
+    fn read_auxv(&self) -> Result<HashMap<u64, u64>> {
+        Process::read_auxv(self)
+    }
+
+    fn threads(&self) -> Result<Vec<Pid>> {
+        Process::threads(self)
+    }
+
+    fn thread_stack_pointer(&self, tid: Pid) -> Result<Option<u64>> {
+        Process::thread_stack_pointer(self, tid)
+    }
+
+    fn memory_regions(&self) -> Result<Vec<MemoryRegion>> {
+        maps::read_smaps(self.pid)
+    }
+
+    fn process(&self) -> Option<&Process> {
+        Some(self)
+    }
+
+    fn process_mut(&mut self) -> Option<&mut Process> {
+        Some(self)
+    }
+
+    fn can_resume(&self) -> bool {
+        true
+    }
+
+    fn write_register(&mut self, info: &RegisterInfo, data: &[u8]) -> Result<()> {
+        Process::write_register(self, info, data)
+    }
+
+    fn write_memory(&mut self, address: u64, data: &[u8]) -> Result<()> {
+        Process::write_memory_around_sites(self, address, data)
+    }
+
+    fn resume(&mut self) -> Result<()> {
+        Process::resume(self)
+    }
+
+    fn step_instruction(&mut self) -> Result<StopReason> {
+        Process::step_instruction(self)
+    }
+
+    fn wait_on_signal(&mut self) -> Result<StopReason> {
+        Process::wait_on_signal(self)
+    }
+
+    fn add_breakpoint_site(&mut self, address: u64) -> Result<()> {
+        Process::add_breakpoint_site(self, address)
+    }
+
+    fn remove_breakpoint_site(&mut self, address: u64) -> Result<()> {
+        Process::remove_breakpoint_site(self, address)
+    }
+
+    fn forget_breakpoint_site(&mut self, address: u64) {
+        Process::forget_breakpoint_site(self, address)
+    }
+
+    fn has_breakpoint_site(&self, address: u64) -> bool {
+        Process::has_breakpoint_site(self, address)
+    }
+
+    fn syscall_catch_policy(&self) -> &SyscallCatchPolicy {
+        Process::syscall_catch_policy(self)
+    }
+
+    fn set_syscall_catch_policy(&mut self, policy: SyscallCatchPolicy) -> Result<()> {
+        Process::set_syscall_catch_policy(self, policy);
+        Ok(())
+    }
+
+    fn event_catch(&self) -> &EventCatch {
+        Process::event_catch(self)
+    }
+
+    fn set_event_catch(&mut self, events: EventCatch) -> Result<()> {
+        Process::set_event_catch(self, events)
+    }
+}
@@ -0,0 +1,199 @@
+use crate::commands::{self, Command};
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+
+// Commands added by users. An alias names the start of another command line, as in
+// `alias bt = backtrace`, and a definition runs a list of command lines, in which `$arg0` and
+// on are replaced by its arguments and `$argc` by how many there are.
+#[derive(Default)]
+pub struct UserCommands {
+    aliases: BTreeMap<String, String>,
+    definitions: BTreeMap<String, Vec<String>>,
+    // How many definitions are running, to stop those which run themselves
+    pub depth: usize,
+}
+
+impl UserCommands {
+    // Aliases `name` to a command line, which may start with an alias itself
+    pub fn alias(&mut self, commands: &[Command], name: &str, command: &str) -> Result<()> {
+        check_name(commands, name)?;
+        if self.definitions.contains_key(name) {
+            bail!("\"{name}\" is a user command");
+        }
+        let command = self.expand(command);
+        let (word, _) = split_first(&command);
+        if word.is_empty() {
+            bail!("usage: alias <name> = <command>");
+        }
+        if word == name {
+            bail!("alias \"{name}\" would run itself");
+        }
+        if !self.definitions.contains_key(word) {
+            commands::find(commands, word.split('/').next().unwrap_or_default())?;
+        }
+        self.aliases.insert(name.to_string(), command);
+        Ok(())
+    }
+
+    // Defines a command, replacing any definition of the name
+    pub fn define(&mut self, commands: &[Command], name: &str, lines: Vec<String>) -> Result<()> {
+        self.check_definition(commands, name)?;
+        self.definitions.insert(name.to_string(), lines);
+        Ok(())
+    }
+
+    // Checks a name can be defined, before its lines are read
+    pub fn check_definition(&self, commands: &[Command], name: &str) -> Result<()> {
+        check_name(commands, name)?;
+        if self.aliases.contains_key(name) {
+            bail!("\"{name}\" is an alias");
+        }
+        Ok(())
+    }
+
+    // The line with a leading alias replaced by what it stands for
+    pub fn expand(&self, line: &str) -> String {
+        let (word, rest) = split_first(line);
+        match self.aliases.get(word) {
+            Some(command) if rest.is_empty() => command.clone(),
+            Some(command) => format!("{command} {rest}"),
+            None => line.to_string(),
+        }
+    }
+
+    // The lines to run for a line calling a definition, with its arguments put in
+    pub fn lines_for(&self, line: &str) -> Option<Result<Vec<String>>> {
+        let (word, rest) = split_first(line);
+        let lines = self.definitions.get(word)?;
+        let args: Vec<&str> = rest.split_whitespace().collect();
+        Some(
+            lines
+                .iter()
+                .map(|line| substitute(word, line, &args))
+                .collect(),
+        )
+    }
+
+    pub fn aliases(&self) -> impl Iterator<Item = (&str, &str)> {
+        let aliases = self.aliases.iter();
+        aliases.map(|(name, command)| (name.as_str(), command.as_str()))
+    }
+
+    pub fn definition(&self, name: &str) -> Option<&[String]> {
+        self.definitions.get(name).map(Vec::as_slice)
+    }
+
+    pub fn definitions(&self) -> impl Iterator<Item = &str> {
+        self.definitions.keys().map(String::as_str)
+    }
+
+    // The names of the aliases and definitions
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        let aliases = self.aliases.keys().map(String::as_str);
+        aliases.chain(self.definitions())
+    }
+}
+
+// User commands may not hide a command, as scripts rely on them, but may take over one of its
+// short names, as in `alias bt = backtrace`. They are expanded before commands are looked up.
+fn check_name(commands: &[Command], name: &str) -> Result<()> {
+    let valid = name
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+    if name.is_empty() || !valid {
+        bail!("invalid command name \"{name}\"");
+    }
+    if commands.iter().any(|command| command.name == name) {
+        bail!("\"{name}\" is already a command");
+    }
+    Ok(())
+}
+
+fn split_first(line: &str) -> (&str, &str) {
+    let line = line.trim();
+    let end = line.find(char::is_whitespace).unwrap_or(line.len());
+    (&line[..end], line[end..].trim_start())
+}
+
+// Replaces `$argc` and `$arg<n>` in a line of a definition
+fn substitute(name: &str, line: &str, args: &[&str]) -> Result<String> {
+    let mut text = String::new();
+    let mut rest = line;
+    while let Some(index) = rest.find("$arg") {
+        text.push_str(&rest[..index]);
+        rest = &rest[index + 4..];
+        if let Some(after) = rest.strip_prefix('c') {
+            text.push_str(&args.len().to_string());
+            rest = after;
+            continue;
+        }
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits == 0 {
+            text.push_str("$arg");
+            continue;
+        }
+        let number: usize = rest[..digits].parse()?;
+        match args.get(number) {
+            Some(arg) => text.push_str(arg),
+            None => bail!(
+                "{name} takes $arg{number} but was given {} arguments",
+                args.len()
+            ),
+        }
+        rest = &rest[digits..];
+    }
+    text.push_str(rest);
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::user::UserCommands;
+    use crate::COMMANDS;
+
+    #[test]
+    fn aliases_and_definitions_expand_into_commands() {
+        let mut user = UserCommands::default();
+        user.alias(COMMANDS, "where", "backtrace").unwrap();
+        user.alias(COMMANDS, "stack", "where").unwrap();
+        assert_eq!(user.expand("stack"), "backtrace");
+        assert_eq!(user.expand("print 1"), "print 1");
+        user.alias(COMMANDS, "words", "x/4xg").unwrap();
+        assert_eq!(user.expand("words $rsp"), "x/4xg $rsp");
+
+        let lines = vec!["print $arg0 + $arg1".to_string(), "print $argc".to_string()];
+        user.define(COMMANDS, "sum", lines).unwrap();
+        let lines = user.lines_for("sum 1 2").unwrap().unwrap();
+        assert_eq!(lines, ["print 1 + 2", "print 2"]);
+        let err = user.lines_for("sum 1").unwrap().unwrap_err();
+        assert_eq!(err.to_string(), "sum takes $arg1 but was given 1 arguments");
+        assert!(user.lines_for("print 1").is_none());
+
+        // Short names can be taken over, but not the names of commands
+        user.alias(COMMANDS, "bt", "where").unwrap();
+        assert_eq!(user.expand("bt 3"), "backtrace 3");
+        let err = |result: anyhow::Result<()>| result.unwrap_err().to_string();
+        assert_eq!(
+            err(user.alias(COMMANDS, "backtrace", "where")),
+            "\"backtrace\" is already a command"
+        );
+        assert_eq!(
+            err(user.alias(COMMANDS, "loop", "loop")),
+            "alias \"loop\" would run itself"
+        );
+        assert_eq!(
+            err(user.alias(COMMANDS, "nope", "frobnicate")),
+            "unknown command \"frobnicate\", try \"help\""
+        );
+        assert_eq!(
+            err(user.alias(COMMANDS, "sum", "print")),
+            "\"sum\" is a user command"
+        );
+        assert_eq!(
+            err(user.define(COMMANDS, "stack", vec![])),
+            "\"stack\" is an alias"
+        );
+    }
+}